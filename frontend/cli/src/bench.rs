@@ -0,0 +1,196 @@
+//! Measure how fast a program runs.
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::result;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use intcode::run::cell::Cell;
+use intcode::run::{Computer, Stats};
+
+/// The integer type used for each cell of the computer's memory.
+///
+/// Each one is a separately compiled computer, so comparing them shows what
+/// the wider arithmetic costs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    I32,
+    I64,
+    I128,
+}
+
+/// The result of running a program a number of times.
+#[derive(Debug)]
+pub struct Measurement {
+    pub backend: Backend,
+    pub iterations: u32,
+    pub elapsed: Duration,
+    /// The counters after a single run.
+    pub stats: Stats,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "i32" => Ok(Self::I32),
+            "i64" => Ok(Self::I64),
+            "i128" => Ok(Self::I128),
+            s => Err(format!(
+                "unknown backend `{}`, expected one of `i32`, `i64`, or `i128`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::I128 => "i128",
+        })
+    }
+}
+
+impl Measurement {
+    /// Returns the average wall time of a single run.
+    pub fn per_iteration(&self) -> Duration {
+        self.elapsed / self.iterations.max(1)
+    }
+
+    /// Returns the number of instructions executed per second.
+    pub fn rate(&self) -> f64 {
+        let secs = self.per_iteration().as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.stats.instructions as f64 / secs
+    }
+}
+
+fn convert<C>(values: &[i64]) -> Result<Vec<C>>
+where
+    C: TryFrom<i64>,
+    C::Error: error::Error + Send + Sync + 'static,
+{
+    values
+        .iter()
+        .map(|&v| C::try_from(v).with_context(|| format!("`{}` does not fit", v)))
+        .collect()
+}
+
+fn measure_with<C>(
+    backend: Backend,
+    program: &[i64],
+    input: &[i64],
+    iterations: u32,
+) -> Result<Measurement>
+where
+    C: Cell + TryFrom<i64>,
+    <C as TryFrom<i64>>::Error: error::Error + Send + Sync + 'static,
+{
+    let context = || format!("program does not fit in {} cells", backend);
+    let snapshot = Computer::<C>::new(convert(program).with_context(context)?);
+    let input: Vec<C> = convert(input).with_context(context)?;
+    let mut computer = snapshot.clone();
+    let mut stats = Stats::default();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        computer.reset_to(&snapshot);
+        computer
+            .run_with_input(&input)
+            .with_context(|| format!("program failed with {} cells", backend))?;
+        stats = computer.stats();
+    }
+    Ok(Measurement {
+        backend,
+        iterations,
+        elapsed: start.elapsed(),
+        stats,
+    })
+}
+
+/// Runs the program to completion `iterations` times with the given input.
+pub fn measure(
+    backend: Backend,
+    program: &[i64],
+    input: &[i64],
+    iterations: u32,
+) -> Result<Measurement> {
+    match backend {
+        Backend::I32 => measure_with::<i32>(backend, program, input, iterations),
+        Backend::I64 => measure_with::<i64>(backend, program, input, iterations),
+        Backend::I128 => measure_with::<i128>(backend, program, input, iterations),
+    }
+}
+
+/// Formats a large number with a metric suffix.
+fn metric(n: f64) -> String {
+    match n {
+        n if n >= 1e9 => format!("{:.2}G", n / 1e9),
+        n if n >= 1e6 => format!("{:.2}M", n / 1e6),
+        n if n >= 1e3 => format!("{:.2}k", n / 1e3),
+        n => format!("{:.0}", n),
+    }
+}
+
+/// Formats the measurements as a table with one row for each backend.
+pub fn table(measurements: &[Measurement]) -> String {
+    let mut out = format!(
+        "{:<8} {:>10} {:>12} {:>14} {:>10} {:>10}\n",
+        "backend", "iterations", "time/iter", "instructions", "instr/s", "cells"
+    );
+    for m in measurements {
+        out += &format!(
+            "{:<8} {:>10} {:>12} {:>14} {:>10} {:>10}\n",
+            m.backend,
+            m.iterations,
+            format!("{:.3?}", m.per_iteration()),
+            m.stats.instructions,
+            metric(m.rate()),
+            m.stats.cells,
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_backends() {
+        // Outputs the input multiplied by two.
+        let program = [3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+        for backend in [Backend::I32, Backend::I64, Backend::I128] {
+            let m = measure(backend, &program, &[21], 3).unwrap();
+            assert_eq!(m.iterations, 3);
+            assert_eq!(
+                m.stats,
+                Stats {
+                    instructions: 4,
+                    cells: 10
+                }
+            );
+        }
+        let err = measure(Backend::I32, &[104, 1 << 40, 99], &[], 1).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "program does not fit in i32 cells: `1099511627776` does not fit: out of range integral type conversion attempted"
+        );
+    }
+
+    #[test]
+    fn metric_suffixes() {
+        assert_eq!(metric(12.0), "12");
+        assert_eq!(metric(12_345.0), "12.35k");
+        assert_eq!(metric(12_345_678.0), "12.35M");
+        assert_eq!(metric(1_234_567_890.0), "1.23G");
+    }
+}
@@ -0,0 +1,176 @@
+//! Cache assembled programs so that unchanged files are not assembled again.
+//!
+//! Each entry is keyed by a hash of the source and the assembler options,
+//! and records a hash of every file the program included. An entry is only
+//! used if all of those files are unchanged. Programs that assembled with
+//! warnings are never cached so that the warnings are shown every time.
+//!
+//! An entry is a single text file:
+//!
+//! ```text
+//! include <hash> <path>
+//! output <value>,<value>,...
+//!
+//! <debug info>
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use intcode::assemble::debug::DebugInfo;
+use intcode::assemble::{Intcode, Options};
+
+use crate::parse_program;
+
+/// Changed whenever the entry format or the assembler output changes.
+const VERSION: &str = concat!("1 ", env!("CARGO_PKG_VERSION"));
+
+/// A directory of cached programs.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+fn hash(data: &[u8]) -> u64 {
+    let mut h = DefaultHasher::new();
+    data.hash(&mut h);
+    h.finish()
+}
+
+fn key(asm: &str, opts: &Options) -> u64 {
+    let mut h = DefaultHasher::new();
+    VERSION.hash(&mut h);
+    asm.hash(&mut h);
+    format!("{:?}", opts).hash(&mut h);
+    h.finish()
+}
+
+/// Returns whether the file still has the given hash.
+fn is_fresh(path: &Path, expected: u64) -> bool {
+    fs::read(path).map_or(false, |data| hash(&data) == expected)
+}
+
+/// Parses an entry, returning `None` if it is malformed or stale.
+fn parse(entry: &str) -> Option<Intcode> {
+    let (header, debug) = entry.split_once("\n\n")?;
+    let mut output = None;
+    let mut includes = Vec::new();
+    for line in header.lines() {
+        match line.split_once(' ')? {
+            ("include", rest) => {
+                let (h, path) = rest.split_once(' ')?;
+                let path = PathBuf::from(path);
+                if !is_fresh(&path, u64::from_str_radix(h, 16).ok()?) {
+                    return None;
+                }
+                includes.push(path);
+            }
+            ("output", values) => output = Some(parse_program(values).ok()?),
+            _ => return None,
+        }
+    }
+    Some(Intcode {
+        output: output?,
+        warnings: Vec::new(),
+        debug: debug.parse::<DebugInfo>().ok()?,
+        source_map: None,
+        includes,
+    })
+}
+
+/// Renders an entry, returning `None` if an included file can no longer be
+/// read.
+fn render(intcode: &Intcode) -> Option<String> {
+    let mut entry = String::new();
+    for path in &intcode.includes {
+        let data = fs::read(path).ok()?;
+        entry.push_str(&format!("include {:x} {}\n", hash(&data), path.to_str()?));
+    }
+    let values: Vec<_> = intcode.output.iter().map(i64::to_string).collect();
+    entry.push_str(&format!("output {}\n\n", values.join(",")));
+    entry.push_str(&intcode.debug.to_string());
+    Some(entry)
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the cache in `$INTCODE_CACHE_DIR`, or the `intcode` directory
+    /// in the user's cache directory.
+    pub fn from_env() -> Option<Self> {
+        if let Some(dir) = env::var_os("INTCODE_CACHE_DIR") {
+            return Some(Self::new(dir));
+        }
+        let base = match env::var_os("XDG_CACHE_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+        };
+        Some(Self::new(base.join("intcode")))
+    }
+
+    fn path(&self, asm: &str, opts: &Options) -> PathBuf {
+        self.dir.join(format!("{:016x}", key(asm, opts)))
+    }
+
+    /// Returns the cached program, if there is one and it is not stale.
+    pub fn get(&self, asm: &str, opts: &Options) -> Option<Intcode> {
+        let entry = fs::read_to_string(self.path(asm, opts)).ok()?;
+        parse(&entry)
+    }
+
+    /// Stores the assembled program.
+    pub fn put(&self, asm: &str, opts: &Options, intcode: &Intcode) -> Result<()> {
+        if !intcode.warnings.is_empty() || intcode.source_map.is_some() {
+            return Ok(());
+        }
+        let entry = match render(intcode) {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        fs::create_dir_all(&self.dir)?;
+        // Write to a temporary file first so that a concurrent build never
+        // reads a partial entry.
+        let path = self.path(asm, opts);
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, entry)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = env::temp_dir().join("intcode-cache");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("blob.intcode"), "104,42,99\n").unwrap();
+        let cache = Cache::new(dir.join("cache"));
+        let asm = "    JZ #0, #blob\nblob: BINCLUDE \"blob.intcode\"\n";
+        let opts = Options::new().include_dir(&dir);
+
+        assert!(cache.get(asm, &opts).is_none());
+        let intcode = intcode::assemble::to_intcode_with(asm, &opts).unwrap();
+        cache.put(asm, &opts, &intcode).unwrap();
+        let cached = cache.get(asm, &opts).unwrap();
+        assert_eq!(cached.output, intcode.output);
+        assert_eq!(cached.debug, intcode.debug);
+        assert_eq!(cached.includes, intcode.includes);
+
+        // Different options are a different entry.
+        assert!(cache.get(asm, &opts.clone().strict(true)).is_none());
+
+        // Changing an included file invalidates the entry.
+        fs::write(dir.join("blob.intcode"), "104,7,99\n").unwrap();
+        assert!(cache.get(asm, &opts).is_none());
+    }
+}
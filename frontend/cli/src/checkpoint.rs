@@ -0,0 +1,122 @@
+//! Save checkpoints of a long run to a directory and resume from them.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use intcode::run::checkpoint::Checkpoint;
+use intcode::run::Computer;
+
+use crate::log;
+
+/// The extension of checkpoint files.
+const EXTENSION: &str = "checkpoint";
+
+/// A directory holding the most recent checkpoints of a run.
+#[derive(Debug)]
+pub struct Checkpoints {
+    dir: PathBuf,
+    keep: usize,
+}
+
+/// Returns the checkpoint files in the directory, oldest first.
+fn list(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension() == Some(OsStr::new(EXTENSION)) {
+            paths.push(path);
+        }
+    }
+    // The names are zero padded step counts so they sort by age.
+    paths.sort();
+    Ok(paths)
+}
+
+impl Checkpoints {
+    /// Creates the directory if needed, at most `keep` checkpoints are kept
+    /// in it.
+    pub fn new(dir: PathBuf, keep: usize) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create `{}`", dir.display()))?;
+        Ok(Self {
+            dir,
+            keep: keep.max(1),
+        })
+    }
+
+    /// Writes a checkpoint of the computer and the outputs so far, then
+    /// removes the oldest checkpoints.
+    ///
+    /// The checkpoint is written to a temporary file first so that an
+    /// interruption never leaves a partial checkpoint behind.
+    pub fn save(&self, c: &Computer, outputs: &[i64]) -> Result<()> {
+        let mut checkpoint = c.checkpoint();
+        checkpoint.outputs = outputs.to_vec();
+        let name = format!("{:020}.{}", checkpoint.steps, EXTENSION);
+        let path = self.dir.join(name);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, checkpoint.to_string())?;
+        fs::rename(&tmp, &path)?;
+        log::info!("wrote checkpoint {}", path.display());
+        let paths = list(&self.dir)?;
+        let excess = paths.len().saturating_sub(self.keep);
+        for path in &paths[..excess] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Loads the checkpoint from the file, or the most recent one if the path is
+/// a directory.
+pub fn load(path: &Path) -> Result<Checkpoint> {
+    let path = match path.is_dir() {
+        true => match list(path)?.pop() {
+            Some(path) => path,
+            None => bail!("no checkpoints in `{}`", path.display()),
+        },
+        false => path.to_owned(),
+    };
+    let checkpoint = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?
+        .parse()
+        .with_context(|| format!("failed to parse `{}`", path.display()))?;
+    log::info!("resuming from {}", path.display());
+    Ok(checkpoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use intcode::run::{Pause, State};
+
+    use super::*;
+
+    #[test]
+    fn ring() {
+        let dir = env::temp_dir().join("intcode-checkpoints");
+        let _ = fs::remove_dir_all(&dir);
+        let checkpoints = Checkpoints::new(dir.clone(), 2).unwrap();
+        // Counts down from 10 and then outputs the counter.
+        let program = vec![1001, 10, -1, 10, 1005, 10, 0, 4, 10, 99, 10];
+        let mut c = Computer::new(program.clone());
+        c.pause_every(Some(3));
+        while let State::Paused {
+            reason: Pause::Interval { .. },
+        } = c.next().unwrap()
+        {
+            checkpoints.save(&c, &[1, 2]).unwrap();
+        }
+        assert_eq!(list(&dir).unwrap().len(), 2);
+
+        let checkpoint = load(&dir).unwrap();
+        assert_eq!(checkpoint.outputs, [1, 2]);
+        let mut resumed = Computer::new(program);
+        resumed.restore(&checkpoint).unwrap();
+        assert_eq!(resumed.run_with([]).unwrap(), [0]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
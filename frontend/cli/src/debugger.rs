@@ -0,0 +1,697 @@
+//! Interactively debug a running program.
+//!
+//! The debugger reads commands from stdin and uses the labels from the debug
+//! info, so breakpoints can be set and memory printed using the names from
+//! the source.
+//!
+//! A breakpoint can have a condition, see [`intcode::assemble::expr`], and a
+//! hit count. The computer pauses on every breakpoint and the debugger
+//! carries on straight away unless the condition holds and the breakpoint has
+//! been hit enough times. Watch expressions use the same language and are
+//! printed whenever the program stops.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::result;
+use std::str::FromStr;
+
+use anyhow::Result;
+use intcode::assemble::debug::DebugInfo;
+use intcode::assemble::expr::{Env, Expr};
+use intcode::fmt::Plain;
+use intcode::run::{Computer, Pause, State};
+
+use crate::line::Lines;
+use crate::parse_program;
+
+const HELP: &str = "\
+step [n]        execute the next n instructions, default 1
+continue        run until a breakpoint, input, or the program halts
+break <loc> [if <cond>] [count <n>]
+                pause before the instruction at the location, only when the
+                condition holds and from the nth time it does
+delete <loc>    remove a breakpoint
+print <loc>     print the value in memory at the location
+watch <expr>    print the value of the expression whenever the program stops
+unwatch <n>     remove the nth watch expression
+regs            print the instruction pointer and relative base
+disas [n]       disassemble the next n instructions, default 5
+input <values>  feed comma separated integers to the program
+help            print this message
+quit            exit the debugger
+
+A location is an address, a label, or a label with an offset as in `x+1`.
+
+A condition or watch is an expression using numbers, labels, `ptr`, `rb`, and memory
+as in `mem[x+1]`, with arithmetic, comparison, `&&`, `||`, and `!` operators,
+for example `mem[1000] == 5 && rb > 0`.";
+
+/// A location in memory.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Location {
+    Addr(usize),
+    Label(String, usize),
+}
+
+/// A debugger command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Step(usize),
+    Continue,
+    Break {
+        loc: Location,
+        cond: Option<Expr>,
+        count: Option<u64>,
+    },
+    Delete(Location),
+    Print(Location),
+    Watch(Expr),
+    Unwatch(usize),
+    Regs,
+    Disas(usize),
+    Input(Vec<i64>),
+    Help,
+    Quit,
+}
+
+/// The condition and hit count of a breakpoint.
+#[derive(Debug, Clone)]
+struct Breakpoint {
+    cond: Option<Expr>,
+    count: Option<u64>,
+    hits: u64,
+}
+
+/// A program being debugged.
+pub struct Debugger {
+    c: Computer,
+    debug: DebugInfo,
+    breakpoints: HashMap<usize, Breakpoint>,
+    watches: Vec<Expr>,
+}
+
+/// The state of the computer that expressions are evaluated against.
+struct Scope<'a> {
+    c: &'a Computer,
+    debug: &'a DebugInfo,
+}
+
+impl FromStr for Location {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        if let Ok(addr) = s.parse() {
+            return Ok(Self::Addr(addr));
+        }
+        let (label, offset) = match s.split_once('+') {
+            Some((label, offset)) => {
+                let offset = offset
+                    .parse()
+                    .map_err(|_| format!("invalid offset `{}`", offset))?;
+                (label, offset)
+            }
+            None => (s, 0),
+        };
+        Ok(Self::Label(label.to_owned(), offset))
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let cmd = words.next().unwrap_or("");
+        let rest = words.collect::<Vec<_>>().join(" ");
+        let count = |default| match rest.as_str() {
+            "" => Ok(default),
+            n => n.parse().map_err(|_| format!("invalid count `{}`", n)),
+        };
+        let location = || match rest.as_str() {
+            "" => Err(format!("`{}` expects a location", cmd)),
+            loc => loc.parse(),
+        };
+        match cmd {
+            "step" | "s" => count(1).map(Self::Step),
+            "continue" | "c" => Ok(Self::Continue),
+            "break" | "b" => breakpoint(&rest),
+            "delete" | "d" => location().map(Self::Delete),
+            "print" | "p" => location().map(Self::Print),
+            "watch" | "w" => expr(&rest, "expression").map(Self::Watch),
+            "unwatch" => match rest.as_str() {
+                "" => Err("`unwatch` expects a number".to_owned()),
+                _ => count(0).map(Self::Unwatch),
+            },
+            "regs" | "r" => Ok(Self::Regs),
+            "disas" => count(5).map(Self::Disas),
+            "input" | "i" => parse_program(&rest)
+                .map(Self::Input)
+                .map_err(|_| format!("invalid input `{}`", rest)),
+            "help" | "h" => Ok(Self::Help),
+            "quit" | "q" => Ok(Self::Quit),
+            cmd => Err(format!(
+                "unknown command `{}`, type `help` for a list of commands",
+                cmd
+            )),
+        }
+    }
+}
+
+/// Parses an expression, formatting any error with the expression.
+fn expr(text: &str, what: &str) -> result::Result<Expr, String> {
+    match text {
+        "" => Err(format!("expected {}", what)),
+        text => text.parse().map_err(|err| {
+            let snippet = Plain::new(text).error(&err);
+            format!("invalid {}{}", what, snippet.trim_end())
+        }),
+    }
+}
+
+/// Parses the arguments of a `break` command.
+fn breakpoint(rest: &str) -> result::Result<Command, String> {
+    let mut words: Vec<_> = rest.split_whitespace().collect();
+    if words.is_empty() {
+        return Err("`break` expects a location".to_owned());
+    }
+    let loc = words.remove(0).parse()?;
+    let count = match words[..] {
+        [.., "count", n] => Some(n.parse().map_err(|_| format!("invalid count `{}`", n))?),
+        _ => None,
+    };
+    if count.is_some() {
+        words.truncate(words.len() - 2);
+    }
+    let cond = match &words[..] {
+        [] => None,
+        ["if", cond @ ..] if !cond.is_empty() => Some(expr(&cond.join(" "), "condition")?),
+        _ => {
+            return Err(format!(
+                "expected `if <cond>` or `count <n>`, found `{}`",
+                words.join(" ")
+            ))
+        }
+    };
+    Ok(Command::Break { loc, cond, count })
+}
+
+fn mnemonic(opcode: i64) -> Option<(&'static str, usize)> {
+    let m = match opcode {
+        1 => ("ADD", 3),
+        2 => ("MUL", 3),
+        3 => ("IN", 1),
+        4 => ("OUT", 1),
+        5 => ("JNZ", 2),
+        6 => ("JZ", 2),
+        7 => ("LT", 3),
+        8 => ("EQ", 3),
+        9 => ("ARB", 1),
+        99 => ("HLT", 0),
+        _ => return None,
+    };
+    Some(m)
+}
+
+impl Debugger {
+    pub fn new(c: Computer, debug: DebugInfo) -> Self {
+        Self {
+            c,
+            debug,
+            breakpoints: HashMap::new(),
+            watches: Vec::new(),
+        }
+    }
+
+    /// Formats the value of each watch expression.
+    fn watches(&self) -> Vec<String> {
+        let scope = Scope {
+            c: &self.c,
+            debug: &self.debug,
+        };
+        self.watches
+            .iter()
+            .enumerate()
+            .map(|(i, expr)| match expr.eval(&scope) {
+                Ok(value) => format!("{}: {} = {}", i + 1, expr, value),
+                Err(msg) => format!("{}: {} = <error: {}>", i + 1, expr, msg),
+            })
+            .collect()
+    }
+
+    /// Counts a hit of the breakpoint at the address and returns whether the
+    /// debugger should stop there.
+    fn hit(&mut self, addr: usize) -> result::Result<bool, String> {
+        let bp = match self.breakpoints.get_mut(&addr) {
+            Some(bp) => bp,
+            None => return Ok(true),
+        };
+        if let Some(cond) = &bp.cond {
+            let scope = Scope {
+                c: &self.c,
+                debug: &self.debug,
+            };
+            if cond.eval(&scope)? == 0 {
+                return Ok(false);
+            }
+        }
+        bp.hits += 1;
+        Ok(bp.hits >= bp.count.unwrap_or(1))
+    }
+
+    /// Returns the address of the location.
+    fn resolve(&self, loc: &Location) -> result::Result<usize, String> {
+        match loc {
+            Location::Addr(addr) => Ok(*addr),
+            Location::Label(label, offset) => self
+                .debug
+                .labels
+                .iter()
+                .find(|(name, _)| name == label)
+                .map(|(_, addr)| addr + offset)
+                .ok_or_else(|| format!("unknown label `{}`", label)),
+        }
+    }
+
+    /// Describes an address using the closest label before it.
+    fn describe(&self, addr: usize) -> String {
+        let label = self
+            .debug
+            .labels
+            .iter()
+            .filter(|(_, a)| *a <= addr)
+            .max_by_key(|(_, a)| *a);
+        match label {
+            Some((name, a)) if *a == addr => format!("{} ({})", addr, name),
+            Some((name, a)) => format!("{} ({}+{})", addr, name, addr - a),
+            None => addr.to_string(),
+        }
+    }
+
+    /// Formats the instruction at the address and returns the address after
+    /// it.
+    fn disas(&self, addr: usize) -> (String, usize) {
+        let value = self.c.peek(addr);
+        let (name, arity) = match mnemonic(value % 100) {
+            Some(m) => m,
+            None => return (format!("DB {}", value), addr + 1),
+        };
+        let params: Vec<_> = (1..=arity)
+            .map(|i| {
+                let v = self.c.peek(addr + i);
+                match value / [100, 1000, 10000][i - 1] % 10 {
+                    0 => match usize::try_from(v).ok().and_then(|a| self.debug.label(a)) {
+                        Some(label) => label.to_owned(),
+                        None => v.to_string(),
+                    },
+                    1 => format!("#{}", v),
+                    2 if v == 0 => "rb".to_owned(),
+                    2 if v < 0 => format!("rb{}", v),
+                    2 => format!("rb+{}", v),
+                    _ => format!("?{}", v),
+                }
+            })
+            .collect();
+        let text = match params.is_empty() {
+            true => name.to_owned(),
+            false => format!("{} {}", name, params.join(", ")),
+        };
+        (text, addr + arity + 1)
+    }
+
+    /// Formats the listing line for the current instruction.
+    pub fn current(&self) -> String {
+        self.listing(self.c.ptr()).0
+    }
+
+    /// Formats a line of the listing for the instruction at the address.
+    fn listing(&self, addr: usize) -> (String, usize) {
+        let (text, next) = self.disas(addr);
+        let marker = match addr == self.c.ptr() {
+            true => "=>",
+            false => "  ",
+        };
+        let label = match self.debug.label(addr) {
+            Some(label) => format!("{}: ", label),
+            None => String::new(),
+        };
+        (format!("{} {:>5}  {}{}", marker, addr, label, text), next)
+    }
+
+    /// Describes a state the computer stopped in.
+    fn stopped(&self, state: State) -> String {
+        match state {
+            State::Yielded(value) => format!("output: {}", value),
+            State::Waiting => format!("waiting for input at {}", self.describe(self.c.ptr())),
+            State::Complete => "program halted".to_owned(),
+            State::Paused {
+                reason: Pause::Breakpoint { ptr },
+            } => format!("breakpoint at {}", self.describe(ptr)),
+            State::Paused {
+                reason: Pause::Watchpoint { ptr, addr, .. },
+            } => format!(
+                "watchpoint on {} at {}",
+                self.describe(addr),
+                self.describe(ptr)
+            ),
+            State::Paused {
+                reason: Pause::Interval { steps },
+            } => format!("paused after {} steps", steps),
+            State::Paused {
+                reason: Pause::Cancelled { ptr },
+            } => format!("interrupted at {}", self.describe(ptr)),
+            State::OutOfFuel => format!("out of fuel at {}", self.describe(self.c.ptr())),
+        }
+    }
+
+    /// Executes a command and returns the text to print.
+    pub fn exec(&mut self, cmd: &Command) -> Result<String> {
+        let mut out = Vec::new();
+        match cmd {
+            Command::Step(n) => {
+                for _ in 0..*n {
+                    let mut info = self.c.step()?;
+                    // A breakpoint on the instruction doesn't stop a step.
+                    if let Some(State::Paused { .. }) = info.state {
+                        info = self.c.step()?;
+                    }
+                    if info.state != Some(State::Waiting) {
+                        let (text, _) = self.disas(info.ptr);
+                        out.push(format!("{:>8}  {}", info.ptr, text));
+                    }
+                    if let Some((addr, value)) = info.write {
+                        out.push(format!("{:>8}  {} = {}", "", self.describe(addr), value));
+                    }
+                    match info.state {
+                        Some(state @ State::Yielded(_)) => out.push(self.stopped(state)),
+                        Some(state) => {
+                            out.push(self.stopped(state));
+                            break;
+                        }
+                        None => {}
+                    }
+                }
+            }
+            Command::Continue => loop {
+                let state = self.c.next()?;
+                if let State::Paused {
+                    reason: Pause::Breakpoint { ptr },
+                } = state
+                {
+                    match self.hit(ptr) {
+                        Ok(false) => continue,
+                        Ok(true) => {}
+                        Err(msg) => {
+                            out.push(self.stopped(state));
+                            out.push(format!("error: failed to evaluate condition: {}", msg));
+                            break;
+                        }
+                    }
+                }
+                out.push(self.stopped(state));
+                if !matches!(state, State::Yielded(_)) {
+                    break;
+                }
+            },
+            Command::Break { loc, cond, count } => {
+                let addr = self.resolve(loc).map_err(anyhow::Error::msg)?;
+                self.c.add_breakpoint(addr);
+                self.breakpoints.insert(
+                    addr,
+                    Breakpoint {
+                        cond: cond.clone(),
+                        count: *count,
+                        hits: 0,
+                    },
+                );
+                let mut msg = format!("breakpoint set at {}", self.describe(addr));
+                if let Some(cond) = cond {
+                    msg.push_str(&format!(" if {}", cond));
+                }
+                if let Some(count) = count {
+                    msg.push_str(&format!(" count {}", count));
+                }
+                out.push(msg);
+            }
+            Command::Delete(loc) => {
+                let addr = self.resolve(loc).map_err(anyhow::Error::msg)?;
+                self.breakpoints.remove(&addr);
+                match self.c.remove_breakpoint(addr) {
+                    true => out.push(format!("breakpoint removed at {}", self.describe(addr))),
+                    false => out.push(format!("no breakpoint at {}", self.describe(addr))),
+                }
+            }
+            Command::Print(loc) => {
+                let addr = self.resolve(loc).map_err(anyhow::Error::msg)?;
+                out.push(format!("{} = {}", self.describe(addr), self.c.peek(addr)));
+            }
+            Command::Watch(expr) => {
+                self.watches.push(expr.clone());
+                out.extend(self.watches().pop());
+            }
+            Command::Unwatch(n) => match n.checked_sub(1).filter(|&i| i < self.watches.len()) {
+                Some(i) => {
+                    let expr = self.watches.remove(i);
+                    out.push(format!("removed watch {}: {}", n, expr));
+                }
+                None => anyhow::bail!("no watch {}", n),
+            },
+            Command::Regs => {
+                out.push(format!("ptr: {}", self.describe(self.c.ptr())));
+                out.push(format!("rb:  {}", self.c.relative_base()));
+                out.push(format!("instructions: {}", self.c.stats().instructions));
+            }
+            Command::Disas(n) => {
+                let mut addr = self.c.ptr();
+                for _ in 0..*n {
+                    let (line, next) = self.listing(addr);
+                    out.push(line);
+                    addr = next;
+                }
+            }
+            Command::Input(values) => {
+                self.c.feed(values.iter().copied());
+                out.push(format!("fed {} values", values.len()));
+            }
+            Command::Help => out.push(HELP.to_owned()),
+            Command::Quit => {}
+        }
+        if matches!(cmd, Command::Step(_) | Command::Continue) {
+            out.extend(self.watches());
+        }
+        Ok(out.join("\n"))
+    }
+
+    /// Reads and executes commands until the user quits.
+    pub fn session(&mut self, lines: &mut Lines) -> Result<()> {
+        loop {
+            eprint!("(intcode) ");
+            io::stderr().flush()?;
+            let line = lines.read_line()?;
+            if line.is_empty() {
+                break Ok(());
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let cmd = match line.parse() {
+                Ok(Command::Quit) => break Ok(()),
+                Ok(cmd) => cmd,
+                Err(msg) => {
+                    eprintln!("error: {}", msg);
+                    continue;
+                }
+            };
+            match self.exec(&cmd) {
+                Ok(text) if text.is_empty() => {}
+                Ok(text) => println!("{}", text),
+                Err(err) => eprintln!("error: {:#}", err),
+            }
+        }
+    }
+}
+
+impl Env for Scope<'_> {
+    fn mem(&self, addr: usize) -> i64 {
+        self.c.peek(addr)
+    }
+
+    fn ptr(&self) -> usize {
+        self.c.ptr()
+    }
+
+    fn rb(&self) -> i64 {
+        self.c.relative_base()
+    }
+
+    fn label(&self, name: &str) -> Option<usize> {
+        self.debug
+            .labels
+            .iter()
+            .find(|(label, _)| label == name)
+            .map(|(_, addr)| *addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn debugger() -> Debugger {
+        let asm = "
+    IN x
+loop:
+    ADD x, #-1, x
+    OUT x
+    JNZ x, #loop
+    HLT
+x:  DB 0
+";
+        let intcode = intcode::assemble::to_intcode(asm).unwrap();
+        Debugger::new(Computer::new(intcode.output), intcode.debug)
+    }
+
+    #[track_caller]
+    fn exec(d: &mut Debugger, cmd: &str) -> String {
+        d.exec(&cmd.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!("s 3".parse(), Ok(Command::Step(3)));
+        assert_eq!(
+            "break x+1".parse(),
+            Ok(Command::Break {
+                loc: Location::Label("x".into(), 1),
+                cond: None,
+                count: None,
+            })
+        );
+        assert_eq!(
+            "b 247 if mem[1000] == 5 count 10".parse(),
+            Ok(Command::Break {
+                loc: Location::Addr(247),
+                cond: Some("mem[1000] == 5".parse().unwrap()),
+                count: Some(10),
+            })
+        );
+        assert_eq!(
+            "break 247 count x".parse::<Command>(),
+            Err("invalid count `x`".into())
+        );
+        assert_eq!(
+            "break 247 when x".parse::<Command>(),
+            Err("expected `if <cond>` or `count <n>`, found `when x`".into())
+        );
+        assert!("break 247 if mem[x"
+            .parse::<Command>()
+            .unwrap_err()
+            .starts_with("invalid condition\n"));
+        assert_eq!("p 12".parse(), Ok(Command::Print(Location::Addr(12))));
+        assert_eq!("input 1,2".parse(), Ok(Command::Input(vec![1, 2])));
+        assert_eq!(
+            "print".parse::<Command>(),
+            Err("`print` expects a location".into())
+        );
+        assert_eq!(
+            "jump".parse::<Command>(),
+            Err("unknown command `jump`, type `help` for a list of commands".into())
+        );
+    }
+
+    #[test]
+    fn debug_session() {
+        let mut d = debugger();
+        assert_eq!(
+            exec(&mut d, "disas 2"),
+            "=>     0  IN x\n       2  loop: ADD x, #-1, x"
+        );
+        assert_eq!(exec(&mut d, "step"), "waiting for input at 0");
+        assert_eq!(exec(&mut d, "input 2"), "fed 1 values");
+        assert_eq!(exec(&mut d, "break loop"), "breakpoint set at 2 (loop)");
+        assert_eq!(exec(&mut d, "continue"), "breakpoint at 2 (loop)");
+        assert_eq!(
+            exec(&mut d, "step 2"),
+            "       2  ADD x, #-1, x\n          12 (x) = 1\n       6  OUT x\noutput: 1"
+        );
+        assert_eq!(exec(&mut d, "print x"), "12 (x) = 1");
+        assert_eq!(
+            exec(&mut d, "regs"),
+            "ptr: 8 (loop+6)\nrb:  0\ninstructions: 3"
+        );
+        assert_eq!(
+            exec(&mut d, "delete loop"),
+            "breakpoint removed at 2 (loop)"
+        );
+        assert_eq!(exec(&mut d, "continue"), "output: 0\nprogram halted");
+        assert_eq!(
+            d.exec(&"break y".parse().unwrap()).unwrap_err().to_string(),
+            "unknown label `y`"
+        );
+    }
+
+    #[test]
+    fn conditional_breakpoints() {
+        let mut d = debugger();
+        exec(&mut d, "input 5");
+        assert_eq!(
+            exec(&mut d, "break loop if mem[x] < 3"),
+            "breakpoint set at 2 (loop) if mem[x] < 3"
+        );
+        assert_eq!(
+            exec(&mut d, "continue"),
+            "output: 4\noutput: 3\noutput: 2\nbreakpoint at 2 (loop)"
+        );
+        assert_eq!(exec(&mut d, "print x"), "12 (x) = 2");
+        assert_eq!(
+            exec(&mut d, "break loop count 2"),
+            "breakpoint set at 2 (loop) count 2"
+        );
+        assert_eq!(
+            exec(&mut d, "continue"),
+            "output: 1\noutput: 0\nprogram halted"
+        );
+    }
+
+    #[test]
+    fn watch_expressions() {
+        let mut d = debugger();
+        assert_eq!(
+            exec(&mut d, "watch mem[x] * 2 + rb"),
+            "1: mem[x] * 2 + rb = 0"
+        );
+        assert_eq!(exec(&mut d, "w ptr"), "2: ptr = 0");
+        exec(&mut d, "input 3");
+        assert_eq!(
+            exec(&mut d, "step 2"),
+            "       0  IN x\n          12 (x) = 3\n       2  ADD x, #-1, x\n          12 (x) = 2\n1: mem[x] * 2 + rb = 4\n2: ptr = 6"
+        );
+        assert_eq!(exec(&mut d, "unwatch 2"), "removed watch 2: ptr");
+        assert_eq!(exec(&mut d, "watch y"), "2: y = <error: unknown label `y`>");
+        assert_eq!(
+            d.exec(&Command::Unwatch(3)).unwrap_err().to_string(),
+            "no watch 3"
+        );
+        assert!("watch mem["
+            .parse::<Command>()
+            .unwrap_err()
+            .starts_with("invalid expression\n"));
+    }
+
+    #[test]
+    fn hit_counts() {
+        let mut d = debugger();
+        exec(&mut d, "input 5");
+        exec(&mut d, "break loop count 3");
+        assert_eq!(
+            exec(&mut d, "continue"),
+            "output: 4\noutput: 3\nbreakpoint at 2 (loop)"
+        );
+        assert_eq!(exec(&mut d, "print x"), "12 (x) = 3");
+        exec(&mut d, "break loop if y");
+        assert_eq!(
+            exec(&mut d, "continue"),
+            "output: 2\nbreakpoint at 2 (loop)\nerror: failed to evaluate condition: unknown label `y`"
+        );
+    }
+}
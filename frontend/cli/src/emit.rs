@@ -0,0 +1,395 @@
+//! Emit assembled intcode in different output formats.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::result;
+use std::str::FromStr;
+
+use intcode::assemble::bundle::Bundle;
+use intcode::assemble::debug::DebugInfo;
+use intcode::Image;
+
+/// The number of values to place on a single line in source code formats.
+const PER_LINE: usize = 16;
+
+/// An output format for an assembled program.
+pub trait Emit {
+    /// The file extension to use when no output file is given.
+    fn extension(&self) -> &'static str;
+
+    /// Write the program to the given writer.
+    ///
+    /// If debug information is given then formats that can hold it embed the
+    /// label addresses alongside the program. The source is the path of the
+    /// assembly file.
+    fn emit(
+        &self,
+        w: &mut dyn Write,
+        intcode: &[i64],
+        debug: Option<&DebugInfo>,
+        source: &Path,
+    ) -> io::Result<()>;
+}
+
+/// The formats that can be selected on the command line.
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Intcode,
+    Rust,
+    C,
+    Python,
+    Meta,
+    Bin,
+}
+
+/// Comma separated intcode.
+struct Intcode;
+
+/// A Rust constant slice.
+struct Rust;
+
+/// A C array.
+struct C;
+
+/// A Python list.
+struct Python;
+
+/// Comma separated intcode after a header with the debug information.
+struct Meta;
+
+/// The compact binary form, each value as a varint.
+struct Bin;
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "intcode" => Ok(Self::Intcode),
+            "rust" => Ok(Self::Rust),
+            "c" => Ok(Self::C),
+            "python" => Ok(Self::Python),
+            "meta" => Ok(Self::Meta),
+            "bin" => Ok(Self::Bin),
+            s => Err(format!(
+                "unknown format `{}`, expected one of `intcode`, `rust`, `c`, `python`, `meta`, \
+                 or `bin`",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    pub fn emitter(self) -> &'static dyn Emit {
+        match self {
+            Self::Intcode => &Intcode,
+            Self::Rust => &Rust,
+            Self::C => &C,
+            Self::Python => &Python,
+            Self::Meta => &Meta,
+            Self::Bin => &Bin,
+        }
+    }
+}
+
+/// Writes the values as indented lines each ending with a comma.
+fn write_lines(w: &mut dyn Write, intcode: &[i64], literal: fn(&i64) -> String) -> io::Result<()> {
+    for chunk in intcode.chunks(PER_LINE) {
+        let line = chunk.iter().map(literal).collect::<Vec<_>>().join(", ");
+        writeln!(w, "    {},", line)?;
+    }
+    Ok(())
+}
+
+impl Emit for Intcode {
+    fn extension(&self) -> &'static str {
+        "intcode"
+    }
+
+    fn emit(
+        &self,
+        w: &mut dyn Write,
+        intcode: &[i64],
+        _: Option<&DebugInfo>,
+        _: &Path,
+    ) -> io::Result<()> {
+        let output = intcode
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        w.write_all(output.as_bytes())
+    }
+}
+
+impl Emit for Rust {
+    fn extension(&self) -> &'static str {
+        "rs"
+    }
+
+    fn emit(
+        &self,
+        w: &mut dyn Write,
+        intcode: &[i64],
+        debug: Option<&DebugInfo>,
+        _: &Path,
+    ) -> io::Result<()> {
+        writeln!(w, "pub const PROGRAM: &[i64] = &[")?;
+        write_lines(w, intcode, i64::to_string)?;
+        writeln!(w, "];")?;
+        if let Some(debug) = debug {
+            writeln!(w)?;
+            writeln!(w, "pub const LABELS: &[(&str, usize)] = &[")?;
+            for (name, addr) in &debug.labels {
+                writeln!(w, "    ({:?}, {}),", name, addr)?;
+            }
+            writeln!(w, "];")?;
+        }
+        Ok(())
+    }
+}
+
+impl Emit for C {
+    fn extension(&self) -> &'static str {
+        "h"
+    }
+
+    fn emit(
+        &self,
+        w: &mut dyn Write,
+        intcode: &[i64],
+        debug: Option<&DebugInfo>,
+        _: &Path,
+    ) -> io::Result<()> {
+        writeln!(w, "#include <stddef.h>")?;
+        writeln!(w, "#include <stdint.h>")?;
+        writeln!(w)?;
+        // Static so that the header can be included in more than one file.
+        writeln!(w, "static const size_t PROGRAM_LEN = {};", intcode.len())?;
+        writeln!(w)?;
+        writeln!(w, "static const int64_t PROGRAM[] = {{")?;
+        write_lines(w, intcode, c_literal)?;
+        writeln!(w, "}};")?;
+        if let Some(debug) = debug {
+            writeln!(w)?;
+            // The macro names are upper case, so labels that only differ in
+            // case would be defined twice.
+            let mut macros = HashMap::new();
+            for (name, addr) in &debug.labels {
+                let upper = name.to_uppercase();
+                if let Some(other) = macros.insert(upper.clone(), name) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "labels `{}` and `{}` would both be defined as `LABEL_{}`",
+                            other, name, upper
+                        ),
+                    ));
+                }
+                writeln!(w, "#define LABEL_{} {}", upper, addr)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats the value as a C integer literal.
+///
+/// The literal `9223372036854775808` doesn't fit in an `int64_t` so the
+/// minimum value can't be written as a negated literal.
+fn c_literal(value: &i64) -> String {
+    match *value {
+        i64::MIN => "(-9223372036854775807LL - 1)".to_owned(),
+        value => value.to_string(),
+    }
+}
+
+impl Emit for Python {
+    fn extension(&self) -> &'static str {
+        "py"
+    }
+
+    fn emit(
+        &self,
+        w: &mut dyn Write,
+        intcode: &[i64],
+        debug: Option<&DebugInfo>,
+        _: &Path,
+    ) -> io::Result<()> {
+        writeln!(w, "PROGRAM = [")?;
+        write_lines(w, intcode, i64::to_string)?;
+        writeln!(w, "]")?;
+        if let Some(debug) = debug {
+            writeln!(w)?;
+            writeln!(w, "LABELS = {{")?;
+            for (name, addr) in &debug.labels {
+                writeln!(w, "    {:?}: {},", name, addr)?;
+            }
+            writeln!(w, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Emit for Meta {
+    fn extension(&self) -> &'static str {
+        "intcode"
+    }
+
+    fn emit(
+        &self,
+        w: &mut dyn Write,
+        intcode: &[i64],
+        debug: Option<&DebugInfo>,
+        source: &Path,
+    ) -> io::Result<()> {
+        let debug = debug.cloned().unwrap_or_default();
+        // Only the file name is kept so that the output doesn't depend on
+        // where the file was built.
+        let mut bundle = Bundle::new(intcode.to_vec(), debug);
+        if let Some(name) = source.file_name() {
+            bundle = bundle.source(name.to_string_lossy());
+        }
+        write!(w, "{}", bundle)
+    }
+}
+
+impl Emit for Bin {
+    fn extension(&self) -> &'static str {
+        "bin"
+    }
+
+    fn emit(
+        &self,
+        w: &mut dyn Write,
+        intcode: &[i64],
+        _: Option<&DebugInfo>,
+        _: &Path,
+    ) -> io::Result<()> {
+        w.write_all(&Image::new(intcode.to_vec()).to_compact())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn emit(format: &str, labels: bool) -> String {
+        let asm = "
+start:
+    OUT #1
+    HLT
+";
+        let intcode = intcode::assemble::to_intcode(asm).unwrap();
+        let debug = labels.then(|| &intcode.debug);
+        let mut buf = Vec::new();
+        let format: Format = format.parse().unwrap();
+        format
+            .emitter()
+            .emit(&mut buf, &intcode.output, debug, Path::new("test.ints"))
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn emit_rust() {
+        assert_eq!(
+            emit("rust", false),
+            "pub const PROGRAM: &[i64] = &[\n    104, 1, 99,\n];\n"
+        );
+        assert_eq!(
+            emit("rust", true),
+            "pub const PROGRAM: &[i64] = &[
+    104, 1, 99,
+];
+
+pub const LABELS: &[(&str, usize)] = &[
+    (\"start\", 0),
+];
+"
+        );
+    }
+
+    #[test]
+    fn emit_c() {
+        assert_eq!(
+            emit("c", false),
+            "#include <stddef.h>
+#include <stdint.h>
+
+static const size_t PROGRAM_LEN = 3;
+
+static const int64_t PROGRAM[] = {
+    104, 1, 99,
+};
+"
+        );
+        assert!(emit("c", true).ends_with("};\n\n#define LABEL_START 0\n"));
+    }
+
+    #[test]
+    fn emit_c_min() {
+        let mut buf = Vec::new();
+        C.emit(&mut buf, &[i64::MIN, -1], None, Path::new("test.ints"))
+            .unwrap();
+        assert!(String::from_utf8(buf)
+            .unwrap()
+            .contains("\n    (-9223372036854775807LL - 1), -1,\n"));
+    }
+
+    #[test]
+    fn emit_c_label_collision() {
+        let asm = "start: OUT #1\nStart: HLT\n";
+        let intcode = intcode::assemble::to_intcode(asm).unwrap();
+        let mut buf = Vec::new();
+        let err = C
+            .emit(
+                &mut buf,
+                &intcode.output,
+                Some(&intcode.debug),
+                Path::new("test.ints"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "labels `start` and `Start` would both be defined as `LABEL_START`"
+        );
+    }
+
+    #[test]
+    fn emit_python() {
+        assert_eq!(emit("python", false), "PROGRAM = [\n    104, 1, 99,\n]\n");
+        assert_eq!(
+            emit("python", true),
+            "PROGRAM = [
+    104, 1, 99,
+]
+
+LABELS = {
+    \"start\": 0,
+}
+"
+        );
+    }
+
+    #[test]
+    fn emit_long_lines() {
+        let intcode: Vec<i64> = (0..20).collect();
+        let mut buf = Vec::new();
+        Python
+            .emit(&mut buf, &intcode, None, Path::new("test.ints"))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "PROGRAM = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    16, 17, 18, 19,
+]
+"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Write the machine event log to a file.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::result;
+use std::str::FromStr;
+
+use anyhow::Result;
+use intcode::run::events::{EventLog, EventMask};
+
+/// The format to write the event log in.
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+/// The kinds of events to record.
+#[derive(Debug, Clone, Copy)]
+pub struct Mask(pub EventMask);
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            s => Err(format!(
+                "unknown format `{}`, expected one of `json` or `csv`",
+                s
+            )),
+        }
+    }
+}
+
+impl FromStr for Mask {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        s.split(',')
+            .map(|kind| match kind.trim() {
+                "all" => Ok(EventMask::ALL),
+                "input" => Ok(EventMask::INPUT),
+                "output" => Ok(EventMask::OUTPUT),
+                "jump" => Ok(EventMask::JUMP),
+                "arb" => Ok(EventMask::ADJUST_RELATIVE_BASE),
+                kind => Err(format!(
+                    "unknown event kind `{}`, expected one of `all`, `input`, `output`, `jump`, or `arb`",
+                    kind
+                )),
+            })
+            .try_fold(EventMask::NONE, |mask, kind| Ok(mask | kind?))
+            .map(Self)
+    }
+}
+
+pub fn write(path: &Path, format: Format, log: &EventLog) -> Result<()> {
+    let w = BufWriter::new(File::create(path)?);
+    match format {
+        Format::Json => serde_json::to_writer_pretty(w, log.events())?,
+        Format::Csv => log.write_csv(w)?,
+    }
+    Ok(())
+}
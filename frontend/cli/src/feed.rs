@@ -0,0 +1,217 @@
+//! Parse input to feed to a program.
+//!
+//! A feed can be given in one of the following forms:
+//!
+//! - `1,2,3`: comma separated integers.
+//! - `ascii:"north\n"`: the bytes of a string, a few escapes are supported.
+//! - `@path/to/file`: the bytes of a file.
+//!
+//! Programs that declare their inputs using the `INPUTS` directive can also
+//! be given each input by name, as in `x=5`.
+
+use std::fs;
+use std::path::PathBuf;
+use std::result;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+use crate::parse_program;
+
+/// A specification of input values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedSpec {
+    /// Exact integers.
+    Values(Vec<i64>),
+    /// The bytes of a string.
+    Ascii(String),
+    /// The bytes of a file.
+    File(PathBuf),
+}
+
+/// A value for a named input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub name: String,
+    pub value: i64,
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected `name=value`, found `{}`", s))?;
+        let value = value
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid value for input `{}`: {}", name, err))?;
+        Ok(Self {
+            name: name.trim().to_owned(),
+            value,
+        })
+    }
+}
+
+/// Returns the values of the bindings in the order of the declared inputs.
+pub fn bind(inputs: &[String], bindings: &[Binding]) -> result::Result<Vec<i64>, String> {
+    if let Some(b) = bindings.iter().find(|b| !inputs.contains(&b.name)) {
+        return Err(match inputs.is_empty() {
+            true => format!(
+                "program does not declare any inputs, but `{}` was given",
+                b.name
+            ),
+            false => format!(
+                "program has no input named `{}`, expected one of `{}`",
+                b.name,
+                inputs.join("`, `")
+            ),
+        });
+    }
+    inputs
+        .iter()
+        .map(|name| {
+            let mut values = bindings.iter().filter(|b| b.name == *name);
+            match (values.next(), values.next()) {
+                (Some(b), None) => Ok(b.value),
+                (Some(_), Some(_)) => Err(format!("input `{}` was given more than once", name)),
+                (None, _) => Err(format!("missing a value for input `{}`", name)),
+            }
+        })
+        .collect()
+}
+
+impl FromStr for FeedSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix('@') {
+            if path.is_empty() {
+                return Err("expected a file path after `@`".into());
+            }
+            Ok(Self::File(path.into()))
+        } else if let Some(ascii) = s.strip_prefix("ascii:") {
+            unquote(ascii).map(Self::Ascii)
+        } else {
+            parse_program(s)
+                .map(Self::Values)
+                .map_err(|err| format!("invalid integer feed `{}`: {}", s, err))
+        }
+    }
+}
+
+/// Parse a string, if it is quoted then the escapes `\n`, `\r`, `\t`, `\0`,
+/// `\\`, and `\"` are supported.
+fn unquote(s: &str) -> result::Result<String, String> {
+    let inner = match s.strip_prefix('"') {
+        Some(rest) => rest
+            .strip_suffix('"')
+            .ok_or_else(|| format!("undelimited string `{}`", s))?,
+        None => return Ok(s.to_owned()),
+    };
+    let mut value = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let c = match chars.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some(c) => return Err(format!("unknown escape character `{}`", c)),
+            None => return Err("unexpected end of string after `\\`".into()),
+        };
+        value.push(c);
+    }
+    Ok(value)
+}
+
+impl FeedSpec {
+    /// Returns the values to feed to the program.
+    pub fn values(&self) -> Result<Vec<i64>> {
+        match self {
+            Self::Values(values) => Ok(values.clone()),
+            Self::Ascii(s) => Ok(s.bytes().map(i64::from).collect()),
+            Self::File(path) => {
+                let bytes = fs::read(path)
+                    .with_context(|| format!("failed to read `{}`", path.display()))?;
+                Ok(bytes.into_iter().map(i64::from).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let spec: FeedSpec = "1,2,-3".parse().unwrap();
+        assert_eq!(spec, FeedSpec::Values(vec![1, 2, -3]));
+        assert!("1,x".parse::<FeedSpec>().is_err());
+    }
+
+    #[test]
+    fn ascii() {
+        let spec: FeedSpec = r#"ascii:"north\n""#.parse().unwrap();
+        assert_eq!(spec, FeedSpec::Ascii("north\n".into()));
+        assert_eq!(spec.values().unwrap(), [110, 111, 114, 116, 104, 10]);
+
+        let spec: FeedSpec = "ascii:hi".parse().unwrap();
+        assert_eq!(spec.values().unwrap(), [104, 105]);
+
+        assert!(r#"ascii:"north"#.parse::<FeedSpec>().is_err());
+        assert!(r#"ascii:"\q""#.parse::<FeedSpec>().is_err());
+    }
+
+    #[test]
+    fn bindings() {
+        let inputs = ["x".to_owned(), "y".to_owned()];
+        let b = |s: &str| s.parse::<Binding>().unwrap();
+        assert_eq!(bind(&inputs, &[b("y=7"), b("x=5")]), Ok(vec![5, 7]));
+        assert_eq!(
+            bind(&inputs, &[b("x=5")]),
+            Err("missing a value for input `y`".into())
+        );
+        assert_eq!(
+            bind(&inputs, &[b("x=5"), b("y=7"), b("z=1")]),
+            Err("program has no input named `z`, expected one of `x`, `y`".into())
+        );
+        assert!(bind(&inputs, &[b("x=5"), b("x=6"), b("y=7")]).is_err());
+        assert!(bind(&[], &[b("x=5")]).is_err());
+        assert!("x".parse::<Binding>().is_err());
+        assert!("x=a".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn file() {
+        let spec: FeedSpec = "@input.txt".parse().unwrap();
+        assert_eq!(spec, FeedSpec::File("input.txt".into()));
+        assert!("@".parse::<FeedSpec>().is_err());
+
+        let dir = std::env::temp_dir().join("intcode-feed");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("input.txt"), "hi\n").unwrap();
+        let spec = FeedSpec::File(dir.join("input.txt"));
+        assert_eq!(spec.values().unwrap(), [104, 105, 10]);
+
+        fs::write(dir.join("empty.txt"), "").unwrap();
+        let spec = FeedSpec::File(dir.join("empty.txt"));
+        assert!(spec.values().unwrap().is_empty());
+
+        let missing = dir.join("missing.txt");
+        let err = FeedSpec::File(missing.clone()).values().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("failed to read `{}`", missing.display())
+        );
+    }
+}
@@ -1,8 +1,12 @@
 use std::fmt;
 use std::fmt::Display;
 use std::path::Path;
+use std::result;
+use std::str::FromStr;
 
-use intcode::error::fmt::{Context, Mark, Options, Paint};
+use intcode::error::files::Files;
+use intcode::error::{Error, Warning};
+use intcode::fmt::{Context, Json, Mark, Options, Paint};
 
 pub struct Ansi;
 
@@ -10,6 +14,10 @@ impl Ansi {
     pub fn new<'i>(input: &'i str, path: &'i Path) -> Options<'i, Self> {
         Options::new(input, path)
     }
+
+    pub fn files(files: &Files) -> Options<'_, Self> {
+        Options::with_files(files)
+    }
 }
 
 impl Paint for Ansi {
@@ -23,6 +31,7 @@ impl Paint for Ansi {
             (_, Mark::Margin) => yansi::Paint::blue(this).bold(),
             (Context::Warning, Mark::Underline) => yansi::Paint::yellow(this).bold(),
             (Context::Error, Mark::Underline) => yansi::Paint::red(this).bold(),
+            (Context::Note, Mark::Underline) => yansi::Paint::cyan(this).bold(),
             (_, Mark::Message) => yansi::Paint::default(this).bold(),
             (_, _) => yansi::Paint::default(this),
         };
@@ -30,3 +39,62 @@ impl Paint for Ansi {
         Ok(())
     }
 }
+
+/// How to write assembler diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageFormat {
+    /// The source snippet with the span underlined.
+    Human,
+    /// A JSON object on each line.
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            s => Err(format!(
+                "unknown message format `{}`, expected one of `human` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+impl MessageFormat {
+    pub fn error(self, files: &Files, error: &Error) -> String {
+        match self {
+            Self::Human => Ansi::files(files).error(error),
+            Self::Json => Json::files(files).error(error),
+        }
+    }
+
+    pub fn warning(self, files: &Files, warning: &Warning) -> String {
+        match self {
+            Self::Human => Ansi::files(files).warning(warning),
+            Self::Json => Json::files(files).warning(warning),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_format() {
+        assert_eq!("human".parse(), Ok(MessageFormat::Human));
+        assert_eq!("json".parse(), Ok(MessageFormat::Json));
+        assert!("xml".parse::<MessageFormat>().is_err());
+
+        let files = Files::new("main.ints", "OUT x\n");
+        let error = Error::new("undefined label", 4..5);
+        assert_eq!(
+            MessageFormat::Json.error(&files, &error),
+            r#"{"severity":"error","message":"undefined label","file":"main.ints","span":{"start":4,"end":5},"line":1,"column":5}"#
+        );
+    }
+}
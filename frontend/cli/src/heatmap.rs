@@ -0,0 +1,58 @@
+//! Write the memory access heatmap to a file.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::result;
+use std::str::FromStr;
+
+use anyhow::Result;
+use intcode::run::heatmap::Heatmap;
+use serde_json::json;
+
+/// The format to write the heatmap in.
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Csv,
+    Json,
+    Ppm,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "ppm" => Ok(Self::Ppm),
+            s => Err(format!(
+                "unknown format `{}`, expected one of `csv`, `json`, or `ppm`",
+                s
+            )),
+        }
+    }
+}
+
+pub fn write(path: &Path, format: Format, width: usize, heatmap: &Heatmap) -> Result<()> {
+    let w = BufWriter::new(File::create(path)?);
+    match format {
+        Format::Csv => heatmap.write_csv(w)?,
+        Format::Json => {
+            let cells: Vec<_> = heatmap
+                .iter()
+                .map(|(addr, c)| {
+                    json!({
+                        "address": addr,
+                        "reads": c.reads,
+                        "writes": c.writes,
+                        "executes": c.executes,
+                    })
+                })
+                .collect();
+            serde_json::to_writer_pretty(w, &cells)?
+        }
+        Format::Ppm => heatmap.write_ppm(w, width)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,178 @@
+//! Read lines of input for a program running interactively.
+//!
+//! With the `readline` feature enabled and a terminal attached, lines are
+//! read using a line editor. This supports arrow key editing, history that is
+//! persisted to a file, and tab completion of the list entries and words the
+//! program printed or commands that were typed before.
+
+use std::io;
+use std::io::{BufRead, BufReader, Stdin};
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+/// Reads lines from stdin.
+pub struct Lines {
+    stdin: BufReader<Stdin>,
+    #[cfg(feature = "readline")]
+    editor: Option<editor::LineEditor>,
+}
+
+impl Lines {
+    /// Creates a new line reader, loading the history from the given file.
+    ///
+    /// History is only used when the `readline` feature is enabled.
+    #[cfg_attr(not(feature = "readline"), allow(unused_variables))]
+    pub fn new(history: Option<PathBuf>) -> Self {
+        Self {
+            stdin: BufReader::new(io::stdin()),
+            #[cfg(feature = "readline")]
+            editor: atty::is(atty::Stream::Stdin).then(|| editor::LineEditor::new(history)),
+        }
+    }
+
+    /// Reads the next line including the newline, or an empty string once
+    /// there is no more input.
+    pub fn read_line(&mut self) -> Result<String> {
+        #[cfg(feature = "readline")]
+        if let Some(editor) = &mut self.editor {
+            return editor.read_line();
+        }
+        let mut line = String::new();
+        self.stdin.read_line(&mut line)?;
+        Ok(line)
+    }
+
+    /// Remembers text printed by the program for tab completion.
+    #[cfg_attr(not(feature = "readline"), allow(unused_variables))]
+    pub fn learn(&mut self, text: &str) {
+        #[cfg(feature = "readline")]
+        if let Some(editor) = &mut self.editor {
+            editor.learn(text);
+        }
+    }
+}
+
+#[cfg(feature = "readline")]
+mod editor {
+    use std::collections::BTreeSet;
+    use std::iter;
+    use std::path::PathBuf;
+    use std::process;
+
+    use anyhow::Result;
+    use rustyline::completion::Completer;
+    use rustyline::error::ReadlineError;
+    use rustyline::highlight::Highlighter;
+    use rustyline::hint::Hinter;
+    use rustyline::validate::Validator;
+    use rustyline::{Context, Editor, Helper};
+
+    /// A line editor with persistent history.
+    pub struct LineEditor {
+        editor: Editor<Completions>,
+        history: Option<PathBuf>,
+    }
+
+    /// The phrases that can be tab completed.
+    #[derive(Default)]
+    struct Completions {
+        phrases: BTreeSet<String>,
+    }
+
+    impl Completions {
+        fn add_words(&mut self, text: &str) {
+            let words = text
+                .split(|c: char| !c.is_ascii_alphabetic())
+                .filter(|w| w.len() > 2);
+            self.phrases.extend(words.map(str::to_owned));
+        }
+    }
+
+    impl Completer for Completions {
+        type Candidate = String;
+
+        /// Completes the longest suffix of the line before the cursor that
+        /// starts a known phrase.
+        fn complete(
+            &self,
+            line: &str,
+            pos: usize,
+            _: &Context<'_>,
+        ) -> rustyline::Result<(usize, Vec<String>)> {
+            let line = &line[..pos];
+            let starts = iter::once(0).chain(line.match_indices(' ').map(|(i, _)| i + 1));
+            for start in starts {
+                let prefix = &line[start..];
+                let matches: Vec<_> = self
+                    .phrases
+                    .iter()
+                    .filter(|p| p.len() > prefix.len() && p.starts_with(prefix))
+                    .cloned()
+                    .collect();
+                if !matches.is_empty() {
+                    return Ok((start, matches));
+                }
+            }
+            Ok((pos, Vec::new()))
+        }
+    }
+
+    impl Hinter for Completions {
+        type Hint = String;
+    }
+
+    impl Highlighter for Completions {}
+
+    impl Validator for Completions {}
+
+    impl Helper for Completions {}
+
+    impl LineEditor {
+        pub fn new(history: Option<PathBuf>) -> Self {
+            let mut editor = Editor::new();
+            editor.set_helper(Some(Completions::default()));
+            if let Some(path) = &history {
+                // The history file does not exist the first time.
+                if editor.load_history(path).is_err() {
+                    log::debug!("no history loaded from {}", path.display());
+                }
+            }
+            Self { editor, history }
+        }
+
+        pub fn read_line(&mut self) -> Result<String> {
+            match self.editor.readline("") {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        self.editor.add_history_entry(line.as_str());
+                        if let Some(path) = &self.history {
+                            self.editor.save_history(path)?;
+                        }
+                        let completions = self.editor.helper_mut().unwrap();
+                        completions.add_words(&line);
+                        completions.phrases.insert(line.trim().to_owned());
+                    }
+                    Ok(line + "\n")
+                }
+                Err(ReadlineError::Eof) => Ok(String::new()),
+                Err(ReadlineError::Interrupted) => process::exit(130),
+                Err(err) => Err(err.into()),
+            }
+        }
+
+        /// Remembers the list entries like `- north` or `- mug` as whole
+        /// phrases, as well as any other words.
+        pub fn learn(&mut self, text: &str) {
+            let completions = self.editor.helper_mut().unwrap();
+            for line in text.lines() {
+                match line.strip_prefix("- ") {
+                    Some(entry) => {
+                        completions.phrases.insert(entry.trim().to_owned());
+                    }
+                    None => completions.add_words(line),
+                }
+            }
+        }
+    }
+}
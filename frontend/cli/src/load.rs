@@ -0,0 +1,63 @@
+//! Load intcode programs from files.
+//!
+//! Unless asked to be exact, the program is found with
+//! [`lenient`](intcode::run::load::lenient) and every other line is reported
+//! and ignored.
+//!
+//! A bundle written by `build --emit meta` is read as a whole instead, and
+//! its debug information is returned alongside the program. A program
+//! written by `build --emit bin` is detected and decoded too.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use intcode::assemble::bundle::{self, Bundle};
+use intcode::assemble::debug::DebugInfo;
+use intcode::run::load::{lenient, Lenient};
+use intcode::Image;
+
+use crate::parse_program;
+
+/// Reads a program from a file.
+///
+/// If `exact` is set then the file must contain only the program.
+pub fn program(path: &Path, exact: bool) -> Result<Vec<i64>> {
+    bundle(path, exact).map(|(program, _)| program)
+}
+
+/// Reads a program from a file, along with its debug information if the file
+/// is a bundle.
+pub fn bundle(path: &Path, exact: bool) -> Result<(Vec<i64>, Option<DebugInfo>)> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    if Image::is_compact(&bytes) {
+        let Image { program, .. } = Image::from_compact(&bytes)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        return Ok((program, None));
+    }
+    let input = String::from_utf8(bytes)
+        .with_context(|| format!("`{}` is not a text or binary program", path.display()))?;
+    if bundle::is_bundle(&input) {
+        let Bundle {
+            program,
+            assembler,
+            debug,
+            ..
+        } = input
+            .parse()
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        log::info!("loaded bundle built by {}", assembler);
+        return Ok((program, Some(debug)));
+    }
+    if exact {
+        let program = parse_program(&input)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        return Ok((program, None));
+    }
+    let Lenient { program, ignored } = lenient(&input)
+        .ok_or_else(|| anyhow!("`{}` does not contain an intcode program", path.display()))?;
+    for (line, text) in ignored {
+        log::warn!("ignored line {} of {}: {}", line, path.display(), text);
+    }
+    Ok((program, None))
+}
@@ -1,33 +1,219 @@
+//! Log messages and events to stderr, as text or as JSON.
+//!
+//! Messages from the `log` macros only have a level and some text. Events
+//! also have a name and fields so that other programs can pick out the
+//! interesting parts of a run without scraping the text. In the text format
+//! an event is written just like a message.
+
+use std::fmt;
+use std::result;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
 pub use log::{error, info, warn};
 use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Value};
 
 static LOGGER: Logger = Logger;
 
+/// Whether to write JSON instead of text.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Whether to start each line of text with a timestamp.
+static TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+
 struct Logger;
 
+/// How to write log records.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// A colored level and the message.
+    Text,
+    /// A JSON object on each line.
+    Json,
+}
+
+/// A named log record with fields.
+#[derive(Debug)]
+pub struct Event {
+    name: &'static str,
+    fields: Map<String, Value>,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            s => Err(format!(
+                "unknown log format `{}`, expected one of `text` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+/// Formats the time as an RFC 3339 timestamp in UTC.
+fn timestamp(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // Convert the days since the epoch to a civil date.
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem / 60 % 60,
+        rem % 60,
+        since.subsec_millis()
+    )
+}
+
+fn json(level: Level, target: &str, msg: String, event: Option<Event>) -> String {
+    let mut record = Map::new();
+    record.insert("timestamp".into(), timestamp(SystemTime::now()).into());
+    record.insert("level".into(), level.to_string().to_lowercase().into());
+    record.insert("target".into(), target.into());
+    record.insert("message".into(), msg.into());
+    if let Some(Event { name, fields }) = event {
+        record.insert("event".into(), name.into());
+        record.insert("fields".into(), fields.into());
+    }
+    Value::Object(record).to_string()
+}
+
+fn text(level: Level, msg: String) -> String {
+    let header = match level {
+        Level::Trace => yansi::Paint::fixed(244, "trace"),
+        Level::Debug => yansi::Paint::default("debug"),
+        Level::Info => yansi::Paint::green("info"),
+        Level::Warn => yansi::Paint::yellow("warn"),
+        Level::Error => yansi::Paint::red("error"),
+    }
+    .bold();
+    let colon = yansi::Paint::default(":").bold();
+    match TIMESTAMPS.load(Ordering::Relaxed) {
+        true => format!(
+            "{} {}{} {}",
+            timestamp(SystemTime::now()),
+            header,
+            colon,
+            msg
+        ),
+        false => format!("{}{} {}", header, colon, msg),
+    }
+}
+
+fn write(level: Level, target: &str, msg: String, event: Option<Event>) {
+    let line = match JSON.load(Ordering::Relaxed) {
+        true => json(level, target, msg, event),
+        false => text(level, msg),
+    };
+    eprintln!("{}", line);
+}
+
 impl Log for Logger {
-    fn enabled(&self, _: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
     }
 
     fn log(&self, record: &Record) {
-        let header = match record.level() {
-            Level::Trace => yansi::Paint::fixed(244, "trace"),
-            Level::Debug => yansi::Paint::default("debug"),
-            Level::Info => yansi::Paint::green("info"),
-            Level::Warn => yansi::Paint::yellow("warn"),
-            Level::Error => yansi::Paint::red("error"),
+        if self.enabled(record.metadata()) {
+            write(
+                record.level(),
+                record.target(),
+                record.args().to_string(),
+                None,
+            );
         }
-        .bold();
-        let colon = yansi::Paint::default(":").bold();
-        eprintln!("{}{} {}", header, colon, record.args());
     }
 
     fn flush(&self) {}
 }
 
-pub fn init() {
+impl Event {
+    /// Constructs an event with the given name and no fields.
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            fields: Map::new(),
+        }
+    }
+
+    /// Adds a field to the event.
+    pub fn field(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.fields.insert(key.into(), value.into());
+        self
+    }
+
+    /// Logs the event with the given level and message.
+    pub fn log(self, level: Level, msg: fmt::Arguments<'_>) {
+        if level <= log::max_level() {
+            write(level, "intcode", msg.to_string(), Some(self));
+        }
+    }
+
+    pub fn debug(self, msg: fmt::Arguments<'_>) {
+        self.log(Level::Debug, msg)
+    }
+
+    pub fn info(self, msg: fmt::Arguments<'_>) {
+        self.log(Level::Info, msg)
+    }
+
+    pub fn error(self, msg: fmt::Arguments<'_>) {
+        self.log(Level::Error, msg)
+    }
+}
+
+/// Installs the logger, only records at or above the level are written.
+pub fn init(level: LevelFilter, format: LogFormat, timestamps: bool) {
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+    TIMESTAMPS.store(timestamps, Ordering::Relaxed);
     log::set_logger(&LOGGER)
-        .map(|()| log::set_max_level(LevelFilter::Trace))
+        .map(|()| log::set_max_level(level))
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        let at = |secs, millis| {
+            timestamp(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis))
+        };
+        assert_eq!(at(0, 0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(at(951_782_400, 5), "2000-02-29T00:00:00.005Z");
+        assert_eq!(at(1_700_000_000, 123), "2023-11-14T22:13:20.123Z");
+    }
+
+    #[test]
+    fn json_records() {
+        let event = Event::new("run_finish").field("steps", 42);
+        let line = json(Level::Info, "intcode", "done".into(), Some(event));
+        let value: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["level"], "info");
+        assert_eq!(value["message"], "done");
+        assert_eq!(value["event"], "run_finish");
+        assert_eq!(value["fields"]["steps"], 42);
+    }
+}
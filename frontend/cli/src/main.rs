@@ -1,146 +1,849 @@
+mod bench;
+mod cache;
+mod checkpoint;
+mod debugger;
+mod emit;
+mod events;
+mod feed;
 mod fmt;
+mod heatmap;
+mod line;
+mod load;
 mod log;
+mod man;
+mod opt;
 mod run;
 
 use std::ffi::OsStr;
 use std::fs;
 use std::io;
-use std::io::prelude::*;
+use std::mem;
 use std::num::ParseIntError;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
 use std::result;
-use std::str::FromStr;
+use std::time::Duration;
 
-use anyhow::Result;
-use clap::{AppSettings, Clap};
-use intcode::assemble::Intcode;
+use anyhow::{Context, Result};
+use clap::{Clap, IntoApp};
+use clap_generate::generate;
+use clap_generate::generators::{Bash, Fish, PowerShell, Zsh};
+use intcode::assemble::debug::DebugInfo;
+use intcode::assemble::meta::Metadata;
+use intcode::assemble::{Intcode, WarningLevel};
 use intcode::disassemble;
+use intcode::error::files::Files;
 use intcode::error::ErrorSet;
+use intcode::run::cancel::CancelToken;
+use intcode::run::{ascii, lockstep, mem_init, Computer, UninitRead};
 
-#[derive(Debug, Clone, Clap)]
-#[clap(
-    author,
-    global_setting = AppSettings::DeriveDisplayOrder,
-    global_setting = AppSettings::DisableHelpSubcommand,
-    global_setting = AppSettings::DisableVersionForSubcommands,
-)]
-enum Opt {
-    Build {
-        /// The input file.
-        #[clap()]
-        input: PathBuf,
-
-        /// The output file.
-        #[clap(long, short)]
-        output: Option<PathBuf>,
-    },
-    Run {
-        #[clap()]
-        input: PathBuf,
-
-        #[clap(long)]
-        basic: bool,
-    },
-    Unbuild {
-        #[clap()]
-        input: PathBuf,
-        #[clap(long, multiple_occurrences(true))]
-        feed: Vec<Feed>,
-    },
-}
-
-#[derive(Debug, Clone)]
-struct Feed(Vec<i64>);
-
-impl FromStr for Feed {
-    type Err = ParseIntError;
-
-    fn from_str(s: &str) -> result::Result<Self, ParseIntError> {
-        parse_program(s).map(Self)
-    }
-}
+use crate::bench::Backend;
+use crate::cache::Cache;
+use crate::checkpoint::Checkpoints;
+use crate::feed::{Binding, FeedSpec};
+use crate::fmt::MessageFormat;
+use crate::opt::{
+    Annotate, Command, Confidence, LabelScheme, LineEnding, Lint, Opt, Shell, Syntax,
+};
+use crate::run::{Flush, Style};
+
+/// How many steps `lockstep` takes before giving up by default.
+const LOCKSTEP_STEPS: u64 = 100_000_000;
 
 fn parse_program(input: &str) -> result::Result<Vec<i64>, ParseIntError> {
     input.trim().split(',').map(str::parse).collect()
 }
 
-fn assemble(path: &Path) -> Result<Vec<i64>> {
-    let asm = fs::read_to_string(path)?;
-    let fmt = fmt::Ansi::new(&asm, path);
-    log::info!("assembling {}", path.display());
-    intcode::assemble::to_intcode(&asm)
-        .map(|Intcode { output, warnings }| {
-            for warning in warnings {
-                eprintln!("{}", fmt.warning(&warning));
+fn assemble(
+    path: &Path,
+    opts: &intcode::assemble::Options,
+    cache: Option<&Cache>,
+    format: MessageFormat,
+) -> Result<Intcode> {
+    assemble_source(&fs::read_to_string(path)?, path, opts, cache, format)
+}
+
+fn assemble_source(
+    asm: &str,
+    path: &Path,
+    opts: &intcode::assemble::Options,
+    cache: Option<&Cache>,
+    format: MessageFormat,
+) -> Result<Intcode> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let opts = opts.clone().include_dir(dir);
+    if let Some(intcode) = cache.and_then(|cache| cache.get(asm, &opts)) {
+        log::Event::new("assemble_cached")
+            .field("path", path.display().to_string())
+            .info(format_args!("using cached build of {}", path.display()));
+        return Ok(intcode);
+    }
+    log::Event::new("assemble_start")
+        .field("path", path.display().to_string())
+        .info(format_args!("assembling {}", path.display()));
+    let mut files = Files::new(path, asm);
+    let result = intcode::assemble::to_intcode_files(&mut files, &opts);
+    result
+        .map(|mut intcode| {
+            if let Some(cache) = cache {
+                if let Err(err) = cache.put(asm, &opts, &intcode) {
+                    log::warn!("failed to cache the build: {}", err);
+                }
             }
-            output
+            log::Event::new("assemble_finish")
+                .field("path", path.display().to_string())
+                .field("len", intcode.output.len())
+                .field("warnings", intcode.warnings.len())
+                .debug(format_args!("assembled {}", path.display()));
+            let warnings = mem::take(&mut intcode.warnings);
+            let set = ErrorSet {
+                warnings,
+                ..ErrorSet::default()
+            };
+            report(&files, format, set);
+            intcode
         })
-        .map_err(|ErrorSet { errors, warnings }| {
-            for warning in warnings {
-                eprintln!("{}", fmt.warning(&warning));
-            }
-            for error in errors {
-                eprintln!("{}", fmt.error(&error));
-            }
-            log::error!("could not assemble `{}`", path.display());
+        .map_err(|set: ErrorSet| {
+            let set = report(&files, format, set);
+            log::Event::new("assemble_failed")
+                .field("path", path.display().to_string())
+                .field("errors", set.errors.len())
+                .error(format_args!("could not assemble `{}`", path.display()));
             process::exit(1);
         })
 }
 
-fn build(path: PathBuf, output: Option<PathBuf>) -> Result<()> {
-    let output = output.unwrap_or_else(|| path.with_extension("intcode"));
-    let intcode = assemble(&path)?;
-    fs::write(
-        &output,
-        intcode
-            .into_iter()
-            .map(|d| d.to_string())
-            .collect::<Vec<_>>()
-            .join(","),
-    )?;
-    log::info!("finished {}", output.display());
+/// Returns the build cache unless it is disabled.
+fn cache(no_cache: bool) -> Option<Cache> {
+    match no_cache {
+        true => None,
+        false => Cache::from_env(),
+    }
+}
+
+/// Prints the errors and warnings, merging the identical ones unless they are
+/// for other programs to read.
+fn report(files: &Files, format: MessageFormat, mut set: ErrorSet) -> ErrorSet {
+    // Other programs reading JSON want every diagnostic.
+    if format == MessageFormat::Human {
+        set.normalize();
+    }
+    for warning in &set.warnings {
+        eprintln!("{}", format.warning(files, warning));
+    }
+    for error in &set.errors {
+        eprintln!("{}", format.error(files, error));
+    }
+    if set.omitted > 0 {
+        eprintln!("{}", fmt::Ansi::files(files).omitted(set.omitted));
+    }
+    set
+}
+
+fn build(args: opt::Build) -> Result<()> {
+    let opt::Build {
+        input: path,
+        output,
+        emit: format,
+        labels,
+        strip,
+        debug_file,
+        embed_meta,
+        syntax: Syntax(syntax),
+        strict,
+        no_cache,
+        message_format,
+        fingerprint,
+        deny_warnings,
+        allow,
+    } = args;
+    if strict && embed_meta {
+        log::error!("`--embed-meta` is not allowed in strict mode");
+        process::exit(1);
+    }
+    if strip && (labels || embed_meta) {
+        log::error!("`--strip` cannot be used with `--labels` or `--embed-meta`");
+        process::exit(1);
+    }
+    let emitter = format.emitter();
+    let output = output.unwrap_or_else(|| path.with_extension(emitter.extension()));
+    let level = match deny_warnings {
+        true => WarningLevel::Deny,
+        false => WarningLevel::Warn,
+    };
+    let mut opts = intcode::assemble::Options::new()
+        .embed_metadata(embed_meta)
+        .syntax(syntax)
+        .strict(strict)
+        .fingerprint(fingerprint)
+        .warning_level(level);
+    for Lint(lint) in allow {
+        opts = opts.lint(lint, WarningLevel::Allow);
+    }
+    let Intcode {
+        output: intcode,
+        debug,
+        ..
+    } = assemble(&path, &opts, cache(no_cache).as_ref(), message_format)?;
+    let mut buf = Vec::new();
+    // Only the `meta` format holds the debug information by default.
+    let keep = match format {
+        emit::Format::Meta => !strip,
+        _ => labels,
+    };
+    emitter.emit(&mut buf, &intcode, keep.then(|| &debug), &path)?;
+    fs::write(&output, buf)?;
+    if let Some(debug_file) = debug_file {
+        fs::write(&debug_file, debug.to_string())?;
+        log::info!("wrote debug info to {}", debug_file.display());
+    }
+    log::Event::new("build_finish")
+        .field("input", path.display().to_string())
+        .field("output", output.display().to_string())
+        .info(format_args!("finished {}", output.display()));
+    Ok(())
+}
+
+fn format_asm(path: PathBuf, check: bool, Syntax(syntax): Syntax) -> Result<()> {
+    let asm = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    let opts = intcode::assemble::Options::new().syntax(syntax);
+    let formatted = intcode::assemble::format::format(&asm, &opts).unwrap_or_else(|mut set| {
+        set.normalize();
+        let files = Files::new(&path, &*asm);
+        for error in &set.errors {
+            eprintln!("{}", MessageFormat::Human.error(&files, error));
+        }
+        log::error!("could not format `{}`", path.display());
+        process::exit(1);
+    });
+    if formatted == asm {
+        log::info!("{} is formatted", path.display());
+    } else if check {
+        log::error!("{} is not formatted", path.display());
+        process::exit(1);
+    } else {
+        fs::write(&path, formatted)?;
+        log::info!("formatted {}", path.display());
+    }
+    Ok(())
+}
+
+fn run(args: opt::Run) -> Result<()> {
+    let opt::Run {
+        input: path,
+        basic,
+        render,
+        events,
+        events_format: format,
+        events_mask: events::Mask(mask),
+        heatmap,
+        heatmap_format,
+        heatmap_width,
+        show_line,
+        info,
+        enable,
+        syntax: Syntax(syntax),
+        history,
+        strict,
+        poison,
+        entry,
+        mem_init,
+        keep_cr,
+        echo,
+        line_ending: LineEnding(line_ending),
+        bindings,
+        feed,
+        input_file,
+        expect,
+        expect_last,
+        flush,
+        checkpoint_dir,
+        checkpoint_every,
+        checkpoint_keep,
+        resume_from_checkpoint,
+        max_steps,
+        exact,
+        no_cache,
+        message_format,
+    } = args;
+    let line_opts = ascii::LineOptions::new()
+        .strip_cr(!keep_cr)
+        .echo(echo)
+        .ending(line_ending);
+    let feeds: Vec<_> = feed
+        .into_iter()
+        .chain(input_file.map(FeedSpec::File))
+        .collect();
+    let (intcode, source, bundled) = match path.extension().and_then(OsStr::to_str) {
+        Some("ints") => {
+            let asm = fs::read_to_string(&path)?;
+            let opts = intcode::assemble::Options::new()
+                .syntax(syntax)
+                .strict(strict);
+            let Intcode { output, debug, .. } =
+                assemble_source(&asm, &path, &opts, cache(no_cache).as_ref(), message_format)?;
+            (output, Some((asm, debug)), None)
+        }
+        Some("intcode" | "bin") | None => {
+            let (program, debug) = load::bundle(&path, exact)?;
+            (program, None, debug)
+        }
+        Some(ext) => {
+            log::error!("unrecognized file extension `{}`", ext);
+            process::exit(1);
+        }
+    };
+    let known = source.as_ref().map(|(_, debug)| debug).or(bundled.as_ref());
+    let meta = match known {
+        Some(debug) => debug.meta.clone(),
+        None => Metadata::from_header(&intcode).unwrap_or_default(),
+    };
+    if info {
+        print_info(&meta);
+        return Ok(());
+    }
+    if strict && !enable.is_empty() {
+        log::error!("extensions cannot be enabled in strict mode");
+        process::exit(1);
+    }
+    let nonstandard = [
+        ("--poison", poison.is_some()),
+        ("--entry", entry.is_some()),
+        ("--mem-init", mem_init.is_some()),
+    ];
+    if let (true, Some((flag, _))) = (strict, nonstandard.iter().find(|(_, used)| *used)) {
+        log::error!("`{}` is not allowed in strict mode", flag);
+        process::exit(1);
+    }
+    if let (true, Some(ext)) = (strict, meta.requires.first()) {
+        log::error!(
+            "program requires the `{}` extension which is not allowed in strict mode",
+            ext
+        );
+        process::exit(1);
+    }
+    if let Some(ext) = meta.requires.iter().find(|ext| !enable.contains(ext)) {
+        log::error!(
+            "program requires the `{}` extension, enable it with `--enable {}`",
+            ext,
+            ext
+        );
+        process::exit(1);
+    }
+    // Without any bindings the inputs are read interactively as usual.
+    let inputs = match feed::bind(&meta.inputs, &bindings) {
+        _ if bindings.is_empty() => Vec::new(),
+        Ok(inputs) => inputs,
+        Err(msg) => {
+            log::error!("{}", msg);
+            process::exit(1);
+        }
+    };
+    let fed = feeds
+        .iter()
+        .map(FeedSpec::values)
+        .collect::<Result<Vec<_>>>()?
+        .concat();
+    // An embedded header jumps to the entry point and sets the relative base
+    // itself.
+    let embedded = Metadata::from_header(&intcode).is_some();
+    let entry = entry.or(meta.entry.filter(|_| known.is_some() && !embedded));
+    let relative_base = meta.relative_base.filter(|_| known.is_some() && !embedded);
+    let labels = known.cloned().unwrap_or_default();
+    let show = match source {
+        Some((asm, debug)) if show_line => Some((asm, debug)),
+        None if show_line => {
+            log::warn!("`--show-line` requires a `.ints` file");
+            None
+        }
+        _ => None,
+    };
+    let show = show
+        .as_ref()
+        .map(|(asm, debug)| run::ShowLine::new(asm, &path, debug.clone()));
+    log::Event::new("run_start")
+        .field("path", path.display().to_string())
+        .info(format_args!("running {}", path.display()));
+    let mut c = Computer::new(intcode);
+    if let Some(entry) = entry {
+        c = c.with_entry(entry)?;
+    }
+    if let Some(path) = mem_init {
+        let kv = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        let values = mem_init::parse(&kv)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        c.init_memory(values);
+    }
+    c.set_strict(strict);
+    c.set_poison(poison)?;
+    if let Some(relative_base) = relative_base {
+        c.set_relative_base(relative_base);
+    }
+    c.feed(inputs);
+    c.feed(fed);
+    if events.is_some() {
+        c.record_events(mask);
+    }
+    if heatmap.is_some() {
+        c.record_heatmap();
+    }
+    let resumed = match resume_from_checkpoint {
+        Some(path) => {
+            let checkpoint = checkpoint::load(&path)?;
+            c.restore(&checkpoint)
+                .with_context(|| format!("failed to resume from `{}`", path.display()))?;
+            checkpoint.outputs
+        }
+        None => Vec::new(),
+    };
+    // The interval starts from the restored step count.
+    let checkpoints = checkpoint_dir
+        .map(|dir| Checkpoints::new(dir, checkpoint_keep))
+        .transpose()?;
+    if checkpoints.is_some() {
+        c.pause_every(Some(checkpoint_every.saturating_mul(1_000_000)));
+    }
+    if let Some(max_steps) = max_steps {
+        c.set_fuel(max_steps);
+    }
+    let cancel = CancelToken::new();
+    ctrlc::set_handler({
+        let cancel = cancel.clone();
+        move || {
+            // The program doesn't notice while it waits for input, so a
+            // second Ctrl-C exits straight away.
+            if cancel.is_cancelled() {
+                process::exit(130);
+            }
+            cancel.cancel();
+        }
+    })?;
+    c.set_cancel_token(Some(cancel));
+    let mut lines = line::Lines::new(history);
+    let result = if basic {
+        run::basic(
+            &mut c,
+            show.as_ref(),
+            &mut lines,
+            flush,
+            &mut *render.renderer(),
+            checkpoints.as_ref(),
+            resumed,
+        )
+    } else {
+        run::utf8(
+            &mut c,
+            show.as_ref(),
+            &mut lines,
+            &line_opts,
+            flush,
+            checkpoints.as_ref(),
+            resumed,
+        )
+    };
+    if let Some(run::Interrupted { outputs }) = result.as_ref().err().and_then(|e| e.downcast_ref())
+    {
+        interrupted(&c, labels, &path, outputs, checkpoints.as_ref(), &mut lines)?;
+    }
+    if let (Some(path), Some(log)) = (events, c.take_events()) {
+        events::write(&path, format, &log)?;
+        log::info!("wrote events to {}", path.display());
+    }
+    if let (Some(path), Some(counts)) = (heatmap, c.take_heatmap()) {
+        heatmap::write(&path, heatmap_format, heatmap_width, &counts)?;
+        log::info!("wrote heatmap to {}", path.display());
+    }
+    if let Some(UninitRead { ptr, addr }) = c.uninit_read() {
+        log::warn!(
+            "instruction at address {} read uninitialized address {}",
+            ptr,
+            addr
+        );
+    }
+    let stats = c.stats();
+    log::Event::new("run_finish")
+        .field("path", path.display().to_string())
+        .field("ok", result.is_ok())
+        .field("steps", stats.instructions)
+        .field("cells", stats.cells)
+        .debug(format_args!("ran {} instructions", stats.instructions));
+    let outputs = result?;
+    if let Err(diff) = run::check_outputs(&outputs, &expect, expect_last) {
+        eprintln!("{}", diff);
+        log::error!("outputs did not match the expected outputs");
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Describes where the program was interrupted and saves a checkpoint, if
+/// checkpoints are enabled, or offers to save one.
+fn interrupted(
+    c: &Computer,
+    labels: DebugInfo,
+    path: &Path,
+    outputs: &[i64],
+    checkpoints: Option<&Checkpoints>,
+    lines: &mut line::Lines,
+) -> Result<()> {
+    let steps = c.stats().instructions;
+    eprintln!("interrupted after {} steps", steps);
+    eprintln!("{}", debugger::Debugger::new(c.clone(), labels).current());
+    eprintln!("relative base: {}", c.relative_base());
+    if let Some(checkpoints) = checkpoints {
+        return checkpoints.save(c, outputs);
+    }
+    let file = path.with_extension("checkpoint");
+    eprint!("save a checkpoint to {}? [y/N] ", file.display());
+    if lines.read_line()?.trim().eq_ignore_ascii_case("y") {
+        let mut checkpoint = c.checkpoint();
+        checkpoint.outputs = outputs.to_vec();
+        fs::write(&file, checkpoint.to_string())?;
+        log::info!("wrote checkpoint {}", file.display());
+    }
     Ok(())
 }
 
-fn run(path: PathBuf, basic: bool) -> Result<()> {
-    let intcode = match path.extension().and_then(OsStr::to_str) {
-        Some("ints") => assemble(&path)?,
-        Some("intcode") | None => parse_program(&fs::read_to_string(&path)?)?,
+fn print_info(meta: &Metadata) {
+    if meta.is_empty() {
+        println!("no metadata");
+        return;
+    }
+    if let Some(name) = &meta.name {
+        println!("name:     {}", name);
+    }
+    if let Some(author) = &meta.author {
+        println!("author:   {}", author);
+    }
+    if let Some(entry) = meta.entry {
+        println!("entry:    {}", entry);
+    }
+    if !meta.requires.is_empty() {
+        println!("requires: {}", meta.requires.join(", "));
+    }
+    if !meta.inputs.is_empty() {
+        println!("inputs:   {}", meta.inputs.join(", "));
+    }
+    if let Some(relative_base) = meta.relative_base {
+        println!("rbinit:   {}", relative_base);
+    }
+}
+
+fn debug(path: PathBuf, debug_file: Option<PathBuf>, history: Option<PathBuf>) -> Result<()> {
+    let (intcode, debug, source) = match path.extension().and_then(OsStr::to_str) {
+        Some("ints") => {
+            let asm = fs::read_to_string(&path)?;
+            let Intcode { output, debug, .. } = assemble_source(
+                &asm,
+                &path,
+                &intcode::assemble::Options::new(),
+                None,
+                MessageFormat::Human,
+            )?;
+            (output, debug, true)
+        }
+        Some("intcode" | "bin") | None => {
+            let (program, bundled) = load::bundle(&path, false)?;
+            let source = bundled.is_some();
+            let debug = match debug_file {
+                Some(file) => fs::read_to_string(&file)?
+                    .parse::<DebugInfo>()
+                    .with_context(|| format!("failed to parse `{}`", file.display()))?,
+                None => bundled.unwrap_or_default(),
+            };
+            (program, debug, source)
+        }
         Some(ext) => {
             log::error!("unrecognized file extension `{}`", ext);
             process::exit(1);
         }
     };
-    log::info!("running {}", path.display());
-    if basic {
-        run::basic(intcode)?;
+    // An embedded header jumps to the entry point and sets the relative base
+    // itself.
+    let embedded = Metadata::from_header(&intcode).is_some();
+    let mut c = Computer::new(intcode);
+    if let Some(entry) = debug.meta.entry.filter(|_| source && !embedded) {
+        c = c.with_entry(entry)?;
+    }
+    if let Some(relative_base) = debug.meta.relative_base.filter(|_| source && !embedded) {
+        c.set_relative_base(relative_base);
+    }
+    log::Event::new("debug_start")
+        .field("path", path.display().to_string())
+        .info(format_args!("debugging {}", path.display()));
+    debugger::Debugger::new(c, debug).session(&mut line::Lines::new(history))
+}
+
+fn unbuild(args: opt::Unbuild) -> Result<()> {
+    let opt::Unbuild {
+        input: path,
+        feed: feeds,
+        comments,
+        debug_file,
+        label_scheme: LabelScheme(label_scheme),
+        sections,
+        annotate,
+        min_confidence: Confidence(min_confidence),
+        fold_relative_base,
+        exact,
+        max_steps,
+        timeout,
+        progress,
+    } = args;
+    let (intcode, bundled) = load::bundle(&path, exact)?;
+    let mut opts = disassemble::Options::new()
+        .comments(comments)
+        .label_scheme(label_scheme)
+        .sections(sections)
+        .xrefs(annotate.contains(&Annotate::Xrefs))
+        .loops(annotate.contains(&Annotate::Loops))
+        .constants(annotate.contains(&Annotate::Constants))
+        .pseudo(annotate.contains(&Annotate::Pseudo))
+        .confidence(annotate.contains(&Annotate::Confidence))
+        .min_confidence(min_confidence)
+        .fold_relative_base(fold_relative_base);
+    if let Some(debug_file) = debug_file {
+        let debug = fs::read_to_string(&debug_file)?
+            .parse::<DebugInfo>()
+            .with_context(|| format!("failed to parse `{}`", debug_file.display()))?;
+        opts = opts.debug_info(debug);
+    } else if let Some(debug) = bundled {
+        opts = opts.debug_info(debug);
+    }
+    if progress {
+        opts = opts.progress(progress_bar);
+    }
+    let runs = feeds
+        .iter()
+        .map(|feed| {
+            let input = disassemble::Input::Static(feed.values()?);
+            let mut run = disassemble::Run::new().input(input);
+            if let Some(max_steps) = max_steps {
+                run = run.max_steps(max_steps);
+            }
+            if let Some(timeout) = timeout {
+                run = run.timeout(Duration::from_secs(timeout));
+            }
+            Ok(run)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let disassemble::Disassembly { ast, report } = disassemble::disassemble(intcode, runs, &opts)?;
+    if progress {
+        eprintln!();
+    }
+    log::Event::new("analysis_finish")
+        .field("path", path.display().to_string())
+        .field("diagnostics", report.diagnostics.len())
+        .field("routines", report.routines.len())
+        .field("loops", report.loops.len())
+        .field("relocations", report.relocations.len())
+        .field("stopped_runs", report.stopped_runs.clone())
+        .field("budget_exhausted", report.budget_exhausted)
+        .debug(format_args!("analysed {}", path.display()));
+    for run in &report.stopped_runs {
+        log::warn!(
+            "run {} was stopped early, the output may be incomplete",
+            run + 1
+        );
+    }
+    for diagnostic in &report.diagnostics {
+        log::warn!("could not disassemble {}", diagnostic);
+    }
+    if report.budget_exhausted {
+        log::warn!("analysis stopped early, the output may be incomplete");
+    }
+    for r in &report.relocations {
+        log::info!(
+            "loop at address {} copies {} addresses from {} to {}",
+            r.header,
+            r.len,
+            r.src,
+            r.dst
+        );
+    }
+    for addr in &report.tainted_outputs {
+        log::info!("output at address {} depends on the input", addr);
+    }
+    for addr in &report.tainted_jumps {
+        log::info!("jump at address {} depends on the input", addr);
+    }
+    ast.stream_to(&mut io::BufWriter::new(io::stdout().lock()))?;
+    Ok(())
+}
+
+/// Draws a progress bar for the dynamic runs on the current line of stderr.
+fn progress_bar(p: disassemble::Progress) {
+    const WIDTH: usize = 30;
+    let filled = ((p.percent_marked / 100.0 * WIDTH as f64) as usize).min(WIDTH);
+    eprint!(
+        "\r[{}{}] {:5.1}% marked, run {}, {} steps",
+        "#".repeat(filled),
+        "-".repeat(WIDTH - filled),
+        p.percent_marked,
+        p.run + 1,
+        p.steps
+    );
+}
+
+fn adventure(path: PathBuf) -> Result<()> {
+    let intcode = load::program(&path, false)?;
+    log::info!("playing {}", path.display());
+    let password = intcode::run::adventure::solve(Computer::new(intcode))?;
+    println!("{}", password);
+    Ok(())
+}
+
+fn wrap(path: PathBuf, script: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let intcode = load::program(&path, false)?;
+    let script = fs::read_to_string(&script)
+        .with_context(|| format!("failed to read `{}`", script.display()))?;
+    let input: Vec<_> = script.bytes().map(i64::from).collect();
+    let output = output.unwrap_or_else(|| path.with_extension("wrapped.intcode"));
+    let wrapped = intcode::assemble::wrap::with_input(&intcode, &input);
+    let mut buf = Vec::new();
+    emit::Format::Intcode
+        .emitter()
+        .emit(&mut buf, &wrapped, None, &path)?;
+    fs::write(&output, buf)?;
+    log::info!("finished {}", output.display());
+    Ok(())
+}
+
+fn bench(
+    path: PathBuf,
+    feeds: Vec<FeedSpec>,
+    iterations: u32,
+    backends: Vec<Backend>,
+) -> Result<()> {
+    let intcode = load::program(&path, false)?;
+    let input = feeds
+        .iter()
+        .map(FeedSpec::values)
+        .collect::<Result<Vec<_>>>()?
+        .concat();
+    let backends = if backends.is_empty() {
+        vec![Backend::I64]
     } else {
-        run::utf8(intcode)?;
+        backends
+    };
+    let measurements = backends
+        .into_iter()
+        .map(|backend| {
+            log::info!("measuring {} with {} cells", path.display(), backend);
+            bench::measure(backend, &intcode, &input, iterations)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    print!("{}", bench::table(&measurements));
+    Ok(())
+}
+
+fn lockstep(
+    left: PathBuf,
+    right: PathBuf,
+    feeds: Vec<FeedSpec>,
+    outputs: bool,
+    max_steps: Option<u64>,
+) -> Result<()> {
+    let l = Computer::new(load::program(&left, false)?);
+    let r = Computer::new(load::program(&right, false)?);
+    let input = feeds
+        .iter()
+        .map(FeedSpec::values)
+        .collect::<Result<Vec<_>>>()?
+        .concat();
+    let level = match outputs {
+        true => lockstep::Level::Outputs,
+        false => lockstep::Level::Instructions,
+    };
+    let unit = match outputs {
+        true => "outputs",
+        false => "instructions",
+    };
+    match lockstep::compare(l, r, &input, level, max_steps.unwrap_or(LOCKSTEP_STEPS))? {
+        lockstep::Outcome::Same { steps } => {
+            log::info!("programs behaved the same for {} {}", steps, unit);
+        }
+        lockstep::Outcome::Undecided { steps } => {
+            log::warn!("programs behaved the same for the first {} {}", steps, unit);
+        }
+        lockstep::Outcome::Diverged(d) => {
+            println!("programs diverged after {} matching {}", d.step, unit);
+            println!(
+                "  {}: {} at address {}",
+                left.display(),
+                d.left.effect,
+                d.left.ptr
+            );
+            println!(
+                "  {}: {} at address {}",
+                right.display(),
+                d.right.effect,
+                d.right.ptr
+            );
+            process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+fn completions(shell: Shell) -> Result<()> {
+    let mut app = Opt::into_app();
+    let buf = &mut io::stdout();
+    match shell {
+        Shell::Bash => generate::<Bash, _>(&mut app, "intcode", buf),
+        Shell::Zsh => generate::<Zsh, _>(&mut app, "intcode", buf),
+        Shell::Fish => generate::<Fish, _>(&mut app, "intcode", buf),
+        Shell::Powershell => generate::<PowerShell, _>(&mut app, "intcode", buf),
     }
     Ok(())
 }
 
-fn unbuild(path: PathBuf, feeds: Vec<Feed>) -> Result<()> {
-    let intcode = parse_program(&fs::read_to_string(path)?)?;
-    let display = disassemble::to_ast(
-        intcode,
-        feeds
-            .into_iter()
-            .map(|Feed(i)| disassemble::Run::new().input(disassemble::Input::Static(i))),
-    )?
-    .to_string();
-    io::stdout().lock().write_all(display.as_bytes())?;
+fn man() -> Result<()> {
+    print!("{}", man::render(&Opt::into_app()));
     Ok(())
 }
 
 fn main() {
-    log::init();
-    if let Err(err) = match Opt::parse() {
-        Opt::Build { input, output } => build(input, output),
-        Opt::Run { input, basic } => run(input, basic),
-        Opt::Unbuild { input, feed } => unbuild(input, feed),
+    let opt = Opt::parse();
+    log::init(opt.log_level, opt.log_format, opt.log_timestamps);
+    if let Err(err) = match opt.command {
+        Command::Build(args) => build(args),
+        Command::Fmt {
+            input,
+            check,
+            syntax,
+        } => format_asm(input, check, syntax),
+        Command::Run(args) => run(args),
+        Command::Debug {
+            input,
+            debug_file,
+            history,
+        } => debug(input, debug_file, history),
+        Command::Unbuild(args) => unbuild(args),
+        Command::Adventure { input } => adventure(input),
+        Command::Wrap {
+            input,
+            script,
+            output,
+        } => wrap(input, script, output),
+        Command::Bench {
+            input,
+            feed,
+            iterations,
+            backend,
+        } => bench(input, feed, iterations, backend),
+        Command::Lockstep {
+            left,
+            right,
+            feed,
+            outputs,
+            max_steps,
+        } => lockstep(left, right, feed, outputs, max_steps),
+        Command::Completions { shell } => completions(shell),
+        Command::Man => man(),
     } {
         log::error!("{:#}", err);
     }
@@ -0,0 +1,99 @@
+//! Render a man page from the command line interface definition.
+
+use clap::{App, Arg};
+
+/// Escapes text so that it is not interpreted as roff requests.
+fn escape(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = line.replace('\\', "\\e").replace('-', "\\-");
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{}", line)
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn help<'a>(long: Option<&'a str>, short: Option<&'a str>) -> Option<&'a str> {
+    long.or(short)
+}
+
+fn push_arg(out: &mut String, arg: &Arg<'_>) {
+    out.push_str(".TP\n");
+    if arg.is_positional() {
+        out.push_str(&format!(
+            "\\fI<{}>\\fR\n",
+            escape(&arg.get_name().to_uppercase())
+        ));
+    } else {
+        let mut flags = Vec::new();
+        if let Some(short) = arg.get_short() {
+            flags.push(format!("\\fB\\-{}\\fR", short));
+        }
+        if let Some(long) = arg.get_long() {
+            flags.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+        }
+        out.push_str(&flags.join(", "));
+        out.push('\n');
+    }
+    if let Some(help) = help(arg.get_long_about(), arg.get_about()) {
+        out.push_str(&escape(help));
+        out.push('\n');
+    }
+}
+
+/// Renders the man page for the application and all its subcommands.
+pub fn render(app: &App<'_>) -> String {
+    let name = app.get_bin_name().unwrap_or_else(|| app.get_name());
+    let mut out = format!(
+        ".TH {} 1\n.SH NAME\n{}",
+        escape(&name.to_uppercase()),
+        escape(name)
+    );
+    if let Some(about) = app.get_about() {
+        out.push_str(&format!(" \\- {}", escape(about)));
+    }
+    out.push_str(&format!(
+        "\n.SH SYNOPSIS\n\\fB{}\\fR <\\fICOMMAND\\fR> [\\fIOPTIONS\\fR]\n",
+        escape(name)
+    ));
+    if let Some(about) = app.get_long_about() {
+        out.push_str(&format!(".SH DESCRIPTION\n{}\n", escape(about)));
+    }
+    let mut args = app.get_arguments().peekable();
+    if args.peek().is_some() {
+        out.push_str(".SH OPTIONS\n");
+        for arg in args {
+            push_arg(&mut out, arg);
+        }
+    }
+    out.push_str(".SH COMMANDS\n");
+    for cmd in app.get_subcommands() {
+        out.push_str(&format!(".SS {}\n", escape(cmd.get_name())));
+        if let Some(about) = help(cmd.get_long_about(), cmd.get_about()) {
+            out.push_str(&format!("{}\n", escape(about)));
+        }
+        for arg in cmd.get_arguments() {
+            push_arg(&mut out, arg);
+        }
+    }
+    if let Some(author) = app.get_author() {
+        out.push_str(&format!(".SH AUTHOR\n{}\n", escape(author)));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_roff() {
+        assert_eq!(escape("a --flag"), "a \\-\\-flag");
+        assert_eq!(escape("C:\\path"), "C:\\epath");
+        assert_eq!(escape(".TH\n'quote\nok"), "\\&.TH\n\\&'quote\nok");
+    }
+}
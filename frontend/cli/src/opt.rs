@@ -0,0 +1,646 @@
+//! The command line interface definition.
+//!
+//! Everything the command line accepts is defined here so that the help
+//! text, shell completions, and man page are all generated from one place.
+
+use std::path::PathBuf;
+use std::result;
+use std::str::FromStr;
+
+use clap::{AppSettings, Clap};
+use intcode::disassemble;
+use intcode::run::ascii;
+use log::LevelFilter;
+
+use crate::bench::Backend;
+use crate::feed::{Binding, FeedSpec};
+use crate::fmt::MessageFormat;
+use crate::log::LogFormat;
+use crate::run::{Flush, Style};
+use crate::{emit, events, heatmap};
+
+/// Assemble, disassemble, and run intcode programs.
+#[derive(Debug, Clone, Clap)]
+#[clap(
+    author,
+    global_setting = AppSettings::DeriveDisplayOrder,
+    global_setting = AppSettings::DisableHelpSubcommand,
+    global_setting = AppSettings::DisableVersionForSubcommands,
+)]
+pub struct Opt {
+    /// The least severe log messages to show: `off`, `error`, `warn`,
+    /// `info`, `debug`, or `trace`.
+    #[clap(long, global = true, default_value = "info")]
+    pub log_level: LevelFilter,
+
+    /// How to write log messages: `text` or `json`.
+    ///
+    /// The `json` format writes an object on each line with a timestamp, the
+    /// level, and the message. Key events like the start and end of a build
+    /// or run also have an `event` name and `fields`.
+    #[clap(long, global = true, default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Start each text log message with a timestamp.
+    #[clap(long, global = true)]
+    pub log_timestamps: bool,
+
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Clap)]
+pub enum Command {
+    /// Assemble a program.
+    ///
+    /// The input is intcode assembly and the output is written next to it
+    /// with the extension of the output format unless `--output` is given.
+    Build(Build),
+    /// Format an assembly file.
+    ///
+    /// The file is rewritten in place unless `--check` is given.
+    Fmt {
+        /// The input file.
+        #[clap()]
+        input: PathBuf,
+
+        /// Do not write the file, fail if it is not formatted.
+        #[clap(long)]
+        check: bool,
+
+        /// The version of the assembly grammar: `v1` or `v2`.
+        #[clap(long, default_value = "v2")]
+        syntax: Syntax,
+    },
+    /// Run a program.
+    ///
+    /// The input is either an intcode program or intcode assembly with the
+    /// `.ints` extension, which is assembled first. Once any `--feed` input
+    /// is used up, input is read from stdin and sent to the program as ASCII
+    /// text.
+    Run(Run),
+    /// Debug a program interactively.
+    ///
+    /// The input is either an intcode program or intcode assembly with the
+    /// `.ints` extension, which is assembled first. Commands are read from
+    /// stdin, type `help` for a list of them.
+    Debug {
+        /// The intcode program or assembly file.
+        #[clap()]
+        input: PathBuf,
+
+        /// Read label names from this file, for intcode programs.
+        #[clap(long)]
+        debug_file: Option<PathBuf>,
+
+        /// Persist the command history to this file, requires the `readline`
+        /// feature.
+        #[clap(long)]
+        history: Option<PathBuf>,
+    },
+    /// Disassemble a program.
+    ///
+    /// The listing is written to stdout as intcode assembly.
+    Unbuild(Unbuild),
+    /// Play the text adventure and print the password for the airlock.
+    Adventure {
+        /// The intcode program.
+        #[clap()]
+        input: PathBuf,
+    },
+    /// Bake an input script into a program.
+    ///
+    /// The output is written next to the input with the `.wrapped.intcode`
+    /// extension unless `--output` is given.
+    Wrap {
+        /// The intcode program.
+        #[clap()]
+        input: PathBuf,
+
+        /// A file containing the ASCII input to bake into the program.
+        #[clap(long)]
+        script: PathBuf,
+
+        /// The output file.
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Measure how fast a program runs.
+    Bench {
+        /// The intcode program.
+        #[clap()]
+        input: PathBuf,
+
+        /// Input to feed the program, as integers, `ascii:text`, or `@file`.
+        #[clap(long, multiple_occurrences(true))]
+        feed: Vec<FeedSpec>,
+
+        /// The number of times to run the program.
+        #[clap(long, default_value = "10")]
+        iterations: u32,
+
+        /// The cell type to measure: `i32`, `i64`, or `i128`. Give this more
+        /// than once to compare them side by side.
+        #[clap(long, multiple_occurrences(true))]
+        backend: Vec<Backend>,
+    },
+    /// Run two programs side by side and report where they first differ.
+    ///
+    /// Both programs get the same input and their values written, jumps
+    /// taken, and outputs are compared after every instruction, unless
+    /// `--outputs` is given.
+    Lockstep {
+        /// The first intcode program.
+        #[clap()]
+        left: PathBuf,
+
+        /// The second intcode program.
+        #[clap()]
+        right: PathBuf,
+
+        /// Input to feed both programs, as integers, `ascii:text`, or
+        /// `@file`.
+        #[clap(long, multiple_occurrences(true))]
+        feed: Vec<FeedSpec>,
+
+        /// Only compare the outputs, for rewrites that execute different
+        /// instructions.
+        #[clap(long)]
+        outputs: bool,
+
+        /// Give up after this many steps, 100 million by default. With
+        /// `--outputs` each program may also execute at most this many
+        /// instructions.
+        #[clap(long)]
+        max_steps: Option<u64>,
+    },
+    /// Print a shell completion script.
+    ///
+    /// For example, add `source <(intcode completions bash)` to your
+    /// `.bashrc`.
+    Completions {
+        /// The shell: `bash`, `zsh`, `fish`, or `powershell`.
+        #[clap()]
+        shell: Shell,
+    },
+    /// Print the man page.
+    ///
+    /// For example, `intcode man > intcode.1`.
+    Man,
+}
+
+/// The options of the `build` subcommand.
+#[derive(Debug, Clone, Clap)]
+pub struct Build {
+    /// The input file.
+    #[clap()]
+    pub input: PathBuf,
+
+    /// The output file.
+    #[clap(long, short)]
+    pub output: Option<PathBuf>,
+
+    /// The output format: `intcode`, `rust`, `c`, `python`, `meta`, or
+    /// `bin`.
+    ///
+    /// The `meta` format is intcode with the label addresses, source
+    /// file, and assembler version in a header. The `bin` format is a
+    /// compact binary encoding for large programs. Both are detected by
+    /// `run`, `debug`, and `unbuild`.
+    #[clap(long, default_value = "intcode")]
+    pub emit: emit::Format,
+
+    /// Embed the label addresses in the `rust`, `c`, and `python`
+    /// output.
+    #[clap(long)]
+    pub labels: bool,
+
+    /// Leave the label addresses, source lines, and metadata out of the
+    /// `meta` output.
+    ///
+    /// The other formats only hold the program unless `--labels` or
+    /// `--embed-meta` is given, so this cannot be used with either.
+    #[clap(long)]
+    pub strip: bool,
+
+    /// Write label names, source lines, and metadata to this file.
+    #[clap(long)]
+    pub debug_file: Option<PathBuf>,
+
+    /// Embed the program metadata at the start of the output.
+    #[clap(long)]
+    pub embed_meta: bool,
+
+    /// The version of the assembly grammar: `v1` or `v2`.
+    #[clap(long, default_value = "v2")]
+    pub syntax: Syntax,
+
+    /// Reject pseudo-instructions and directives that are not standard
+    /// intcode.
+    #[clap(long)]
+    pub strict: bool,
+
+    /// Always assemble the file instead of using a cached build.
+    #[clap(long)]
+    pub no_cache: bool,
+
+    /// How to write assembler diagnostics: `human` or `json`.
+    #[clap(long, default_value = "human")]
+    pub message_format: MessageFormat,
+
+    /// Add a hash of the assembler version, options, and source files to
+    /// the debug information, to check a program against its source.
+    #[clap(long)]
+    pub fingerprint: bool,
+
+    /// Fail if there are any warnings.
+    #[clap(long)]
+    pub deny_warnings: bool,
+
+    /// Leave out the warnings of this lint: `unused-label`,
+    /// `label-offset`, `fall-through`, `immediate-write`,
+    /// `unreachable-code`, or `unused-data`.
+    ///
+    /// Writes to immediate parameters are errors unless their lint is
+    /// allowed.
+    #[clap(long, multiple_occurrences(true))]
+    pub allow: Vec<Lint>,
+}
+
+/// The options of the `run` subcommand.
+#[derive(Debug, Clone, Clap)]
+pub struct Run {
+    /// The intcode program or assembly file.
+    #[clap()]
+    pub input: PathBuf,
+
+    /// Read and write integers, one on each line, instead of ASCII text.
+    #[clap(long)]
+    pub basic: bool,
+
+    /// How to write each output in basic mode: `decimal`, `hex`, or
+    /// `ascii`.
+    #[clap(long, default_value = "decimal")]
+    pub render: Style,
+
+    /// Record machine events and write them to this file.
+    #[clap(long)]
+    pub events: Option<PathBuf>,
+
+    /// The event log format: `json` or `csv`.
+    #[clap(long, default_value = "json")]
+    pub events_format: events::Format,
+
+    /// The comma separated kinds of events to record: `input`, `output`,
+    /// `jump`, `arb`, or `all`.
+    #[clap(long, default_value = "all")]
+    pub events_mask: events::Mask,
+
+    /// Count the reads, writes, and executions of each address and write
+    /// them to this file.
+    #[clap(long)]
+    pub heatmap: Option<PathBuf>,
+
+    /// The heatmap format: `csv`, `json`, or `ppm`.
+    ///
+    /// The `ppm` format is an image with a pixel for each address of the
+    /// program. Writes are red, reads are green, and executions are blue.
+    #[clap(long, default_value = "csv")]
+    pub heatmap_format: heatmap::Format,
+
+    /// The number of addresses in each row of a `ppm` heatmap.
+    #[clap(long, default_value = "64")]
+    pub heatmap_width: usize,
+
+    /// Show the source line whenever the program waits for input.
+    #[clap(long)]
+    pub show_line: bool,
+
+    /// Display the program metadata instead of running it.
+    #[clap(long)]
+    pub info: bool,
+
+    /// Enable an extension required by the program.
+    #[clap(long, multiple_occurrences(true))]
+    pub enable: Vec<String>,
+
+    /// The version of the assembly grammar: `v1` or `v2`.
+    #[clap(long, default_value = "v2")]
+    pub syntax: Syntax,
+
+    /// Persist the input history to this file, requires the `readline`
+    /// feature.
+    #[clap(long)]
+    pub history: Option<PathBuf>,
+
+    /// Only allow canonical Advent of Code behaviour, no extensions.
+    #[clap(long)]
+    pub strict: bool,
+
+    /// Read this value instead of zero from memory that was never
+    /// initialized, and report the first such read.
+    #[clap(long, allow_hyphen_values(true))]
+    pub poison: Option<i64>,
+
+    /// Start execution at this address instead of the first one.
+    #[clap(long)]
+    pub entry: Option<usize>,
+
+    /// Overwrite memory before running using a file with an `addr=value`
+    /// pair on each line.
+    #[clap(long)]
+    pub mem_init: Option<PathBuf>,
+
+    /// Keep a carriage return at the end of each input line.
+    #[clap(long)]
+    pub keep_cr: bool,
+
+    /// Echo each input line to the output.
+    #[clap(long)]
+    pub echo: bool,
+
+    /// What ends each input line: `lf` or `crlf`.
+    #[clap(long, default_value = "lf")]
+    pub line_ending: LineEnding,
+
+    /// Give a value to an input declared with the `INPUTS` directive, as
+    /// in `x=5`.
+    #[clap(long = "let", multiple_occurrences(true))]
+    pub bindings: Vec<Binding>,
+
+    /// Input to feed the program before reading stdin, as integers,
+    /// `ascii:text`, or `@file`. Give this more than once to feed each in
+    /// order.
+    #[clap(long, multiple_occurrences(true))]
+    pub feed: Vec<FeedSpec>,
+
+    /// A file whose bytes are fed to the program after any `--feed`
+    /// input, the same as `--feed @file`.
+    #[clap(long)]
+    pub input_file: Option<PathBuf>,
+
+    /// Fail unless the program outputs exactly these values in order.
+    #[clap(long, multiple_occurrences(true))]
+    pub expect: Vec<i64>,
+
+    /// Fail unless the last value the program outputs is this.
+    #[clap(long)]
+    pub expect_last: Option<i64>,
+
+    /// When to flush output: `output`, `line`, `manual`, or a number of
+    /// outputs. Output is always flushed when the program waits for
+    /// input.
+    #[clap(long, default_value = "manual")]
+    pub flush: Flush,
+
+    /// Periodically save the state of the machine to this directory, so
+    /// that a long run can be resumed with `--resume-from-checkpoint`.
+    #[clap(long)]
+    pub checkpoint_dir: Option<PathBuf>,
+
+    /// The number of steps between checkpoints, in millions.
+    #[clap(long, default_value = "100")]
+    pub checkpoint_every: u64,
+
+    /// The number of most recent checkpoints to keep.
+    #[clap(long, default_value = "3")]
+    pub checkpoint_keep: usize,
+
+    /// Resume from this checkpoint file, or the most recent checkpoint in
+    /// this directory. The program must be the same one.
+    #[clap(long)]
+    pub resume_from_checkpoint: Option<PathBuf>,
+
+    /// Fail if the program doesn't halt within this many steps.
+    #[clap(long)]
+    pub max_steps: Option<u64>,
+
+    /// Fail if an intcode file contains anything other than the program,
+    /// instead of ignoring the other lines.
+    #[clap(long)]
+    pub exact: bool,
+
+    /// Always assemble an assembly file instead of using a cached build.
+    #[clap(long)]
+    pub no_cache: bool,
+
+    /// How to write assembler diagnostics: `human` or `json`.
+    #[clap(long, default_value = "human")]
+    pub message_format: MessageFormat,
+}
+
+/// The options of the `unbuild` subcommand.
+#[derive(Debug, Clone, Clap)]
+pub struct Unbuild {
+    /// The intcode program.
+    #[clap()]
+    pub input: PathBuf,
+
+    /// Run the program with this input to find more of the code, as
+    /// integers, `ascii:text`, or `@file`. Give this more than once for
+    /// separate runs.
+    #[clap(long, multiple_occurrences(true))]
+    pub feed: Vec<FeedSpec>,
+
+    /// Add comments explaining what could not be disassembled.
+    #[clap(long)]
+    pub comments: bool,
+
+    /// Restore label names and source lines from this file.
+    #[clap(long)]
+    pub debug_file: Option<PathBuf>,
+
+    /// How to name generated labels: `letters` or `semantic`.
+    #[clap(long, default_value = "letters")]
+    pub label_scheme: LabelScheme,
+
+    /// Split the output into sections, one for each detected routine.
+    #[clap(long)]
+    pub sections: bool,
+
+    /// Add extra comments to the output: `xrefs`, `loops`, `constants`,
+    /// `pseudo`, or `confidence`.
+    #[clap(long, multiple_occurrences(true))]
+    pub annotate: Vec<Annotate>,
+
+    /// Output anything marked with less confidence than this as raw data:
+    /// `low`, `medium`, or `high`.
+    #[clap(long, default_value = "low")]
+    pub min_confidence: Confidence,
+
+    /// Replace an `ARB` at the start of the program with an `RBINIT`
+    /// directive. Every address after it moves down by two.
+    #[clap(long)]
+    pub fold_relative_base: bool,
+
+    /// Fail if the file contains anything other than the program, instead
+    /// of ignoring the other lines.
+    #[clap(long)]
+    pub exact: bool,
+
+    /// Stop each run with `--feed` after this many steps.
+    #[clap(long)]
+    pub max_steps: Option<u64>,
+
+    /// Stop each run with `--feed` after this many seconds.
+    #[clap(long)]
+    pub timeout: Option<u64>,
+
+    /// Show a progress bar while running the program.
+    #[clap(long)]
+    pub progress: bool,
+}
+
+/// The shell to generate completions for.
+#[derive(Debug, Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            "powershell" => Ok(Self::Powershell),
+            s => Err(format!(
+                "unknown shell `{}`, expected one of `bash`, `zsh`, `fish`, or `powershell`",
+                s
+            )),
+        }
+    }
+}
+
+/// The version of the assembly grammar.
+#[derive(Debug, Clone, Copy)]
+pub struct Syntax(pub intcode::assemble::Syntax);
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "v1" => Ok(Self(intcode::assemble::Syntax::V1)),
+            "v2" => Ok(Self(intcode::assemble::Syntax::V2)),
+            s => Err(format!(
+                "unknown syntax `{}`, expected one of `v1` or `v2`",
+                s
+            )),
+        }
+    }
+}
+
+/// The name of an assembler lint.
+#[derive(Debug, Clone, Copy)]
+pub struct Lint(pub &'static str);
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match intcode::assemble::LINTS.iter().find(|lint| **lint == s) {
+            Some(lint) => Ok(Self(lint)),
+            None => Err(format!(
+                "unknown lint `{}`, expected one of {}",
+                s,
+                intcode::assemble::LINTS
+                    .iter()
+                    .map(|lint| format!("`{}`", lint))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+/// What ends each line of input.
+#[derive(Debug, Clone, Copy)]
+pub struct LineEnding(pub ascii::LineEnding);
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(Self(ascii::LineEnding::Lf)),
+            "crlf" => Ok(Self(ascii::LineEnding::CrLf)),
+            s => Err(format!(
+                "unknown line ending `{}`, expected one of `lf` or `crlf`",
+                s
+            )),
+        }
+    }
+}
+
+/// How to name generated labels.
+#[derive(Debug, Clone, Copy)]
+pub struct LabelScheme(pub disassemble::LabelScheme);
+
+impl FromStr for LabelScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "letters" => Ok(Self(disassemble::LabelScheme::Letters)),
+            "semantic" => Ok(Self(disassemble::LabelScheme::Semantic)),
+            s => Err(format!(
+                "unknown label scheme `{}`, expected one of `letters` or `semantic`",
+                s
+            )),
+        }
+    }
+}
+
+/// How confident the disassembler needs to be to output code.
+#[derive(Debug, Clone, Copy)]
+pub struct Confidence(pub disassemble::Confidence);
+
+impl FromStr for Confidence {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Self(disassemble::Confidence::Low)),
+            "medium" => Ok(Self(disassemble::Confidence::Medium)),
+            "high" => Ok(Self(disassemble::Confidence::High)),
+            s => Err(format!(
+                "unknown confidence `{}`, expected one of `low`, `medium`, or `high`",
+                s
+            )),
+        }
+    }
+}
+
+/// Extra comments to add to the disassembly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Annotate {
+    Xrefs,
+    Loops,
+    Constants,
+    Pseudo,
+    Confidence,
+}
+
+impl FromStr for Annotate {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "xrefs" => Ok(Self::Xrefs),
+            "loops" => Ok(Self::Loops),
+            "constants" => Ok(Self::Constants),
+            "pseudo" => Ok(Self::Pseudo),
+            "confidence" => Ok(Self::Confidence),
+            s => Err(format!(
+                "unknown annotation `{}`, expected one of `xrefs`, `loops`, `constants`, `pseudo`, or `confidence`",
+                s
+            )),
+        }
+    }
+}
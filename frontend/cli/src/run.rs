@@ -1,52 +1,379 @@
 use std::convert::TryInto;
+use std::error;
 use std::io;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::result;
+use std::str::FromStr;
 
-use anyhow::Result;
-use intcode::run::{Computer, State};
+use anyhow::{bail, Result};
+use intcode::assemble::debug::DebugInfo;
+use intcode::run::ascii::LineOptions;
+use intcode::run::render::{self, Render};
+use intcode::run::{Computer, Pause, State};
 
-use crate::parse_program;
+use crate::checkpoint::Checkpoints;
+use crate::line::Lines;
+use crate::{fmt, parse_program};
 
-pub fn basic(intcode: Vec<i64>) -> Result<()> {
-    let mut c = Computer::new(intcode);
-    let mut r = BufReader::new(io::stdin());
+/// Shows the source line of the current instruction when the program waits
+/// for input.
+pub struct ShowLine<'i> {
+    asm: &'i str,
+    path: &'i Path,
+    debug: DebugInfo,
+}
+
+impl<'i> ShowLine<'i> {
+    pub fn new(asm: &'i str, path: &'i Path, debug: DebugInfo) -> Self {
+        Self { asm, path, debug }
+    }
+
+    fn show(&self, c: &Computer) {
+        let line = match self.debug.line_at(c.ptr()) {
+            Some(line) => line,
+            None => return,
+        };
+        // Underline the line excluding the indentation and trailing comment.
+        let start: usize = self
+            .asm
+            .split_inclusive('\n')
+            .take(line - 1)
+            .map(str::len)
+            .sum();
+        let code = self.asm[start..].lines().next().unwrap_or("");
+        let code = code.split(';').next().unwrap().trim_end();
+        let indent = code.len() - code.trim_start().len();
+        let fmt = fmt::Ansi::new(self.asm, self.path);
+        let msg = format!("waiting for input at address {}", c.ptr());
+        eprint!("{}", fmt.note(&msg, start + indent..start + code.len()));
+    }
+}
+
+/// The error returned when the program is interrupted with Ctrl-C.
+#[derive(Debug)]
+pub struct Interrupted {
+    /// The outputs before the interruption.
+    pub outputs: Vec<i64>,
+}
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("program was interrupted")
+    }
+}
+
+impl error::Error for Interrupted {}
+
+/// When to flush the program's output to stdout.
+///
+/// Output is always flushed when the program waits for input or finishes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flush {
+    /// After every output.
+    Output,
+    /// After this many outputs.
+    Every(usize),
+    /// After every newline, in basic mode each output is a line.
+    Line,
+    /// Only when the program waits for input or finishes.
+    Manual,
+}
+
+/// How to write each output in basic mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Decimal,
+    Hex,
+    Ascii,
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "decimal" => Ok(Self::Decimal),
+            "hex" => Ok(Self::Hex),
+            "ascii" => Ok(Self::Ascii),
+            s => Err(format!(
+                "unknown output style `{}`, expected one of `decimal`, `hex`, or `ascii`",
+                s
+            )),
+        }
+    }
+}
+
+impl Style {
+    pub fn renderer(self) -> Box<dyn Render> {
+        match self {
+            Self::Decimal => Box::new(render::Decimal),
+            Self::Hex => Box::new(render::Hex),
+            Self::Ascii => Box::new(render::Ascii),
+        }
+    }
+}
+
+impl FromStr for Flush {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "output" => Ok(Self::Output),
+            "line" => Ok(Self::Line),
+            "manual" => Ok(Self::Manual),
+            s => match s.parse() {
+                Ok(n) if n > 0 => Ok(Self::Every(n)),
+                _ => Err(format!(
+                    "unknown flush policy `{}`, expected one of `output`, `line`, `manual`, or a number of outputs",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl Flush {
+    /// Returns whether to flush now that there are `pending` outputs since
+    /// the last flush, the last of which may have ended a line.
+    fn is_due(self, pending: usize, newline: bool) -> bool {
+        match self {
+            Self::Output => true,
+            Self::Every(n) => pending >= n,
+            Self::Line => newline,
+            Self::Manual => false,
+        }
+    }
+}
+
+/// Checks the outputs against the expected outputs and the expected last
+/// output, returning a description of the differences if they don't match.
+pub fn check_outputs(
+    outputs: &[i64],
+    expect: &[i64],
+    expect_last: Option<i64>,
+) -> result::Result<(), String> {
+    let mut diffs = Vec::new();
+    if !expect.is_empty() {
+        let len = outputs.len().max(expect.len());
+        for i in 0..len {
+            match (expect.get(i), outputs.get(i)) {
+                (Some(e), Some(a)) if e == a => {}
+                (Some(e), Some(a)) => {
+                    diffs.push(format!("output {}: expected {}, found {}", i, e, a))
+                }
+                (Some(e), None) => diffs.push(format!("output {}: expected {}, found none", i, e)),
+                (None, Some(a)) => diffs.push(format!("output {}: unexpected {}", i, a)),
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+    if let Some(e) = expect_last {
+        match outputs.last() {
+            Some(a) if *a == e => {}
+            Some(a) => diffs.push(format!("last output: expected {}, found {}", e, a)),
+            None => diffs.push(format!("last output: expected {}, found none", e)),
+        }
+    }
+    match diffs.is_empty() {
+        true => Ok(()),
+        false => Err(diffs.join("\n")),
+    }
+}
+
+/// Runs the program reading integers and writing outputs using the renderer,
+/// returning the outputs including any from before the run was resumed.
+pub fn basic(
+    c: &mut Computer,
+    show: Option<&ShowLine<'_>>,
+    lines: &mut Lines,
+    flush: Flush,
+    r: &mut dyn Render,
+    checkpoints: Option<&Checkpoints>,
+    mut outputs: Vec<i64>,
+) -> Result<Vec<i64>> {
     let mut w = BufWriter::new(io::stdout());
+    let mut pending = 0;
+    // The fuel only counts the steps since resuming from a checkpoint.
+    let start = c.stats().instructions;
     loop {
         match c.next()? {
             State::Yielded(value) => {
-                writeln!(w, "{}", value)?;
+                r.render(&mut w, value)?;
+                outputs.push(value);
+                pending += 1;
+                if flush.is_due(pending, true) {
+                    w.flush()?;
+                    pending = 0;
+                }
             }
             State::Waiting => {
                 w.flush()?;
-                let mut line = String::new();
-                r.read_line(&mut line)?;
+                pending = 0;
+                if let Some(show) = show {
+                    show.show(c);
+                }
+                let line = lines.read_line()?;
                 c.feed(parse_program(&line)?);
             }
             State::Complete => {
-                break Ok(w.flush()?);
+                w.flush()?;
+                break Ok(outputs);
+            }
+            State::Paused {
+                reason: Pause::Cancelled { .. },
+            } => {
+                w.flush()?;
+                return Err(Interrupted { outputs }.into());
+            }
+            State::Paused {
+                reason: Pause::Interval { .. },
+            } => {
+                if let Some(checkpoints) = checkpoints {
+                    w.flush()?;
+                    pending = 0;
+                    checkpoints.save(c, &outputs)?;
+                }
+            }
+            State::Paused { .. } => {}
+            State::OutOfFuel => {
+                w.flush()?;
+                bail!(
+                    "program did not halt within {} steps",
+                    c.stats().instructions - start
+                );
             }
         }
     }
 }
 
-pub fn utf8(intcode: Vec<i64>) -> Result<()> {
-    let mut c = Computer::new(intcode);
-    let mut r = BufReader::new(io::stdin());
+pub fn utf8(
+    c: &mut Computer,
+    show: Option<&ShowLine<'_>>,
+    lines: &mut Lines,
+    opts: &LineOptions,
+    flush: Flush,
+    checkpoints: Option<&Checkpoints>,
+    mut outputs: Vec<i64>,
+) -> Result<Vec<i64>> {
     let mut w = BufWriter::new(io::stdout());
+    let mut pending = 0;
+    // The fuel only counts the steps since resuming from a checkpoint.
+    let start = c.stats().instructions;
+    // The text output since the last input, used for tab completion.
+    let mut text = Vec::new();
     loop {
         match c.next()? {
             State::Yielded(value) => {
-                w.write_all(&[value.try_into()?])?;
+                let byte = value.try_into()?;
+                w.write_all(&[byte])?;
+                text.push(byte);
+                outputs.push(value);
+                pending += 1;
+                if flush.is_due(pending, byte == b'\n') {
+                    w.flush()?;
+                    pending = 0;
+                }
             }
             State::Waiting => {
                 w.flush()?;
-                let mut line = String::new();
-                r.read_line(&mut line)?;
-                c.feed(line.bytes().map(i64::from));
+                pending = 0;
+                if let Some(show) = show {
+                    show.show(c);
+                }
+                lines.learn(&String::from_utf8_lossy(&text));
+                text.clear();
+                let line = lines.read_line()?;
+                // The line is empty once there is no more input, it must not
+                // be turned into a line ending.
+                if line.is_empty() {
+                    bail!("unexpected end of input");
+                }
+                let values = opts.encode(&line);
+                if opts.is_echo() {
+                    let bytes: Vec<_> = values.iter().map(|&v| v as u8).collect();
+                    w.write_all(&bytes)?;
+                }
+                c.feed(values);
             }
             State::Complete => {
-                break Ok(w.flush()?);
+                w.flush()?;
+                break Ok(outputs);
+            }
+            State::Paused {
+                reason: Pause::Cancelled { .. },
+            } => {
+                w.flush()?;
+                return Err(Interrupted { outputs }.into());
+            }
+            State::Paused {
+                reason: Pause::Interval { .. },
+            } => {
+                if let Some(checkpoints) = checkpoints {
+                    w.flush()?;
+                    pending = 0;
+                    checkpoints.save(c, &outputs)?;
+                }
+            }
+            State::Paused { .. } => {}
+            State::OutOfFuel => {
+                w.flush()?;
+                bail!(
+                    "program did not halt within {} steps",
+                    c.stats().instructions - start
+                );
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flush_policy() {
+        assert_eq!("output".parse(), Ok(Flush::Output));
+        assert_eq!("line".parse(), Ok(Flush::Line));
+        assert_eq!("manual".parse(), Ok(Flush::Manual));
+        assert_eq!("16".parse(), Ok(Flush::Every(16)));
+        assert!("0".parse::<Flush>().is_err());
+        assert!("often".parse::<Flush>().is_err());
+
+        assert!(Flush::Output.is_due(1, false));
+        assert!(!Flush::Every(3).is_due(2, true));
+        assert!(Flush::Every(3).is_due(3, false));
+        assert!(Flush::Line.is_due(1, true));
+        assert!(!Flush::Line.is_due(5, false));
+        assert!(!Flush::Manual.is_due(100, true));
+    }
+
+    #[test]
+    fn output_style() {
+        assert_eq!("hex".parse(), Ok(Style::Hex));
+        assert_eq!(
+            "binary".parse::<Style>(),
+            Err(
+                "unknown output style `binary`, expected one of `decimal`, `hex`, or `ascii`"
+                    .into()
+            )
+        );
+        let mut w = Vec::new();
+        Style::Hex.renderer().render(&mut w, 255).unwrap();
+        assert_eq!(w, b"0xff\n");
+    }
+
+    #[test]
+    fn outputs() {
+        assert_eq!(check_outputs(&[1, 2], &[1, 2], Some(2)), Ok(()));
+        assert_eq!(check_outputs(&[1, 2], &[], None), Ok(()));
+        assert_eq!(
+            check_outputs(&[1, 3, 4], &[1, 2], None),
+            Err("output 1: expected 2, found 3\noutput 2: unexpected 4".into())
+        );
+        assert_eq!(
+            check_outputs(&[], &[7], Some(7)),
+            Err("output 0: expected 7, found none\nlast output: expected 7, found none".into())
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! The analysis of a single open document.
+
+use std::path::Path;
+
+use intcode::assemble::symbols::{self, Symbol};
+use intcode::assemble::{Intcode, Options};
+use intcode::error::files::Files;
+use intcode::error::span::Span;
+use intcode::error::ErrorSet;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+/// An open document and what is known about it.
+#[derive(Debug)]
+pub struct Document {
+    text: String,
+    symbols: Vec<Symbol>,
+    /// The address of each label, empty if the program did not assemble.
+    labels: Vec<(String, usize)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Returns the position of the byte offset, the character is counted in
+/// UTF-16 code units.
+pub fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[start..].encode_utf16().count() as u32,
+    }
+}
+
+/// Returns the byte offset of the position, clamped to the end of the line.
+pub fn offset(text: &str, position: Position) -> usize {
+    let mut start = 0;
+    for _ in 0..position.line {
+        match text[start..].find('\n') {
+            Some(i) => start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line = text[start..].split('\n').next().unwrap();
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    start + line.len()
+}
+
+fn range(text: &str, span: Span) -> Range {
+    Range::new(position(text, span.m), position(text, span.n))
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.m <= offset && offset <= span.n
+}
+
+impl Document {
+    /// Analyzes the text, `path` is used to find the files included with
+    /// `INCLUDE` directives.
+    pub fn new(text: String, path: Option<&Path>) -> Self {
+        let opts = Options::new();
+        let symbols = symbols::symbols(&text, &opts).unwrap_or_default();
+        let opts = match path.and_then(Path::parent) {
+            Some(dir) => opts.include_dir(dir),
+            None => opts,
+        };
+        let mut files = Files::new(path.unwrap_or_else(|| Path::new("<input>")), &*text);
+        let (labels, errors, warnings) =
+            match intcode::assemble::to_intcode_files(&mut files, &opts) {
+                Ok(Intcode {
+                    debug, warnings, ..
+                }) => (debug.labels, Vec::new(), warnings),
+                Err(ErrorSet {
+                    errors, warnings, ..
+                }) => (Vec::new(), errors, warnings),
+            };
+        let diagnostic = |severity, msg: &str, span: Span| {
+            // Problems in an included file are shown on the directive that
+            // included it.
+            let file = files.file_of(span.m);
+            let message = match file {
+                0 => msg.to_owned(),
+                _ => format!("{}: {}", files.name(file).display(), msg),
+            };
+            Diagnostic {
+                range: range(&text, files.origin(span)),
+                severity: Some(severity),
+                source: Some("intcode".into()),
+                message,
+                ..Diagnostic::default()
+            }
+        };
+        let diagnostics = errors
+            .iter()
+            .map(|e| diagnostic(DiagnosticSeverity::Error, &*e.msg, e.span))
+            .chain(
+                warnings
+                    .iter()
+                    .map(|w| diagnostic(DiagnosticSeverity::Warning, &*w.msg, w.span)),
+            )
+            .collect();
+        Self {
+            text,
+            symbols,
+            labels,
+            diagnostics,
+        }
+    }
+
+    /// Returns the errors and warnings from assembling the document.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Returns the symbol at the position and the span of its name there.
+    fn symbol_at(&self, position: Position) -> Option<(&Symbol, Span)> {
+        let offset = offset(&self.text, position);
+        self.symbols.iter().find_map(|symbol| {
+            symbol
+                .defs
+                .iter()
+                .chain(&symbol.refs)
+                .find(|span| contains(**span, offset))
+                .map(|span| (symbol, *span))
+        })
+    }
+
+    /// Returns the range of the definition of the symbol at the position.
+    pub fn definition(&self, position: Position) -> Option<Range> {
+        let (symbol, _) = self.symbol_at(position)?;
+        let def = symbol.defs.first()?;
+        Some(range(&self.text, *def))
+    }
+
+    /// Returns the address of the label at the position and the range of
+    /// the label.
+    pub fn hover(&self, position: Position) -> Option<(String, Range)> {
+        let (symbol, span) = self.symbol_at(position)?;
+        let (_, addr) = self.labels.iter().find(|(name, _)| *name == symbol.name)?;
+        let msg = format!("`{}` is at address {}", symbol.name, addr);
+        Some((msg, range(&self.text, span)))
+    }
+
+    /// Returns the names of the labels and constants in the document.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.symbols.iter().map(|symbol| symbol.name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn positions() {
+        let text = "OUT #1\nDB \"😎\", x\n";
+        assert_eq!(position(text, 0), pos(0, 0));
+        assert_eq!(position(text, 11), pos(1, 4));
+        assert_eq!(position(text, 18), pos(1, 9));
+        assert_eq!(offset(text, pos(1, 9)), 18);
+        assert_eq!(offset(text, pos(1, 99)), 19);
+        assert_eq!(offset(text, pos(9, 0)), text.len());
+    }
+
+    #[test]
+    fn labels() {
+        let doc = Document::new("    JZ #0, #end\nend:\n    HLT\n".into(), None);
+        assert!(doc.diagnostics().is_empty());
+        assert_eq!(
+            doc.definition(pos(0, 13)),
+            Some(Range::new(pos(1, 0), pos(1, 3)))
+        );
+        assert_eq!(
+            doc.hover(pos(0, 13)),
+            Some((
+                "`end` is at address 3".into(),
+                Range::new(pos(0, 12), pos(0, 15))
+            ))
+        );
+        assert_eq!(doc.hover(pos(0, 5)), None);
+        assert_eq!(doc.names().collect::<Vec<_>>(), ["end"]);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let doc = Document::new("OUT x\n".into(), None);
+        let [d] = <[Diagnostic; 1]>::try_from(doc.diagnostics().to_vec()).unwrap();
+        assert_eq!(d.message, "undefined label");
+        assert_eq!(d.severity, Some(DiagnosticSeverity::Error));
+        assert_eq!(d.range, Range::new(pos(0, 4), pos(0, 5)));
+    }
+}
@@ -0,0 +1,147 @@
+//! A language server for intcode assembly.
+//!
+//! The server talks over stdin and stdout. It reports the errors and warnings
+//! from assembling each document as it changes, goes to the definition of
+//! labels and constants, shows the address of a label on hover, and
+//! completes mnemonics and names.
+
+mod document;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use intcode::assemble::MNEMONICS;
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, InitializeResult, Location, MarkupContent,
+    MarkupKind, OneOf, ServerCapabilities, ServerInfo, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use tower_lsp::{Client, LanguageServer, LspService, Server};
+
+use crate::document::Document;
+
+struct Backend {
+    client: Client,
+    documents: Mutex<HashMap<Url, Document>>,
+}
+
+impl Backend {
+    /// Analyzes the new text of the document and publishes its diagnostics.
+    async fn update(&self, uri: Url, text: String, version: i32) {
+        let path = uri.to_file_path().ok();
+        let doc = Document::new(text, path.as_deref());
+        let diagnostics = doc.diagnostics().to_vec();
+        self.documents.lock().unwrap().insert(uri.clone(), doc);
+        self.client
+            .publish_diagnostics(uri, diagnostics, Some(version))
+            .await;
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+        Ok(InitializeResult {
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::Full,
+                )),
+                definition_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                completion_provider: Some(CompletionOptions::default()),
+                ..ServerCapabilities::default()
+            },
+            server_info: Some(ServerInfo {
+                name: env!("CARGO_PKG_NAME").into(),
+                version: Some(env!("CARGO_PKG_VERSION").into()),
+            }),
+        })
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let doc = params.text_document;
+        self.update(doc.uri, doc.text, doc.version).await;
+    }
+
+    async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
+        // The whole document is sent on every change.
+        if let Some(change) = params.content_changes.pop() {
+            let doc = params.text_document;
+            self.update(doc.uri, change.text, doc.version).await;
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.documents.lock().unwrap().remove(&uri);
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let params = params.text_document_position_params;
+        let uri = params.text_document.uri;
+        let documents = self.documents.lock().unwrap();
+        let range = documents
+            .get(&uri)
+            .and_then(|doc| doc.definition(params.position));
+        Ok(range.map(|range| GotoDefinitionResponse::Scalar(Location::new(uri, range))))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let params = params.text_document_position_params;
+        let documents = self.documents.lock().unwrap();
+        let hover = documents
+            .get(&params.text_document.uri)
+            .and_then(|doc| doc.hover(params.position));
+        Ok(hover.map(|(value, range)| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(range),
+        }))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = params.text_document_position.text_document.uri;
+        let mut items: Vec<_> = MNEMONICS
+            .iter()
+            .map(|mnemonic| CompletionItem {
+                label: (*mnemonic).into(),
+                kind: Some(CompletionItemKind::Keyword),
+                ..CompletionItem::default()
+            })
+            .collect();
+        if let Some(doc) = self.documents.lock().unwrap().get(&uri) {
+            items.extend(doc.names().map(|name| CompletionItem {
+                label: name.into(),
+                kind: Some(CompletionItemKind::Variable),
+                ..CompletionItem::default()
+            }));
+        }
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let (service, messages) = LspService::new(|client| Backend {
+        client,
+        documents: Mutex::default(),
+    });
+    Server::new(tokio::io::stdin(), tokio::io::stdout())
+        .interleave(messages)
+        .serve(service)
+        .await;
+}
@@ -1,5 +1,5 @@
 import dynamic from "next/dynamic";
-import { useEffect, createRef, useState } from "react";
+import { useEffect, createRef, useRef, useState } from "react";
 import { Listbox } from "@headlessui/react";
 import { SelectorIcon } from "@heroicons/react/solid";
 
@@ -34,6 +34,8 @@ export default function Index() {
     compilerOutput: "",
     programOutput: [],
   });
+  // Counts the runs, so that a cancelled run stops calling the machine
+  const run = useRef(0);
 
   // Loads the WASM module
   useEffect(() => {
@@ -60,6 +62,25 @@ export default function Index() {
     }
   };
 
+  // Appends the output of the machine, and keeps calling it while it is still
+  // running so that the browser stays responsive
+  const drive = (id, result) => {
+    setOutput((output) => ({
+      ...output,
+      programOutput: output.programOutput.concat(result.output),
+    }));
+    if (result.state == "Complete") {
+      setState(State.COMPLETE);
+    } else if (result.state == "Running") {
+      setTimeout(() => {
+        if (run.current == id) {
+          drive(id, wasm.next(null));
+        }
+      });
+    }
+    // Machine wants input, leave in WAITING state
+  };
+
   // Called when the user clicks the "Run" button
   const onRun = () => {
     setState(State.WAITING);
@@ -74,34 +95,24 @@ export default function Index() {
       return;
     }
 
-    const result2 = wasm.next(null);
     setOutput({
       compiledIntcode: result.intcode,
       compilerOutput: result.output,
-      programOutput: [result2.output],
+      programOutput: [],
     });
-    if (result2.state == "Complete") {
-      setState(State.COMPLETE);
-    }
-    // Machine wants input, leave in WAITING state
+    run.current += 1;
+    drive(run.current, wasm.next(null));
   };
 
   // Cancelled when the user clicks the "Cancel" button
   const onCancel = () => {
+    run.current += 1;
     setState(State.COMPLETE);
   };
 
   // Called when the user inputs a value
   const onInput = (input) => {
-    const result = wasm.next(input + "\n");
-    setOutput({
-      ...output,
-      programOutput: output.programOutput.concat(result.output),
-    });
-    if (result.state == "Complete") {
-      setState(State.COMPLETE);
-    }
-    // Machine still wants input, leave in WAITING state
+    drive(run.current, wasm.next(input + "\n"));
   };
 
   return (
@@ -110,6 +121,7 @@ export default function Index() {
         example={example}
         setExample={(e) => {
           setExample(e);
+          run.current += 1;
           setState(State.COMPLETE);
         }}
         state={state}
@@ -11,9 +11,15 @@ use wasm_bindgen::prelude::*;
 use intcode::assemble::Intcode;
 use intcode::error::ErrorSet;
 use intcode::run;
+use intcode::run::ascii::{LineEnding, LineOptions};
 
 static COMPUTER: Lazy<Mutex<Option<run::Computer>>> = Lazy::new(Default::default);
 
+static LINE_OPTIONS: Lazy<Mutex<LineOptions>> = Lazy::new(Default::default);
+
+/// The number of instructions to execute in each call to `next()`.
+const FUEL: u64 = 1_000_000;
+
 #[derive(Debug, Serialize)]
 pub enum AssembleState {
     Running,
@@ -29,6 +35,7 @@ pub struct AssembleOutput {
 
 #[derive(Debug, Serialize)]
 pub enum NextState {
+    Running,
     Waiting,
     Complete,
 }
@@ -55,12 +62,21 @@ pub fn assemble(asm: &str) -> Result<JsValue, JsValue> {
         Ok(Intcode {
             output: intcode,
             warnings,
+            ..
         }) => {
+            let mut set = ErrorSet {
+                warnings,
+                ..ErrorSet::default()
+            };
+            set.normalize();
             let mut output = String::new();
-            for warning in warnings {
-                output.push_str(&opts.warning(&warning));
+            for warning in &set.warnings {
+                output.push_str(&opts.warning(warning));
                 output.push('\n');
             }
+            if set.omitted > 0 {
+                output.push_str(&opts.omitted(set.omitted));
+            }
             let human_intcode = intcode
                 .iter()
                 .map(|d| d.to_string())
@@ -73,16 +89,20 @@ pub fn assemble(asm: &str) -> Result<JsValue, JsValue> {
                 intcode: Some(human_intcode),
             }
         }
-        Err(ErrorSet { errors, warnings }) => {
+        Err(mut set) => {
+            set.normalize();
             let mut output = String::new();
-            for warning in warnings {
-                output.push_str(&opts.warning(&warning));
+            for warning in &set.warnings {
+                output.push_str(&opts.warning(warning));
                 output.push('\n');
             }
-            for error in errors {
-                output.push_str(&opts.error(&error));
+            for error in &set.errors {
+                output.push_str(&opts.error(error));
                 output.push('\n');
             }
+            if set.omitted > 0 {
+                output.push_str(&opts.omitted(set.omitted));
+            }
             AssembleOutput {
                 state: AssembleState::Failed,
                 output,
@@ -93,14 +113,38 @@ pub fn assemble(asm: &str) -> Result<JsValue, JsValue> {
     JsValue::from_serde(&output).map_err(to_js_value)
 }
 
+/// Configures how lines of input passed to `next()` are translated.
+#[wasm_bindgen]
+pub fn configure_lines(strip_cr: bool, echo: bool, crlf: bool) {
+    let ending = if crlf {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    };
+    *LINE_OPTIONS.lock().unwrap() = LineOptions::new()
+        .strip_cr(strip_cr)
+        .echo(echo)
+        .ending(ending);
+}
+
+/// Runs the program until it needs input or halts.
+///
+/// This gives up after a while and returns the `Running` state so that the
+/// browser stays responsive, it should then be called again without input.
 #[wasm_bindgen]
 pub fn next(input: Option<String>) -> Result<JsValue, JsValue> {
     let mut computer = COMPUTER.lock().unwrap();
     let computer = computer.as_mut().unwrap();
     let mut output = Vec::new();
     if let Some(i) = input {
-        computer.feed(i.into_bytes().into_iter().map(i64::from));
+        let opts = LINE_OPTIONS.lock().unwrap();
+        let values = opts.encode(&i);
+        if opts.is_echo() {
+            output.extend(values.iter().map(|&v| v as u8));
+        }
+        computer.feed(values);
     }
+    computer.set_fuel(FUEL);
     let output = loop {
         match computer.next().map_err(to_js_value)? {
             run::State::Yielded(value) => {
@@ -120,6 +164,14 @@ pub fn next(input: Option<String>) -> Result<JsValue, JsValue> {
                     output,
                 };
             }
+            run::State::Paused { .. } => {}
+            run::State::OutOfFuel => {
+                let output = String::from_utf8(output).map_err(to_js_value)?;
+                break NextOutput {
+                    state: NextState::Running,
+                    output,
+                };
+            }
         }
     };
     JsValue::from_serde(&output).map_err(to_js_value)
@@ -72,6 +72,62 @@ pub enum Instr<'i> {
 
     /// (Pseudo) Places raw data in the program.
     Data(Vec<S<RawParam<'i>>>),
+    /// (Pseudo) Places an instruction in the program whose opcode is modified
+    /// at runtime, the values are placed exactly like `DB`.
+    Mutable(Vec<S<RawParam<'i>>>),
+    /// (Pseudo) Outputs each character of a string, or each value of the
+    /// data placed at a label, using one `OUT` instruction for each.
+    OutputString(S<RawParam<'i>>),
+
+    /// (Directive) Places the values of an intcode file in the program.
+    Include(S<String<'i>>),
+
+    /// (Directive) Records metadata about the program.
+    Meta(Meta<'i>),
+}
+
+/// A metadata directive.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Meta<'i> {
+    /// The name of the program.
+    Name(String<'i>),
+    /// The author of the program.
+    Author(String<'i>),
+    /// The label execution should start at.
+    Entry(S<Label<'i>>),
+    /// An extension the runner must enable.
+    Requires(String<'i>),
+    /// The names of the values the program inputs, in order.
+    Inputs(Vec<S<&'i str>>),
+    /// The relative base the program expects to start with.
+    RelativeBase(i64),
+    /// A name for a number, usable anywhere a number literal is.
+    Constant(S<&'i str>, i64),
+    /// An assembly file whose statements follow this one.
+    Include(S<String<'i>>),
+}
+
+/// A blank line or a comment on its own line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trivium<'i> {
+    BlankLine,
+    /// The comment including the `;` prefix.
+    Comment(S<&'i str>),
+}
+
+/// The comments and blank lines around a statement.
+///
+/// These are only kept if [`Options::trivia`][crate::Options::trivia] is
+/// enabled, otherwise they are always empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trivia<'i> {
+    /// The lines before the statement.
+    pub leading: Vec<Trivium<'i>>,
+    /// The lines between the label and the instruction, starting with the
+    /// comment at the end of the label's line if there is one.
+    pub inner: Vec<Trivium<'i>>,
+    /// The comment at the end of the instruction's line.
+    pub trailing: Option<S<&'i str>>,
 }
 
 /// A single line in a program.
@@ -81,12 +137,15 @@ pub enum Instr<'i> {
 pub struct Stmt<'i> {
     pub label: Option<S<Label<'i>>>,
     pub instr: S<Instr<'i>>,
+    pub trivia: Trivia<'i>,
 }
 
 /// An entire program.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Program<'i> {
     pub stmts: Vec<Stmt<'i>>,
+    /// The lines after the last statement, only kept with trivia.
+    pub end: Vec<Trivium<'i>>,
 }
 
 impl From<Mode> for i64 {
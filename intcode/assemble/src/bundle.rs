@@ -0,0 +1,122 @@
+//! A program bundled with its debug information in a single file.
+//!
+//! The debug information is written as a header of comment lines before the
+//! program so that the file can still be told apart from plain intcode.
+//!
+//! ```text
+//! # intcode bundle 1
+//! # assembler intcode-assemble 0.1.0
+//! # source hello.ints
+//! # label 4 msg
+//! # line 0 1
+//! 4,4,99,...
+//! ```
+//!
+//! The `label`, `line`, and metadata lines are the same as in a
+//! [debug info file][crate::debug].
+
+use std::fmt;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::debug::{DebugInfo, ParseError};
+
+/// The first line of every bundle.
+const MAGIC: &str = "# intcode bundle 1";
+
+/// A program with its debug information.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bundle {
+    pub program: Vec<i64>,
+    /// The name and version of the assembler that built the program.
+    pub assembler: String,
+    /// The name of the source file the program was assembled from.
+    pub source: Option<String>,
+    pub debug: DebugInfo,
+}
+
+/// Returns whether the text looks like a bundle instead of plain intcode.
+pub fn is_bundle(s: &str) -> bool {
+    s.lines().next().map(str::trim_end) == Some(MAGIC)
+}
+
+impl Bundle {
+    /// Constructs a bundle built by this assembler.
+    pub fn new(program: Vec<i64>, debug: DebugInfo) -> Self {
+        Self {
+            program,
+            assembler: concat!("intcode-assemble ", env!("CARGO_PKG_VERSION")).to_owned(),
+            source: None,
+            debug,
+        }
+    }
+
+    /// The name of the source file the program was assembled from.
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+impl Display for Bundle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", MAGIC)?;
+        writeln!(f, "# assembler {}", self.assembler)?;
+        if let Some(source) = &self.source {
+            writeln!(f, "# source {}", source)?;
+        }
+        for line in self.debug.to_string().lines() {
+            writeln!(f, "# {}", line)?;
+        }
+        let program: Vec<_> = self.program.iter().map(|v| v.to_string()).collect();
+        writeln!(f, "{}", program.join(","))
+    }
+}
+
+impl FromStr for Bundle {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !is_bundle(s) {
+            return Err(ParseError::new(1, "expected a bundle header"));
+        }
+        let mut assembler = None;
+        let mut source = None;
+        let mut program = None;
+        // Lines that are not debug info are left blank so that the line
+        // numbers in errors from parsing it are still right.
+        let mut debug = String::new();
+        for (i, line) in s.lines().enumerate().skip(1) {
+            let line = line.trim_end();
+            if let Some(entry) = line.strip_prefix('#') {
+                let entry = entry.trim_start();
+                if program.is_some() {
+                    return Err(ParseError::new(i + 1, "unexpected line after the program"));
+                } else if let Some(name) = entry.strip_prefix("assembler ") {
+                    assembler = Some(name.trim().to_owned());
+                } else if let Some(name) = entry.strip_prefix("source ") {
+                    source = Some(name.trim().to_owned());
+                } else {
+                    debug.push_str(entry);
+                }
+            } else if !line.is_empty() {
+                if program.is_some() {
+                    return Err(ParseError::new(i + 1, "unexpected line after the program"));
+                }
+                let values = line
+                    .split(',')
+                    .map(|v| v.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| ParseError::new(i + 1, "invalid intcode program"))?;
+                program = Some(values);
+            }
+            debug.push('\n');
+        }
+        Ok(Self {
+            program: program.ok_or_else(|| ParseError::new(1, "expected a program"))?,
+            assembler: assembler.ok_or_else(|| ParseError::new(1, "expected an assembler"))?,
+            source,
+            debug: format!("\n{}", debug).parse()?,
+        })
+    }
+}
@@ -0,0 +1,179 @@
+//! Debug information about an assembled program.
+//!
+//! This is kept separate from the intcode itself so that the output can stay
+//! as plain numbers. It can be written to and read from a sidecar file using
+//! the [`Display`] and [`FromStr`] implementations, the format is one entry
+//! per line:
+//!
+//! ```text
+//! label <address> <name>
+//! line <address> <line>
+//! name <text>
+//! author <text>
+//! entry <address>
+//! requires <extension>
+//! input <name>
+//! relative_base <value>
+//! fingerprint <hash>
+//! ```
+
+use std::error;
+use std::fmt;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::meta::Metadata;
+
+/// Debug information about an assembled program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// The address of each label, in the order they first appear.
+    pub labels: Vec<(String, usize)>,
+    /// The source line of each statement, keyed by the address of the first
+    /// value it assembled to. Lines start at 1.
+    pub lines: Vec<(usize, usize)>,
+    /// The metadata set using directives.
+    pub meta: Metadata,
+    /// A hash of the assembler version, options, and source files, if it was
+    /// asked for.
+    pub fingerprint: Option<String>,
+}
+
+/// An error parsing a debug info file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    msg: &'static str,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, msg: &'static str) -> Self {
+        Self { line, msg }
+    }
+}
+
+impl DebugInfo {
+    /// Returns the name of the label at the given address.
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(_, a)| *a == addr)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the source line of the statement at the given address.
+    pub fn line(&self, addr: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .find(|(a, _)| *a == addr)
+            .map(|(_, line)| *line)
+    }
+
+    /// Returns the source line of the statement that contains the given
+    /// address.
+    pub fn line_at(&self, addr: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .filter(|(a, _)| *a <= addr)
+            .max_by_key(|(a, _)| *a)
+            .map(|(_, line)| *line)
+    }
+}
+
+impl Display for DebugInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, addr) in &self.labels {
+            writeln!(f, "label {} {}", addr, name)?;
+        }
+        for (addr, line) in &self.lines {
+            writeln!(f, "line {} {}", addr, line)?;
+        }
+        if let Some(name) = &self.meta.name {
+            writeln!(f, "name {}", name)?;
+        }
+        if let Some(author) = &self.meta.author {
+            writeln!(f, "author {}", author)?;
+        }
+        if let Some(entry) = self.meta.entry {
+            writeln!(f, "entry {}", entry)?;
+        }
+        for ext in &self.meta.requires {
+            writeln!(f, "requires {}", ext)?;
+        }
+        for name in &self.meta.inputs {
+            writeln!(f, "input {}", name)?;
+        }
+        if let Some(relative_base) = self.meta.relative_base {
+            writeln!(f, "relative_base {}", relative_base)?;
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            writeln!(f, "fingerprint {}", fingerprint)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DebugInfo {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut debug = Self::default();
+        for (i, line) in s.lines().enumerate() {
+            let err = |msg| ParseError { line: i + 1, msg };
+            let mut words = line.split_whitespace();
+            let kind = match words.next() {
+                Some(kind) => kind,
+                None => continue,
+            };
+            let text = || {
+                let text = words.clone().collect::<Vec<_>>().join(" ");
+                match text.is_empty() {
+                    true => Err(err("expected a value")),
+                    false => Ok(text),
+                }
+            };
+            match kind {
+                "name" => debug.meta.name = Some(text()?),
+                "author" => debug.meta.author = Some(text()?),
+                "requires" => debug.meta.requires.push(text()?),
+                "input" => debug.meta.inputs.push(text()?),
+                "fingerprint" => debug.fingerprint = Some(text()?),
+                "entry" => {
+                    let entry = text()?.parse().map_err(|_| err("invalid address"))?;
+                    debug.meta.entry = Some(entry);
+                }
+                "relative_base" => {
+                    let value = text()?.parse().map_err(|_| err("invalid relative base"))?;
+                    debug.meta.relative_base = Some(value);
+                }
+                "label" | "line" => {
+                    let addr = words
+                        .next()
+                        .ok_or_else(|| err("expected an address"))?
+                        .parse()
+                        .map_err(|_| err("invalid address"))?;
+                    let value = words.next().ok_or_else(|| err("expected a value"))?;
+                    if words.next().is_some() {
+                        return Err(err("unexpected trailing value"));
+                    }
+                    if kind == "label" {
+                        debug.labels.push((value.to_owned(), addr));
+                    } else {
+                        let line = value.parse().map_err(|_| err("invalid line number"))?;
+                        debug.lines.push((addr, line));
+                    }
+                }
+                _ => return Err(err("unknown entry kind")),
+            }
+        }
+        Ok(debug)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on line {}", self.msg, self.line)
+    }
+}
+
+impl error::Error for ParseError {}
@@ -0,0 +1,113 @@
+//! Custom directives defined outside of the assembler.
+//!
+//! A custom directive is registered with [`Options::directive`] and is then
+//! parsed like any other directive. Its parameters are checked against the
+//! kinds it was registered with and passed to its callback, which returns the
+//! values to place in the program. A label on the directive refers to the
+//! first of these values, just like a label on `DB`.
+//!
+//! Custom directives are expanded while parsing, so the syntax tree returned
+//! by [`to_ast`] holds a `DB` statement with the values in place of the
+//! directive. A file written back out from the syntax tree therefore does not
+//! keep the directive. They are not allowed in [strict] mode.
+//!
+//! [`Options::directive`]: crate::Options::directive
+//! [`to_ast`]: crate::to_ast
+//! [strict]: crate::Options::strict
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::parse::MNEMONICS;
+
+/// The kind of a parameter of a custom directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A number literal like `7` or `-0x10`.
+    Number,
+    /// A string literal like `"hello"`.
+    String,
+}
+
+/// A parameter passed to a custom directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Number(i64),
+    String(String),
+}
+
+/// An error returned by a custom directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectiveError {
+    pub(crate) msg: String,
+    pub(crate) param: Option<usize>,
+}
+
+type Expand = dyn Fn(&[Value]) -> Result<Vec<i64>, DirectiveError> + Send + Sync;
+
+/// A custom directive.
+#[derive(Clone)]
+pub struct Directive {
+    pub(crate) name: String,
+    pub(crate) params: Vec<Kind>,
+    pub(crate) expand: Arc<Expand>,
+}
+
+impl DirectiveError {
+    /// Constructs an error that points at the whole directive.
+    pub fn new(msg: impl Into<String>) -> Self {
+        Self {
+            msg: msg.into(),
+            param: None,
+        }
+    }
+
+    /// Points the error at the parameter with the given index instead.
+    pub fn param(mut self, index: usize) -> Self {
+        self.param = Some(index);
+        self
+    }
+}
+
+impl Directive {
+    /// Constructs a directive with the given name and parameter kinds.
+    ///
+    /// # Panics
+    ///
+    /// If the name is not made up of uppercase letters and digits, or if it
+    /// is the name of a built-in mnemonic or directive.
+    pub fn new<F>(name: &str, params: &[Kind], expand: F) -> Self
+    where
+        F: Fn(&[Value]) -> Result<Vec<i64>, DirectiveError> + Send + Sync + 'static,
+    {
+        assert!(
+            !name.is_empty() && name.chars().all(|c| matches!(c, '0'..='9' | 'A'..='Z')),
+            "directive name `{}` must be made up of uppercase letters and digits",
+            name
+        );
+        assert!(
+            !MNEMONICS.contains(&name),
+            "directive name `{}` is already a mnemonic",
+            name
+        );
+        Self {
+            name: name.to_owned(),
+            params: params.to_vec(),
+            expand: Arc::new(expand),
+        }
+    }
+
+    /// Returns the name of the directive.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Debug for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Directive")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .finish()
+    }
+}
@@ -0,0 +1,286 @@
+//! Parse and evaluate expressions over the state of a computer.
+//!
+//! This is used by debuggers for breakpoint conditions and watch
+//! expressions. An expression is made up of integers, labels, `ptr`, `rb`, memory reads
+//! like `mem[x+1]`, and the arithmetic, comparison, and logical operators
+//! with the same precedence as in Rust. A label evaluates to its address,
+//! and comparisons and logical operators evaluate to `1` or `0`.
+
+use std::fmt;
+use std::result;
+use std::str::FromStr;
+
+use intcode_error::span::Span;
+use intcode_error::{Error, Result};
+use intcode_lex::parse::{integer, Sign};
+
+/// The values an expression can refer to, usually a computer along with the
+/// debug info of its program.
+pub trait Env {
+    /// Returns the value in memory at the address.
+    fn mem(&self, addr: usize) -> i64;
+    /// Returns the instruction pointer.
+    fn ptr(&self) -> usize;
+    /// Returns the relative base.
+    fn rb(&self) -> i64;
+    /// Returns the address of the label.
+    fn label(&self, name: &str) -> Option<usize>;
+}
+
+/// A parsed expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr {
+    text: String,
+    node: Node,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    Label(String),
+    Ptr,
+    Rb,
+    Mem(Box<Node>),
+    Neg(Box<Node>),
+    Not(Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Number,
+    Ident,
+    Punct(&'static str),
+    Eof,
+}
+
+struct Parser<'i> {
+    input: &'i str,
+    tokens: Vec<(Token, Span)>,
+    i: usize,
+}
+
+/// The binary operators from the lowest precedence to the highest.
+const LEVELS: &[&[(&str, Op)]] = &[
+    &[("||", Op::Or)],
+    &[("&&", Op::And)],
+    &[
+        ("==", Op::Eq),
+        ("!=", Op::Ne),
+        ("<=", Op::Le),
+        (">=", Op::Ge),
+        ("<", Op::Lt),
+        (">", Op::Gt),
+    ],
+    &[("+", Op::Add), ("-", Op::Sub)],
+    &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem)],
+];
+
+const PUNCTS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!", "(", ")", "[", "]",
+];
+
+fn tokenize(input: &str) -> Result<Vec<(Token, Span)>> {
+    let ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while let Some(c) = input[i..].chars().next() {
+        let rest = &input[i..];
+        let (token, len) = if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if c.is_ascii_digit() {
+            (
+                Token::Number,
+                rest.find(|c| !ident(c)).unwrap_or(rest.len()),
+            )
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            (Token::Ident, rest.find(|c| !ident(c)).unwrap_or(rest.len()))
+        } else {
+            match PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+                Some(p) => (Token::Punct(*p), p.len()),
+                None => return Err(Error::new("unexpected character", i..i + c.len_utf8())),
+            }
+        };
+        tokens.push((token, (i..i + len).into()));
+        i += len;
+    }
+    tokens.push((Token::Eof, (i..i).into()));
+    Ok(tokens)
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> (Token, Span) {
+        self.tokens[self.i]
+    }
+
+    fn next(&mut self) -> (Token, Span) {
+        let next = self.peek();
+        if next.0 != Token::Eof {
+            self.i += 1;
+        }
+        next
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<()> {
+        match self.next() {
+            (Token::Punct(p), _) if p == punct => Ok(()),
+            (_, span) => Err(Error::new(format!("expected `{}`", punct), span)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Node> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node> {
+        let ops = match LEVELS.get(level) {
+            Some(ops) => ops,
+            None => return self.unary(),
+        };
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&(_, op)) = ops.iter().find(|(p, _)| self.peek().0 == Token::Punct(*p)) {
+            self.next();
+            let rhs = self.binary(level + 1)?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        match self.peek().0 {
+            Token::Punct("-") => {
+                self.next();
+                Ok(Node::Neg(Box::new(self.unary()?)))
+            }
+            Token::Punct("!") => {
+                self.next();
+                Ok(Node::Not(Box::new(self.unary()?)))
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node> {
+        match self.next() {
+            (Token::Number, span) => {
+                integer(self.input, span, Sign::Positive, 64).map(Node::Number)
+            }
+            (Token::Ident, span) => match span.as_str(self.input) {
+                "ptr" => Ok(Node::Ptr),
+                "rb" => Ok(Node::Rb),
+                "mem" => {
+                    self.expect("[")?;
+                    let addr = self.expr()?;
+                    self.expect("]")?;
+                    Ok(Node::Mem(Box::new(addr)))
+                }
+                label => Ok(Node::Label(label.to_owned())),
+            },
+            (Token::Punct("("), _) => {
+                let node = self.expr()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            (_, span) => Err(Error::new(
+                "expected a number, a label, `ptr`, `rb`, or `mem[..]`",
+                span,
+            )),
+        }
+    }
+}
+
+impl Expr {
+    /// Parses an expression.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            input: text,
+            tokens: tokenize(text)?,
+            i: 0,
+        };
+        let node = parser.expr()?;
+        match parser.next() {
+            (Token::Eof, _) => Ok(Self {
+                text: text.to_owned(),
+                node,
+            }),
+            (_, span) => Err(Error::new("expected an operator", span)),
+        }
+    }
+
+    /// Evaluates the expression.
+    pub fn eval(&self, env: &dyn Env) -> result::Result<i64, String> {
+        eval(&self.node, env)
+    }
+}
+
+fn eval(node: &Node, env: &dyn Env) -> result::Result<i64, String> {
+    let value = match node {
+        Node::Number(n) => *n,
+        Node::Label(name) => {
+            env.label(name)
+                .ok_or_else(|| format!("unknown label `{}`", name))? as i64
+        }
+        Node::Ptr => env.ptr() as i64,
+        Node::Rb => env.rb(),
+        Node::Mem(addr) => {
+            let addr = eval(addr, env)?;
+            let addr = usize::try_from(addr).map_err(|_| format!("negative address {}", addr))?;
+            env.mem(addr)
+        }
+        Node::Neg(node) => eval(node, env)?.wrapping_neg(),
+        Node::Not(node) => (eval(node, env)? == 0) as i64,
+        Node::Binary(Op::And, lhs, rhs) => (eval(lhs, env)? != 0 && eval(rhs, env)? != 0) as i64,
+        Node::Binary(Op::Or, lhs, rhs) => (eval(lhs, env)? != 0 || eval(rhs, env)? != 0) as i64,
+        Node::Binary(op, lhs, rhs) => {
+            let (x, y) = (eval(lhs, env)?, eval(rhs, env)?);
+            match op {
+                Op::Add => x.wrapping_add(y),
+                Op::Sub => x.wrapping_sub(y),
+                Op::Mul => x.wrapping_mul(y),
+                Op::Div | Op::Rem if y == 0 => return Err("division by zero".to_owned()),
+                Op::Div => x.wrapping_div(y),
+                Op::Rem => x.wrapping_rem(y),
+                Op::Eq => (x == y) as i64,
+                Op::Ne => (x != y) as i64,
+                Op::Lt => (x < y) as i64,
+                Op::Le => (x <= y) as i64,
+                Op::Gt => (x > y) as i64,
+                Op::Ge => (x >= y) as i64,
+                Op::And | Op::Or => unreachable!(),
+            }
+        }
+    };
+    Ok(value)
+}
+
+impl FromStr for Expr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
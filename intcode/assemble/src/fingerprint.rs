@@ -0,0 +1,55 @@
+//! A hash of everything that goes into an assembled program.
+//!
+//! Two builds with the same fingerprint had the same assembler version,
+//! options, and source files, so they have the same output. This is meant
+//! for checking where a program came from, anyone with the source can build
+//! it again and compare the fingerprint and the output.
+
+use sha2::{Digest, Sha256};
+
+use intcode_error::files::Files;
+
+use crate::Options;
+
+/// Adds a length prefixed value to the hash, so that the boundaries between
+/// values are part of the hash too.
+fn update(hasher: &mut Sha256, value: &[u8]) {
+    hasher.update((value.len() as u64).to_le_bytes());
+    hasher.update(value);
+}
+
+/// Returns the fingerprint as lowercase hexadecimal.
+///
+/// Only the options that change the output are included, so for example the
+/// include directory does not matter. The values of `BINCLUDE` files are
+/// passed separately since they are not part of `files`.
+pub fn fingerprint<'a>(
+    files: &Files,
+    binaries: impl Iterator<Item = &'a [i64]>,
+    opts: &Options,
+) -> String {
+    let mut hasher = Sha256::new();
+    update(&mut hasher, env!("CARGO_PKG_VERSION").as_bytes());
+    let Options {
+        embed_metadata,
+        syntax,
+        width,
+        strict,
+        directives,
+        ..
+    } = opts;
+    let options = format!("{:?} {:?} {} {}", syntax, width, strict, embed_metadata);
+    update(&mut hasher, options.as_bytes());
+    for directive in directives {
+        let directive = format!("{} {:?}", directive.name, directive.params);
+        update(&mut hasher, directive.as_bytes());
+    }
+    for file in 0..files.len() {
+        update(&mut hasher, files.source(file).as_bytes());
+    }
+    for values in binaries {
+        let bytes: Vec<_> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        update(&mut hasher, &bytes);
+    }
+    format!("{:x}", hasher.finalize())
+}
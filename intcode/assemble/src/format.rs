@@ -0,0 +1,131 @@
+//! Format assembly code in a canonical style.
+//!
+//! Labels are placed on their own line, instructions are indented by four
+//! spaces and directives are not. Mnemonics are padded so that operands line
+//! up and operands are separated by a comma and a space. Blank lines are kept
+//! but runs of them are collapsed into one.
+//!
+//! The parser throws comments away, so after checking that the program
+//! parses each line is lexed again with its comment. A comment on its own
+//! line stays there, and comments after code are lined up with the other
+//! comments in the same block of lines.
+
+use intcode_error::span::S;
+use intcode_error::ResultSet;
+use intcode_lex::Token;
+
+use crate::parse::Parser;
+use crate::Options;
+
+/// The mnemonics that are not indented.
+const DIRECTIVES: &[&str] = &[
+    "NAME", "AUTHOR", "ENTRY", "REQUIRES", "INPUTS", "RBINIT", "EQU", "BINCLUDE", "INCLUDE",
+];
+
+/// A formatted line.
+#[derive(Debug)]
+enum Line<'i> {
+    Blank,
+    /// A comment on its own line.
+    Comment(&'static str, &'i str),
+    /// A label, instruction, or directive with an optional comment.
+    Code(String, Option<&'i str>),
+}
+
+/// Formats a single line of input, which becomes two lines if there is a
+/// label and an instruction.
+fn format_line(line: &str) -> Vec<Line<'_>> {
+    // The whole program parses so there are no invalid tokens.
+    let tokens: Vec<_> = intcode_lex::line(line)
+        .filter_map(Result::ok)
+        .filter(|S(tk, _)| *tk != Token::Whitespace)
+        .collect();
+    let mut rest = tokens.as_slice();
+    let mut comment = match rest {
+        [init @ .., S(Token::Comment, span)] => {
+            rest = init;
+            Some(span.as_str(line).trim_end())
+        }
+        _ => None,
+    };
+    let mut lines = Vec::new();
+    if let [S(Token::Ident, span), S(Token::Colon, _), tail @ ..] = rest {
+        let label = format!("{}:", span.as_str(line));
+        rest = tail;
+        lines.push(Line::Code(label, comment.filter(|_| rest.is_empty())));
+    }
+    match rest {
+        [] if lines.is_empty() => match comment {
+            Some(comment) => {
+                let indent = match line.starts_with(char::is_whitespace) {
+                    true => "    ",
+                    false => "",
+                };
+                lines.push(Line::Comment(indent, comment));
+            }
+            None => lines.push(Line::Blank),
+        },
+        [] => {}
+        [S(_, span), operands @ ..] => {
+            let mnemonic = span.as_str(line);
+            let mut code = match DIRECTIVES.contains(&mnemonic) {
+                true => String::new(),
+                false => String::from("    "),
+            };
+            if operands.is_empty() {
+                code.push_str(mnemonic);
+            } else {
+                let operands: Vec<_> = operands
+                    .split(|S(tk, _)| *tk == Token::Comma)
+                    .map(|operand| {
+                        operand
+                            .iter()
+                            .map(|S(_, span)| span.as_str(line))
+                            .collect::<String>()
+                    })
+                    .collect();
+                code.push_str(&format!("{:<3} {}", mnemonic, operands.join(", ")));
+            }
+            lines.push(Line::Code(code, comment.take()));
+        }
+    }
+    lines
+}
+
+/// Formats the program, which must parse.
+pub fn format(asm: &str, opts: &Options) -> ResultSet<String> {
+    Parser::new(asm, opts).eat_program()?;
+    let mut lines: Vec<_> = asm.lines().flat_map(format_line).collect();
+    lines.dedup_by(|a, b| matches!((a, b), (Line::Blank, Line::Blank)));
+
+    let mut out = String::new();
+    let blocks = lines.split(|line| matches!(line, Line::Blank));
+    for block in blocks.filter(|block| !block.is_empty()) {
+        let column = block
+            .iter()
+            .filter_map(|line| match line {
+                Line::Code(code, Some(_)) => Some(code.len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        for line in block {
+            match line {
+                Line::Blank => unreachable!(),
+                Line::Comment(indent, comment) => {
+                    out.push_str(&format!("{}{}\n", indent, comment));
+                }
+                Line::Code(code, Some(comment)) => {
+                    out.push_str(&format!("{:<w$} {}\n", code, comment, w = column));
+                }
+                Line::Code(code, None) => {
+                    out.push_str(&format!("{}\n", code));
+                }
+            }
+        }
+    }
+    Ok(out)
+}
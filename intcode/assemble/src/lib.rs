@@ -1,30 +1,270 @@
-mod ast;
+pub mod ast;
+pub mod bundle;
+pub mod debug;
+pub mod directive;
+pub mod expr;
+#[cfg(feature = "fingerprint")]
+mod fingerprint;
+pub mod format;
+mod lint;
+pub mod meta;
 mod parse;
+pub mod source_map;
+mod splice;
+pub mod symbols;
+pub mod wrap;
+
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
 
 use indexmap::IndexMap;
 
+use intcode_error::files::Files;
 use intcode_error::span::{Span, S};
 use intcode_error::{Error, ErrorSet, ResultSet, Warning};
 
-use crate::ast::{Instr, Label, Param, Program, RawParam, Stmt};
+use crate::ast::{Instr, Label, Meta, Mode, Param, Program, RawParam, Stmt};
+use crate::debug::DebugInfo;
+use crate::directive::Directive;
+use crate::meta::Metadata;
 use crate::parse::Parser;
+use crate::source_map::{Mapping, SourceMap};
+
+pub use crate::lint::LINTS;
+pub use crate::parse::MNEMONICS;
 
 #[derive(Debug, Clone)]
 pub struct Intcode {
     pub output: Vec<i64>,
     pub warnings: Vec<Warning>,
+    /// Label names, source lines, and metadata for the program.
+    pub debug: DebugInfo,
+    /// The source of each range of output addresses, if it was asked for.
+    pub source_map: Option<SourceMap>,
+    /// The files read by `INCLUDE` and `BINCLUDE` directives, so that a
+    /// cached output can be invalidated when any of them change.
+    pub includes: Vec<PathBuf>,
+}
+
+/// A version of the assembly grammar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// The original grammar.
+    ///
+    /// Only the known mnemonics are treated as mnemonics, so any other
+    /// identifier, like `LOOP`, can be used as a label. The `_` and `ip`
+    /// labels are not reserved.
+    V1,
+    /// The current grammar.
+    ///
+    /// Any identifier made up of only uppercase letters and digits is a
+    /// mnemonic, and the `_` and `ip` labels refer to a runtime value and the
+    /// instruction pointer.
+    V2,
+}
+
+/// The width of the integers in the memory of the computer the program is
+/// for.
+///
+/// Number literals that do not fit are an error. The output is always `i64`,
+/// so computers with wider integers can run programs assembled for 64 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    Bits32,
+    Bits64,
+}
+
+/// What to do with a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningLevel {
+    /// Leave the warning out.
+    Allow,
+    /// Return the warning with the output.
+    Warn,
+    /// Turn the warning into an error.
+    Deny,
+}
+
+/// Options for assembling a program.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    embed_metadata: bool,
+    syntax: Syntax,
+    width: Width,
+    strict: bool,
+    include_dir: PathBuf,
+    max_include_depth: Option<usize>,
+    source_map: bool,
+    trivia: bool,
+    #[cfg(feature = "fingerprint")]
+    fingerprint: bool,
+    directives: Vec<Directive>,
+    warning_level: WarningLevel,
+    lints: Vec<(&'static str, WarningLevel)>,
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Self::V2
+    }
+}
+
+impl Default for WarningLevel {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+
+impl Default for Width {
+    fn default() -> Self {
+        Self::Bits64
+    }
+}
+
+impl Width {
+    /// Returns the number of bits.
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::Bits32 => 32,
+            Self::Bits64 => 64,
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to embed the metadata at the start of the program as a data
+    /// header.
+    pub fn embed_metadata(mut self, embed_metadata: bool) -> Self {
+        self.embed_metadata = embed_metadata;
+        self
+    }
+
+    /// The version of the assembly grammar to parse.
+    pub fn syntax(mut self, syntax: Syntax) -> Self {
+        self.syntax = syntax;
+        self
+    }
+
+    /// The width of the integers in the target computer's memory.
+    pub fn width(mut self, width: Width) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Whether to only allow what every Advent of Code interpreter supports.
+    ///
+    /// The `DBZ`, `DBL`, `MUT`, and `OUTS` pseudo-instructions and all the
+    /// directives, including `EQU`, the includes, and custom directives, are
+    /// errors, so metadata
+    /// is never embedded. Plain `DB` is still allowed because it only places
+    /// values in the program.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// The directory that `BINCLUDE` and `INCLUDE` paths in the main file are
+    /// relative to. Paths in an included file are relative to that file.
+    ///
+    /// Defaults to the current directory.
+    pub fn include_dir(mut self, include_dir: impl Into<PathBuf>) -> Self {
+        self.include_dir = include_dir.into();
+        self
+    }
+
+    /// How many files deep `INCLUDE` directives can be nested, the main file
+    /// is not counted.
+    ///
+    /// Defaults to 32.
+    pub fn max_include_depth(mut self, max_include_depth: usize) -> Self {
+        self.max_include_depth = Some(max_include_depth);
+        self
+    }
+
+    /// Whether to build a [`SourceMap`] of the output.
+    pub fn source_map(mut self, source_map: bool) -> Self {
+        self.source_map = source_map;
+        self
+    }
+
+    /// Whether to add a fingerprint of the assembler version, options, and
+    /// source files to the debug information.
+    #[cfg(feature = "fingerprint")]
+    pub fn fingerprint(mut self, fingerprint: bool) -> Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    /// Adds a custom directive, see [`directive`] for details.
+    pub fn directive(mut self, directive: Directive) -> Self {
+        self.directives.push(directive);
+        self
+    }
+
+    /// Whether to keep the comments and blank lines in the syntax tree
+    /// returned by [`to_ast`], so that a tool can write the file back out
+    /// without losing them. Custom directives are not kept, see [`directive`].
+    pub fn trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+
+    /// The level of every warning whose lint is not given its own level with
+    /// [`lint()`][Self::lint].
+    ///
+    /// Defaults to [`WarningLevel::Warn`]. The `immediate-write` lint is not
+    /// affected, it is always denied unless it is given its own level.
+    pub fn warning_level(mut self, level: WarningLevel) -> Self {
+        self.warning_level = level;
+        self
+    }
+
+    /// The level of the warnings raised by the lint, see [`LINTS`] for their
+    /// names.
+    ///
+    /// # Panics
+    ///
+    /// If there is no lint with the name.
+    pub fn lint(mut self, name: &str, level: WarningLevel) -> Self {
+        let lint = LINTS
+            .iter()
+            .copied()
+            .find(|lint| *lint == name)
+            .unwrap_or_else(|| panic!("unknown lint `{}`", name));
+        self.lints.retain(|(l, _)| *l != lint);
+        self.lints.push((lint, level));
+        self
+    }
+
+    /// Returns the level of a warning raised by the lint.
+    fn level_of(&self, lint: Option<&str>) -> WarningLevel {
+        match self.lints.iter().find(|(name, _)| Some(*name) == lint) {
+            Some((_, level)) => *level,
+            None if lint == Some("immediate-write") => WarningLevel::Deny,
+            None => self.warning_level,
+        }
+    }
 }
 
 #[derive(Debug, Default)]
 struct State {
     defs: Vec<(usize, Span)>,
     refs: Vec<(usize, Span)>,
+    /// The length of the data if the first definition of this label is on a
+    /// `DB` or `MUT` statement.
+    data: Option<usize>,
 }
 
 fn insert_label<'a>(
     labels: &mut IndexMap<&'a str, State>,
     label: Option<S<Label<'a>>>,
     address: usize,
+    data: Option<usize>,
 ) -> Result<(), Error> {
     match label {
         Some(S(Label::Underscore, span)) => {
@@ -46,36 +286,241 @@ fn insert_label<'a>(
             ));
         }
         Some(S(Label::Fixed(label), span)) => {
-            labels.entry(label).or_default().defs.push((address, span));
+            let state = labels.entry(label).or_default();
+            if state.defs.is_empty() {
+                state.data = data;
+            }
+            state.defs.push((address, span));
         }
         None => {}
     }
     Ok(())
 }
 
-fn assemble(ast: Program<'_>) -> ResultSet<Intcode> {
+/// Adds the offset to the value, which is an error if the result doesn't fit
+/// in the target's integers.
+fn add_offset(value: i64, offset: i64, width: Width, span: Span) -> Result<i64, Error> {
+    let sum = i128::from(value) + i128::from(offset);
+    let bits = width.bits();
+    match (-(1i128 << (bits - 1))..1i128 << (bits - 1)).contains(&sum) {
+        true => Ok(sum as i64),
+        false => Err(Error::new(
+            format!(
+                "computed value {} is out of range for {}-bit integer",
+                sum, bits
+            ),
+            span,
+        )),
+    }
+}
+
+/// Returns the index of the start of each line in the input.
+fn line_starts(asm: &str) -> Vec<usize> {
+    iter::once(0)
+        .chain(asm.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Returns the line number of the given index into the input.
+fn line_of(starts: &[usize], index: usize) -> usize {
+    starts.partition_point(|&start| start <= index)
+}
+
+/// Reads the values of the intcode file included by a `BINCLUDE` directive.
+fn include(path: &Path, span: Span) -> Result<Vec<i64>, Error> {
+    let input = fs::read_to_string(path).map_err(|err| {
+        let msg = format!("failed to read `{}`: {}", path.display(), err);
+        Error::new(msg, span)
+    })?;
+    input
+        .trim()
+        .split(',')
+        .map(|value| value.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| {
+            let msg = format!("`{}` is not a valid intcode program", path.display());
+            Error::new(msg, span)
+        })
+}
+
+/// Collects the metadata directives, returning the metadata and the entry
+/// label which still needs to be resolved.
+fn metadata<'a>(stmts: &[Stmt<'a>], errors: &mut Vec<Error>) -> (Metadata, Option<S<&'a str>>) {
+    let mut meta = Metadata::default();
+    let mut entry = None;
+    let mut seen = Vec::<(&str, Span)>::new();
+    for Stmt { label, instr, .. } in stmts {
+        let directive = match &instr.0 {
+            Instr::Meta(directive) => directive,
+            _ => continue,
+        };
+        if let Some(S(_, span)) = label {
+            errors.push(Error::new("label not allowed on a directive", *span));
+        }
+        let kind = match directive {
+            Meta::Name(name) => {
+                meta.name = Some(name.to_string());
+                "NAME"
+            }
+            Meta::Author(author) => {
+                meta.author = Some(author.to_string());
+                "AUTHOR"
+            }
+            Meta::Entry(S(Label::Fixed(label), span)) => {
+                entry = Some(S(*label, *span));
+                "ENTRY"
+            }
+            Meta::Entry(_) => unreachable!(),
+            Meta::Requires(ext) => {
+                meta.requires.push(ext.to_string());
+                continue;
+            }
+            Meta::RelativeBase(value) => {
+                meta.relative_base = Some(*value);
+                "RBINIT"
+            }
+            Meta::Constant(..) | Meta::Include(_) => continue,
+            Meta::Inputs(names) => {
+                for S(name, span) in names {
+                    if meta.inputs.iter().any(|i| i == name) {
+                        errors.push(Error::new("input declared more than once", *span));
+                    } else {
+                        meta.inputs.push(name.to_string());
+                    }
+                }
+                continue;
+            }
+        };
+        match seen.iter().find(|(k, _)| *k == kind) {
+            Some((_, first)) => {
+                let error = Error::new("directive redefined here", instr.1);
+                errors.push(error.note("first definition of directive", *first));
+            }
+            None => seen.push((kind, instr.1)),
+        }
+    }
+    (meta, entry)
+}
+
+/// Returns the number of values placed by each `DB` or `MUT` statement with a
+/// label.
+///
+/// Like the addresses, a label defined more than once keeps the length from
+/// its first definition.
+fn data_lens<'a>(stmts: &[Stmt<'a>]) -> IndexMap<&'a str, usize> {
+    let mut lens = IndexMap::new();
+    for stmt in stmts {
+        if let (Some(S(Label::Fixed(label), _)), Instr::Data(data) | Instr::Mutable(data)) =
+            (stmt.label, &stmt.instr.0)
+        {
+            lens.entry(label)
+                .or_insert_with(|| data.iter().map(|p| p.len()).sum());
+        }
+    }
+    lens
+}
+
+/// Collects the values of the `EQU` constants.
+fn constants<'a>(stmts: &[Stmt<'a>], errors: &mut Vec<Error>) -> IndexMap<&'a str, S<i64>> {
+    let mut constants = IndexMap::<&str, S<i64>>::new();
+    for stmt in stmts {
+        if let Instr::Meta(Meta::Constant(S(name, span), value)) = stmt.instr.0 {
+            match constants.get(name) {
+                Some(S(_, first)) => {
+                    let error = Error::new("constant redefined here", span);
+                    errors.push(error.note("first definition of constant", *first));
+                }
+                None => {
+                    constants.insert(name, S(value, span));
+                }
+            }
+        }
+    }
+    constants
+}
+
+fn assemble<'a>(files: &'a Files, ast: Program<'a>, opts: &Options) -> ResultSet<Intcode> {
     let mut output = Vec::new();
     let mut errors = Vec::new();
     let mut warnings = Vec::new();
     let mut labels = IndexMap::<&str, State>::new();
+    let mut debug = DebugInfo::default();
+    let mut source_map = opts.source_map.then(SourceMap::default);
+    let mut includes: Vec<_> = (1..files.len()).map(|i| files.name(i).to_owned()).collect();
+    let mut placed = Vec::new();
+    let mut referenced = Vec::new();
+    let mut out_of_range = Vec::new();
 
-    for Stmt { label, instr } in ast.stmts {
-        insert_label(&mut labels, label, output.len())
+    let (mut meta, entry) = metadata(&ast.stmts, &mut errors);
+    let constants = constants(&ast.stmts, &mut errors);
+    let data_lens = data_lens(&ast.stmts);
+    lint::fall_through(&ast.stmts, &mut warnings);
+    lint::immediate_writes(&ast.stmts, &mut warnings);
+    let embed = opts.embed_metadata && !opts.strict;
+    if embed {
+        output.resize(meta.header_len(), 0);
+    }
+
+    let starts = line_starts(files.text());
+    for Stmt { label, instr, .. } in ast.stmts {
+        if let Instr::Meta(_) = instr.0 {
+            continue;
+        }
+        // Statements from included files are on the line that included them.
+        let line = line_of(&starts, files.origin(instr.1).m);
+        debug.lines.push((output.len(), line));
+        let (start, span) = (output.len(), instr.1);
+        let (kind, labelled) = (lint::Kind::of(&instr.0), label.is_some());
+        let included = match &instr.0 {
+            Instr::Include(S(path, span)) => {
+                let dir = splice::dir(files, files.file_of(span.m), opts);
+                let path = dir.join(&**path);
+                match include(&path, *span) {
+                    Ok(values) => {
+                        includes.push(path);
+                        Some(values)
+                    }
+                    Err(err) => {
+                        errors.push(err);
+                        Some(Vec::new())
+                    }
+                }
+            }
+            _ => None,
+        };
+        let data = match &instr.0 {
+            Instr::Data(data) | Instr::Mutable(data) => Some(data.iter().map(|p| p.len()).sum()),
+            _ => included.as_ref().map(Vec::len),
+        };
+        insert_label(&mut labels, label, output.len(), data)
             .map_err(|err| errors.push(err))
             .ok();
 
-        let mut param = |output: &mut Vec<_>, S(p, _), ip| -> i64 {
+        let mut param = |output: &mut Vec<_>, S(p, span), ip| -> i64 {
+            let mut add = |value, offset| {
+                add_offset(value, offset, opts.width, span).unwrap_or_else(|err| {
+                    out_of_range.push(err);
+                    0
+                })
+            };
             let (mode, value) = match p {
                 Param::Number(m, value) => (m.into(), value),
                 Param::Label(m, S(Label::Underscore, _), offset) => (m.into(), offset),
-                Param::Label(m, S(Label::InstructionPointer, _), offset) => (m.into(), ip + offset),
+                Param::Label(m, S(Label::InstructionPointer, _), offset) => {
+                    (m.into(), add(ip, offset))
+                }
                 Param::Label(m, S(Label::Fixed(label), span), offset) => {
-                    labels
-                        .entry(label)
-                        .or_default()
-                        .refs
-                        .push((output.len(), span));
-                    (m.into(), offset)
+                    match constants.get(label) {
+                        Some(S(value, _)) => (m.into(), add(*value, offset)),
+                        None => {
+                            labels
+                                .entry(label)
+                                .or_default()
+                                .refs
+                                .push((output.len(), span));
+                            (m.into(), offset)
+                        }
+                    }
                 }
             };
             output.push(value);
@@ -110,24 +555,35 @@ fn assemble(ast: Program<'_>) -> ResultSet<Intcode> {
                 let mode = param(&mut output, p, ip);
                 output[i] += mode * 100;
             }
-            Instr::Data(data) => {
+            Instr::Data(data) | Instr::Mutable(data) => {
                 let data_len: usize = data.iter().map(|p| p.len()).sum();
                 let ip = (output.len() + data_len) as i64;
-                for S(p, _) in data {
+                for S(p, param_span) in data {
+                    let mut add = |value, offset| {
+                        add_offset(value, offset, opts.width, param_span).unwrap_or_else(|err| {
+                            out_of_range.push(err);
+                            0
+                        })
+                    };
                     match p {
                         RawParam::Label(S(Label::Underscore, _), offset) => {
                             output.push(offset);
                         }
                         RawParam::Label(S(Label::InstructionPointer, _), offset) => {
-                            output.push(ip + offset);
+                            output.push(add(ip, offset));
                         }
                         RawParam::Label(S(Label::Fixed(label), span), offset) => {
-                            labels
-                                .entry(label)
-                                .or_default()
-                                .refs
-                                .push((output.len(), span));
-                            output.push(offset);
+                            match constants.get(label) {
+                                Some(S(value, _)) => output.push(add(*value, offset)),
+                                None => {
+                                    labels
+                                        .entry(label)
+                                        .or_default()
+                                        .refs
+                                        .push((output.len(), span));
+                                    output.push(offset);
+                                }
+                            }
                         }
                         RawParam::Number(value) => {
                             output.push(value);
@@ -140,11 +596,86 @@ fn assemble(ast: Program<'_>) -> ResultSet<Intcode> {
                     }
                 }
             }
+            Instr::OutputString(S(p, span)) => {
+                let params: Vec<_> = match p {
+                    RawParam::String(string) => string
+                        .bytes()
+                        .map(|b| Param::Number(Mode::Immediate, b.into()))
+                        .collect(),
+                    RawParam::Label(label @ S(Label::Fixed(name), s), offset) => {
+                        match data_lens.get(name).map(|&len| len as i64) {
+                            Some(len) if (0..len).contains(&offset) => (offset..len)
+                                .map(|i| Param::Label(Mode::Positional, label, i))
+                                .collect(),
+                            Some(_) => {
+                                errors
+                                    .push(Error::new("label offset is outside of the data", span));
+                                Vec::new()
+                            }
+                            None => {
+                                errors.push(Error::new("expected a label on a `DB` statement", s));
+                                Vec::new()
+                            }
+                        }
+                    }
+                    _ => unreachable!(),
+                };
+                for p in params {
+                    let i = output.len();
+                    let ip = (i + 2) as i64;
+                    output.push(Instr::Output(S(p, span)).opcode());
+                    let mode = param(&mut output, S(p, span), ip);
+                    output[i] += mode * 100;
+                }
+            }
+            Instr::Include(_) => output.extend(included.unwrap()),
             Instr::Halt => output.push(instr.opcode()),
+            Instr::Meta(_) => unreachable!(),
+        }
+        if let Some(map) = &mut source_map {
+            map.mappings.push(Mapping {
+                addrs: start..output.len(),
+                span,
+            });
         }
+        placed.push(lint::Placed {
+            addrs: start..output.len(),
+            span,
+            labelled,
+            kind,
+        });
     }
 
-    for (label, State { defs, refs }) in labels {
+    errors.append(&mut out_of_range);
+
+    if let Some(S(label, span)) = entry {
+        match labels.get(label).map(|state| state.defs.as_slice()) {
+            Some([(address, _)]) => {
+                meta.entry = Some(*address);
+                referenced.push(*address as i64);
+            }
+            Some([_, _, ..]) => {}
+            _ => errors.push(Error::new("undefined label", span)),
+        }
+    }
+
+    // The addresses of the labelled data, to tell which data a label offset
+    // points into.
+    let regions: Vec<_> = labels
+        .iter()
+        .filter_map(|(label, state)| match (state.defs.as_slice(), state.data) {
+            ([(address, span)], Some(len)) => Some((*label, *address..*address + len, *span)),
+            _ => None,
+        })
+        .collect();
+
+    for (label, State { defs, refs, data }) in labels {
+        if constants.contains_key(label) {
+            for (_, span) in defs {
+                errors.push(Error::new("label has the same name as a constant", span));
+            }
+            continue;
+        }
         match *defs.as_slice() {
             [] => {
                 for (_, span) in refs {
@@ -152,33 +683,129 @@ fn assemble(ast: Program<'_>) -> ResultSet<Intcode> {
                 }
             }
             [(address, span)] => {
-                if refs.is_empty() && !label.starts_with('_') {
-                    warnings.push(Warning::new("label is never used", span))
+                debug.labels.push((label.to_owned(), address));
+                let is_entry = matches!(entry, Some(S(e, _)) if e == label);
+                if refs.is_empty() && !label.starts_with('_') && !is_entry {
+                    warnings.push(Warning::new("label is never used", span).lint("unused-label"))
                 } else {
-                    for (r, _) in refs {
-                        output[r] += address as i64;
+                    for (r, ref_span) in refs {
+                        // The output currently holds the offset from the label.
+                        let offset = output[r];
+                        // One past the end is allowed because it marks the
+                        // end of the data.
+                        if matches!(data, Some(len) if offset < 0 || offset > len as i64) {
+                            let target = address as i64 + offset;
+                            let other = regions.iter().find(|(_, range, _)| {
+                                usize::try_from(target).map_or(false, |t| range.contains(&t))
+                            });
+                            let warning = match other {
+                                Some((other, _, other_span)) => {
+                                    let msg =
+                                        format!("label offset points into the data of `{}`", other);
+                                    Warning::new(msg, ref_span)
+                                        .note(format!("`{}` is defined here", label), span)
+                                        .note(format!("`{}` is defined here", other), *other_span)
+                                }
+                                None => {
+                                    Warning::new("label offset is outside of the data", ref_span)
+                                        .note(format!("`{}` is defined here", label), span)
+                                }
+                            };
+                            warnings.push(warning.lint("label-offset"));
+                        }
+                        match add_offset(address as i64, offset, opts.width, ref_span) {
+                            Ok(value) => {
+                                output[r] = value;
+                                referenced.push(value);
+                            }
+                            Err(err) => errors.push(err),
+                        }
                     }
                 }
             }
-            _ => {
-                for (i, (_, span)) in defs.into_iter().enumerate() {
-                    let msg = if i == 0 {
-                        "first definition of label"
-                    } else {
-                        "label redefined here"
-                    };
-                    errors.push(Error::new(msg, span))
+            [(_, first), ..] => {
+                for (_, span) in &defs[1..] {
+                    let error = Error::new("label redefined here", *span);
+                    errors.push(error.note("first definition of label", first));
                 }
             }
         }
     }
+    if errors.is_empty() {
+        lint::unused(&placed, &referenced, &mut warnings);
+    }
+    let mut kept = Vec::new();
+    for warning in warnings {
+        match opts.level_of(warning.lint) {
+            WarningLevel::Allow => {}
+            WarningLevel::Warn => kept.push(warning),
+            WarningLevel::Deny => errors.push(warning.into_error()),
+        }
+    }
+    let warnings = kept;
+    if embed {
+        let header = meta.header();
+        output.splice(..header.len(), header);
+    }
+    debug.meta = meta;
+    #[cfg(feature = "fingerprint")]
+    if opts.fingerprint {
+        let binaries = placed
+            .iter()
+            .filter(|p| p.kind == lint::Kind::Other)
+            .map(|p| &output[p.addrs.clone()]);
+        debug.fingerprint = Some(fingerprint::fingerprint(files, binaries, opts));
+    }
     match errors.is_empty() {
-        true => Ok(Intcode { output, warnings }),
-        false => Err(ErrorSet { errors, warnings }),
+        true => Ok(Intcode {
+            output,
+            warnings,
+            debug,
+            source_map,
+            includes,
+        }),
+        false => Err(ErrorSet {
+            errors,
+            warnings,
+            omitted: 0,
+        }),
     }
 }
 
+/// Parse the program into a syntax tree without assembling it.
+///
+/// `INCLUDE` directives are left as they are instead of being replaced by
+/// the statements of the included file.
+pub fn to_ast<'i>(asm: &'i str, opts: &Options) -> ResultSet<Program<'i>> {
+    Parser::new(asm, opts).eat_program()
+}
+
 /// Assemble the program as intcode.
 pub fn to_intcode(asm: &str) -> ResultSet<Intcode> {
-    Parser::new(asm).eat_program().and_then(assemble)
+    to_intcode_with(asm, &Options::new())
+}
+
+/// Assemble the program as intcode using the given options.
+pub fn to_intcode_with(asm: &str, opts: &Options) -> ResultSet<Intcode> {
+    let files = Files::new("<input>", asm);
+    let ast = Parser::new(files.text(), opts).eat_program()?;
+    let errors = splice::unsupported(&ast);
+    if !errors.is_empty() {
+        return Err(ErrorSet {
+            errors,
+            ..Default::default()
+        });
+    }
+    assemble(&files, ast, opts)
+}
+
+/// Assemble a program made up of several files as intcode.
+///
+/// The files included with `INCLUDE` directives are added to `files`, which
+/// should be used to format any errors since their spans can point into any
+/// of the files.
+pub fn to_intcode_files(files: &mut Files, opts: &Options) -> ResultSet<Intcode> {
+    splice::load(files, opts)?;
+    let ast = splice::splice(files, 0, opts)?;
+    assemble(files, ast, opts)
 }
@@ -0,0 +1,163 @@
+//! Warnings about parts of the program that can never be used.
+
+use std::ops::Range;
+
+use intcode_error::span::{Span, S};
+use intcode_error::Warning;
+
+use crate::ast::{Instr, Mode, Param, Stmt};
+
+/// The name of each lint that can raise a warning.
+///
+/// - `unused-label`: a label that nothing refers to.
+/// - `label-offset`: a label offset that points outside of the labelled data.
+/// - `fall-through`: data that execution falls into from the code before it.
+/// - `immediate-write`: a parameter that is written to in immediate mode.
+///   This is denied by default because the program can never work.
+/// - `unreachable-code`: instructions that execution can never reach.
+/// - `unused-data`: data that nothing refers to.
+pub const LINTS: &[&str] = &[
+    "unused-label",
+    "label-offset",
+    "fall-through",
+    "immediate-write",
+    "unreachable-code",
+    "unused-data",
+];
+
+/// What a statement places in the output, as far as the lints care.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// An instruction that execution can continue past.
+    Code,
+    /// A `HLT` or a jump that is always taken.
+    Terminal,
+    /// Data placed with `DB` or one of its variants.
+    Data,
+    /// Anything else, like the values of a `BINCLUDE` file.
+    Other,
+}
+
+/// A statement after it has been placed in the output.
+#[derive(Debug)]
+pub struct Placed {
+    pub addrs: Range<usize>,
+    pub span: Span,
+    pub labelled: bool,
+    pub kind: Kind,
+}
+
+impl Kind {
+    pub fn of(instr: &Instr<'_>) -> Self {
+        match instr {
+            Instr::Halt => Self::Terminal,
+            Instr::JumpZero(S(Param::Number(Mode::Immediate, 0), _), _) => Self::Terminal,
+            Instr::JumpNonZero(S(Param::Number(Mode::Immediate, v), _), _) if *v != 0 => {
+                Self::Terminal
+            }
+            Instr::Data(_) => Self::Data,
+            Instr::Include(_) | Instr::Meta(_) => Self::Other,
+            _ => Self::Code,
+        }
+    }
+}
+
+/// Warns about data placed straight after code, which execution would fall
+/// into because there is no `HLT` or jump in between.
+pub fn fall_through(stmts: &[Stmt<'_>], warnings: &mut Vec<Warning>) {
+    let mut after_code = false;
+    for Stmt { instr, .. } in stmts {
+        match instr.0 {
+            Instr::Add(..)
+            | Instr::Multiply(..)
+            | Instr::LessThan(..)
+            | Instr::Equal(..)
+            | Instr::Input(_)
+            | Instr::Output(_)
+            | Instr::AdjustRelativeBase(_)
+            | Instr::Mutable(_)
+            | Instr::OutputString(_) => after_code = true,
+            Instr::JumpNonZero(..) | Instr::JumpZero(..) | Instr::Halt | Instr::Include(_) => {
+                after_code = false
+            }
+            Instr::Data(_) => {
+                if after_code {
+                    warnings.push(
+                        Warning::new(
+                            "execution falls into this data, add a `HLT` before it",
+                            instr.1,
+                        )
+                        .lint("fall-through"),
+                    );
+                }
+                after_code = false;
+            }
+            Instr::Meta(_) => {}
+        }
+    }
+}
+
+/// Warns about parameters that are written to but are in immediate mode,
+/// which would write over the parameter itself.
+pub fn immediate_writes(stmts: &[Stmt<'_>], warnings: &mut Vec<Warning>) {
+    for Stmt { instr, .. } in stmts {
+        let param = match &instr.0 {
+            Instr::Add(_, _, p)
+            | Instr::Multiply(_, _, p)
+            | Instr::LessThan(_, _, p)
+            | Instr::Equal(_, _, p)
+            | Instr::Input(p) => p,
+            _ => continue,
+        };
+        if let S(Param::Number(Mode::Immediate, _) | Param::Label(Mode::Immediate, ..), span) =
+            param
+        {
+            warnings.push(
+                Warning::new("parameter is written to but is in immediate mode", *span)
+                    .lint("immediate-write"),
+            );
+        }
+    }
+}
+
+/// Warns about instructions that nothing can reach and data that nothing
+/// refers to, given the addresses that parameters refer to.
+///
+/// An instruction can't be reached if it comes after a `HLT` or a jump that
+/// is always taken, with no label or reference in between. Data with a label
+/// is left to the "label is never used" warning.
+pub fn unused(placed: &[Placed], referenced: &[i64], warnings: &mut Vec<Warning>) {
+    let is_referenced = |addrs: &Range<usize>| {
+        referenced
+            .iter()
+            .any(|&a| a >= addrs.start as i64 && a < addrs.end as i64)
+    };
+    let mut dead = false;
+    let mut warned = false;
+    for Placed {
+        addrs,
+        span,
+        labelled,
+        kind,
+    } in placed
+    {
+        let used = *labelled || is_referenced(addrs);
+        if used {
+            dead = false;
+            warned = false;
+        }
+        match kind {
+            Kind::Code | Kind::Terminal => {
+                if dead && !warned {
+                    warnings.push(Warning::new("unreachable code", *span).lint("unreachable-code"));
+                    warned = true;
+                }
+                dead |= *kind == Kind::Terminal;
+            }
+            Kind::Data if !used => {
+                warnings.push(Warning::new("data is never used", *span).lint("unused-data"));
+            }
+            Kind::Data | Kind::Other => {}
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Metadata about a program, set using the `NAME`, `AUTHOR`, `ENTRY`,
+//! `REQUIRES`, `INPUTS`, and `RBINIT` directives.
+//!
+//! The metadata can optionally be embedded at the start of the program as a
+//! data header. The header jumps over itself to the entry point so that the
+//! program still runs on any interpreter. The layout is:
+//!
+//! ```text
+//! 1106, 0, <entry>, <magic>, <name>, 0, <author>, 0, <requires>, 0
+//! ```
+//!
+//! Where each string is encoded as ASCII and the required extensions are
+//! separated by commas. The input names are not embedded.
+//!
+//! If there is an initial relative base then the header jumps to a trampoline
+//! at the end of it instead, which sets the relative base and then jumps to
+//! the entry point:
+//!
+//! ```text
+//! ..., <requires>, 0, 109, <relative base>, 1105, 1, <entry>
+//! ```
+
+/// The marker following the header jump, "META" in ASCII.
+const MAGIC: i64 = 0x4d45_5441;
+
+/// Metadata about a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// The name of the program.
+    pub name: Option<String>,
+    /// The author of the program.
+    pub author: Option<String>,
+    /// The address execution should start at.
+    pub entry: Option<usize>,
+    /// The extensions the runner must enable to run the program.
+    pub requires: Vec<String>,
+    /// The names of the values the program inputs, in order.
+    pub inputs: Vec<String>,
+    /// The relative base the program expects to start with.
+    pub relative_base: Option<i64>,
+}
+
+fn encode(output: &mut Vec<i64>, s: &str) {
+    output.extend(s.bytes().map(i64::from));
+    output.push(0);
+}
+
+fn decode(iter: &mut impl Iterator<Item = i64>) -> Option<String> {
+    let mut s = String::new();
+    loop {
+        match iter.next()? {
+            0 => break Some(s),
+            value => s.push(char::from(u8::try_from(value).ok()?)),
+        }
+    }
+}
+
+impl Metadata {
+    /// Returns the number of values the header takes up.
+    pub fn header_len(&self) -> usize {
+        self.header().len()
+    }
+
+    /// Returns the header that embeds this metadata in a program.
+    ///
+    /// If there is no entry point then the header jumps to the address
+    /// straight after itself.
+    pub fn header(&self) -> Vec<i64> {
+        let mut header = vec![1106, 0, 0, MAGIC];
+        encode(&mut header, self.name.as_deref().unwrap_or(""));
+        encode(&mut header, self.author.as_deref().unwrap_or(""));
+        encode(&mut header, &self.requires.join(","));
+        match self.relative_base {
+            Some(relative_base) => {
+                header[2] = header.len() as i64;
+                header.extend([109, relative_base, 1105, 1, 0]);
+                let last = header.len() - 1;
+                header[last] = self.entry.unwrap_or(header.len()) as i64;
+            }
+            None => header[2] = self.entry.unwrap_or(header.len()) as i64,
+        }
+        header
+    }
+
+    /// Reads the metadata from the header at the start of the program, if
+    /// there is one.
+    pub fn from_header(program: &[i64]) -> Option<Self> {
+        match program {
+            [1106, 0, entry, MAGIC, ..] => {
+                let mut iter = program[4..].iter().copied();
+                let name = decode(&mut iter)?;
+                let author = decode(&mut iter)?;
+                let requires = decode(&mut iter)?;
+                let end = program.len() - iter.count();
+                let (entry, relative_base) = match program[end..] {
+                    [109, relative_base, 1105, 1, target, ..] if *entry == end as i64 => {
+                        (target, Some(relative_base))
+                    }
+                    _ => (*entry, None),
+                };
+                let non_empty = |s: String| (!s.is_empty()).then(|| s);
+                Some(Self {
+                    name: non_empty(name),
+                    author: non_empty(author),
+                    entry: usize::try_from(entry).ok(),
+                    requires: requires
+                        .split(',')
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_owned)
+                        .collect(),
+                    inputs: Vec::new(),
+                    relative_base,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns whether no metadata was set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
@@ -2,17 +2,35 @@
 
 mod unpack;
 
+use std::ops;
+
 use intcode_error::span::{Span, S};
 use intcode_error::{Error, ErrorSet, Result, ResultSet};
 use intcode_lex::parse::{self, Sign};
 use intcode_lex::{Token, Tokens};
 
-use crate::ast::{Instr, Label, Mode, Param, Program, RawParam, Stmt};
+use crate::ast::{Instr, Label, Meta, Mode, Param, Program, RawParam, Stmt, Trivia, Trivium};
+use crate::directive::{Directive, Kind, Value};
 use crate::parse::unpack::TryUnpack;
+use crate::{Options, Syntax, Width};
+
+/// The mnemonics and directives the parser knows about.
+pub const MNEMONICS: &[&str] = &[
+    "ADD", "MUL", "JNZ", "JZ", "LT", "EQ", "IN", "OUT", "ARB", "HLT", "DB", "DBZ", "DBL", "MUT",
+    "OUTS", "NAME", "AUTHOR", "ENTRY", "REQUIRES", "INPUTS", "RBINIT", "EQU", "BINCLUDE",
+    "INCLUDE",
+];
 
 pub struct Parser<'i> {
     input: &'i str,
     tokens: Tokens<'i>,
+    syntax: Syntax,
+    width: Width,
+    strict: bool,
+    trivia: bool,
+    directives: Vec<Directive>,
+    /// The trivia after the last statement.
+    end: Vec<Trivium<'i>>,
 }
 
 enum Ident {
@@ -21,8 +39,12 @@ enum Ident {
 }
 
 impl Ident {
-    fn new(s: &str) -> Self {
-        match s.chars().all(|c| matches!(c, '0'..='9'| 'A'..='Z')) {
+    fn new(s: &str, syntax: Syntax) -> Self {
+        let is_mnemonic = match syntax {
+            Syntax::V1 => MNEMONICS.contains(&s),
+            Syntax::V2 => s.chars().all(|c| matches!(c, '0'..='9'| 'A'..='Z')),
+        };
+        match is_mnemonic {
             false => Self::Label,
             true => Self::Mnemonic,
         }
@@ -65,20 +87,36 @@ mod token {
     }
 }
 
-impl<'i> From<&'i str> for Label<'i> {
-    fn from(s: &'i str) -> Self {
-        match s {
-            "_" => Self::Underscore,
-            "ip" => Self::InstructionPointer,
-            label => Self::Fixed(label),
+impl<'i> Label<'i> {
+    fn new(s: &'i str, syntax: Syntax) -> Self {
+        match (s, syntax) {
+            (label, Syntax::V1) => Self::Fixed(label),
+            ("_", Syntax::V2) => Self::Underscore,
+            ("ip", Syntax::V2) => Self::InstructionPointer,
+            (label, Syntax::V2) => Self::Fixed(label),
         }
     }
 }
 
 impl<'i> Parser<'i> {
-    pub fn new(input: &'i str) -> Self {
-        let tokens = Tokens::new(input);
-        Self { input, tokens }
+    pub fn new(input: &'i str, opts: &Options) -> Self {
+        Self::with_range(input, 0..input.len(), opts)
+    }
+
+    /// Constructs a parser for part of the input, like one of the
+    /// [`Files`][intcode_error::files::Files].
+    pub fn with_range(input: &'i str, range: ops::Range<usize>, opts: &Options) -> Self {
+        let tokens = Tokens::with_range(input, range);
+        Self {
+            input,
+            tokens,
+            syntax: opts.syntax,
+            width: opts.width,
+            strict: opts.strict,
+            trivia: opts.trivia,
+            directives: opts.directives.clone(),
+            end: Vec::new(),
+        }
     }
 
     fn peek(&self) -> Result<S<Token>> {
@@ -104,6 +142,55 @@ impl<'i> Parser<'i> {
         Ok(())
     }
 
+    /// Eats the blank lines and comments before the next token, keeping them
+    /// if trivia is enabled. A line only counts as blank if it starts after
+    /// the current position.
+    fn eat_trivia(&mut self, line_start: bool) -> Result<Vec<Trivium<'i>>> {
+        let mut trivia = Vec::new();
+        if !self.trivia {
+            self.eat_all(Token::Newline)?;
+            return Ok(trivia);
+        }
+        let mut blank = line_start;
+        loop {
+            let mut tokens = self.tokens.clone();
+            match tokens.next()? {
+                S(Token::Whitespace, _) => {}
+                S(Token::Comment, span) => {
+                    trivia.push(Trivium::Comment(S(span.as_str(self.input), span)));
+                    blank = false;
+                }
+                S(Token::Newline, _) => {
+                    if blank {
+                        trivia.push(Trivium::BlankLine);
+                    }
+                    blank = true;
+                }
+                _ => break,
+            }
+            self.tokens = tokens;
+        }
+        Ok(trivia)
+    }
+
+    /// Eats the comment at the end of the line if trivia is enabled.
+    fn eat_comment(&mut self) -> Result<Option<S<&'i str>>> {
+        if !self.trivia {
+            return Ok(None);
+        }
+        let mut tokens = self.tokens.clone();
+        loop {
+            match tokens.next()? {
+                S(Token::Whitespace, _) => {}
+                S(Token::Comment, span) => {
+                    self.tokens = tokens;
+                    return Ok(Some(S(span.as_str(self.input), span)));
+                }
+                _ => return Ok(None),
+            }
+        }
+    }
+
     fn expect(&mut self, want: Token) -> Result<S<Token>> {
         match self.peek()? {
             token if *token == want => {
@@ -125,30 +212,32 @@ impl<'i> Parser<'i> {
             }
             S(Token::Minus, span) => {
                 let S(_, s) = self.expect(Token::Number)?;
-                let value = parse::integer(self.input, s, Sign::Negative)?;
+                let value = parse::integer(self.input, s, Sign::Negative, self.width.bits())?;
                 Ok(S(RawParam::Number(value), span.include(s)))
             }
             S(Token::Number, span) => {
-                let value = parse::integer(self.input, span, Sign::Positive)?;
+                let value = parse::integer(self.input, span, Sign::Positive, self.width.bits())?;
                 Ok(S(RawParam::Number(value), span))
             }
             S(Token::Ident, span) => {
                 let value = span.as_str(self.input);
-                if Ident::new(value).is_mnemonic() {
+                if Ident::new(value, self.syntax).is_mnemonic() {
                     return Err(Error::new("expected a parameter, found a mnemonic", span));
                 }
-                let label = S(Label::from(value), span);
+                let label = S(Label::new(value, self.syntax), span);
                 match *self.peek()? {
                     Token::Minus => {
                         self.advance();
                         let S(_, s) = self.expect(Token::Number)?;
-                        let offset = parse::integer(self.input, s, Sign::Negative)?;
+                        let offset =
+                            parse::integer(self.input, s, Sign::Negative, self.width.bits())?;
                         Ok(S(RawParam::Label(label, offset), span.include(s)))
                     }
                     Token::Plus => {
                         self.advance();
                         let S(_, s) = self.expect(Token::Number)?;
-                        let offset = parse::integer(self.input, s, Sign::Positive)?;
+                        let offset =
+                            parse::integer(self.input, s, Sign::Positive, self.width.bits())?;
                         Ok(S(RawParam::Label(label, offset), span.include(s)))
                     }
                     _ => Ok(S(RawParam::Label(label, 0), span)),
@@ -237,9 +326,82 @@ impl<'i> Parser<'i> {
             .collect()
     }
 
+    fn eat_meta_param(&mut self, span: Span) -> Result<S<RawParam<'i>>> {
+        let mut params = self.eat_raw_params()?;
+        if params.len() != 1 {
+            let msg = format!("expected 1 parameter, found {}", params.len());
+            return Err(Error::new(msg, span));
+        }
+        match params.remove(0) {
+            (Some(span), _) => Err(Error::new(
+                "immediate mode not allowed with directives",
+                span,
+            )),
+            (None, raw_param) => Ok(raw_param),
+        }
+    }
+
+    fn eat_meta_string(&mut self, span: Span) -> Result<dairy::String<'i>> {
+        match self.eat_meta_param(span)? {
+            S(RawParam::String(value), _) => Ok(value),
+            S(_, span) => Err(Error::new("expected a string", span)),
+        }
+    }
+
+    fn directive(&self, name: &str) -> Option<Directive> {
+        self.directives.iter().find(|d| d.name == name).cloned()
+    }
+
+    fn eat_directive(&mut self, directive: &Directive, span: Span) -> Result<Instr<'i>> {
+        let params = self.eat_raw_params()?;
+        if params.len() != directive.params.len() {
+            let msg = format!(
+                "expected {} parameter{}, found {}",
+                directive.params.len(),
+                if directive.params.len() != 1 { "s" } else { "" },
+                params.len()
+            );
+            return Err(Error::new(msg, span));
+        }
+        let mut values = Vec::new();
+        let mut spans = Vec::new();
+        for ((prefix, S(raw, s)), kind) in params.into_iter().zip(&directive.params) {
+            let value = match (prefix, raw, kind) {
+                (None, RawParam::Number(value), Kind::Number) => Value::Number(value),
+                (None, RawParam::String(value), Kind::String) => Value::String(value.to_string()),
+                (_, _, Kind::Number) => return Err(Error::new("expected a number", s)),
+                (_, _, Kind::String) => return Err(Error::new("expected a string", s)),
+            };
+            values.push(value);
+            spans.push(s);
+        }
+        let values = (directive.expand)(&values).map_err(|err| {
+            let s = err.param.and_then(|i| spans.get(i).copied());
+            Error::new(err.msg, s.unwrap_or(span))
+        })?;
+        let data = values
+            .into_iter()
+            .map(|value| S(RawParam::Number(value), span))
+            .collect();
+        Ok(Instr::Data(data))
+    }
+
     fn eat_instr(&mut self) -> Result<S<Instr<'i>>> {
         let S(_, span) = self.expect(Token::Ident)?;
         let opcode = span.as_str(self.input);
+        if self.strict {
+            let kind = match opcode {
+                "DBZ" | "DBL" | "MUT" | "OUTS" => Some("pseudo-instruction"),
+                "NAME" | "AUTHOR" | "REQUIRES" | "ENTRY" | "INPUTS" | "RBINIT" | "EQU"
+                | "INCLUDE" | "BINCLUDE" => Some("directive"),
+                s if self.directive(s).is_some() => Some("directive"),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                let msg = format!("{} `{}` is not allowed in strict mode", kind, opcode);
+                return Err(Error::new(msg, span));
+            }
+        }
         let instr = match opcode {
             "ADD" => {
                 let (x, y, z) = self.eat_params(span)?;
@@ -285,42 +447,143 @@ impl<'i> Parser<'i> {
                 let data = self.eat_data_params()?;
                 Instr::Data(data)
             }
-            s => {
-                let msg = match Ident::new(s) {
-                    Ident::Mnemonic => "unknown operation mnemonic",
-                    Ident::Label => "expected a mnemonic, found an identifier",
-                };
-                return Err(Error::new(msg, span));
+            "DBZ" => {
+                let mut data = self.eat_data_params()?;
+                data.push(S(RawParam::Number(0), span));
+                Instr::Data(data)
+            }
+            "DBL" => {
+                let mut data = self.eat_data_params()?;
+                let len: usize = data.iter().map(|p| p.len()).sum();
+                data.insert(0, S(RawParam::Number(len as i64), span));
+                Instr::Data(data)
+            }
+            "MUT" => {
+                let data = self.eat_data_params()?;
+                if data.is_empty() {
+                    return Err(Error::new("expected at least 1 parameter, found 0", span));
+                }
+                Instr::Mutable(data)
             }
+            "OUTS" => {
+                let mut params = self.eat_raw_params()?;
+                if params.len() != 1 {
+                    let msg = format!("expected 1 parameter, found {}", params.len());
+                    return Err(Error::new(msg, span));
+                }
+                match params.remove(0) {
+                    (None, p @ S(RawParam::String(_), _))
+                    | (None, p @ S(RawParam::Label(S(Label::Fixed(_), _), _), _)) => {
+                        Instr::OutputString(p)
+                    }
+                    (_, S(_, span)) => {
+                        return Err(Error::new("expected a string or a label", span))
+                    }
+                }
+            }
+            "BINCLUDE" => match self.eat_meta_param(span)? {
+                S(RawParam::String(path), span) => Instr::Include(S(path, span)),
+                S(_, span) => return Err(Error::new("expected a string", span)),
+            },
+            "INCLUDE" => match self.eat_meta_param(span)? {
+                S(RawParam::String(path), span) => Instr::Meta(Meta::Include(S(path, span))),
+                S(_, span) => return Err(Error::new("expected a string", span)),
+            },
+            "NAME" => Instr::Meta(Meta::Name(self.eat_meta_string(span)?)),
+            "AUTHOR" => Instr::Meta(Meta::Author(self.eat_meta_string(span)?)),
+            "REQUIRES" => Instr::Meta(Meta::Requires(self.eat_meta_string(span)?)),
+            "INPUTS" => {
+                let mut names = Vec::new();
+                for param in self.eat_raw_params()? {
+                    match param {
+                        (None, S(RawParam::Label(S(Label::Fixed(name), span), 0), _)) => {
+                            names.push(S(name, span))
+                        }
+                        (_, S(_, span)) => return Err(Error::new("expected an input name", span)),
+                    }
+                }
+                if names.is_empty() {
+                    return Err(Error::new("expected at least 1 parameter, found 0", span));
+                }
+                Instr::Meta(Meta::Inputs(names))
+            }
+            "RBINIT" => match self.eat_meta_param(span)? {
+                S(RawParam::Number(value), _) => Instr::Meta(Meta::RelativeBase(value)),
+                S(_, span) => return Err(Error::new("expected a number", span)),
+            },
+            "EQU" => match self.eat_raw_params()?.as_slice() {
+                [(None, S(RawParam::Label(S(Label::Fixed(name), s), 0), _)), value] => {
+                    match value {
+                        (None, S(RawParam::Number(value), _)) => {
+                            Instr::Meta(Meta::Constant(S(name, *s), *value))
+                        }
+                        (_, S(_, span)) => return Err(Error::new("expected a number", *span)),
+                    }
+                }
+                [(_, S(_, span)), _] => {
+                    return Err(Error::new("expected a constant name", *span));
+                }
+                params => {
+                    let msg = format!("expected 2 parameters, found {}", params.len());
+                    return Err(Error::new(msg, span));
+                }
+            },
+            "ENTRY" => match self.eat_meta_param(span)? {
+                S(RawParam::Label(label @ S(Label::Fixed(_), _), 0), _) => {
+                    Instr::Meta(Meta::Entry(label))
+                }
+                S(_, span) => return Err(Error::new("expected a label", span)),
+            },
+            s => match self.directive(s) {
+                Some(directive) => self.eat_directive(&directive, span)?,
+                None => {
+                    let msg = match Ident::new(s, self.syntax) {
+                        Ident::Mnemonic => "unknown operation mnemonic",
+                        Ident::Label => "expected a mnemonic, found an identifier",
+                    };
+                    return Err(Error::new(msg, span));
+                }
+            },
         };
         let S(_, s) = self.peek()?;
         Ok(S(instr, span.include(s.m..s.m)))
     }
 
     fn eat_stmt(&mut self) -> Result<Option<Stmt<'i>>> {
-        self.eat_all(Token::Newline)?;
+        let leading = self.eat_trivia(true)?;
         if self.is_next(token::is_eof)? {
+            self.end = leading;
             return Ok(None);
         }
         let label = match self.peek()? {
             S(Token::Ident, span) => {
                 let value = span.as_str(self.input);
-                if Ident::new(value).is_label() {
+                if Ident::new(value, self.syntax).is_label() && self.directive(value).is_none() {
                     self.advance();
                     self.expect(Token::Colon)?;
-                    Some(S(Label::from(value), span))
+                    Some(S(Label::new(value, self.syntax), span))
                 } else {
                     None
                 }
             }
             _ => None,
         };
-        self.eat_all(Token::Newline)?;
+        let inner = self.eat_trivia(false)?;
         let instr = self.eat_instr()?;
+        let trailing = self.eat_comment()?;
         if !self.is_next(token::is_eof)? {
             self.expect(Token::Newline)?;
         }
-        Ok(Some(Stmt { label, instr }))
+        let trivia = Trivia {
+            leading,
+            inner,
+            trailing,
+        };
+        Ok(Some(Stmt {
+            label,
+            instr,
+            trivia,
+        }))
     }
 
     pub fn eat_program(mut self) -> ResultSet<Program<'i>> {
@@ -338,7 +601,10 @@ impl<'i> Parser<'i> {
             }
         }
         match errors.is_empty() {
-            true => Ok(Program { stmts }),
+            true => Ok(Program {
+                stmts,
+                end: self.end,
+            }),
             false => Err(ErrorSet {
                 errors,
                 ..Default::default()
@@ -0,0 +1,46 @@
+//! Map output addresses back to the source they were assembled from.
+//!
+//! Unlike the source lines in the [`DebugInfo`][crate::debug::DebugInfo],
+//! which only record the first address of each statement, a source map
+//! records the full range of addresses each statement assembled to and the
+//! span of the statement in the source. It is only built when asked for with
+//! [`Options::source_map()`][crate::Options::source_map].
+
+use std::ops::Range;
+
+use intcode_error::span::Span;
+
+/// The addresses that a single statement assembled to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    /// The output addresses, empty if the statement produced no values.
+    pub addrs: Range<usize>,
+    /// The span of the statement in the source, excluding any label.
+    pub span: Span,
+}
+
+/// A table of output addresses and the source they were assembled from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// The mappings in address order.
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    /// Returns the span of the statement that assembled to the address.
+    pub fn span(&self, addr: usize) -> Option<Span> {
+        self.mappings
+            .iter()
+            .find(|m| m.addrs.contains(&addr))
+            .map(|m| m.span)
+    }
+
+    /// Returns the addresses of the statement whose span contains the given
+    /// index into the source.
+    pub fn addrs(&self, index: usize) -> Option<Range<usize>> {
+        self.mappings
+            .iter()
+            .find(|m| (m.span.m..m.span.n).contains(&index))
+            .map(|m| m.addrs.clone())
+    }
+}
@@ -0,0 +1,196 @@
+//! Assemble programs made up of several files.
+//!
+//! Each `INCLUDE` directive is followed by the statements of the file it
+//! names, which is resolved relative to the file containing the directive.
+//! All the files are read into the [`Files`] before any statements are kept
+//! so that the statements can borrow from it. A file is only read once, so
+//! including it again places nothing.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use intcode_error::files::Files;
+use intcode_error::span::{Span, S};
+use intcode_error::{Error, ErrorSet, ResultSet};
+
+use crate::ast::{Instr, Meta, Program};
+use crate::parse::Parser;
+use crate::Options;
+
+/// How many files deep `INCLUDE` directives can be nested by default.
+pub const MAX_INCLUDE_DEPTH: usize = 32;
+
+/// Returns the directory that paths in the file are relative to.
+pub fn dir(files: &Files, file: usize, opts: &Options) -> PathBuf {
+    match file {
+        0 => opts.include_dir.clone(),
+        _ => files
+            .name(file)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    }
+}
+
+/// Returns the canonical form of the path, or the path itself if it doesn't
+/// exist.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn is_same(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Returns the file and the files that included it, outermost first.
+fn ancestors(files: &Files, mut file: usize) -> Vec<usize> {
+    let mut chain = vec![file];
+    while let Some(parent) = files.get(file).parent {
+        file = files.file_of(parent.m);
+        chain.push(file);
+    }
+    chain.reverse();
+    chain
+}
+
+/// Returns an error describing the chain of files ending with the one that
+/// includes `path`, the other `INCLUDE` directives in the chain are added as
+/// notes.
+fn chain_error(files: &Files, chain: &[usize], path: &Path, msg: &str, span: Span) -> Error {
+    let mut desc = format!("`{}`", files.name(chain[0]).display());
+    let names = chain[1..].iter().map(|&f| files.name(f)).chain([path]);
+    for (i, name) in names.enumerate() {
+        let includes = match i {
+            0 => "includes",
+            _ => "which includes",
+        };
+        desc.push_str(&format!(" {} `{}`", includes, name.display()));
+    }
+    let mut error = Error::new(format!("{}: {}", msg, desc), span);
+    for &f in &chain[1..] {
+        if let Some(parent) = files.get(f).parent {
+            let name = files.name(f).display();
+            error = error.note(format!("`{}` is included here", name), parent);
+        }
+    }
+    error
+}
+
+/// Returns the path and the spans of the path and the whole directive for
+/// each `INCLUDE` directive in the program.
+fn includes(program: &Program<'_>) -> Vec<(String, Span, Span)> {
+    program
+        .stmts
+        .iter()
+        .filter_map(|stmt| match &stmt.instr.0 {
+            Instr::Meta(Meta::Include(S(path, span))) => {
+                Some((path.to_string(), *span, stmt.instr.1))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Reads every file included by the files, adding them to the end. Each file
+/// is only read the first time it is included.
+pub fn load(files: &mut Files, opts: &Options) -> ResultSet<()> {
+    let mut loaded: HashSet<_> = (0..files.len()).map(|f| canonical(files.name(f))).collect();
+    let mut errors = Vec::new();
+    for file in 0..files.len() {
+        load_file(files, file, opts, &mut loaded, &mut errors);
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(ErrorSet {
+            errors,
+            ..Default::default()
+        }),
+    }
+}
+
+/// Reads the files included by the file that have not been read yet, and
+/// the files they include, in the order they are included.
+fn load_file(
+    files: &mut Files,
+    file: usize,
+    opts: &Options,
+    loaded: &mut HashSet<PathBuf>,
+    errors: &mut Vec<Error>,
+) {
+    let max_depth = opts.max_include_depth.unwrap_or(MAX_INCLUDE_DEPTH);
+    let range = files.get(file).range.clone();
+    let includes = match Parser::with_range(files.text(), range, opts).eat_program() {
+        Ok(program) => includes(&program),
+        Err(set) => {
+            errors.extend(set.errors);
+            Vec::new()
+        }
+    };
+    let dir = dir(files, file, opts);
+    for (path, span, parent) in includes {
+        let path = dir.join(path);
+        let chain = ancestors(files, file);
+        if let Some(i) = chain.iter().position(|&f| is_same(files.name(f), &path)) {
+            let msg = "file includes itself";
+            errors.push(chain_error(files, &chain[i..], &path, msg, span));
+            continue;
+        }
+        if chain.len() > max_depth {
+            let msg = format!("`INCLUDE` is nested more than {} files deep", max_depth);
+            errors.push(chain_error(files, &chain, &path, &msg, span));
+            continue;
+        }
+        let key = canonical(&path);
+        if loaded.contains(&key) {
+            continue;
+        }
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                loaded.insert(key);
+                let included = files.push(path, &text, parent);
+                load_file(files, included, opts, loaded, errors);
+            }
+            Err(err) => {
+                let msg = format!("failed to read `{}`: {}", path.display(), err);
+                errors.push(Error::new(msg, span));
+            }
+        }
+    }
+}
+
+/// Parses the file, placing the statements of each included file after the
+/// directive that included it.
+pub fn splice<'t>(files: &'t Files, file: usize, opts: &Options) -> ResultSet<Program<'t>> {
+    let range = files.get(file).range.clone();
+    let program = Parser::with_range(files.text(), range, opts).eat_program()?;
+    let mut stmts = Vec::new();
+    for stmt in program.stmts {
+        let included = match &stmt.instr.0 {
+            Instr::Meta(Meta::Include(_)) => {
+                (0..files.len()).find(|&i| files.get(i).parent == Some(stmt.instr.1))
+            }
+            _ => None,
+        };
+        stmts.push(stmt);
+        if let Some(included) = included {
+            stmts.extend(splice(files, included, opts)?.stmts);
+        }
+    }
+    Ok(Program {
+        stmts,
+        end: program.end,
+    })
+}
+
+/// Returns an error for each `INCLUDE` directive, for when the program is not
+/// assembled from files.
+pub fn unsupported(program: &Program<'_>) -> Vec<Error> {
+    includes(program)
+        .into_iter()
+        .map(|(_, _, span)| Error::new("`INCLUDE` is only supported when assembling files", span))
+        .collect()
+}
@@ -0,0 +1,80 @@
+//! The labels and constants defined and used in a program, for editors.
+
+use indexmap::IndexMap;
+
+use intcode_error::span::{Span, S};
+use intcode_error::ResultSet;
+
+use crate::ast::{Instr, Label, Meta, Param, RawParam, Stmt};
+use crate::parse::Parser;
+use crate::Options;
+
+/// A label or `EQU` constant and where it appears.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// The spans of each definition, more than one is an error.
+    pub defs: Vec<Span>,
+    /// The spans of each use.
+    pub refs: Vec<Span>,
+}
+
+fn entry<'a, 'b>(symbols: &'b mut IndexMap<&'a str, Symbol>, name: &'a str) -> &'b mut Symbol {
+    symbols.entry(name).or_insert_with(|| Symbol {
+        name: name.to_owned(),
+        defs: Vec::new(),
+        refs: Vec::new(),
+    })
+}
+
+fn param<'a>(S(p, _): &S<Param<'a>>) -> Option<S<&'a str>> {
+    match *p {
+        Param::Label(_, S(Label::Fixed(name), span), _) => Some(S(name, span)),
+        _ => None,
+    }
+}
+
+fn raw_param<'a>(S(p, _): &S<RawParam<'a>>) -> Option<S<&'a str>> {
+    match *p {
+        RawParam::Label(S(Label::Fixed(name), span), _) => Some(S(name, span)),
+        _ => None,
+    }
+}
+
+/// Returns the symbols of the program in the order they first appear.
+///
+/// The program only needs to parse, so this works even if it can't be
+/// assembled because of an undefined label.
+pub fn symbols(asm: &str, opts: &Options) -> ResultSet<Vec<Symbol>> {
+    let ast = Parser::new(asm, opts).eat_program()?;
+    let mut symbols = IndexMap::new();
+    for Stmt { label, instr, .. } in &ast.stmts {
+        if let Some(S(Label::Fixed(name), span)) = *label {
+            entry(&mut symbols, name).defs.push(span);
+        }
+        let refs: Vec<_> = match &instr.0 {
+            Instr::Add(x, y, z)
+            | Instr::Multiply(x, y, z)
+            | Instr::LessThan(x, y, z)
+            | Instr::Equal(x, y, z) => [x, y, z].into_iter().filter_map(param).collect(),
+            Instr::JumpNonZero(x, y) | Instr::JumpZero(x, y) => {
+                [x, y].into_iter().filter_map(param).collect()
+            }
+            Instr::Input(p) | Instr::Output(p) | Instr::AdjustRelativeBase(p) => {
+                param(p).into_iter().collect()
+            }
+            Instr::Data(data) | Instr::Mutable(data) => data.iter().filter_map(raw_param).collect(),
+            Instr::OutputString(p) => raw_param(p).into_iter().collect(),
+            Instr::Meta(Meta::Entry(S(Label::Fixed(name), span))) => vec![S(*name, *span)],
+            Instr::Meta(Meta::Constant(S(name, span), _)) => {
+                entry(&mut symbols, *name).defs.push(*span);
+                Vec::new()
+            }
+            Instr::Halt | Instr::Include(_) | Instr::Meta(_) => Vec::new(),
+        };
+        for S(name, span) in refs {
+            entry(&mut symbols, name).refs.push(span);
+        }
+    }
+    Ok(symbols.into_iter().map(|(_, symbol)| symbol).collect())
+}
@@ -0,0 +1,252 @@
+//! Bake a fixed input script into a program.
+//!
+//! The generated program contains a small intcode interpreter followed by
+//! the input and the original program. The interpreter runs the original
+//! program and serves each input instruction with the next value from the
+//! embedded input. Once it runs out the real input is used, so the result
+//! still works with interpreters that provide input.
+//!
+//! Input instructions can't simply be patched in place because they are only
+//! two values long and any jump to a routine that loads from the table is
+//! three. Interpreting the program means the original memory layout is
+//! preserved exactly, including any self-modifying code.
+
+use std::iter;
+
+use crate::to_intcode;
+
+/// The interpreter, the `table` and `mem` labels are appended after this.
+///
+/// Addresses in the original program are relative to `mem` and the original
+/// relative base is kept in `base`. The `locate` and `fetch` routines return
+/// to the address in `ret`.
+const INTERPRETER: &str = r#"
+step:
+    ADD #mem, pc, ld_op+1
+ld_op:
+    ADD 0, #0, op
+    ADD #0, #0, m1
+    ADD #0, #0, m2
+    ADD #0, #0, m3
+mode3:
+    LT op, #10000, t
+    JNZ t, #mode2
+    ADD op, #-10000, op
+    ADD m3, #1, m3
+    JZ #0, #mode3
+mode2:
+    LT op, #1000, t
+    JNZ t, #mode1
+    ADD op, #-1000, op
+    ADD m2, #1, m2
+    JZ #0, #mode2
+mode1:
+    LT op, #100, t
+    JNZ t, #dispatch
+    ADD op, #-100, op
+    ADD m1, #1, m1
+    JZ #0, #mode1
+dispatch:
+    EQ op, #3, t
+    JNZ t, #input
+    EQ op, #4, t
+    JNZ t, #output
+    EQ op, #9, t
+    JNZ t, #adjust
+    EQ op, #5, t
+    JNZ t, #jump
+    EQ op, #6, t
+    JNZ t, #jump
+    LT op, #1, t
+    JNZ t, #halt
+    LT op, #9, t
+    JNZ t, #arith
+halt:
+    HLT
+
+arith:
+    ADD #1, #0, i
+    ADD m1, #0, m
+    ADD #arith_x, #0, ret
+    JZ #0, #fetch
+arith_x:
+    ADD v, #0, x
+    ADD #2, #0, i
+    ADD m2, #0, m
+    ADD #arith_y, #0, ret
+    JZ #0, #fetch
+arith_y:
+    ADD v, #0, y
+    ADD #3, #0, i
+    ADD m3, #0, m
+    ADD #arith_op, #0, ret
+    JZ #0, #locate
+arith_op:
+    ADD #4, #0, n
+    EQ op, #1, t
+    JZ t, #arith_mul
+    ADD x, y, v
+    JZ #0, #store
+arith_mul:
+    EQ op, #2, t
+    JZ t, #arith_lt
+    MUL x, y, v
+    JZ #0, #store
+arith_lt:
+    EQ op, #7, t
+    JZ t, #arith_eq
+    LT x, y, v
+    JZ #0, #store
+arith_eq:
+    EQ x, y, v
+    JZ #0, #store
+
+input:
+    ADD #1, #0, i
+    ADD m1, #0, m
+    ADD #input_next, #0, ret
+    JZ #0, #locate
+input_next:
+    ADD #2, #0, n
+    LT used, table, t
+    JZ t, #input_real
+    ADD used, #1, used
+    ADD #table, used, ld_in+1
+ld_in:
+    ADD 0, #0, v
+    JZ #0, #store
+input_real:
+    IN v
+    JZ #0, #store
+
+output:
+    ADD #1, #0, i
+    ADD m1, #0, m
+    ADD #output_x, #0, ret
+    JZ #0, #fetch
+output_x:
+    OUT v
+    ADD pc, #2, pc
+    JZ #0, #step
+
+adjust:
+    ADD #1, #0, i
+    ADD m1, #0, m
+    ADD #adjust_x, #0, ret
+    JZ #0, #fetch
+adjust_x:
+    ADD base, v, base
+    ADD pc, #2, pc
+    JZ #0, #step
+
+jump:
+    ADD #1, #0, i
+    ADD m1, #0, m
+    ADD #jump_x, #0, ret
+    JZ #0, #fetch
+jump_x:
+    ADD v, #0, x
+    ADD #2, #0, i
+    ADD m2, #0, m
+    ADD #jump_y, #0, ret
+    JZ #0, #fetch
+jump_y:
+    ADD pc, #3, pc
+    EQ op, #5, t
+    JZ t, #jump_zero
+    JZ x, #step
+    ADD v, #0, pc
+    JZ #0, #step
+jump_zero:
+    JNZ x, #step
+    ADD v, #0, pc
+    JZ #0, #step
+
+; Writes `v` to the address `a` and moves forward `n` values.
+store:
+    ADD #mem, a, st+3
+st:
+    ADD v, #0, 0
+    ADD pc, n, pc
+    JZ #0, #step
+
+; Sets `a` to the address of parameter `i` which has mode `m`.
+locate:
+    ADD pc, i, a
+    EQ m, #1, t
+    JNZ t, ret
+    ADD #mem, a, ld_loc+1
+ld_loc:
+    ADD 0, #0, a
+    EQ m, #2, t
+    JZ t, ret
+    ADD a, base, a
+    JZ #0, ret
+
+; Sets `v` to the value of parameter `i` which has mode `m`.
+fetch:
+    ADD pc, i, a
+    EQ m, #1, t
+    JNZ t, #fetch_value
+    ADD #mem, a, ld_fetch+1
+ld_fetch:
+    ADD 0, #0, a
+    EQ m, #2, t
+    JZ t, #fetch_value
+    ADD a, base, a
+fetch_value:
+    ADD #mem, a, ld_value+1
+ld_value:
+    ADD 0, #0, v
+    JZ #0, ret
+
+pc:   DB 0
+base: DB 0
+used: DB 0
+ret:  DB 0
+op:   DB 0
+m1:   DB 0
+m2:   DB 0
+m3:   DB 0
+m:    DB 0
+i:    DB 0
+n:    DB 0
+a:    DB 0
+v:    DB 0
+x:    DB 0
+y:    DB 0
+t:    DB 0
+"#;
+
+fn join(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns a program that runs the given program with the input baked in.
+///
+/// The original program behaves exactly as if the input had been provided by
+/// the interpreter, but runs a lot slower.
+///
+/// # Panics
+///
+/// If the program is empty.
+pub fn with_input(program: &[i64], input: &[i64]) -> Vec<i64> {
+    assert!(!program.is_empty(), "program is empty");
+    // The input is prefixed with its length.
+    let table: Vec<_> = iter::once(input.len() as i64)
+        .chain(input.iter().copied())
+        .collect();
+    let asm = format!(
+        "{}table: DB {}\nmem: DB {}\n",
+        INTERPRETER,
+        join(&table),
+        join(program)
+    );
+    to_intcode(&asm)
+        .expect("interpreter should always assemble")
+        .output
+}
@@ -0,0 +1,50 @@
+use intcode_assemble::ast::{Instr, Trivia, Trivium};
+use intcode_assemble::Options;
+use intcode_error::span::s;
+use pretty_assertions::assert_eq;
+
+const ASM: &str = "; header
+
+start: ; label comment
+    ; inner
+    OUT #1 ; out
+
+    HLT
+; the end
+";
+
+#[test]
+fn to_ast_trivia() {
+    let ast = intcode_assemble::to_ast(ASM, &Options::new().trivia(true)).unwrap();
+    let trivia: Vec<_> = ast.stmts.iter().map(|stmt| &stmt.trivia).collect();
+    assert_eq!(
+        trivia,
+        [
+            &Trivia {
+                leading: vec![Trivium::Comment(s("; header", 0..8)), Trivium::BlankLine],
+                inner: vec![
+                    Trivium::Comment(s("; label comment", 17..32)),
+                    Trivium::Comment(s("; inner", 37..44)),
+                ],
+                trailing: Some(s("; out", 56..61)),
+            },
+            &Trivia {
+                leading: vec![Trivium::BlankLine],
+                ..Trivia::default()
+            },
+        ]
+    );
+    assert_eq!(ast.stmts[1].instr.0, Instr::Halt);
+    assert_eq!(ast.end, [Trivium::Comment(s("; the end", 71..80))]);
+}
+
+#[test]
+fn to_ast_without_trivia() {
+    let ast = intcode_assemble::to_ast(ASM, &Options::new()).unwrap();
+    assert_eq!(ast.stmts.len(), 2);
+    assert!(ast
+        .stmts
+        .iter()
+        .all(|stmt| stmt.trivia == Trivia::default()));
+    assert!(ast.end.is_empty());
+}
@@ -1,10 +1,14 @@
-use intcode_error::{fmt, ErrorSet};
+use intcode_assemble::directive::{Directive, DirectiveError, Kind, Value};
+use intcode_error::files::Files;
+use intcode_error::{fmt, Error, ErrorSet, Warning};
 
 use pretty_assertions::assert_eq;
 
 #[track_caller]
 fn assemble(asm: &str) -> String {
-    let ErrorSet { errors, warnings } = intcode_assemble::to_intcode(asm).unwrap_err();
+    let ErrorSet {
+        errors, warnings, ..
+    } = intcode_assemble::to_intcode(asm).unwrap_err();
     let fmt = fmt::Plain::new(asm);
     warnings
         .iter()
@@ -218,6 +222,20 @@ fn parse_invalid_base_16_integer() {
     assert_eq!(assemble(asm), expected);
 }
 
+#[test]
+fn parse_invalid_integer_overflow_32_bit() {
+    let asm = "DB -2147483648, 2147483648";
+    let opts = intcode_assemble::Options::new().width(intcode_assemble::Width::Bits32);
+    let ErrorSet { errors, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap_err();
+    let expected = "
+  --> <input>:1:17
+   |
+ 1 | DB -2147483648, 2147483648
+   |                 ^^^^^^^^^^ base 10 literal out of range for 32-bit integer
+";
+    assert_eq!(fmt::Plain::new(asm).error(&errors[0]), expected);
+}
+
 #[test]
 fn parse_invalid_integer_overflow() {
     let asm = "DB 0xFFFFFFFFFFFFFFFF";
@@ -278,6 +296,54 @@ fn parse_unexpected_string_emoji_escape() {
     assert_eq!(assemble(asm), expected);
 }
 
+#[test]
+fn parse_unexpected_string_emoji_joined() {
+    let asm = "\"\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\"";
+    let expected = "
+  --> <input>:1:1
+   |
+ 1 | \"\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\"
+   | ^^^^ expected an identifier, found a string
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn lex_unexpected_char_after_wide_chars() {
+    let asm = r#"DB "中文" @"#;
+    let expected = r#"
+  --> <input>:1:11
+   |
+ 1 | DB "中文" @
+   |           ^ unexpected character
+"#;
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn lex_unexpected_char_after_combining_mark() {
+    let asm = "DB \"e\u{301}\" @";
+    let expected = "
+  --> <input>:1:8
+   |
+ 1 | DB \"e\u{301}\" @
+   |        ^ unexpected character
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn lex_unexpected_char_after_emoji_line() {
+    let asm = "DB \"😎\"\nADD @";
+    let expected = "
+  --> <input>:2:5
+   |
+ 2 | ADD @
+   |     ^ unexpected character
+";
+    assert_eq!(assemble(asm), expected);
+}
+
 #[test]
 fn parse_invalid_string_escape() {
     let asm = r#"ADD "tes\"\\\t\r\n\s""#;
@@ -489,16 +555,15 @@ test: IN _
 test: HLT
 ";
     let expected = "
-  --> <input>:1:1
-   |
- 1 | test: IN _
-   | ^^^^ first definition of label
-
-
   --> <input>:2:1
    |
  2 | test: HLT
    | ^^^^ label redefined here
+
+  --> <input>:1:1
+   |
+ 1 | test: IN _
+   | ^^^^ first definition of label
 ";
     assert_eq!(assemble(asm), expected);
 }
@@ -520,3 +585,503 @@ fn assemble_undefined_label() {
 ";
     assert_eq!(assemble(asm), expected);
 }
+
+#[test]
+fn assemble_directive_redefined() {
+    let asm = r#"NAME "a"
+NAME "b"
+HLT"#;
+    let expected = r#"
+  --> <input>:2:1
+   |
+ 2 | NAME "b"
+   | ^^^^^^^^ directive redefined here
+
+  --> <input>:1:1
+   |
+ 1 | NAME "a"
+   | ^^^^^^^^ first definition of directive
+"#;
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_constant_redefined() {
+    let asm = "EQU width, 25
+EQU width, 6
+width: OUT #width
+HLT";
+    let expected = r#"
+  --> <input>:2:5
+   |
+ 2 | EQU width, 6
+   |     ^^^^^ constant redefined here
+
+  --> <input>:1:5
+   |
+ 1 | EQU width, 25
+   |     ^^^^^ first definition of constant
+
+
+  --> <input>:3:1
+   |
+ 3 | width: OUT #width
+   | ^^^^^ label has the same name as a constant
+"#;
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn parse_constant_expected_number() {
+    let asm = "EQU width, height";
+    let expected = r#"
+  --> <input>:1:12
+   |
+ 1 | EQU width, height
+   |            ^^^^^^ expected a number
+"#;
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn parse_directive_expected_label() {
+    let asm = "ENTRY 1";
+    let expected = "
+  --> <input>:1:7
+   |
+ 1 | ENTRY 1
+   |       ^ expected a label
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn parse_strict_pseudo_instruction() {
+    let asm = r#"DBZ "hi""#;
+    let opts = intcode_assemble::Options::new().strict(true);
+    let ErrorSet { errors, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap_err();
+    let expected = r#"
+  --> <input>:1:1
+   |
+ 1 | DBZ "hi"
+   | ^^^ pseudo-instruction `DBZ` is not allowed in strict mode
+"#;
+    assert_eq!(fmt::Plain::new(asm).error(&errors[0]), expected);
+}
+
+#[test]
+fn parse_strict_directives() {
+    let opts = intcode_assemble::Options::new().strict(true);
+    for asm in ["EQU x, 1", r#"INCLUDE "a.ints""#, r#"BINCLUDE "a.intcode""#] {
+        let ErrorSet { errors, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap_err();
+        let name = asm.split(' ').next().unwrap();
+        let msg = format!("directive `{}` is not allowed in strict mode", name);
+        assert_eq!(errors[0].to_string(), msg);
+    }
+}
+
+#[test]
+fn normalize_merges_identical_errors() {
+    let asm = "ADD x, #1, x\nOUT x\nHLT";
+    let mut set = intcode_assemble::to_intcode(asm).unwrap_err();
+    set.normalize();
+    let fmt = fmt::Plain::new(asm);
+    let actual: Vec<_> = set.errors.iter().map(|e| fmt.error(e)).collect();
+    let expected = "
+  --> <input>:1:5
+   |
+ 1 | ADD x, #1, x
+   |     ^ undefined label
+   = also at 1:12, 2:5 (3 occurrences)
+";
+    assert_eq!(actual, [expected]);
+    assert_eq!(set.omitted, 0);
+}
+
+#[test]
+fn normalize_lists_a_few_locations() {
+    let asm = "OUT x\n".repeat(1000);
+    let mut set = intcode_assemble::to_intcode(&asm).unwrap_err();
+    set.normalize();
+    let fmt = fmt::Plain::new(&asm);
+    let actual: Vec<_> = set.errors.iter().map(|e| fmt.error(e)).collect();
+    let expected = "
+  --> <input>:1:5
+   |
+ 1 | OUT x
+   |     ^ undefined label
+   = also at 2:5, 3:5, 4:5, and 996 more (1000 occurrences)
+";
+    assert_eq!(actual, [expected]);
+}
+
+#[test]
+fn normalize_keeps_redefinitions_apart() {
+    let asm = "a: HLT\nb: HLT\na: HLT\nb: HLT\na: HLT\nOUT a\nOUT b";
+    let mut set = intcode_assemble::to_intcode(asm).unwrap_err();
+    set.normalize();
+    let fmt = fmt::Plain::new(asm);
+    let actual: Vec<_> = set.errors.iter().map(|e| fmt.error(e)).collect();
+    let expected = [
+        "
+  --> <input>:3:1
+   |
+ 3 | a: HLT
+   | ^ label redefined here
+   = also at 5:1 (2 occurrences)
+
+  --> <input>:1:1
+   |
+ 1 | a: HLT
+   | ^ first definition of label
+",
+        "
+  --> <input>:4:1
+   |
+ 4 | b: HLT
+   | ^ label redefined here
+
+  --> <input>:2:1
+   |
+ 2 | b: HLT
+   | ^ first definition of label
+",
+    ];
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn normalize_keeps_lints_apart() {
+    let mut set = ErrorSet {
+        warnings: vec![
+            Warning::new("is never used", 0..1).lint("unused-label"),
+            Warning::new("is never used", 2..3).lint("unused-data"),
+            Warning::new("is never used", 4..5).lint("unused-label"),
+        ],
+        ..ErrorSet::default()
+    };
+    set.normalize();
+    let warnings: Vec<_> = set
+        .warnings
+        .iter()
+        .map(|w| (w.lint, w.also.len()))
+        .collect();
+    assert_eq!(
+        warnings,
+        [(Some("unused-label"), 1), (Some("unused-data"), 0)]
+    );
+}
+
+#[test]
+fn binclude_missing_file() {
+    let asm = r#"BINCLUDE "missing.intcode""#;
+    let expected = r#"
+  --> <input>:1:10
+   |
+ 1 | BINCLUDE "missing.intcode"
+   |          ^^^^^^^^^^^^^^^^^ failed to read `missing.intcode`: No such file or directory (os error 2)
+"#;
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_value_out_of_range() {
+    let asm = "EQU max, 9223372036854775807
+OUT max+1
+HLT
+DB x+9223372036854775807, ip+9223372036854775807, -9223372036854775808
+x: HLT";
+    let expected = "
+  --> <input>:2:5
+   |
+ 2 | OUT max+1
+   |     ^^^^^ computed value 9223372036854775808 is out of range for 64-bit integer
+
+
+  --> <input>:4:27
+   |
+ 4 | DB x+9223372036854775807, ip+9223372036854775807, -9223372036854775808
+   |                           ^^^^^^^^^^^^^^^^^^^^^^ computed value 9223372036854775813 is out of range for 64-bit integer
+
+
+  --> <input>:4:4
+   |
+ 4 | DB x+9223372036854775807, ip+9223372036854775807, -9223372036854775808
+   |    ^ computed value 9223372036854775813 is out of range for 64-bit integer
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_value_out_of_range_32_bit() {
+    let asm = "DB x+2147483647\nx: HLT";
+    let opts = intcode_assemble::Options::new().width(intcode_assemble::Width::Bits32);
+    let ErrorSet { errors, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap_err();
+    let expected = "
+  --> <input>:1:4
+   |
+ 1 | DB x+2147483647
+   |    ^ computed value 2147483648 is out of range for 32-bit integer
+";
+    assert_eq!(fmt::Plain::new(asm).error(&errors[0]), expected);
+}
+
+#[test]
+fn assemble_constant_out_of_range_32_bit() {
+    let asm = "EQU max, 2147483647
+EQU min, -2147483648
+OUT max+1
+DB min-1, max-1
+HLT";
+    let opts = intcode_assemble::Options::new().width(intcode_assemble::Width::Bits32);
+    let ErrorSet { errors, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap_err();
+    let msgs: Vec<_> = errors.iter().map(|e| (e.to_string(), e.span.m)).collect();
+    assert_eq!(
+        msgs,
+        [
+            (
+                "computed value 2147483648 is out of range for 32-bit integer".to_owned(),
+                45
+            ),
+            (
+                "computed value -2147483649 is out of range for 32-bit integer".to_owned(),
+                54
+            ),
+        ]
+    );
+}
+
+#[test]
+fn assemble_input_declared_twice() {
+    let asm = "INPUTS x, y\nINPUTS x";
+    let expected = "
+  --> <input>:2:8
+   |
+ 2 | INPUTS x
+   |        ^ input declared more than once
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn error_set_merge_and_promote() {
+    let mut set = intcode_assemble::to_intcode("OUT y\nHLT").unwrap_err();
+    let other = intcode_assemble::to_intcode("x: HLT\nOUT z").unwrap_err();
+    set.merge(other);
+    assert_eq!(set.len(), 3);
+    set.sort_by_span();
+    let msgs: Vec<_> = set.errors().map(|e| (&*e.msg, e.span.m)).collect();
+    assert_eq!(msgs, [("undefined label", 4), ("undefined label", 11)]);
+    set.promote_warnings();
+    assert_eq!(set.warnings().count(), 0);
+    assert_eq!(&*set.errors().last().unwrap().msg, "label is never used");
+    assert!(!set.is_empty());
+    assert!(ErrorSet::default().is_empty());
+}
+
+#[track_caller]
+fn assemble_files(dir: &str, asm: &str) -> String {
+    assemble_files_with(dir, asm, intcode_assemble::Options::new())
+}
+
+#[track_caller]
+fn assemble_files_with(dir: &str, asm: &str, opts: intcode_assemble::Options) -> String {
+    let dir = std::env::temp_dir().join(dir);
+    let mut files = Files::new("main.ints", asm);
+    let opts = opts.include_dir(&dir);
+    let ErrorSet { errors, .. } =
+        intcode_assemble::to_intcode_files(&mut files, &opts).unwrap_err();
+    let fmt = fmt::Plain::files(&files);
+    errors
+        .iter()
+        .map(|e| fmt.error(e))
+        .collect::<Vec<String>>()
+        .join("\n")
+        .replace(&format!("{}/", dir.display()), "")
+}
+
+#[test]
+fn include_error_in_included_file() {
+    let dir = std::env::temp_dir().join("intcode-include-errors");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("lib.ints"), "OUT y\nHLT\n").unwrap();
+    let asm = "INCLUDE \"lib.ints\"\nOUT x\n";
+    let expected = "
+  --> lib.ints:1:5
+   |
+ 1 | OUT y
+   |     ^ undefined label
+
+
+  --> main.ints:2:5
+   |
+ 2 | OUT x
+   |     ^ undefined label
+";
+    assert_eq!(assemble_files("intcode-include-errors", asm), expected);
+}
+
+#[test]
+fn include_cycle() {
+    let dir = std::env::temp_dir().join("intcode-include-cycle");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.ints"), "INCLUDE \"b.ints\"\n").unwrap();
+    std::fs::write(dir.join("b.ints"), "HLT\nINCLUDE \"a.ints\"\n").unwrap();
+    let asm = "INCLUDE \"a.ints\"\n";
+    let expected = r#"
+  --> b.ints:2:9
+   |
+ 2 | INCLUDE "a.ints"
+   |         ^^^^^^^^ file includes itself: `a.ints` includes `b.ints` which includes `a.ints`
+
+  --> a.ints:1:1
+   |
+ 1 | INCLUDE "b.ints"
+   | ^^^^^^^^^^^^^^^^ `b.ints` is included here
+"#;
+    assert_eq!(assemble_files("intcode-include-cycle", asm), expected);
+}
+
+#[test]
+fn include_too_deep() {
+    let dir = std::env::temp_dir().join("intcode-include-too-deep");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.ints"), "INCLUDE \"b.ints\"\n").unwrap();
+    std::fs::write(dir.join("b.ints"), "HLT\n").unwrap();
+    let asm = "INCLUDE \"a.ints\"\n";
+    let opts = intcode_assemble::Options::new().max_include_depth(1);
+    let expected = r#"
+  --> a.ints:1:9
+   |
+ 1 | INCLUDE "b.ints"
+   |         ^^^^^^^^ `INCLUDE` is nested more than 1 files deep: `main.ints` includes `a.ints` which includes `b.ints`
+
+  --> main.ints:1:1
+   |
+ 1 | INCLUDE "a.ints"
+   | ^^^^^^^^^^^^^^^^ `a.ints` is included here
+"#;
+    assert_eq!(
+        assemble_files_with("intcode-include-too-deep", asm, opts),
+        expected
+    );
+}
+
+#[test]
+fn include_without_files() {
+    let asm = r#"INCLUDE "lib.ints""#;
+    let expected = r#"
+  --> <input>:1:1
+   |
+ 1 | INCLUDE "lib.ints"
+   | ^^^^^^^^^^^^^^^^^^ `INCLUDE` is only supported when assembling files
+"#;
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn parse_output_string_expected_string_or_label() {
+    let asm = "OUTS #5";
+    let expected = "
+  --> <input>:1:6
+   |
+ 1 | OUTS #5
+   |      ^^ expected a string or a label
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_output_string_not_data() {
+    let asm = "OUTS nowhere\nHLT";
+    let expected = "
+  --> <input>:1:6
+   |
+ 1 | OUTS nowhere
+   |      ^^^^^^^ expected a label on a `DB` statement
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_output_string_offset_outside_data() {
+    let asm = "OUTS msg+2\nOUT msg\nHLT\nmsg: DB \"hi\"";
+    let expected = "
+  --> <input>:1:6
+   |
+ 1 | OUTS msg+2
+   |      ^^^^^ label offset is outside of the data
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn json_format() {
+    let asm = "\"😎\"\nOUT x\n";
+    let ErrorSet { errors, .. } = intcode_assemble::to_intcode(asm).unwrap_err();
+    let fmt = fmt::Json::new(asm);
+    assert_eq!(
+        fmt.error(&errors[0]),
+        r#"{"severity":"error","message":"expected an identifier, found a string","file":"<input>","span":{"start":0,"end":6},"line":1,"column":1}"#
+    );
+    assert_eq!(
+        fmt.note("say \"hi\"\n", 11..12),
+        r#"{"severity":"note","message":"say \"hi\"\n","file":"<input>","span":{"start":11,"end":12},"line":2,"column":5}"#
+    );
+    let error = Error::new("undefined label", 11..12).note("defined here", 0..6);
+    assert_eq!(
+        fmt.error(&error),
+        r#"{"severity":"error","message":"undefined label","file":"<input>","span":{"start":11,"end":12},"line":2,"column":5}
+{"severity":"note","message":"defined here","file":"<input>","span":{"start":0,"end":6},"line":1,"column":1}"#
+    );
+}
+
+#[test]
+fn custom_directive_errors() {
+    let range = Directive::new(
+        "RANGE",
+        &[Kind::Number, Kind::Number],
+        |params| match params {
+            [Value::Number(a), Value::Number(b)] if a <= b => Ok((*a..*b).collect()),
+            _ => Err(DirectiveError::new("end is before the start").param(1)),
+        },
+    );
+    let opts = intcode_assemble::Options::new().directive(range);
+    let asm = "RANGE 1, \"x\"\nRANGE 3, 2\nRANGE 1\n";
+    let ErrorSet { errors, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap_err();
+    let fmt = fmt::Plain::new(asm);
+    let errors: Vec<_> = errors.iter().map(|e| fmt.error(e)).collect();
+    let expected = r#"
+  --> <input>:1:10
+   |
+ 1 | RANGE 1, "x"
+   |          ^^^ expected a number
+
+  --> <input>:2:10
+   |
+ 2 | RANGE 3, 2
+   |          ^ end is before the start
+
+  --> <input>:3:1
+   |
+ 3 | RANGE 1
+   | ^^^^^ expected 2 parameters, found 1
+"#;
+    assert_eq!(errors.join(""), expected);
+}
+
+#[test]
+fn custom_directive_strict() {
+    let zeros = Directive::new("ZEROS", &[Kind::Number], |params| match params {
+        [Value::Number(n)] => Ok(vec![0; *n as usize]),
+        _ => unreachable!(),
+    });
+    let opts = intcode_assemble::Options::new()
+        .directive(zeros)
+        .strict(true);
+    let ErrorSet { errors, .. } = intcode_assemble::to_intcode_with("ZEROS 3", &opts).unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "directive `ZEROS` is not allowed in strict mode"
+    );
+}
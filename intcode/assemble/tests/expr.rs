@@ -0,0 +1,58 @@
+use intcode_assemble::expr::{Env, Expr};
+
+struct Mock;
+
+impl Env for Mock {
+    fn mem(&self, addr: usize) -> i64 {
+        addr as i64 * 10
+    }
+
+    fn ptr(&self) -> usize {
+        7
+    }
+
+    fn rb(&self) -> i64 {
+        -3
+    }
+
+    fn label(&self, name: &str) -> Option<usize> {
+        (name == "x").then(|| 4)
+    }
+}
+
+#[track_caller]
+fn eval(text: &str) -> Result<i64, String> {
+    Expr::parse(text).unwrap().eval(&Mock)
+}
+
+#[test]
+fn precedence() {
+    assert_eq!(eval("1 + 2 * 3"), Ok(7));
+    assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+    assert_eq!(eval("-2 - -3"), Ok(1));
+    assert_eq!(eval("1 + 1 == 2 && !0"), Ok(1));
+    assert_eq!(eval("0 || 3 < 2"), Ok(0));
+    assert_eq!(eval("0x10 % 5"), Ok(1));
+}
+
+#[test]
+fn computer_state() {
+    assert_eq!(eval("mem[x+1]"), Ok(50));
+    assert_eq!(eval("mem[ptr] == 70 && rb < 0"), Ok(1));
+    assert_eq!(eval("y"), Err("unknown label `y`".into()));
+    assert_eq!(eval("mem[rb]"), Err("negative address -3".into()));
+    assert_eq!(eval("1 / (x - 4)"), Err("division by zero".into()));
+}
+
+#[test]
+fn parse_errors() {
+    let msg = |text: &str| Expr::parse(text).unwrap_err().msg.into_owned();
+    assert_eq!(msg("mem[1"), "expected `]`");
+    assert_eq!(
+        msg("1 +"),
+        "expected a number, a label, `ptr`, `rb`, or `mem[..]`"
+    );
+    assert_eq!(msg("1 2"), "expected an operator");
+    assert_eq!(msg("1 $ 2"), "unexpected character");
+    assert_eq!(msg("0b2"), "invalid digit for base 2 literal");
+}
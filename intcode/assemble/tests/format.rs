@@ -0,0 +1,60 @@
+use intcode_assemble::format::format;
+use intcode_assemble::Options;
+use pretty_assertions::assert_eq;
+
+#[track_caller]
+fn fmt(asm: &str) -> String {
+    let output = format(asm, &Options::new()).unwrap();
+    assert_eq!(format(&output, &Options::new()).unwrap(), output);
+    output
+}
+
+#[test]
+fn format_hello_world() {
+    let asm = r#"
+ARB   #message ; move the relative base to the beginning of our message
+
+
+loop: OUT rb ; output the current character in the message
+  ARB #1    ; move the relative base to the next character
+JNZ rb,#loop ; if the next character is non-zero then go back to `loop`
+HLT
+message:
+  DB "Hello World!\n"
+"#;
+    let expected = r#"    ARB #message ; move the relative base to the beginning of our message
+
+loop:
+    OUT rb        ; output the current character in the message
+    ARB #1        ; move the relative base to the next character
+    JNZ rb, #loop ; if the next character is non-zero then go back to `loop`
+    HLT
+message:
+    DB  "Hello World!\n"
+"#;
+    assert_eq!(fmt(asm), expected);
+}
+
+#[test]
+fn format_comments() {
+    let asm = "; A program.\nstart: ; the start\n    ; nothing happens\n HLT ;done  \n";
+    let expected = "; A program.\nstart:  ; the start\n    ; nothing happens\n    HLT ;done\n";
+    assert_eq!(fmt(asm), expected);
+}
+
+#[test]
+fn format_directives_and_operands() {
+    let asm = "NAME \"test\"\nEQU size,0x10\nADD #size,x+1,rb-2\nx: DB 1,2 , 0b11";
+    let expected = "NAME \"test\"
+EQU size, 0x10
+    ADD #size, x+1, rb-2
+x:
+    DB  1, 2, 0b11
+";
+    assert_eq!(fmt(asm), expected);
+}
+
+#[test]
+fn format_invalid() {
+    assert!(format("ADD #1", &Options::new()).is_err());
+}
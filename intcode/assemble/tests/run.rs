@@ -1,7 +1,38 @@
+use std::collections::VecDeque;
 use std::convert::TryInto;
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
 
-use intcode_assemble::Intcode;
-use intcode_run::{Computer, State};
+use intcode_assemble::bundle::{self, Bundle};
+use intcode_assemble::debug::DebugInfo;
+use intcode_assemble::directive::{Directive, Kind, Value};
+use intcode_assemble::meta::Metadata;
+use intcode_assemble::source_map::Mapping;
+use intcode_assemble::{Intcode, Syntax};
+use intcode_error::files::Files;
+use intcode_run::adventure::{self, Room};
+use intcode_run::ascii::{DecodeAsciiExt, Decoded, LineEnding, LineOptions, Script};
+use intcode_run::cancel::CancelToken;
+use intcode_run::checkpoint::Checkpoint;
+use intcode_run::conformance;
+use intcode_run::events::{Event, EventMask};
+use intcode_run::future::channel;
+use intcode_run::harness::{Answer, Harness, LastOutput, MachineIo, Outputs};
+use intcode_run::heatmap::Counts;
+use intcode_run::load::{self, Lenient};
+use intcode_run::lockstep::{self, Divergence, Effect, Level, Outcome, Side};
+use intcode_run::mem_init;
+use intcode_run::network::{Network, Schedule};
+use intcode_run::pipeline::Pipeline;
+use intcode_run::pool::MachinePool;
+use intcode_run::render;
+use intcode_run::scaffold::{self, Camera};
+use intcode_run::symbolic::{Search, Target};
+use intcode_run::{
+    explore, Access, AsyncComputer, Computer, Error, Pause, State, Stats, StepInfo, UninitRead,
+};
 
 #[track_caller]
 fn assemble(asm: &str) -> String {
@@ -15,7 +46,9 @@ fn assemble(asm: &str) -> String {
 
 #[track_caller]
 fn run_utf8(asm: &str) -> String {
-    let Intcode { output, warnings } = intcode_assemble::to_intcode(asm).unwrap();
+    let Intcode {
+        output, warnings, ..
+    } = intcode_assemble::to_intcode(asm).unwrap();
     assert_eq!(warnings.len(), 0);
     let mut c = Computer::new(output);
     let mut w = Vec::new();
@@ -24,7 +57,7 @@ fn run_utf8(asm: &str) -> String {
             State::Yielded(value) => {
                 w.push(value.try_into().unwrap());
             }
-            State::Waiting => {
+            State::Waiting | State::Paused { .. } | State::OutOfFuel => {
                 unreachable!();
             }
             State::Complete => {
@@ -56,3 +89,1643 @@ fn db_with_ip() {
     let asm = r#"DB _, ip+1, "abc""#;
     assert_eq!(assemble(asm), "0,6,97,98,99");
 }
+
+#[test]
+fn clone_computer() {
+    let asm = r#"
+    IN  x
+    OUT x
+    HLT
+x:  DB 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut a = Computer::new(output);
+    assert!(matches!(a.next().unwrap(), State::Waiting));
+    let mut b = a.clone();
+    a.feed([7]);
+    b.feed([13]);
+    assert!(matches!(a.next().unwrap(), State::Yielded(7)));
+    assert!(matches!(b.next().unwrap(), State::Yielded(13)));
+}
+
+#[test]
+fn explore_corridor() {
+    // A droid in a corridor four tiles long, it can only move east and west.
+    let asm = r#"
+loop:
+    IN  cmd
+    EQ  cmd, #4, t
+    JNZ t, #east
+    EQ  cmd, #3, t
+    JNZ t, #west
+    JZ  #0, #wall
+
+east:
+    LT  x, #3, t
+    JZ  t, #wall
+    ADD x, #1, x
+    OUT #1
+    JZ  #0, #loop
+
+west:
+    LT  #0, x, t
+    JZ  t, #wall
+    ADD x, #-1, x
+    OUT #1
+    JZ  #0, #loop
+
+wall:
+    OUT #0
+    JZ  #0, #loop
+
+cmd: DB 0
+t:   DB 0
+x:   DB 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let map = explore::explore(Computer::new(output), (), |status| match status {
+        0 => None,
+        _ => Some(()),
+    })
+    .unwrap();
+    assert_eq!(map.tiles.len(), 4);
+    assert_eq!(map.walls.len(), 10);
+    assert_eq!(map.distance((3, 0)), Some(3));
+    assert_eq!(map.distances_from((3, 0))[&(0, 0)], 3);
+}
+
+#[test]
+fn ascii_line_options() {
+    let opts = LineOptions::new();
+    assert_eq!(opts.encode("hi"), [104, 105, 10]);
+    assert_eq!(opts.encode("hi\r\n"), [104, 105, 10]);
+    let opts = LineOptions::new().strip_cr(false).ending(LineEnding::CrLf);
+    assert_eq!(opts.encode("hi\r\n"), [104, 105, 13, 13, 10]);
+    assert_eq!(opts.encode("hi"), [104, 105, 13, 10]);
+}
+
+#[test]
+fn springscript_walk() {
+    let script = Script::new()
+        .not("A", "J")
+        .not("C", "T")
+        .and("D", "T")
+        .or("T", "J")
+        .walk()
+        .unwrap();
+    assert_eq!(
+        script.lines(),
+        ["NOT A J", "NOT C T", "AND D T", "OR T J", "WALK"]
+    );
+}
+
+#[test]
+fn springscript_invalid() {
+    let err = Script::new().not("E", "J").walk().unwrap_err();
+    assert!(matches!(err, Error::InvalidScript { .. }));
+    let err = Script::new().or("J", "A").run().unwrap_err();
+    assert!(matches!(err, Error::InvalidScript { .. }));
+    let err = (0..16)
+        .fold(Script::new(), |s, _| s.or("A", "J"))
+        .run()
+        .unwrap_err();
+    assert!(matches!(err, Error::ScriptTooLong { len: 16 }));
+}
+
+#[test]
+fn scaffold_intersections() {
+    let camera = Camera::parse(
+        "\
+..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^..
+",
+    );
+    let alignment: i64 = camera.intersections().iter().map(|(x, y)| x * y).sum();
+    assert_eq!(alignment, 76);
+}
+
+#[test]
+fn scaffold_compress() {
+    let camera = Camera::parse(
+        "\
+#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......
+",
+    );
+    let path = camera.path();
+    assert_eq!(
+        path.iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+    );
+    let routines = scaffold::compress(&path).unwrap();
+    assert!(routines.lines().iter().all(|line| line.len() <= 20));
+    let expanded: Vec<_> = routines
+        .main
+        .iter()
+        .flat_map(|&i| routines.functions[i].iter().copied())
+        .collect();
+    assert_eq!(expanded, path);
+}
+
+#[test]
+fn load_lenient() {
+    let input = "my puzzle input\n\n104,7,99\nexpected: 7\n";
+    assert_eq!(
+        load::lenient(input),
+        Some(Lenient {
+            program: vec![104, 7, 99],
+            ignored: vec![(1, "my puzzle input".into()), (4, "expected: 7".into())],
+        })
+    );
+    assert_eq!(
+        load::lenient("1,2\n3,4\n").unwrap().ignored,
+        [(2, "3,4".into())]
+    );
+    assert_eq!(load::lenient("no program here\n"), None);
+}
+
+#[test]
+fn harness_load() {
+    let dir = std::env::temp_dir().join("intcode-harness-load");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("input.txt");
+    std::fs::write(&path, "104,7,99\nexpected: 7\n").unwrap();
+    let harness = Harness::load(&path)
+        .unwrap()
+        .part("part 1", LastOutput(vec![]))
+        .unwrap();
+    assert_eq!(harness.answers(), [Answer::Number(7)]);
+    std::fs::write(&path, "no program here\n").unwrap();
+    assert!(Harness::load(&path).is_err());
+}
+
+#[test]
+fn harness_parts() {
+    let asm = r#"
+    IN  x
+    MUL x, #2, x
+    OUT x
+    OUT #1
+    HLT
+x:  DB 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let harness = Harness::new(output)
+        .part("last", LastOutput(vec![5]))
+        .unwrap()
+        .part("outputs", Outputs(vec![5]))
+        .unwrap()
+        .part("closure", |io: &mut MachineIo| {
+            io.send([21]);
+            Ok(Answer::from(io.recv()?.unwrap()))
+        })
+        .unwrap();
+    assert_eq!(
+        harness.answers(),
+        [
+            Answer::Number(1),
+            Answer::Text("10,1".into()),
+            Answer::Number(42)
+        ]
+    );
+}
+
+#[test]
+fn assemble_near_i64_limits() {
+    let asm = "DB x+9223372036854775804, ip-9223372036854775807, -9223372036854775808
+x: HLT";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(output, [i64::MAX, 3 - i64::MAX, i64::MIN, 99]);
+}
+
+#[test]
+fn run_with_input() {
+    let asm = "
+    IN  x
+    MUL x, #2, x
+    OUT x
+    HLT
+x:  DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut c = Computer::new(output.clone());
+    assert_eq!(c.run_with_input(&[21]).unwrap(), [42]);
+    assert_eq!(intcode_run::execute(output.clone(), &[4]).unwrap(), [8]);
+    assert!(matches!(
+        intcode_run::execute(output, &[]),
+        Err(Error::Starved { ptr: 0 })
+    ));
+}
+
+#[test]
+fn fuel() {
+    let asm = "
+loop:
+    ADD x, #1, x
+    JNZ #1, #loop
+x:  DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut c = Computer::new(output);
+    c.set_fuel(5);
+    assert!(matches!(c.next().unwrap(), State::OutOfFuel));
+    assert!(matches!(c.next().unwrap(), State::OutOfFuel));
+    assert_eq!(c.fuel(), Some(0));
+    assert_eq!(c.stats().instructions, 5);
+    assert_eq!(c.peek(7), 3);
+    c.set_fuel(2);
+    assert!(matches!(c.next().unwrap(), State::OutOfFuel));
+    assert_eq!(c.peek(7), 4);
+
+    // Waiting for input doesn't use any fuel.
+    let mut c = Computer::new(vec![3, 0, 99]);
+    c.set_fuel(1);
+    assert!(matches!(c.next().unwrap(), State::Waiting));
+    assert_eq!(c.fuel(), Some(1));
+    assert!(matches!(c.run_with([]), Err(Error::Starved { ptr: 0 })));
+    assert!(matches!(c.run_with([7]), Err(Error::OutOfFuel { ptr: 2 })));
+}
+
+#[test]
+fn cancel_token() {
+    let Intcode { output, .. } = intcode_assemble::to_intcode("OUT #7\nHLT\n").unwrap();
+    let mut c = Computer::new(output);
+    let token = CancelToken::new();
+    c.set_cancel_token(Some(token.clone()));
+    token.cancel();
+    assert!(matches!(
+        c.next().unwrap(),
+        State::Paused {
+            reason: Pause::Cancelled { ptr: 0 }
+        }
+    ));
+    // The token stays cancelled until it is reset.
+    assert!(token.is_cancelled());
+    assert!(matches!(
+        c.next().unwrap(),
+        State::Paused {
+            reason: Pause::Cancelled { ptr: 0 }
+        }
+    ));
+    token.reset();
+    assert!(matches!(c.next().unwrap(), State::Yielded(7)));
+    assert!(matches!(c.next().unwrap(), State::Complete));
+
+    // The drivers stop with an error instead.
+    let Intcode { output, .. } = intcode_assemble::to_intcode("OUT #7\nHLT\n").unwrap();
+    let mut c = Computer::new(output);
+    c.set_cancel_token(Some(token.clone()));
+    token.cancel();
+    assert!(matches!(c.run_with([]), Err(Error::Cancelled { ptr: 0 })));
+    token.reset();
+    assert_eq!(c.run_with([]).unwrap(), [7]);
+}
+
+#[test]
+fn run_outcome_extractors() {
+    let asm = r#"
+    IN  x
+    OUT x
+    OUT #105
+    ADD x, #1, 0
+    HLT
+x:  DB 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let outcome = Computer::new(output.clone()).run_to_end([72]).unwrap();
+    assert_eq!(outcome.outputs(), [72, 105]);
+    assert_eq!(outcome.first_output().unwrap(), 72);
+    assert_eq!(outcome.last_output().unwrap(), 105);
+    assert_eq!(outcome.outputs_as_string().unwrap(), "Hi");
+    assert_eq!(outcome.memory0().unwrap(), 73);
+
+    let outcome = Computer::new(output).run_to_end([-1]).unwrap();
+    assert_eq!(
+        outcome.outputs_as_string().unwrap_err().to_string(),
+        "output `-1` at index 0 is not an ASCII character"
+    );
+
+    let Intcode { output, .. } = intcode_assemble::to_intcode("HLT").unwrap();
+    let outcome = Computer::new(output).run_to_end([]).unwrap();
+    assert_eq!(
+        outcome.last_output().unwrap_err().to_string(),
+        "program completed without any output"
+    );
+    assert_eq!(outcome.outputs_as_string().unwrap(), "");
+    assert_eq!(outcome.memory0().unwrap(), 99);
+
+    let output: Vec<i128> = vec![4, 3, 99, i128::MAX];
+    let outcome = Computer::new(output).run_to_end([]).unwrap();
+    assert_eq!(
+        outcome.first_output().unwrap_err().to_string(),
+        format!("output `{}` does not fit in 64 bits", i128::MAX)
+    );
+}
+
+#[test]
+fn events_recorded() {
+    let asm = r#"
+    IN  x
+    JNZ x, #out
+    HLT
+out:
+    OUT x
+    HLT
+x:  DB 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut c = Computer::new(output);
+    c.record_events(EventMask::INPUT | EventMask::OUTPUT);
+    c.feed([7]);
+    assert!(matches!(c.next().unwrap(), State::Yielded(7)));
+    assert!(matches!(c.next().unwrap(), State::Complete));
+    let log = c.take_events().unwrap();
+    assert_eq!(
+        log.events(),
+        [
+            Event::Input { ptr: 0, value: 7 },
+            Event::Output { ptr: 6, value: 7 }
+        ]
+    );
+    let mut csv = Vec::new();
+    log.write_csv(&mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "kind,ptr,value\ninput,0,7\noutput,6,7\n"
+    );
+}
+
+#[test]
+fn heatmap() {
+    let asm = r#"
+    IN  x
+    OUT x
+    ADD x, #1, 100
+    HLT
+x:  DB 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut c = Computer::new(output);
+    c.record_heatmap();
+    assert!(matches!(c.next().unwrap(), State::Waiting));
+    c.feed([5]);
+    assert!(matches!(c.next().unwrap(), State::Yielded(5)));
+    assert!(matches!(c.next().unwrap(), State::Complete));
+    let heatmap = c.take_heatmap().unwrap();
+    let counts = |reads, writes, executes| Counts {
+        reads,
+        writes,
+        executes,
+    };
+    assert_eq!(heatmap.get(0), counts(0, 0, 1));
+    assert_eq!(heatmap.get(6), counts(0, 0, 1));
+    assert_eq!(heatmap.get(9), counts(2, 1, 0));
+    assert_eq!(heatmap.get(100), counts(0, 1, 0));
+
+    let mut csv = Vec::new();
+    heatmap.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    assert!(csv.starts_with("address,reads,writes,executes\n0,0,0,1\n"));
+    assert!(csv.ends_with("9,2,1,0\n100,0,1,0\n"));
+
+    let mut ppm = Vec::new();
+    heatmap.write_ppm(&mut ppm, 4).unwrap();
+    let (header, pixels) = ppm.split_at(11);
+    assert_eq!(header, b"P6\n4 3\n255\n");
+    assert_eq!(pixels.len(), 4 * 3 * 3);
+    assert_eq!(pixels[..3], [0, 0, 255]);
+    assert_eq!(pixels[27..30], [255, 255, 0]);
+    assert_eq!(pixels[30..], [0; 6]);
+}
+
+#[test]
+fn checkpoint_resume() {
+    let asm = r#"
+loop:
+    ADD i, #1, i
+    ADD s, i, s
+    LT  i, #10, t
+    JNZ t, #loop
+    IN  t
+    OUT s
+    OUT t
+    HLT
+i:  DB 0
+s:  DB 0
+t:  DB 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut c = Computer::new(output.clone());
+    c.feed([7]);
+    c.pause_every(Some(10));
+    let reason = Pause::Interval { steps: 10 };
+    assert_eq!(c.next().unwrap(), State::Paused { reason });
+    let checkpoint = c.checkpoint();
+    assert_eq!(checkpoint.steps, 10);
+    assert_eq!(checkpoint.input, [7]);
+    let parsed: Checkpoint = checkpoint.to_string().parse().unwrap();
+    assert_eq!(parsed, checkpoint);
+
+    // The next checkpoint is a whole interval after the restored one, however
+    // the interval and the checkpoint are set up.
+    for before in [true, false] {
+        let mut resumed = Computer::new(output.clone());
+        if before {
+            resumed.pause_every(Some(10));
+        }
+        resumed.restore(&parsed).unwrap();
+        if !before {
+            resumed.pause_every(Some(10));
+        }
+        let reason = Pause::Interval { steps: 20 };
+        assert_eq!(resumed.next().unwrap(), State::Paused { reason });
+    }
+
+    let mut resumed = Computer::new(output);
+    resumed.restore(&parsed).unwrap();
+    assert_eq!(resumed.run_with([]).unwrap(), [55, 7]);
+    assert_eq!(c.run_with([]).unwrap(), [55, 7]);
+    assert_eq!(resumed.stats().instructions, c.stats().instructions);
+
+    assert!(matches!(
+        Computer::new(vec![99]).restore(&parsed),
+        Err(Error::CheckpointMismatch)
+    ));
+    assert_eq!(
+        "# intcode checkpoint 1\nptr x\n"
+            .parse::<Checkpoint>()
+            .unwrap_err()
+            .to_string(),
+        "invalid checkpoint on line 2: invalid number `x`: invalid digit found in string"
+    );
+}
+
+#[test]
+fn memory_mapped_device() {
+    // Reads from address 100 return an incrementing counter and writes to
+    // address 101 are collected.
+    let asm = r#"
+    ADD 100, 100, 101
+    ADD 100, #0, 101
+    HLT
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let mut c = Computer::new(output);
+    let mut counter = 0;
+    c.map_device(
+        100..102,
+        move |_| {
+            counter += 1;
+            counter
+        },
+        {
+            let written = written.clone();
+            move |addr, value| written.lock().unwrap().push((addr, value))
+        },
+    );
+    assert!(matches!(c.next().unwrap(), State::Complete));
+    assert_eq!(*written.lock().unwrap(), [(101, 3), (101, 3)]);
+}
+
+#[test]
+fn drive_starved() {
+    let asm = r#"
+loop:
+    IN  x
+    OUT x
+    JZ  #0, #loop
+x:  DB 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut c = Computer::new(output);
+    let outputs: Vec<_> = c.drive([1, 2]).collect();
+    assert!(matches!(
+        outputs.as_slice(),
+        [Ok(1), Ok(2), Err(Error::Starved { ptr: 0 })]
+    ));
+
+    let mut c = Computer::new(vec![3, 5, 4, 5, 99, 0]);
+    assert_eq!(c.run_with([7]).unwrap(), [7]);
+}
+
+#[test]
+fn conformance_vectors() {
+    for vector in conformance::vectors() {
+        let mut c = Computer::new(vector.program);
+        let output = c.run_with(vector.input).unwrap();
+        assert_eq!(output, vector.output, "vector `{}`", vector.name);
+    }
+}
+
+#[test]
+fn conformance_data_file() {
+    // Regenerate with `conformance::write()` if the vectors change.
+    let mut data = Vec::new();
+    conformance::write(&mut data).unwrap();
+    assert_eq!(
+        String::from_utf8(data).unwrap(),
+        include_str!("../../run/conformance.txt")
+    );
+}
+
+#[test]
+fn decode_ascii_outputs() {
+    let asm = r#"
+    OUT #104
+    OUT #105
+    OUT #10
+    OUT #1000
+    OUT #33
+    HLT
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut c = Computer::new(output);
+    let decoded: Vec<_> = c.outputs().decode_ascii().map(Result::unwrap).collect();
+    assert_eq!(
+        decoded,
+        [
+            Decoded::Text("hi\n".into()),
+            Decoded::Value(1000),
+            Decoded::Text("!".into())
+        ]
+    );
+}
+
+#[test]
+fn metadata_header() {
+    let asm = r#"
+NAME "Echo"
+AUTHOR "Ross"
+ENTRY start
+REQUIRES "ascii"
+
+done: HLT
+start: OUT #42
+JZ #0, #done
+"#;
+    let opts = intcode_assemble::Options::new().embed_metadata(true);
+    let Intcode {
+        output,
+        warnings,
+        debug,
+        ..
+    } = intcode_assemble::to_intcode_with(asm, &opts).unwrap();
+    assert_eq!(warnings.len(), 0);
+    let meta = Metadata::from_header(&output).unwrap();
+    assert_eq!(meta, debug.meta);
+    assert_eq!(meta.name.as_deref(), Some("Echo"));
+    assert_eq!(meta.author.as_deref(), Some("Ross"));
+    assert_eq!(meta.requires, ["ascii"]);
+    assert_eq!(output[meta.header_len()], 99);
+    assert_eq!(meta.entry, Some(meta.header_len() + 1));
+
+    let mut c = Computer::new(output);
+    assert_eq!(c.run_with([]).unwrap(), [42]);
+
+    let Intcode { output, debug, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(output, [99, 104, 42, 1106, 0, 0]);
+    assert_eq!(debug.meta.entry, Some(1));
+    assert_eq!(Metadata::from_header(&output), None);
+    assert_eq!(debug.to_string().parse::<DebugInfo>().unwrap(), debug);
+}
+
+#[test]
+fn relative_base_directive() {
+    let asm = "
+RBINIT 100
+    ADD #7, #0, rb
+    OUT rb
+    HLT
+";
+    let Intcode { output, debug, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(debug.meta.relative_base, Some(100));
+    assert_eq!(debug.to_string().parse::<DebugInfo>().unwrap(), debug);
+    let mut c = Computer::new(output);
+    c.set_relative_base(100);
+    assert_eq!(c.run_with([]).unwrap(), [7]);
+    assert_eq!(c.relative_base(), 100);
+
+    let opts = intcode_assemble::Options::new().embed_metadata(true);
+    let Intcode { output, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap();
+    let meta = Metadata::from_header(&output).unwrap();
+    assert_eq!(meta.relative_base, Some(100));
+    let mut c = Computer::new(output);
+    assert_eq!(c.run_with([]).unwrap(), [7]);
+    assert_eq!(c.relative_base(), 100);
+}
+
+#[test]
+fn inputs_directive() {
+    let asm = r#"
+INPUTS noun, verb
+    IN x
+    IN y
+    MUL x, y, x
+    OUT x
+    HLT
+x:  DB 0
+y:  DB 0
+"#;
+    let Intcode { debug, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(debug.meta.inputs, ["noun", "verb"]);
+    assert_eq!(debug.to_string().parse::<DebugInfo>().unwrap(), debug);
+}
+
+#[test]
+fn bundle_round_trip() {
+    let asm = r#"
+NAME "Hello"
+    OUTS msg
+    HLT
+msg: DB "hi"
+"#;
+    let Intcode { output, debug, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let bundle = Bundle::new(output.clone(), debug).source("hello.ints");
+    let text = bundle.to_string();
+    assert!(bundle::is_bundle(&text));
+    assert!(!bundle::is_bundle("104,7,99"));
+    let parsed: Bundle = text.parse().unwrap();
+    assert_eq!(parsed, bundle);
+    assert_eq!(parsed.program, output);
+    assert_eq!(parsed.source.as_deref(), Some("hello.ints"));
+    assert_eq!(parsed.debug.label(5), Some("msg"));
+    assert_eq!(parsed.debug.meta.name.as_deref(), Some("Hello"));
+
+    let text = "# intcode bundle 1\n# assembler test\n# label x msg\n104,7,99\n";
+    let err = text.parse::<Bundle>().unwrap_err();
+    assert_eq!(err.to_string(), "invalid address on line 3");
+}
+
+#[cfg(feature = "fingerprint")]
+#[test]
+fn fingerprint() {
+    let asm = "OUT #7\nHLT\n";
+    let opts = intcode_assemble::Options::new().fingerprint(true);
+    let fingerprint = |asm: &str, opts: &intcode_assemble::Options| {
+        let Intcode { debug, .. } = intcode_assemble::to_intcode_with(asm, opts).unwrap();
+        debug.fingerprint.unwrap()
+    };
+    let a = fingerprint(asm, &opts);
+    assert_eq!(a.len(), 64);
+    assert_eq!(fingerprint(asm, &opts), a);
+    assert_eq!(fingerprint(asm, &opts.clone().include_dir("elsewhere")), a);
+    assert_ne!(fingerprint("OUT #8\nHLT\n", &opts), a);
+    assert_ne!(fingerprint(asm, &opts.clone().strict(true)), a);
+
+    let Intcode { debug, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap();
+    assert_eq!(debug.to_string().parse::<DebugInfo>().unwrap(), debug);
+    let Intcode { debug, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(debug.fingerprint, None);
+}
+
+#[test]
+fn syntax_v1() {
+    let asm = r#"
+LOOP: OUT ip
+JZ #0, #LOOP
+ip: DB 7
+"#;
+    let opts = intcode_assemble::Options::new().syntax(Syntax::V1);
+    let Intcode { output, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap();
+    assert_eq!(output, [4, 5, 1106, 0, 0, 7]);
+    assert!(intcode_assemble::to_intcode(asm).is_err());
+}
+
+#[test]
+fn binclude_blob() {
+    let dir = std::env::temp_dir().join("intcode-binclude");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("blob.intcode"), "104,42,99\n").unwrap();
+    let asm = r#"
+    JZ #0, #blob
+    DB 0
+blob: BINCLUDE "blob.intcode"
+"#;
+    let opts = intcode_assemble::Options::new().include_dir(&dir);
+    let Intcode {
+        output, includes, ..
+    } = intcode_assemble::to_intcode_with(asm, &opts).unwrap();
+    assert_eq!(output, [1106, 0, 4, 0, 104, 42, 99]);
+    assert_eq!(includes, [dir.join("blob.intcode")]);
+    let mut c = Computer::new(output);
+    assert_eq!(c.run_with([]).unwrap(), [42]);
+}
+
+#[test]
+fn wrap_with_input() {
+    let asm = r#"
+    ARB #100
+loop:
+    IN rb
+    EQ rb, #10, rb+2
+    JNZ rb+2, #done
+    MUL rb, #1, rb+1
+    OUT rb+1
+    JZ #0, #loop
+done:
+    IN x
+    OUT x
+    HLT
+x:  DB 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let wrapped = intcode_assemble::wrap::with_input(&output, b"hi\n".map(i64::from).as_ref());
+    let mut c = Computer::new(wrapped);
+    assert_eq!(c.run_with([7]).unwrap(), [104, 105, 7]);
+
+    // Without any input baked in the real input is used straight away.
+    let wrapped = intcode_assemble::wrap::with_input(&output, &[]);
+    let mut c = Computer::new(wrapped);
+    assert_eq!(c.run_with([33, 10, -5]).unwrap(), [33, -5]);
+}
+
+#[test]
+fn symbolic_search() {
+    let asm = "
+    IN x
+    IN y
+    ADD x, y, sum
+    MUL x, #3, x
+    EQ sum, #100, t
+    JZ t, #fail
+    LT x, #30, t
+    JNZ t, #fail
+    OUT #1
+fail:
+    HLT
+x:   DB 0
+y:   DB 0
+sum: DB 0
+t:   DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let input = Search::new(output.clone(), Target::Output(1))
+        .run()
+        .unwrap();
+    assert_eq!(input.len(), 2);
+    assert_eq!(input[0] + input[1], 100);
+    assert!(input[0] * 3 >= 30);
+    let mut c = Computer::new(output.clone());
+    c.feed(input);
+    assert_eq!(c.run_with([]).unwrap(), [1]);
+
+    let input = Search::new(output, Target::Address(0)).run().unwrap();
+    assert_eq!(input, []);
+}
+
+#[test]
+fn symbolic_search_overflow() {
+    // The expression for `y` overflows so it falls back to the concrete value.
+    let asm = "
+    IN  x
+    MUL x, #4611686018427387904, y
+    MUL y, #4, y
+    EQ  y, #0, t
+    JZ  t, #fail
+    OUT #1
+fail:
+    HLT
+x:  DB 0
+y:  DB 0
+t:  DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let search = |target| Search::new(output.clone(), target).run();
+    assert_eq!(search(Target::Output(1)), Some(vec![0]));
+    assert_eq!(search(Target::Output(2)), None);
+}
+
+#[test]
+fn network_schedule() {
+    let asm = "
+    IN a
+    OUT #255
+    OUT a
+    MUL a, a, b
+    OUT b
+    HLT
+a:  DB 0
+b:  DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let collect = |schedule| {
+        let mut packets = Vec::new();
+        Network::new(output.clone(), 4)
+            .schedule(schedule)
+            .run(|packet| {
+                assert_eq!(packet.y, packet.x * packet.x);
+                packets.push(packet.x);
+                if packets.len() == 4 {
+                    ControlFlow::Break(packets.clone())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap()
+    };
+    let seeded = Schedule::RoundRobin { seed: 7 };
+    assert_eq!(collect(Schedule::default()), [0, 1, 2, 3]);
+    assert_eq!(collect(seeded), collect(seeded));
+    let mut threaded = collect(Schedule::Threaded);
+    threaded.sort_unstable();
+    assert_eq!(threaded, [0, 1, 2, 3]);
+}
+
+#[test]
+fn network_nat() {
+    let asm = "
+    IN  addr
+    OUT #255
+    OUT addr
+    OUT #0
+loop:
+    IN  x
+    EQ  x, #-1, poll
+    JNZ poll, #loop
+    IN  y
+    ADD x, #10, x
+    OUT #255
+    OUT x
+    OUT y
+    JZ  #0, #loop
+addr: DB 0
+x:    DB 0
+y:    DB 0
+poll: DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let collect = |schedule| {
+        let mut woken = Vec::new();
+        Network::new(output.clone(), 4)
+            .schedule(schedule)
+            .nat(255)
+            .run(|packet| {
+                if packet.dest == 0 {
+                    woken.push(packet.x);
+                }
+                match woken.len() {
+                    3 => ControlFlow::Break(woken.clone()),
+                    _ => ControlFlow::Continue(()),
+                }
+            })
+            .unwrap()
+    };
+    assert_eq!(collect(Schedule::default()), [3, 13, 23]);
+    let threaded = collect(Schedule::Threaded);
+    assert_eq!(threaded[1] - threaded[0], 10);
+    assert_eq!(threaded[2] - threaded[1], 10);
+
+    // The token stops every computer in the network.
+    for schedule in [Schedule::default(), Schedule::Threaded] {
+        let token = CancelToken::new();
+        let result = Network::new(output.clone(), 4)
+            .schedule(schedule)
+            .nat(255)
+            .cancel_token(token.clone())
+            .run(|_| {
+                token.cancel();
+                ControlFlow::<()>::Continue(())
+            });
+        assert!(matches!(result, Err(Error::Cancelled { .. })));
+    }
+}
+
+#[test]
+fn pipeline() {
+    let program = vec![
+        3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+    ];
+    let run = |phases: &[i64]| Pipeline::new(program.clone(), phases).run().unwrap();
+    assert_eq!(run(&[4, 3, 2, 1, 0]), 43210);
+    assert_eq!(run(&[0, 1, 2, 3, 4]), 1234);
+
+    let program = vec![
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5,
+    ];
+    let pipeline = Pipeline::new(program.clone(), &[9, 8, 7, 6, 5]).feedback(true);
+    assert_eq!(pipeline.run().unwrap(), 139629729);
+    assert!(matches!(
+        Pipeline::new(program.clone(), &[9, 8, 7, 6, 5]).run(),
+        Err(Error::Starved { .. })
+    ));
+
+    let token = CancelToken::new();
+    token.cancel();
+    assert!(matches!(
+        Pipeline::new(program, &[9, 8, 7, 6, 5])
+            .cancel_token(token)
+            .run(),
+        Err(Error::Cancelled { ptr: 0 })
+    ));
+}
+
+#[test]
+fn async_computer() {
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let asm = "
+    IN  x
+    MUL x, #2, x
+    OUT x
+    ADD n, #-1, n
+    JNZ n, #0
+    HLT
+x:  DB 0
+n:  DB 3
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut first = AsyncComputer::new(output.clone()).budget(1);
+    let mut second = AsyncComputer::new(output);
+    let (tx, rx) = channel();
+    let mut out = Vec::new();
+    let mut a = Box::pin(first.run(VecDeque::from(vec![1, 2, 3]), tx));
+    let mut b = Box::pin(second.run(rx, &mut out));
+    let waker = Waker::from(Arc::new(Noop));
+    let mut cx = Context::from_waker(&waker);
+    let (mut a_done, mut b_done, mut polls) = (false, false, 0);
+    while !(a_done && b_done) {
+        if !a_done {
+            if let Poll::Ready(result) = a.as_mut().poll(&mut cx) {
+                result.unwrap();
+                a_done = true;
+            }
+        }
+        if !b_done {
+            if let Poll::Ready(result) = b.as_mut().poll(&mut cx) {
+                result.unwrap();
+                b_done = true;
+            }
+        }
+        polls += 1;
+    }
+    drop(b);
+    assert_eq!(out, [4, 8, 12]);
+    assert!(polls > 1);
+}
+
+#[test]
+fn machine_pool_reset() {
+    let asm = "
+    IN x
+    ADD x, y, y
+    OUT y
+    HLT
+x:  DB 0
+y:  DB 100
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut snapshot = Computer::new(output);
+    snapshot.feed([5]);
+    let pool = MachinePool::from_snapshot(snapshot, 1);
+    {
+        let mut c = pool.get();
+        assert_eq!(c.run_with([]).unwrap(), [105]);
+        assert!(pool.try_get().is_none());
+        c.reset();
+        assert_eq!(c.run_with([]).unwrap(), [105]);
+    }
+    let mut c = pool.get();
+    assert_eq!(c.ptr(), 0);
+    assert_eq!(c.run_with([]).unwrap(), [105]);
+}
+
+#[test]
+fn adventure_room_parse() {
+    let text = "
+== Hull Breach ==
+You got in through a hole in the floor here.
+
+Doors here lead:
+- north
+- east
+
+Items here:
+- space heater
+
+Command?
+";
+    assert_eq!(
+        Room::parse(text).unwrap(),
+        Room {
+            name: "Hull Breach".into(),
+            doors: vec!["north".into(), "east".into()],
+            items: vec!["space heater".into()],
+        }
+    );
+    assert_eq!(Room::parse("You can't go that way.\n"), None);
+}
+
+#[test]
+fn adventure_solve() {
+    // A single room with one item, the floor to the north only lets the droid
+    // through without the mug.
+    let asm = r#"
+room:
+    ADD #hull, #0, s
+    ADD #r1, #0, ret
+    JZ  #0, #print
+r1: JNZ taken, #ask
+    ADD #items, #0, s
+    ADD #ask, #0, ret
+    JZ  #0, #print
+ask:
+    ADD #prompt, #0, s
+    ADD #read, #0, ret
+    JZ  #0, #print
+read:
+    IN  ch
+skip:
+    IN  t
+    EQ  t, #10, t
+    JZ  t, #skip
+    EQ  ch, #110, t
+    JNZ t, #north
+    EQ  ch, #116, t
+    JNZ t, #take
+    EQ  ch, #100, t
+    JNZ t, #drop
+    JZ  #0, #ask
+take:
+    ADD #1, #0, taken
+    ADD #took, #0, s
+    ADD #ask, #0, ret
+    JZ  #0, #print
+drop:
+    ADD #0, #0, taken
+    ADD #dropped, #0, s
+    ADD #ask, #0, ret
+    JZ  #0, #print
+north:
+    JZ  taken, #win
+    ADD #alert, #0, s
+    ADD #room, #0, ret
+    JZ  #0, #print
+win:
+    ADD #password, #0, s
+    ADD #done, #0, ret
+    JZ  #0, #print
+done:
+    HLT
+
+print:
+    ADD s, #0, c+1
+    ADD s, #0, o+1
+c:  JZ  0, ret
+o:  OUT 0
+    ADD s, #1, s
+    JZ  #0, #print
+
+s:      DB 0
+ret:    DB 0
+ch:     DB 0
+t:      DB 0
+taken:  DB 0
+hull:     DB "\n== Hull ==\nDoors here lead:\n- north\n\n", 0
+items:    DB "Items here:\n- mug\n\n", 0
+prompt:   DB "Command?\n", 0
+took:     DB "You take the mug.\n\n", 0
+dropped:  DB "You drop the mug.\n\n", 0
+alert:    DB "\n== Floor ==\nAlert! Droids on this ship are lighter than the detected value!\n", 0
+password: DB "\n== Floor ==\nYou may proceed by typing 1234 on the keypad.\n", 0
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(adventure::solve(Computer::new(output)).unwrap(), "1234");
+}
+
+#[test]
+fn cell_width() {
+    // Squares the input.
+    let program = [3, 9, 2, 9, 9, 9, 4, 9, 99, 0];
+    let mut c = Computer::<i32>::new(program.map(|v| v as i32).to_vec());
+    assert_eq!(c.run_with([46340]).unwrap(), [2147395600]);
+    let mut c = Computer::<i128>::new(program.map(i128::from).to_vec());
+    assert_eq!(
+        c.run_with([i128::from(i64::MAX)]).unwrap(),
+        [i128::from(i64::MAX) * i128::from(i64::MAX)]
+    );
+}
+
+#[test]
+fn cell_overflow() {
+    // Squares the input.
+    let program = [3, 9, 2, 9, 9, 9, 4, 9, 99, 0];
+    let mut c = Computer::<i32>::new(program.to_vec());
+    assert_eq!(
+        c.run_with([46341]).unwrap_err().to_string(),
+        "instruction at address `2` overflowed the 32-bit cell"
+    );
+    // Adds the input to itself.
+    let mut c = Computer::new(vec![3, 9, 1, 9, 9, 9, 4, 9, 99, 0]);
+    assert!(matches!(
+        c.run_with([i64::MAX]),
+        Err(Error::ArithmeticOverflow { ptr: 2, bits: 64 })
+    ));
+    // Adjusts the relative base twice by the input.
+    let mut c = Computer::new(vec![3, 7, 109, i64::MAX, 9, 7, 99, 0]);
+    assert!(matches!(
+        c.run_with([1]),
+        Err(Error::ArithmeticOverflow { ptr: 4, bits: 64 })
+    ));
+}
+
+#[test]
+fn strict_rejects_devices() {
+    let mut c = Computer::new(vec![4, 100, 99]);
+    c.map_device(100..101, |_| 7, |_, _| {});
+    c.set_strict(true);
+    assert!(matches!(c.next(), Err(Error::Strict { .. })));
+    c.set_strict(false);
+    assert!(matches!(c.next(), Ok(State::Yielded(7))));
+}
+
+#[test]
+fn strict_rejects_overrides() {
+    let program = vec![4, 3, 99, 7];
+    let mut c = Computer::new(program.clone()).with_entry(2).unwrap();
+    c.set_strict(true);
+    assert!(matches!(
+        c.next(),
+        Err(Error::Strict {
+            feature: "entry addresses"
+        })
+    ));
+
+    let mut c = Computer::new(program.clone());
+    c.init_memory([(3, 8)]);
+    c.set_strict(true);
+    assert!(matches!(
+        c.next(),
+        Err(Error::Strict {
+            feature: "memory overrides"
+        })
+    ));
+
+    let mut c = Computer::new(program.clone());
+    c.set_relative_base(1);
+    c.set_strict(true);
+    assert!(matches!(c.next(), Err(Error::Strict { .. })));
+
+    let mut c = Computer::new(program).with_entry(0).unwrap();
+    c.set_strict(true);
+    assert!(matches!(c.next(), Ok(State::Yielded(7))));
+}
+
+#[test]
+fn stats_counters() {
+    // Writes the input far past the end of the program and outputs it.
+    let mut c = Computer::new(vec![3, 100, 4, 100, 99]);
+    assert!(matches!(c.next(), Ok(State::Waiting)));
+    assert_eq!(c.stats().instructions, 0);
+    c.feed([7]);
+    assert!(matches!(c.next(), Ok(State::Yielded(7))));
+    assert!(matches!(c.next(), Ok(State::Complete)));
+    assert_eq!(
+        c.stats(),
+        Stats {
+            instructions: 3,
+            cells: 6
+        }
+    );
+    c.reset_to(&Computer::new(vec![99]));
+    assert_eq!(
+        c.stats(),
+        Stats {
+            instructions: 0,
+            cells: 1
+        }
+    );
+}
+
+#[test]
+fn step_describes_instructions() {
+    // Reads a value, doubles it relative to the base, and outputs it.
+    let mut c = Computer::new(vec![109, 20, 203, 0, 22202, 0, 0, 1, 204, 1, 99]);
+    let info = c.step().unwrap();
+    assert_eq!(
+        info,
+        StepInfo {
+            ptr: 0,
+            opcode: 9,
+            modes: vec![1],
+            addrs: vec![1],
+            reads: vec![20],
+            write: None,
+            next_ptr: 2,
+            relative_base: 0,
+            next_relative_base: 20,
+            state: None,
+        }
+    );
+    let info = c.step().unwrap();
+    assert_eq!((info.next_ptr, info.state), (2, Some(State::Waiting)));
+    assert_eq!(c.stats().instructions, 1);
+    c.feed([21]);
+    assert_eq!(c.step().unwrap().write, Some((20, 21)));
+    let info = c.step().unwrap();
+    assert_eq!(info.modes, [2, 2, 2]);
+    assert_eq!(info.addrs, [20, 20, 21]);
+    assert_eq!(info.reads, [21, 21]);
+    assert_eq!(info.write, Some((21, 441)));
+    let info = c.step().unwrap();
+    assert_eq!(info.state, Some(State::Yielded(441)));
+    assert_eq!(c.step().unwrap().state, Some(State::Complete));
+}
+
+#[test]
+fn breakpoints_and_watchpoints() {
+    // Reads a value into address 9, outputs it, and halts.
+    let mut c = Computer::new(vec![3, 9, 4, 9, 99, 0, 0, 0, 0, 0]);
+    c.add_breakpoint(2);
+    c.add_watchpoint(9);
+    c.feed([5]);
+    let watch = Pause::Watchpoint {
+        ptr: 0,
+        addr: 9,
+        access: Access::Write,
+    };
+    assert_eq!(c.next().unwrap(), State::Paused { reason: watch });
+    assert_eq!(
+        c.next().unwrap(),
+        State::Paused {
+            reason: Pause::Breakpoint { ptr: 2 }
+        }
+    );
+    // The watchpoint on the same instruction doesn't pause it again.
+    assert_eq!(c.next().unwrap(), State::Yielded(5));
+    assert!(c.remove_breakpoint(2));
+    assert!(!c.remove_breakpoint(2));
+    assert_eq!(c.next().unwrap(), State::Complete);
+}
+
+#[test]
+fn poison_uninit_reads() {
+    // Writes to address 20 and outputs it, then outputs address 21.
+    let program = vec![1101, 2, 3, 20, 4, 20, 4, 21, 99];
+    let mut c = Computer::new(program.clone());
+    assert_eq!(c.run_with([]).unwrap(), [5, 0]);
+    assert_eq!(c.uninit_read(), None);
+
+    let mut c = Computer::new(program);
+    c.set_poison(Some(-999)).unwrap();
+    assert_eq!(c.run_with([]).unwrap(), [5, -999]);
+    assert_eq!(c.uninit_read(), Some(UninitRead { ptr: 6, addr: 21 }));
+
+    // Copies address 9 into the parameter of the output instruction, so a
+    // poisoned pointer is an error instead of a panic.
+    let mut c = Computer::new(vec![1001, 9, 0, 5, 4, 0, 99]);
+    c.set_poison(Some(-1)).unwrap();
+    assert!(matches!(
+        c.run_with([]),
+        Err(Error::InvalidAddress { ptr: 4, .. })
+    ));
+    assert_eq!(c.uninit_read(), Some(UninitRead { ptr: 0, addr: 9 }));
+
+    let mut c = Computer::new(vec![99]);
+    c.set_strict(true);
+    assert!(matches!(
+        c.set_poison(Some(-1)),
+        Err(Error::Strict {
+            feature: "poison values"
+        })
+    ));
+    c.set_strict(false);
+    c.set_poison(Some(-1)).unwrap();
+    c.set_strict(true);
+    assert!(matches!(c.next(), Err(Error::Strict { .. })));
+}
+
+#[test]
+fn entry_offset() {
+    let program = vec![104, 1, 104, 2, 99];
+    let mut c = Computer::new(program.clone()).with_entry(2).unwrap();
+    assert_eq!(c.ptr(), 2);
+    assert_eq!(c.run_with([]).unwrap(), [2]);
+    assert!(matches!(
+        Computer::new(program).with_entry(5),
+        Err(Error::InvalidEntry { ptr: 5, len: 5 })
+    ));
+}
+
+#[test]
+fn mem_init_overrides() {
+    let values = mem_init::parse("5=42\n\n 6 = -1\n").unwrap();
+    assert_eq!(values, [(5, 42), (6, -1)]);
+    let mut c = Computer::new(vec![4, 5, 4, 6, 99, 0]);
+    c.init_memory(values);
+    assert_eq!(c.run_with([]).unwrap(), [42, -1]);
+
+    assert!(matches!(
+        mem_init::parse("1=2\n3\n"),
+        Err(Error::MemInit { line: 2, .. })
+    ));
+    assert!(matches!(
+        mem_init::parse("x=2"),
+        Err(Error::MemInit { line: 1, .. })
+    ));
+}
+
+#[test]
+fn lockstep_compare() {
+    // Outputs the input plus one, and a rewrite that uses a scratch cell.
+    let a = [3, 9, 1001, 9, 1, 9, 4, 9, 99, 0];
+    let b = [3, 11, 1001, 11, 1, 12, 4, 12, 99, 0, 0, 0, 0];
+    let compare = |a: &[i64], b: &[i64], level| {
+        lockstep::compare(
+            Computer::new(a.to_vec()),
+            Computer::new(b.to_vec()),
+            &[41],
+            level,
+            100,
+        )
+        .unwrap()
+    };
+    assert_eq!(
+        compare(&a, &a, Level::Instructions),
+        Outcome::Same { steps: 4 }
+    );
+    assert_eq!(compare(&a, &b, Level::Outputs), Outcome::Same { steps: 2 });
+    assert_eq!(
+        compare(&a, &b, Level::Instructions),
+        Outcome::Diverged(Divergence {
+            step: 0,
+            left: Side {
+                ptr: 0,
+                effect: Effect::Write { addr: 9, value: 41 }
+            },
+            right: Side {
+                ptr: 0,
+                effect: Effect::Write {
+                    addr: 11,
+                    value: 41
+                }
+            },
+        })
+    );
+    // Loops forever without any output.
+    let spin = [1105, 1, 0];
+    assert_eq!(
+        compare(&spin, &spin, Level::Outputs),
+        Outcome::Undecided { steps: 0 }
+    );
+    let mut c = a;
+    c[4] = 2;
+    assert_eq!(
+        compare(&a, &c, Level::Outputs),
+        Outcome::Diverged(Divergence {
+            step: 0,
+            left: Side {
+                ptr: 6,
+                effect: Effect::Output(42)
+            },
+            right: Side {
+                ptr: 6,
+                effect: Effect::Output(43)
+            },
+        })
+    );
+}
+
+#[test]
+fn source_map() {
+    let asm = "start: IN x\n  OUT x\n  HLT\nx: DB 0\n";
+    let Intcode { source_map, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(source_map, None);
+
+    let opts = intcode_assemble::Options::new().source_map(true);
+    let Intcode { source_map, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap();
+    let map = source_map.unwrap();
+    let text: Vec<_> = map
+        .mappings
+        .iter()
+        .map(|Mapping { addrs, span }| (addrs.clone(), span.as_str(asm)))
+        .collect();
+    assert_eq!(
+        text,
+        [
+            (0..2, "IN x"),
+            (2..4, "OUT x"),
+            (4..5, "HLT"),
+            (5..6, "DB 0")
+        ]
+    );
+    assert_eq!(map.span(3).map(|s| s.as_str(asm)), Some("OUT x"));
+    assert_eq!(map.span(6), None);
+    assert_eq!(map.addrs(asm.find("HLT").unwrap()), Some(4..5));
+    assert_eq!(map.addrs(0), None);
+}
+
+#[test]
+fn render_outputs() {
+    let program = vec![104, 72, 104, 10, 104, -255, 104, 1000, 99];
+    let render = |r: &mut dyn render::Render| {
+        let mut w = Vec::new();
+        Computer::new(program.clone())
+            .render_with([], r, &mut w)
+            .unwrap();
+        String::from_utf8(w).unwrap()
+    };
+    assert_eq!(render(&mut render::Decimal), "72\n10\n-255\n1000\n");
+    assert_eq!(render(&mut render::Hex), "0x48\n0xa\n-0xff\n0x3e8\n");
+    assert_eq!(render(&mut render::Ascii), "H\n-255\n1000\n");
+    let mut tiles = render::from_fn(|v| match v {
+        10 => "\n".to_owned(),
+        v if v > 0 => "#".to_owned(),
+        _ => ".".to_owned(),
+    });
+    assert_eq!(render(&mut tiles), "#\n.#");
+}
+
+#[test]
+fn constants_directive() {
+    let asm = "
+EQU width, 25
+EQU below, -1
+    OUT #width
+    OUT #width-1
+    OUT table+1
+    HLT
+table: DB width, below
+";
+    let Intcode { output, debug, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(output, [104, 25, 104, 24, 4, 8, 99, 25, -1]);
+    assert_eq!(debug.labels, [("table".to_owned(), 7)]);
+    let mut c = Computer::new(output);
+    assert_eq!(c.run_with([]).unwrap(), [25, 24, -1]);
+}
+
+#[test]
+fn include_files() {
+    let dir = std::env::temp_dir().join("intcode-include");
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(
+        dir.join("lib/print.ints"),
+        "INCLUDE \"value.ints\"\nprint: OUT value\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("lib/value.ints"), "EQU answer, 42\n").unwrap();
+    let asm = r#"
+    JZ #0, #main
+INCLUDE "lib/print.ints"
+    HLT
+main:
+    ADD #answer, #0, value
+    JZ #0, #print
+value: DB 0
+"#;
+    let mut files = Files::new("main.ints", asm);
+    let opts = intcode_assemble::Options::new().include_dir(&dir);
+    let Intcode {
+        output,
+        debug,
+        includes,
+        ..
+    } = intcode_assemble::to_intcode_files(&mut files, &opts).unwrap();
+    assert_eq!(
+        output,
+        [1106, 0, 6, 4, 13, 99, 1101, 42, 0, 13, 1106, 0, 3, 0]
+    );
+    assert_eq!(
+        debug.lines,
+        [(0, 2), (3, 3), (5, 4), (6, 6), (10, 7), (13, 8)]
+    );
+    assert_eq!(
+        includes,
+        [dir.join("lib/print.ints"), dir.join("lib/value.ints")]
+    );
+    assert_eq!(files.len(), 3);
+    let mut c = Computer::new(output);
+    assert_eq!(c.run_with([]).unwrap(), [42]);
+}
+
+#[test]
+fn include_files_once() {
+    let dir = std::env::temp_dir().join("intcode-include-once");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.ints"), "INCLUDE \"value.ints\"\nOUT #answer\n").unwrap();
+    std::fs::write(dir.join("b.ints"), "INCLUDE \"value.ints\"\nOUT #answer\n").unwrap();
+    std::fs::write(dir.join("value.ints"), "EQU answer, 42\n").unwrap();
+    let asm = "INCLUDE \"a.ints\"\nINCLUDE \"b.ints\"\nINCLUDE \"a.ints\"\nHLT\n";
+    let mut files = Files::new("main.ints", asm);
+    let opts = intcode_assemble::Options::new().include_dir(&dir);
+    let Intcode { output, .. } = intcode_assemble::to_intcode_files(&mut files, &opts).unwrap();
+    assert_eq!(files.len(), 4);
+    let mut c = Computer::new(output);
+    assert_eq!(c.run_with([]).unwrap(), [42, 42]);
+}
+
+#[test]
+fn output_strings() {
+    let asm = r#"
+    OUTS "hi"
+    OUTS msg
+    OUTS msg+1
+    HLT
+msg: DB "ok\n"
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(
+        output,
+        [104, 104, 104, 105, 4, 15, 4, 16, 4, 17, 4, 16, 4, 17, 99, 111, 107, 10]
+    );
+    let mut c = Computer::new(output);
+    let out = c.run_with([]).unwrap();
+    assert_eq!(
+        String::from_utf8(out.iter().map(|&v| v as u8).collect()).unwrap(),
+        "hiok\nk\n"
+    );
+}
+
+#[test]
+fn output_strings_mutable() {
+    let asm = r#"
+    OUTS code+1
+    HLT
+code: MUT 104, 7
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    assert_eq!(output, [4, 4, 99, 104, 7]);
+    let mut c = Computer::new(output);
+    assert_eq!(c.run_with([]).unwrap(), [7]);
+}
+
+#[test]
+fn output_strings_terminated() {
+    let asm = r#"
+    OUTS z
+    OUTS l+1
+    HLT
+z:  DBZ "a"
+l:  DBL "bc"
+"#;
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let mut c = Computer::new(output);
+    assert_eq!(c.run_with([]).unwrap(), [97, 0, 98, 99]);
+}
+
+#[test]
+fn symbols_table() {
+    use intcode_assemble::symbols::{symbols, Symbol};
+
+    let asm = "EQU ten, 10\nloop: OUT ten\n    JZ #0, #loop\n    JZ #0, #end\n";
+    let opts = intcode_assemble::Options::new();
+    assert_eq!(
+        symbols(asm, &opts).unwrap(),
+        [
+            Symbol {
+                name: "ten".into(),
+                defs: vec![(4..7).into()],
+                refs: vec![(22..25).into()],
+            },
+            Symbol {
+                name: "loop".into(),
+                defs: vec![(12..16).into()],
+                refs: vec![(38..42).into()],
+            },
+            Symbol {
+                name: "end".into(),
+                defs: vec![],
+                refs: vec![(55..58).into()],
+            },
+        ]
+    );
+}
+
+#[test]
+fn custom_directive() {
+    let squares = Directive::new("SQUARES", &[Kind::Number], |params| match params {
+        [Value::Number(n)] => Ok((0..*n).map(|i| i * i).collect()),
+        _ => unreachable!(),
+    });
+    let opts = intcode_assemble::Options::new().directive(squares);
+    let asm = "OUT table+3\nHLT\ntable: SQUARES 4\n";
+    let Intcode { output, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap();
+    assert_eq!(output, [4, 6, 99, 0, 1, 4, 9]);
+    assert_eq!(Computer::new(output).run_with([]).unwrap(), [9]);
+}
@@ -1,4 +1,4 @@
-use intcode_assemble::Intcode;
+use intcode_assemble::{Intcode, Options, WarningLevel};
 use intcode_error::fmt;
 
 #[track_caller]
@@ -23,3 +23,174 @@ fn assemble_unused_label() {
 ";
     assert_eq!(assemble(asm), expected);
 }
+
+#[test]
+fn assemble_label_offset_outside_data() {
+    let asm = r#"OUT msg+4
+HLT
+msg: DB "hi", 0"#;
+    let expected = r#"
+  --> <input>:1:5
+   |
+ 1 | OUT msg+4
+   |     ^^^ label offset is outside of the data
+
+  --> <input>:3:1
+   |
+ 3 | msg: DB "hi", 0
+   | ^^^ `msg` is defined here
+"#;
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_label_offset_end_of_data() {
+    let asm = r#"OUT msg
+OUT msg+3
+HLT
+msg: DB "hi", 0"#;
+    assert_eq!(assemble(asm), "");
+}
+
+#[test]
+fn assemble_label_offset_into_other_data() {
+    let asm = "OUT a+3
+OUT b
+HLT
+a: DB 1, 2
+b: DB 3, 4";
+    let expected = "
+  --> <input>:1:5
+   |
+ 1 | OUT a+3
+   |     ^ label offset points into the data of `b`
+
+  --> <input>:4:1
+   |
+ 4 | a: DB 1, 2
+   | ^ `a` is defined here
+
+  --> <input>:5:1
+   |
+ 5 | b: DB 3, 4
+   | ^ `b` is defined here
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_data_after_code() {
+    let asm = r#"IN x
+OUT x
+x: DB 0
+HLT
+y: DB 0
+IN y
+JZ y, #z
+z: DB 0"#;
+    let expected = "
+  --> <input>:3:4
+   |
+ 3 | x: DB 0
+   |    ^^^^ execution falls into this data, add a `HLT` before it
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_unreachable_code() {
+    let asm = "JZ #0, #end
+OUT #1
+OUT #2
+end: HLT
+OUT #3";
+    let expected = "
+  --> <input>:2:1
+   |
+ 2 | OUT #1
+   | ^^^^^^ unreachable code
+
+
+  --> <input>:5:1
+   |
+ 5 | OUT #3
+   | ^^^^^^ unreachable code
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_unused_data() {
+    let asm = "OUT x
+HLT
+x: DB 1
+DB 2";
+    let expected = "
+  --> <input>:4:1
+   |
+ 4 | DB 2
+   | ^^^^ data is never used
+";
+    assert_eq!(assemble(asm), expected);
+}
+
+#[test]
+fn assemble_immediate_write() {
+    let asm = "ADD 1, 2, #3
+IN #4
+HLT";
+    let set = intcode_assemble::to_intcode(asm).unwrap_err();
+    let errors: Vec<_> = set.errors.iter().map(|e| (&*e.msg, e.span.m)).collect();
+    assert_eq!(
+        errors,
+        [
+            ("parameter is written to but is in immediate mode", 10),
+            ("parameter is written to but is in immediate mode", 16)
+        ]
+    );
+
+    let opts = Options::new().lint("immediate-write", WarningLevel::Warn);
+    let Intcode { warnings, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap();
+    let fmt = fmt::Plain::new(asm);
+    let warnings: Vec<_> = warnings.iter().map(|w| fmt.warning(w)).collect();
+    let expected = "
+  --> <input>:1:11
+   |
+ 1 | ADD 1, 2, #3
+   |           ^^ parameter is written to but is in immediate mode
+
+
+  --> <input>:2:4
+   |
+ 2 | IN #4
+   |    ^^ parameter is written to but is in immediate mode
+";
+    assert_eq!(warnings.join("\n"), expected);
+}
+
+#[test]
+fn assemble_warning_levels() {
+    let asm = "x: IN #4
+HLT";
+    let set = intcode_assemble::to_intcode(asm).unwrap_err();
+    let errors: Vec<_> = set.errors.iter().map(|e| &*e.msg).collect();
+    assert_eq!(errors, ["parameter is written to but is in immediate mode"]);
+    let lints: Vec<_> = set.warnings.iter().map(|w| w.lint).collect();
+    assert_eq!(lints, [Some("unused-label")]);
+
+    let opts = Options::new()
+        .lint("unused-label", WarningLevel::Allow)
+        .lint("immediate-write", WarningLevel::Warn);
+    let Intcode { warnings, .. } = intcode_assemble::to_intcode_with(asm, &opts).unwrap();
+    let lints: Vec<_> = warnings.iter().map(|w| w.lint).collect();
+    assert_eq!(lints, [Some("immediate-write")]);
+
+    let opts = Options::new()
+        .warning_level(WarningLevel::Deny)
+        .lint("immediate-write", WarningLevel::Warn);
+    let set = intcode_assemble::to_intcode_with(asm, &opts).unwrap_err();
+    let errors: Vec<_> = set.errors.iter().map(|e| &*e.msg).collect();
+    assert_eq!(errors, ["label is never used"]);
+    let lints: Vec<_> = set.warnings.iter().map(|w| w.lint).collect();
+    assert_eq!(lints, [Some("immediate-write")]);
+}
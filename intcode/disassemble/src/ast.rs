@@ -1,6 +1,8 @@
 //! Abstract representation of assembly code.
 
+use std::ops::Range;
 use std::rc::Rc;
+use std::slice;
 
 /// A label specified in a parameter.
 #[derive(Debug, Clone, PartialEq)]
@@ -70,21 +72,65 @@ pub enum Instr {
 
     /// (Pseudo) Places raw data in the program.
     Data(Vec<RawParam>),
+    /// (Pseudo) Places a string followed by a zero in the program.
+    DataZeroTerminated(String),
+    /// (Pseudo) Places the length of a string followed by the string in the
+    /// program.
+    DataLengthPrefixed(String),
     /// (Pseudo) Represents a mutable instruction.
     Mutable(i64, Vec<i64>),
+
+    /// (Directive) The relative base the program expects to start with.
+    RelativeBase(i64),
 }
 
 /// A single line in a program.
 ///
-/// This is simply just an instruction together with an optional label.
+/// This is simply just an instruction together with an optional label and
+/// any comments about it.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Stmt {
     pub label: Option<Label>,
     pub instr: Instr,
+    pub comments: Vec<String>,
+    /// The address in the original program of the first value.
+    pub addr: usize,
+    /// The number of values in the original program the statement covers.
+    ///
+    /// A folded `RBINIT` directive still covers the `ARB` it replaced.
+    pub len: usize,
 }
 
 /// An entire program.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Ast {
     pub stmts: Vec<Stmt>,
+    /// The indexes of the statements that start a new section.
+    pub sections: Vec<usize>,
+    /// Comment lines to add after all the statements.
+    pub appendix: Vec<String>,
+}
+
+impl Stmt {
+    /// Returns the addresses in the original program the statement covers.
+    pub fn addrs(&self) -> Range<usize> {
+        self.addr..self.addr + self.len
+    }
+}
+
+impl Ast {
+    /// Returns an iterator over the statements in address order.
+    pub fn iter(&self) -> slice::Iter<'_, Stmt> {
+        self.stmts.iter()
+    }
+
+    /// Returns the statement that covers the address.
+    pub fn stmt_at(&self, addr: usize) -> Option<&Stmt> {
+        let i = self
+            .stmts
+            .partition_point(|stmt| stmt.addr + stmt.len <= addr);
+        self.stmts
+            .get(i)
+            .filter(|stmt| stmt.addrs().contains(&addr))
+    }
 }
@@ -0,0 +1,82 @@
+//! Limit how much work the static analysis passes can do.
+//!
+//! The passes are cheap for ordinary programs but an adversarial program can
+//! make the fixpoint iterations take a very long time. Once the budget is
+//! used up each pass stops early and the report says so.
+
+/// The most work the static analysis passes may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    visits: usize,
+    iterations: usize,
+}
+
+/// Tracks the work done against a budget.
+#[derive(Debug)]
+pub struct Meter {
+    budget: Budget,
+    visits: usize,
+    exhausted: bool,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            visits: 10_000_000,
+            iterations: 1_000,
+        }
+    }
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most addresses the passes may visit in total.
+    pub fn max_visits(mut self, visits: usize) -> Self {
+        self.visits = visits;
+        self
+    }
+
+    /// The most iterations a single fixpoint computation may take.
+    pub fn max_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+}
+
+impl Meter {
+    pub fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            visits: 0,
+            exhausted: false,
+        }
+    }
+
+    /// Records a visit to an address, returning `false` if the budget has
+    /// been used up.
+    pub fn visit(&mut self) -> bool {
+        if self.visits >= self.budget.visits {
+            self.exhausted = true;
+            return false;
+        }
+        self.visits += 1;
+        true
+    }
+
+    /// Returns whether another iteration is allowed after `n` iterations.
+    pub fn iterate(&mut self, n: usize) -> bool {
+        if n >= self.budget.iterations {
+            self.exhausted = true;
+            return false;
+        }
+        true
+    }
+
+    /// Returns whether any pass stopped early.
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+}
@@ -0,0 +1,219 @@
+//! Build a control flow graph of the marked instructions and find the loops
+//! in it.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::budget::Meter;
+use crate::program::{Mark, Opcode, Program, Purpose};
+use crate::report::Loop;
+
+/// The control flow graph, keyed by the address of each instruction.
+#[derive(Debug, Default)]
+pub struct Cfg {
+    succs: BTreeMap<usize, BTreeSet<usize>>,
+    preds: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl Program {
+    /// Returns the number of values the instruction at the address takes up.
+    fn instr_len(&self, addr: usize) -> usize {
+        1 + self.slots[addr + 1..]
+            .iter()
+            .take_while(|slot| matches!(slot.mark, Some(Mark::Param(_))))
+            .count()
+    }
+}
+
+impl Cfg {
+    pub fn new(p: &Program) -> Self {
+        let mut cfg = Self::default();
+        let is_instr = |addr: usize| matches!(p.slots.get(addr), Some(s) if matches!(s.mark, Some(Mark::Opcode(_))));
+        for addr in (0..p.len()).filter(|&a| is_instr(a)) {
+            cfg.succs.entry(addr).or_default();
+            cfg.preds.entry(addr).or_default();
+            let next = addr + p.instr_len(addr);
+            if !p.is_terminal(addr) && is_instr(next) {
+                cfg.insert(addr, next);
+            }
+            // Jumps to a constant address.
+            if let Some(Mark::Opcode(Opcode::JumpNonZero | Opcode::JumpZero)) = p.slots[addr].mark {
+                if p.slots[addr].raw / 1000 % 10 == 1 {
+                    let target = p.slots[addr + 2].raw;
+                    if target >= 0 && is_instr(target as usize) {
+                        cfg.insert(addr, target as usize);
+                    }
+                }
+            }
+        }
+        // Jumps that were seen while running the program.
+        for (target, slot) in p.slots.iter().enumerate().filter(|(a, _)| is_instr(*a)) {
+            for m in slot.mentions.iter().filter(|m| m.purpose == Purpose::Jump) {
+                if let Some(from) = m.referrer.checked_sub(2).filter(|&a| is_instr(a)) {
+                    cfg.insert(from, target);
+                }
+            }
+        }
+        cfg
+    }
+
+    fn insert(&mut self, from: usize, to: usize) {
+        self.succs.entry(from).or_default().insert(to);
+        self.preds.entry(to).or_default().insert(from);
+    }
+
+    /// Returns the immediate dominator of each instruction reachable from the
+    /// start of the program, or `None` if the budget ran out first.
+    ///
+    /// This is the algorithm by Cooper, Harvey, and Kennedy, which only keeps
+    /// one dominator for each instruction instead of the whole set. Each step
+    /// up the dominator tree counts as a visit.
+    fn dominators(&self, meter: &mut Meter) -> Option<BTreeMap<usize, usize>> {
+        let mut idoms = BTreeMap::new();
+        let entry = match self.succs.keys().next() {
+            Some(&entry) => entry,
+            None => return Some(idoms),
+        };
+
+        // Number the instructions reachable from the entry in postorder.
+        let mut seen = BTreeSet::new();
+        let mut order = BTreeMap::new();
+        let mut postorder = Vec::new();
+        let mut stack = vec![(entry, false)];
+        while let Some((addr, done)) = stack.pop() {
+            if !meter.visit() {
+                return None;
+            }
+            if done {
+                order.insert(addr, postorder.len());
+                postorder.push(addr);
+            } else if seen.insert(addr) {
+                stack.push((addr, true));
+                stack.extend(
+                    self.succs[&addr]
+                        .iter()
+                        .filter(|s| !seen.contains(s))
+                        .map(|&s| (s, false)),
+                );
+            }
+        }
+
+        idoms.insert(entry, entry);
+        let mut changed = true;
+        let mut iterations = 0;
+        while changed {
+            if !meter.iterate(iterations) {
+                return None;
+            }
+            iterations += 1;
+            changed = false;
+            for &addr in postorder.iter().rev().filter(|&&a| a != entry) {
+                let mut new = None;
+                for &pred in self.preds[&addr].iter().filter(|p| idoms.contains_key(p)) {
+                    new = match new {
+                        None => Some(pred),
+                        Some(mut other) => {
+                            // Walk up from both until they meet.
+                            let mut pred = pred;
+                            while pred != other {
+                                if !meter.visit() {
+                                    return None;
+                                }
+                                if order[&pred] < order[&other] {
+                                    pred = idoms[&pred];
+                                } else {
+                                    other = idoms[&other];
+                                }
+                            }
+                            Some(pred)
+                        }
+                    };
+                }
+                if let Some(new) = new {
+                    if idoms.insert(addr, new) != Some(new) {
+                        changed = true;
+                    }
+                }
+            }
+        }
+        Some(idoms)
+    }
+
+    /// Returns whether `a` dominates `b`, or `None` if the budget ran out
+    /// first.
+    fn dominates(
+        idoms: &BTreeMap<usize, usize>,
+        a: usize,
+        mut b: usize,
+        meter: &mut Meter,
+    ) -> Option<bool> {
+        loop {
+            if !meter.visit() {
+                return None;
+            }
+            if a == b {
+                return Some(true);
+            }
+            match idoms[&b] {
+                idom if idom == b => return Some(false),
+                idom => b = idom,
+            }
+        }
+    }
+
+    /// Finds the natural loops in the graph.
+    ///
+    /// If the budget runs out then no loops are returned, because a partial
+    /// search could report loops that don't exist.
+    pub fn loops(&self, meter: &mut Meter) -> Vec<Loop> {
+        let idoms = match self.dominators(meter) {
+            Some(idoms) => idoms,
+            None => return Vec::new(),
+        };
+        let mut loops = BTreeMap::<usize, Loop>::new();
+        for &from in idoms.keys() {
+            for &header in &self.succs[&from] {
+                match Self::dominates(&idoms, header, from, meter) {
+                    Some(true) => {}
+                    Some(false) => continue,
+                    None => return Vec::new(),
+                }
+                let l = loops.entry(header).or_insert_with(|| Loop {
+                    header,
+                    back_edges: Vec::new(),
+                    body: vec![header],
+                    depth: 0,
+                });
+                l.back_edges.push(from);
+                // Everything that reaches the back edge without passing
+                // through the header is in the loop.
+                let mut stack = vec![from];
+                while let Some(addr) = stack.pop() {
+                    if !meter.visit() {
+                        return Vec::new();
+                    }
+                    if !l.body.contains(&addr) {
+                        l.body.push(addr);
+                        stack.extend(&self.preds[&addr]);
+                    }
+                }
+            }
+        }
+        let mut loops: Vec<_> = loops.into_iter().map(|(_, l)| l).collect();
+        for l in &mut loops {
+            l.body.sort_unstable();
+        }
+        let depths: Vec<_> = loops
+            .iter()
+            .map(|l| {
+                loops
+                    .iter()
+                    .filter(|other| other.body.contains(&l.header))
+                    .count()
+            })
+            .collect();
+        for (l, depth) in loops.iter_mut().zip(depths) {
+            l.depth = depth;
+        }
+        loops
+    }
+}
@@ -1,7 +1,9 @@
-use std::cmp::max;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
 use std::iter;
 use std::result;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use thiserror::Error;
 
@@ -10,6 +12,12 @@ use crate::program::{Mention, Opcode, Program, Purpose};
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// How many instructions to execute between each progress report.
+const PROGRESS_INTERVAL: u64 = 100_000;
+
+/// How many instructions to execute between each check of the time budget.
+const CLOCK_INTERVAL: u64 = 1_024;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("unknown mode `{}`", .mode)]
@@ -27,15 +35,29 @@ enum State {
     Yielded(i64),
     Waiting,
     Complete,
+    /// The run used up its step or time budget.
+    Stopped,
 }
 
 #[derive(Debug)]
 struct Computer<'a> {
     prog: &'a mut Program,
     mem: Vec<i64>,
+    /// Memory past the end of the program, stored sparsely because the
+    /// program can use the relative base to write to any address.
+    heap: BTreeMap<usize, i64>,
     ptr: usize,
     relative_base: i64,
     input: VecDeque<i64>,
+    /// The addresses that hold a value that depends on the input.
+    taint: BTreeSet<usize>,
+    /// The index of the current run.
+    run: usize,
+    /// The number of instructions executed in the current run.
+    steps: u64,
+    max_steps: Option<u64>,
+    deadline: Option<Instant>,
+    progress: Option<&'a ProgressFn>,
 }
 
 #[derive(Debug)]
@@ -49,36 +71,93 @@ pub enum Input {
 #[derive(Debug, Default)]
 pub struct Run {
     input: Option<Input>,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+/// How far the dynamic marking has got.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// The index of the current run.
+    pub run: usize,
+    /// The number of instructions executed in the current run.
+    pub steps: u64,
+    /// The percentage of the program marked so far.
+    pub percent_marked: f64,
 }
 
+type Callback = dyn Fn(Progress) + Send + Sync;
+
+/// A callback that is regularly passed the progress of the dynamic marking.
+#[derive(Clone)]
+pub struct ProgressFn(Arc<Callback>);
+
 fn cast(num: i64) -> Result<usize> {
     usize::try_from(num).map_err(|_| Error::BadConversion { num })
 }
 
 impl<'a> Computer<'a> {
-    fn new(prog: &'a mut Program) -> Self {
+    fn new(prog: &'a mut Program, progress: Option<&'a ProgressFn>) -> Self {
         let mem = prog.original();
         Self {
             prog,
             mem,
+            heap: BTreeMap::new(),
             ptr: 0,
             relative_base: 0,
             input: VecDeque::new(),
+            taint: BTreeSet::new(),
+            run: 0,
+            steps: 0,
+            max_steps: None,
+            deadline: None,
+            progress,
         }
     }
 
+    fn report_progress(&self) {
+        if let Some(ProgressFn(f)) = self.progress {
+            f(Progress {
+                run: self.run,
+                steps: self.steps,
+                percent_marked: self.prog.percent_marked(),
+            });
+        }
+    }
+
+    /// Counts an executed instruction, returning `false` if the run has used
+    /// up its budget.
+    fn step(&mut self) -> bool {
+        if self.max_steps.map_or(false, |max| self.steps >= max) {
+            return false;
+        }
+        if self.steps % CLOCK_INTERVAL == 0 && self.deadline.map_or(false, |d| Instant::now() >= d)
+        {
+            return false;
+        }
+        self.steps += 1;
+        if self.steps % PROGRESS_INTERVAL == 0 {
+            self.report_progress();
+        }
+        true
+    }
+
     fn feed(&mut self, iter: impl IntoIterator<Item = i64>) {
         self.input.extend(iter)
     }
 
     fn mem_get(&self, addr: usize) -> i64 {
-        self.mem.get(addr).copied().unwrap_or(0)
+        match self.mem.get(addr) {
+            Some(value) => *value,
+            None => self.heap.get(&addr).copied().unwrap_or(0),
+        }
     }
 
     fn mem_get_mut(&mut self, addr: usize) -> &mut i64 {
-        let new_len = max(self.mem.len(), addr + 1);
-        self.mem.resize(new_len, 0);
-        self.mem.get_mut(addr).unwrap()
+        match self.mem.get_mut(addr) {
+            Some(value) => value,
+            None => self.heap.entry(addr).or_insert(0),
+        }
     }
 
     fn param_ptr(&mut self, i: usize, purpose: Purpose) -> Result<usize> {
@@ -87,7 +166,10 @@ impl<'a> Computer<'a> {
         let divs = [10, 100, 1_000, 10_000];
         let mode = opcode / divs[i] % 10;
         let mode = Mode::from_value(mode).ok_or(Error::UnknownMode { mode })?;
-        self.prog.mark_param(ptr, mode);
+        // Code copied past the end of the program is run but not marked.
+        if ptr < self.prog.len() {
+            self.prog.mark_param(ptr, mode);
+        }
         match mode {
             Mode::Positional => {
                 let addr = cast(self.mem_get(ptr))?;
@@ -109,14 +191,32 @@ impl<'a> Computer<'a> {
         }
     }
 
+    fn is_tainted(&self, addr: usize) -> bool {
+        self.taint.contains(&addr)
+    }
+
     fn param(&mut self, i: usize) -> Result<i64> {
         self.param_ptr(i, Purpose::Read)
             .map(move |addr| self.mem_get(addr))
     }
 
-    fn param_mut(&mut self, i: usize) -> Result<&mut i64> {
-        self.param_ptr(i, Purpose::Write)
-            .map(move |addr| self.mem_get_mut(addr))
+    /// Reads the parameter and whether it depends on the input.
+    fn param_tainted(&mut self, i: usize) -> Result<(i64, bool)> {
+        self.param_ptr(i, Purpose::Read)
+            .map(move |addr| (self.mem_get(addr), self.is_tainted(addr)))
+    }
+
+    /// Writes the parameter and records whether it depends on the input.
+    fn set_param(&mut self, i: usize, value: i64, tainted: bool) -> Result<()> {
+        let addr = self.param_ptr(i, Purpose::Write)?;
+        self.prog.mark_modified(addr);
+        *self.mem_get_mut(addr) = value;
+        if tainted {
+            self.taint.insert(addr);
+        } else {
+            self.taint.remove(&addr);
+        }
+        Ok(())
     }
 
     fn jump_param(&mut self, i: usize) -> Result<usize> {
@@ -128,35 +228,51 @@ impl<'a> Computer<'a> {
 
     fn next(&mut self) -> Result<State> {
         loop {
+            if !self.step() {
+                break Ok(State::Stopped);
+            }
             let opcode = self.mem_get(self.ptr) % 100;
             let opcode = Opcode::from_value(opcode).ok_or(Error::UnknownOpcode { opcode })?;
-            self.prog.mark_opcode(self.ptr, opcode);
+            if self.ptr < self.prog.len() {
+                self.prog.mark_opcode(self.ptr, opcode);
+            }
             match opcode {
                 Opcode::Add => {
-                    *self.param_mut(3)? = self.param(1)? + self.param(2)?;
+                    let (a, ta) = self.param_tainted(1)?;
+                    let (b, tb) = self.param_tainted(2)?;
+                    self.set_param(3, a + b, ta || tb)?;
                     self.ptr += 4;
                 }
                 Opcode::Multiply => {
-                    *self.param_mut(3)? = self.param(1)? * self.param(2)?;
+                    let (a, ta) = self.param_tainted(1)?;
+                    let (b, tb) = self.param_tainted(2)?;
+                    self.set_param(3, a * b, ta || tb)?;
                     self.ptr += 4;
                 }
                 Opcode::Input => {
                     if let Some(input) = self.input.pop_front() {
-                        *self.param_mut(1)? = input;
+                        self.set_param(1, input, true)?;
                         self.ptr += 2;
                     } else {
                         break Ok(State::Waiting);
                     }
                 }
                 Opcode::Output => {
-                    let output = self.param(1)?;
+                    let (output, tainted) = self.param_tainted(1)?;
+                    if tainted {
+                        self.prog.tainted_outputs.insert(self.ptr);
+                    }
                     self.ptr += 2;
                     break Ok(State::Yielded(output));
                 }
                 Opcode::JumpNonZero => {
                     // Make sure to read this parameter so it gets marked.
                     let addr = self.jump_param(2)?;
-                    if self.param(1)? != 0 {
+                    let (cond, tainted) = self.param_tainted(1)?;
+                    if tainted {
+                        self.prog.tainted_jumps.insert(self.ptr);
+                    }
+                    if cond != 0 {
                         self.ptr = addr;
                     } else {
                         self.ptr += 3;
@@ -165,18 +281,26 @@ impl<'a> Computer<'a> {
                 Opcode::JumpZero => {
                     // Make sure to read this parameter so it gets marked.
                     let addr = self.jump_param(2)?;
-                    if self.param(1)? == 0 {
+                    let (cond, tainted) = self.param_tainted(1)?;
+                    if tainted {
+                        self.prog.tainted_jumps.insert(self.ptr);
+                    }
+                    if cond == 0 {
                         self.ptr = addr;
                     } else {
                         self.ptr += 3;
                     }
                 }
                 Opcode::LessThan => {
-                    *self.param_mut(3)? = (self.param(1)? < self.param(2)?) as i64;
+                    let (a, ta) = self.param_tainted(1)?;
+                    let (b, tb) = self.param_tainted(2)?;
+                    self.set_param(3, (a < b) as i64, ta || tb)?;
                     self.ptr += 4;
                 }
                 Opcode::Equal => {
-                    *self.param_mut(3)? = (self.param(1)? == self.param(2)?) as i64;
+                    let (a, ta) = self.param_tainted(1)?;
+                    let (b, tb) = self.param_tainted(2)?;
+                    self.set_param(3, (a == b) as i64, ta || tb)?;
                     self.ptr += 4;
                 }
                 Opcode::AdjustRelativeBase => {
@@ -196,9 +320,13 @@ impl<'a> Computer<'a> {
 
     fn reset(&mut self) {
         self.mem = self.prog.original();
+        self.heap = BTreeMap::new();
         self.ptr = 0;
         self.relative_base = 0;
         self.input = VecDeque::new();
+        self.taint = BTreeSet::new();
+        self.run += 1;
+        self.steps = 0;
     }
 }
 
@@ -212,6 +340,18 @@ impl Run {
         self
     }
 
+    /// Stop the run after executing this many instructions.
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Stop the run after it has taken this long.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     /// Run the program once with the provided input.
     pub fn once(i: Input) -> impl IntoIterator<Item = Run> {
         [Self::new().input(i)]
@@ -223,13 +363,43 @@ impl Run {
     }
 }
 
+impl ProgressFn {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for ProgressFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ProgressFn").finish()
+    }
+}
+
 /// Dynamically mark the program by actually running it and seeing what each
 /// memory location is for.
-pub fn mark(p: &mut Program, runs: impl IntoIterator<Item = Run>) -> Result<()> {
-    let mut c = Computer::new(p);
+///
+/// Returns the indexes of the runs that were stopped because they used up
+/// their budget.
+pub fn mark(
+    p: &mut Program,
+    runs: impl IntoIterator<Item = Run>,
+    progress: Option<&ProgressFn>,
+) -> Result<Vec<usize>> {
+    let mut c = Computer::new(p, progress);
+    let mut stopped = Vec::new();
 
     // Run the program and mark the memory appropriately.
-    for Run { mut input } in runs.into_iter() {
+    for Run {
+        mut input,
+        max_steps,
+        timeout,
+    } in runs.into_iter()
+    {
+        c.max_steps = max_steps;
+        c.deadline = timeout.map(|timeout| Instant::now() + timeout);
         loop {
             match c.next()? {
                 State::Yielded(_) => {
@@ -243,10 +413,15 @@ pub fn mark(p: &mut Program, runs: impl IntoIterator<Item = Run>) -> Result<()>
                 State::Complete => {
                     break;
                 }
+                State::Stopped => {
+                    stopped.push(c.run);
+                    break;
+                }
             }
         }
+        c.report_progress();
         c.reset();
     }
 
-    Ok(())
+    Ok(stopped)
 }
@@ -2,6 +2,7 @@
 
 use std::fmt;
 use std::fmt::Display;
+use std::io;
 use std::iter;
 
 use crate::ast::{Ast, Instr, Label, Mode, Param, RawParam, Stmt};
@@ -83,8 +84,11 @@ impl Display for Instr {
                 }
                 Ok(())
             }
+            Instr::DataZeroTerminated(s) => write!(f, "DBZ {:?}", s),
+            Instr::DataLengthPrefixed(s) => write!(f, "DBL {:?}", s),
+            Instr::RelativeBase(value) => write!(f, "RBINIT {}", value),
             Instr::Mutable(value, params) => {
-                write!(f, "DB ")?;
+                write!(f, "MUT ")?;
                 for (i, d) in iter::once(value as &dyn Display)
                     .chain(params.iter().map(|p| p as &dyn Display))
                     .enumerate()
@@ -104,17 +108,94 @@ impl Display for Instr {
 impl Display for Stmt {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.label {
-            Some(label) => write!(f, "{}: {}", label, self.instr),
-            None => write!(f, "{}", self.instr),
+            Some(label) => write!(f, "{}: {}", label, self.instr)?,
+            None => write!(f, "{}", self.instr)?,
+        }
+        if !self.comments.is_empty() {
+            write!(f, " ; {}", self.comments.join("; "))?;
         }
+        Ok(())
     }
 }
 
-impl Display for Ast {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for stmt in &self.stmts {
+/// The number of lines to write before flushing when streaming a listing.
+const FLUSH_LINES: usize = 1024;
+
+/// Adapts an I/O writer for formatting, flushing it every so many lines.
+struct Stream<'a, W> {
+    inner: &'a mut W,
+    lines: usize,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> Stream<'_, W> {
+    fn write(&mut self, s: &str) -> io::Result<()> {
+        self.inner.write_all(s.as_bytes())?;
+        self.lines += s.matches('\n').count();
+        if self.lines >= FLUSH_LINES {
+            self.lines = 0;
+            self.inner.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: io::Write> fmt::Write for Stream<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+impl Ast {
+    /// Writes the listing one statement at a time.
+    ///
+    /// This is equivalent to using the [`Display`] implementation but works
+    /// with any writer.
+    pub fn write_to(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        for (i, stmt) in self.stmts.iter().enumerate() {
+            if self.sections.contains(&i) {
+                if i > 0 {
+                    writeln!(f)?;
+                }
+                match &stmt.label {
+                    Some(label) => writeln!(f, "; routine `{}`", label)?,
+                    None => writeln!(f, "; routine")?,
+                }
+            }
             writeln!(f, "{}", stmt)?;
         }
+        if !self.appendix.is_empty() {
+            writeln!(f)?;
+            for line in &self.appendix {
+                writeln!(f, "; {}", line)?;
+            }
+        }
         Ok(())
     }
+
+    /// Streams the listing to the I/O writer, flushing it periodically so
+    /// that the start of a large listing can be read while the rest is
+    /// still being formatted.
+    pub fn stream_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        let mut stream = Stream {
+            inner: w,
+            lines: 0,
+            error: None,
+        };
+        match self.write_to(&mut stream) {
+            Ok(()) => stream.inner.flush(),
+            Err(_) => Err(stream
+                .error
+                .unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, "formatter error"))),
+        }
+    }
+}
+
+impl Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_to(f)
+    }
 }
@@ -3,7 +3,67 @@ use std::iter;
 use std::rc::Rc;
 
 use crate::ast::{Label, Param};
-use crate::program::{Mark, Mention, Opcode, Program};
+use crate::program::{Mark, Mention, Opcode, Program, Purpose};
+
+/// Facts about an address that is about to be labelled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LabelFacts {
+    /// The address is an instruction.
+    pub code: bool,
+    /// The address is data, or is read from or written to and not yet
+    /// marked.
+    pub data: bool,
+    /// The address is the start of a string.
+    pub string: bool,
+    /// The number of instructions that jump to this address.
+    pub jumps: usize,
+    /// Whether any of the jumps come from later in the program.
+    pub loop_target: bool,
+    /// The number of parameters that mention this address.
+    pub mentions: usize,
+}
+
+/// How to name the labels generated during disassembly.
+#[derive(Debug, Clone, Copy)]
+pub enum LabelScheme {
+    /// Bare letters like `a`, `b`, and `c`.
+    Letters,
+    /// Letters prefixed with what the label is used for, like `loop_a`,
+    /// `str_b`, and `func_c`.
+    Semantic,
+    /// Letters prefixed with the name returned by the function, if any.
+    Custom(fn(LabelFacts) -> Option<&'static str>),
+}
+
+impl Default for LabelScheme {
+    fn default() -> Self {
+        Self::Letters
+    }
+}
+
+impl LabelScheme {
+    fn prefix(&self, facts: LabelFacts) -> Option<&'static str> {
+        match self {
+            Self::Letters => None,
+            Self::Semantic => semantic(facts),
+            Self::Custom(f) => f(facts),
+        }
+    }
+}
+
+fn semantic(facts: LabelFacts) -> Option<&'static str> {
+    if facts.string {
+        Some("str")
+    } else if facts.loop_target {
+        Some("loop")
+    } else if facts.jumps > 1 {
+        Some("func")
+    } else if facts.data {
+        Some("data")
+    } else {
+        None
+    }
+}
 
 impl Mark {
     fn label_param(&mut self, label: Label, offset: i64) {
@@ -29,8 +89,30 @@ impl Program {
             .label_param(label, offset);
     }
 
-    fn get_or_set_label(&mut self, addr: usize, with: impl FnOnce() -> Label) -> Label {
-        self.slots[addr].label.get_or_insert_with(with).clone()
+    fn get_or_set_label(&mut self, addr: usize, with: impl FnOnce(LabelFacts) -> Label) -> Label {
+        if self.slots[addr].label.is_none() {
+            let facts = self.label_facts(addr);
+            self.slots[addr].label = Some(with(facts));
+        }
+        self.slots[addr].label.clone().unwrap()
+    }
+
+    fn label_facts(&self, addr: usize) -> LabelFacts {
+        let slot = &self.slots[addr];
+        let jumps = || slot.mentions.iter().filter(|m| m.purpose == Purpose::Jump);
+        LabelFacts {
+            code: matches!(slot.mark, Some(Mark::Opcode(_))),
+            // Unmarked addresses that are read or written will be data.
+            data: match slot.mark {
+                Some(Mark::Data) => true,
+                None => slot.has_rw_purpose(),
+                _ => false,
+            },
+            string: matches!(slot.mark, Some(Mark::String)),
+            jumps: jumps().count(),
+            loop_target: jumps().any(|m| m.referrer > addr),
+            mentions: slot.mentions.len(),
+        }
     }
 
     /// Finds the preceding opcode.
@@ -70,7 +152,7 @@ pub fn unique() -> impl Iterator<Item = Label> {
     singles.chain(doubles)
 }
 
-pub fn assign(p: &mut Program, mut labels: impl Iterator<Item = Label>) {
+pub fn assign(p: &mut Program, mut labels: impl Iterator<Item = Label>, scheme: &LabelScheme) {
     let refs: BTreeMap<_, _> = (0..p.len())
         .filter_map(|i| {
             let mentions: Vec<_> = p.slots[i]
@@ -84,7 +166,15 @@ pub fn assign(p: &mut Program, mut labels: impl Iterator<Item = Label>) {
         .collect();
 
     for (addr, mentions) in refs {
-        let labelfn = || labels.next().unwrap();
+        let labelfn = |facts| {
+            let label = labels.next().unwrap();
+            match (scheme.prefix(facts), label) {
+                (Some(prefix), Label::Fixed(name)) => {
+                    Label::Fixed(Rc::new(format!("{}_{}", prefix, name)))
+                }
+                (_, label) => label,
+            }
+        };
         match &p.slots[addr].mark {
             Some(Mark::Opcode(_) | Mark::Data) | None => {
                 // Assign a new label to the referring parameters.
@@ -1,26 +1,226 @@
 mod ast;
+mod budget;
+mod cfg;
 mod dynamically;
 mod fmt;
 mod labels;
 mod program;
+mod pseudo;
+mod relocate;
+mod report;
+mod routines;
 mod statically;
 
-use crate::ast::Ast;
-pub use crate::dynamically::{Input, Result, Run};
+use std::rc::Rc;
+
+use intcode_assemble::debug::DebugInfo;
+
+pub use crate::ast::{Ast, Instr, Label, Mode, Param, RawParam, Stmt};
+pub use crate::budget::Budget;
+use crate::budget::Meter;
+use crate::dynamically::ProgressFn;
+pub use crate::dynamically::{Input, Progress, Result, Run};
+pub use crate::labels::{LabelFacts, LabelScheme};
 use crate::program::Program;
+pub use crate::report::{
+    Confidence, Constant, Diagnostic, Doubt, Loop, Reason, Relocation, Report, Suspicion,
+};
+
+/// Options for disassembling a program.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    comments: bool,
+    debug: Option<DebugInfo>,
+    label_scheme: LabelScheme,
+    sections: bool,
+    xrefs: bool,
+    loops: bool,
+    constants: bool,
+    pseudo: bool,
+    confidence: bool,
+    min_confidence: Confidence,
+    fold_relative_base: bool,
+    budget: Budget,
+    progress: Option<ProgressFn>,
+}
+
+/// A disassembled program together with the analysis report.
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    pub ast: Ast,
+    pub report: Report,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to add the analysis results as comments on the relevant
+    /// statements.
+    pub fn comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// Use the label names and source lines from the assembler instead of
+    /// generating them. Source lines are only shown as comments.
+    pub fn debug_info(mut self, debug: DebugInfo) -> Self {
+        self.debug = Some(debug);
+        self
+    }
+
+    /// Whether to split the output into sections, one for each detected
+    /// routine.
+    pub fn sections(mut self, sections: bool) -> Self {
+        self.sections = sections;
+        self
+    }
+
+    /// Whether to add a comment to each labelled statement listing the places
+    /// that refer to it.
+    pub fn xrefs(mut self, xrefs: bool) -> Self {
+        self.xrefs = xrefs;
+        self
+    }
+
+    /// Whether to add comments marking the start of each loop and the jumps
+    /// back to it.
+    pub fn loops(mut self, loops: bool) -> Self {
+        self.loops = loops;
+        self
+    }
+
+    /// Whether to add a comment block at the end listing the immediate
+    /// constants and where they are used.
+    pub fn constants(mut self, constants: bool) -> Self {
+        self.constants = constants;
+        self
+    }
+
+    /// Whether to add a comment to each instruction with the equivalent
+    /// pseudo-code.
+    pub fn pseudo(mut self, pseudo: bool) -> Self {
+        self.pseudo = pseudo;
+        self
+    }
+
+    /// Whether to add a `low confidence` comment to each instruction that was
+    /// marked as code but looks unlikely to be code.
+    pub fn confidence(mut self, confidence: bool) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// The lowest confidence an address can have to be disassembled as code.
+    ///
+    /// Anything marked with a lower confidence is output as raw data.
+    pub fn min_confidence(mut self, min_confidence: Confidence) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    /// Whether to replace an `ARB` with an immediate parameter at the start
+    /// of the program with an `RBINIT` directive.
+    ///
+    /// This moves every following address down by two, so it is only correct
+    /// if the rest of the program refers to addresses using labels.
+    pub fn fold_relative_base(mut self, fold_relative_base: bool) -> Self {
+        self.fold_relative_base = fold_relative_base;
+        self
+    }
+
+    /// The most work the static analysis may do before giving up.
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Call this regularly during the dynamic runs with how far they have
+    /// got, and at the end of each run.
+    pub fn progress<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(ProgressFn::new(f));
+        self
+    }
+
+    /// How to name the generated labels.
+    pub fn label_scheme(mut self, label_scheme: LabelScheme) -> Self {
+        self.label_scheme = label_scheme;
+        self
+    }
+}
 
 /// Disassemble the intcode program into an AST that can be displayed.
 pub fn to_ast(intcode: Vec<i64>, runs: impl IntoIterator<Item = Run>) -> Result<Ast> {
+    disassemble(intcode, runs, &Options::new()).map(|d| d.ast)
+}
+
+/// Disassemble the intcode program into an AST that can be displayed, and a
+/// report of what was found along the way.
+pub fn disassemble(
+    intcode: Vec<i64>,
+    runs: impl IntoIterator<Item = Run>,
+    opts: &Options,
+) -> Result<Disassembly> {
     let mut p = Program::new(intcode);
-    let mut labels = labels::unique();
+    let mut names = Vec::new();
+    if let Some(debug) = &opts.debug {
+        for (name, addr) in &debug.labels {
+            if let Some(slot) = p.slots.get_mut(*addr) {
+                slot.label = Some(Label::Fixed(Rc::new(name.clone())));
+                names.push(name.as_str());
+            }
+        }
+    }
+    let mut labels = labels::unique().filter(|label| match label {
+        Label::Fixed(name) => !names.contains(&name.as_str()),
+        _ => true,
+    });
 
-    dynamically::mark(&mut p, runs)?;
-    labels::assign(&mut p, &mut labels);
+    let relocations = relocate::detect(&p);
+    let stopped_runs = dynamically::mark(&mut p, runs, opts.progress.as_ref())?;
+    p.assign_confidence(Confidence::High);
+    labels::assign(&mut p, &mut labels, &opts.label_scheme);
     log::info!("{:.1}% marked after dynamic marking", p.percent_marked());
 
-    statically::mark(&mut p);
-    labels::assign(&mut p, &mut labels);
+    relocate::mark(&mut p, &relocations);
+    let mut meter = Meter::new(opts.budget);
+    statically::mark(&mut p, &mut meter);
+    labels::assign(&mut p, &mut labels, &opts.label_scheme);
     log::info!("{:.1}% marked after static marking", p.percent_marked());
 
-    Ok(p.into_ast())
+    let report = Report {
+        diagnostics: p.diagnostics.clone(),
+        doubts: p.doubts.clone(),
+        confidence: p
+            .slots
+            .iter()
+            .map(|slot| slot.confidence.unwrap_or_default())
+            .collect(),
+        modified: p.slots.iter().map(|slot| slot.modified).collect(),
+        routines: routines::detect(&p),
+        loops: match opts.loops {
+            true => cfg::Cfg::new(&p).loops(&mut meter),
+            false => Vec::new(),
+        },
+        relocations,
+        constants: p.constants(),
+        tainted_outputs: p.tainted_outputs.iter().copied().collect(),
+        tainted_jumps: p.tainted_jumps.iter().copied().collect(),
+        budget_exhausted: meter.is_exhausted(),
+        stopped_runs,
+    };
+    p.demote(opts.min_confidence);
+    let mut ast = p.into_ast(opts, &report);
+    if opts.fold_relative_base {
+        if let Some(stmt @ Stmt { label: None, .. }) = ast.stmts.first_mut() {
+            if let Instr::AdjustRelativeBase(Param::Number(Mode::Immediate, value)) = stmt.instr {
+                stmt.instr = Instr::RelativeBase(value);
+            }
+        }
+    }
+    Ok(Disassembly { ast, report })
 }
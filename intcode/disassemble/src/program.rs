@@ -1,7 +1,9 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::iter;
 
 use crate::ast::{Ast, Instr, Label, Mode, Param, RawParam, Stmt};
+use crate::report::{Confidence, Constant, Diagnostic, Doubt, Report};
+use crate::{pseudo, Options};
 
 /// An instruction type.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -27,14 +29,14 @@ pub enum Mark {
     Data,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Purpose {
     Read,
     Write,
     Jump,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Mention {
     pub purpose: Purpose,
     pub referrer: usize,
@@ -47,15 +49,27 @@ pub struct Slot {
     /// An optional mark when we are confident about this memory location.
     pub mark: Option<Mark>,
     /// How this address is mentioned from other places in the program.
-    pub mentions: HashSet<Mention>,
+    pub mentions: BTreeSet<Mention>,
     /// An optional label if we add one.
     pub label: Option<Label>,
+    /// Whether this address was written to while running the program.
+    pub modified: bool,
+    /// How confident we are about the mark, set once the address is marked.
+    pub confidence: Option<Confidence>,
 }
 
 /// Represents an intcode program during our analysis.
 #[derive(Debug, Clone)]
 pub struct Program {
     pub slots: Vec<Slot>,
+    /// Addresses that could not be interpreted as code.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Instructions that were statically marked with low confidence.
+    pub doubts: Vec<Doubt>,
+    /// Output instructions that have output a value depending on the input.
+    pub tainted_outputs: BTreeSet<usize>,
+    /// Jump instructions whose condition has depended on the input.
+    pub tainted_jumps: BTreeSet<usize>,
 }
 
 impl Mode {
@@ -102,6 +116,15 @@ impl Opcode {
         }
     }
 
+    /// The parameter that is written to, if any.
+    pub fn writes(&self) -> Option<usize> {
+        match self {
+            Self::Add | Self::Multiply | Self::LessThan | Self::Equal => Some(3),
+            Self::Input => Some(1),
+            _ => None,
+        }
+    }
+
     fn value(&self) -> i64 {
         match self {
             Self::Add => 1,
@@ -119,6 +142,11 @@ impl Opcode {
     }
 }
 
+fn to_string(bucket: Vec<i64>) -> String {
+    let bytes: Vec<_> = bucket.into_iter().map(|raw| raw as u8).collect();
+    String::from_utf8(bytes).unwrap()
+}
+
 impl Mention {
     pub fn new(purpose: Purpose, referrer: usize) -> Self {
         Self { purpose, referrer }
@@ -160,7 +188,13 @@ impl Program {
                 ..Default::default()
             })
             .collect();
-        Self { slots }
+        Self {
+            slots,
+            diagnostics: Vec::new(),
+            doubts: Vec::new(),
+            tainted_outputs: BTreeSet::new(),
+            tainted_jumps: BTreeSet::new(),
+        }
     }
 
     pub fn original(&self) -> Vec<i64> {
@@ -184,6 +218,32 @@ impl Program {
         self.slots[addr].mentions.insert(mention);
     }
 
+    pub fn mark_modified(&mut self, addr: usize) {
+        if addr >= self.len() {
+            return;
+        }
+        self.slots[addr].modified = true;
+    }
+
+    /// Sets the confidence of every marked address that doesn't have one yet.
+    pub fn assign_confidence(&mut self, confidence: Confidence) {
+        for slot in &mut self.slots {
+            if slot.mark.is_some() && slot.confidence.is_none() {
+                slot.confidence = Some(confidence);
+            }
+        }
+    }
+
+    /// Replaces the mark of every address with a confidence lower than the
+    /// given one with a data mark.
+    pub fn demote(&mut self, min: Confidence) {
+        for slot in &mut self.slots {
+            if slot.confidence.unwrap_or_default() < min {
+                slot.mark = Some(Mark::Data);
+            }
+        }
+    }
+
     pub fn mark(&mut self, addr: usize, mark: Mark) {
         if addr >= self.len() {
             panic!(
@@ -258,10 +318,80 @@ impl Program {
         })
     }
 
+    /// Returns the places that refer to the given address, each relative to
+    /// the nearest preceding label.
+    fn xrefs(&self, addr: usize) -> Vec<String> {
+        let mut referrers: Vec<_> = self.slots[addr]
+            .mentions
+            .iter()
+            .filter(|m| self.slots[m.referrer].raw == addr as i64)
+            .map(|m| m.referrer)
+            .collect();
+        referrers.sort_unstable();
+        referrers.dedup();
+        referrers.into_iter().map(|r| self.location(r)).collect()
+    }
+
+    /// Returns the address relative to the nearest preceding label.
+    fn location(&self, addr: usize) -> String {
+        match self.slots[..=addr].iter().rposition(|s| s.label.is_some()) {
+            Some(a) => {
+                let label = self.slots[a].label.as_ref().unwrap();
+                match addr - a {
+                    0 => label.to_string(),
+                    offset => format!("{}+{}", label, offset),
+                }
+            }
+            None => addr.to_string(),
+        }
+    }
+
+    /// Returns each distinct immediate constant used as a parameter.
+    pub fn constants(&self) -> Vec<Constant> {
+        let mut constants = BTreeMap::<i64, Constant>::new();
+        let mut instr = 0;
+        for (addr, slot) in self.slots.iter().enumerate() {
+            match &slot.mark {
+                Some(Mark::Opcode(_)) => instr = addr,
+                Some(Mark::Param(Param::Number(Mode::Immediate, value))) => {
+                    let value = *value;
+                    let c = constants.entry(value).or_insert_with(|| Constant {
+                        value,
+                        count: 0,
+                        instrs: Vec::new(),
+                    });
+                    c.count += 1;
+                    if c.instrs.last() != Some(&instr) {
+                        c.instrs.push(instr);
+                    }
+                }
+                _ => {}
+            }
+        }
+        constants.into_iter().map(|(_, c)| c).collect()
+    }
+
+    /// Returns the string if the slot is data holding the length of the
+    /// string that directly follows it.
+    fn length_prefixed(&self, ptr: usize) -> Option<String> {
+        match self.slots.get(ptr + 1) {
+            Some(Slot {
+                mark: Some(Mark::String),
+                label: None,
+                ..
+            }) if self.slots[ptr].mark == Some(Mark::Data) => {
+                let bucket = self.bucket_unlabelled(ptr + 1, Mark::String);
+                (bucket.len() as i64 == self.slots[ptr].raw).then(|| to_string(bucket))
+            }
+            _ => None,
+        }
+    }
+
     fn bucket_unlabelled(&self, mut ptr: usize, mark: Mark) -> Vec<i64> {
         let mut v = vec![self.slots[ptr].raw];
         ptr += 1;
         while matches!(self.slots.get(ptr), Some(Slot { mark: Some(m), label: None, .. }) if *m == mark)
+            && !(mark == Mark::Data && self.length_prefixed(ptr).is_some())
         {
             v.push(self.slots[ptr].raw);
             ptr += 1;
@@ -269,11 +399,16 @@ impl Program {
         v
     }
 
-    pub fn into_ast(self) -> Ast {
+    pub fn into_ast(self, opts: &Options, report: &Report) -> Ast {
         let mut ptr = 0;
         let mut stmts = Vec::new();
+        let mut sections = Vec::new();
 
         while let Some(slot) = self.slots.get(ptr) {
+            let start = ptr;
+            if opts.sections && report.routines.contains(&start) {
+                sections.push(stmts.len());
+            }
             match &slot.mark {
                 Some(Mark::Opcode(opcode)) => {
                     let param = |i: usize| self.get_param(ptr + i).unwrap();
@@ -349,6 +484,9 @@ impl Program {
                     stmts.push(Stmt {
                         label: slot.label.clone(),
                         instr,
+                        comments: Vec::new(),
+                        addr: start,
+                        len: ptr - start,
                     });
                 }
 
@@ -359,31 +497,160 @@ impl Program {
                 Some(Mark::String) => {
                     let bucket = self.bucket_unlabelled(ptr, Mark::String);
                     ptr += bucket.len();
-                    let bytes: Vec<_> = bucket.into_iter().map(|raw| raw as u8).collect();
                     let label = slot.label.clone();
-                    let param = String::from_utf8(bytes).unwrap();
-                    let instr = Instr::Data(vec![RawParam::String(param)]);
-                    stmts.push(Stmt { label, instr })
+                    let param = to_string(bucket);
+                    // A zero directly after the string is a terminator.
+                    let instr = match self.slots.get(ptr) {
+                        Some(Slot {
+                            raw: 0,
+                            mark: Some(Mark::Data),
+                            label: None,
+                            ..
+                        }) => {
+                            ptr += 1;
+                            Instr::DataZeroTerminated(param)
+                        }
+                        _ => Instr::Data(vec![RawParam::String(param)]),
+                    };
+                    stmts.push(Stmt {
+                        label,
+                        instr,
+                        comments: Vec::new(),
+                        addr: start,
+                        len: ptr - start,
+                    })
+                }
+
+                Some(Mark::Data) if self.length_prefixed(ptr).is_some() => {
+                    let param = self.length_prefixed(ptr).unwrap();
+                    ptr += 1 + param.len();
+                    stmts.push(Stmt {
+                        label: slot.label.clone(),
+                        instr: Instr::DataLengthPrefixed(param),
+                        comments: Vec::new(),
+                        addr: start,
+                        len: ptr - start,
+                    })
                 }
 
                 Some(Mark::Data) => {
                     let bucket = self.bucket_unlabelled(ptr, Mark::Data);
                     ptr += bucket.len();
-                    let raw_params: Vec<_> = bucket
-                        .into_iter()
-                        .map(|raw| RawParam::Number(raw))
-                        .collect();
+                    let raw_params: Vec<_> = bucket.into_iter().map(RawParam::Number).collect();
                     let label = slot.label.clone();
                     let instr = Instr::Data(raw_params);
-                    stmts.push(Stmt { label, instr })
+                    stmts.push(Stmt {
+                        label,
+                        instr,
+                        comments: Vec::new(),
+                        addr: start,
+                        len: ptr - start,
+                    })
                 }
 
                 None => {
                     panic!("unmarked address `{}`", ptr);
                 }
             }
+
+            if opts.comments {
+                let stmt = stmts.last_mut().unwrap();
+                if let Some(line) = opts.debug.as_ref().and_then(|d| d.line(start)) {
+                    stmt.comments.push(format!("line {}", line));
+                }
+                stmt.comments.extend(
+                    self.diagnostics
+                        .iter()
+                        .filter(|d| (start..ptr).contains(&d.addr))
+                        .map(ToString::to_string),
+                );
+                if self.slots[start..ptr].iter().any(|slot| slot.modified) {
+                    stmt.comments.push("runtime-modified".to_owned());
+                }
+                for r in &report.relocations {
+                    if r.header == start {
+                        stmt.comments.push(format!(
+                            "relocation loop: copies {} addresses from {} to {}",
+                            r.len, r.src, r.dst
+                        ));
+                    }
+                    if (start..ptr).contains(&r.src) {
+                        stmt.comments.push(format!(
+                            "copied to {} by the relocation loop at {}",
+                            r.dst,
+                            self.location(r.header)
+                        ));
+                    }
+                    let entry = r.src + (r.entry - r.dst);
+                    if entry == start {
+                        stmt.comments.push(format!("runs at {}", r.entry));
+                    }
+                }
+            }
+
+            if opts.confidence {
+                let stmt = stmts.last_mut().unwrap();
+                stmt.comments.extend(
+                    self.doubts
+                        .iter()
+                        .filter(|d| d.addr == start)
+                        .map(ToString::to_string),
+                );
+            }
+
+            if opts.loops {
+                let stmt = stmts.last_mut().unwrap();
+                if report.loops.iter().any(|l| l.header == start) {
+                    stmt.comments.push("loop start".to_owned());
+                }
+                for l in report
+                    .loops
+                    .iter()
+                    .filter(|l| l.back_edges.contains(&start))
+                {
+                    let header = match &self.slots[l.header].label {
+                        Some(label) => label.to_string(),
+                        None => l.header.to_string(),
+                    };
+                    stmt.comments.push(format!("back-edge to {}", header));
+                }
+            }
+
+            if opts.xrefs && slot.label.is_some() {
+                let xrefs = self.xrefs(start);
+                if !xrefs.is_empty() {
+                    let stmt = stmts.last_mut().unwrap();
+                    stmt.comments.push(format!("xrefs: {}", xrefs.join(", ")));
+                }
+            }
+        }
+
+        if opts.pseudo {
+            let lines = pseudo::lift(&stmts);
+            for (stmt, line) in stmts.iter_mut().zip(lines) {
+                stmt.comments.extend(line);
+            }
         }
 
-        Ast { stmts }
+        let mut appendix = Vec::new();
+        if opts.constants && !report.constants.is_empty() {
+            appendix.push("constants:".to_owned());
+            for c in &report.constants {
+                let instrs: Vec<_> = c.instrs.iter().map(|&a| self.location(a)).collect();
+                appendix.push(format!(
+                    "  {} used {} time{} by {}",
+                    c.value,
+                    c.count,
+                    if c.count != 1 { "s" } else { "" },
+                    instrs.join(", ")
+                ));
+            }
+        }
+
+        Ast {
+            stmts,
+            sections,
+            appendix,
+        }
     }
 }
@@ -0,0 +1,122 @@
+//! Lift each instruction to a line of readable pseudo-code.
+
+use crate::ast::{Instr, Mode, Param, Stmt};
+
+/// Returns the value of the parameter if it is an immediate number.
+fn constant(p: &Param) -> Option<i64> {
+    match p {
+        Param::Number(Mode::Immediate, value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Formats the parameter as a value.
+fn value(p: &Param) -> String {
+    match p {
+        Param::Number(Mode::Positional, addr) => format!("[{}]", addr),
+        Param::Number(Mode::Immediate, value) => value.to_string(),
+        Param::Number(Mode::Relative, offset) | Param::Label(Mode::Relative, _, offset) => {
+            format!("rb[{}]", offset)
+        }
+        Param::Label(Mode::Positional, label, 0) => label.to_string(),
+        Param::Label(Mode::Positional, label, offset) => format!("{}[{}]", label, offset),
+        Param::Label(Mode::Immediate, label, 0) => format!("&{}", label),
+        Param::Label(Mode::Immediate, label, offset) => format!("&{}{:+}", label, offset),
+    }
+}
+
+/// Formats the parameter as the target of a jump.
+fn target(p: &Param) -> String {
+    match p {
+        Param::Number(Mode::Immediate, addr) => addr.to_string(),
+        Param::Label(Mode::Immediate, label, 0) => label.to_string(),
+        Param::Label(Mode::Immediate, label, offset) => format!("{}{:+}", label, offset),
+        p => format!("*{}", value(p)),
+    }
+}
+
+fn add(a: &Param, b: &Param) -> String {
+    match (constant(a), constant(b)) {
+        (Some(x), Some(y)) if x.checked_add(y).is_some() => (x + y).to_string(),
+        (Some(0), None) => value(b),
+        (None, Some(0)) => value(a),
+        (None, Some(y)) if y < 0 && y.checked_neg().is_some() => {
+            format!("{} - {}", value(a), -y)
+        }
+        _ => format!("{} + {}", value(a), value(b)),
+    }
+}
+
+fn multiply(a: &Param, b: &Param) -> String {
+    match (constant(a), constant(b)) {
+        (Some(x), Some(y)) if x.checked_mul(y).is_some() => (x * y).to_string(),
+        (Some(0), _) | (_, Some(0)) => "0".to_owned(),
+        (Some(1), None) => value(b),
+        (None, Some(1)) => value(a),
+        _ => format!("{} * {}", value(a), value(b)),
+    }
+}
+
+/// Returns the comparison the instruction makes and where the result is
+/// stored, if it is one.
+fn comparison(instr: &Instr) -> Option<(&Param, &str, &str, &Param, &Param)> {
+    match instr {
+        Instr::LessThan(a, b, c) => Some((a, "<", ">=", b, c)),
+        Instr::Equal(a, b, c) => Some((a, "==", "!=", b, c)),
+        _ => None,
+    }
+}
+
+fn jump(prev: Option<&Instr>, stmt: &Stmt, cond: &Param, t: &Param, non_zero: bool) -> String {
+    match constant(cond) {
+        Some(v) if (v != 0) == non_zero => return format!("goto {}", target(t)),
+        Some(_) => return "nop".to_owned(),
+        None => {}
+    }
+    // Fold a comparison that was only just stored in the condition, this is
+    // only safe if nothing else can jump to this statement.
+    if let Some((a, op, neg, b, c)) = prev.and_then(comparison) {
+        if c == cond && stmt.label.is_none() {
+            let op = if non_zero { op } else { neg };
+            return format!("if ({} {} {}) goto {}", value(a), op, value(b), target(t));
+        }
+    }
+    let op = if non_zero { "!=" } else { "==" };
+    format!("if ({} {} 0) goto {}", value(cond), op, target(t))
+}
+
+/// Lifts each statement to pseudo-code, data statements have none.
+pub fn lift(stmts: &[Stmt]) -> Vec<Option<String>> {
+    let mut prev = None;
+    stmts
+        .iter()
+        .map(|stmt| {
+            let line = match &stmt.instr {
+                Instr::Add(a, b, c) => Some(format!("{} = {}", value(c), add(a, b))),
+                Instr::Multiply(a, b, c) => Some(format!("{} = {}", value(c), multiply(a, b))),
+                Instr::LessThan(a, b, c) => {
+                    Some(format!("{} = {} < {}", value(c), value(a), value(b)))
+                }
+                Instr::Equal(a, b, c) => {
+                    Some(format!("{} = {} == {}", value(c), value(a), value(b)))
+                }
+                Instr::JumpNonZero(a, b) => Some(jump(prev, stmt, a, b, true)),
+                Instr::JumpZero(a, b) => Some(jump(prev, stmt, a, b, false)),
+                Instr::Input(a) => Some(format!("{} = input()", value(a))),
+                Instr::Output(a) => Some(format!("output({})", value(a))),
+                Instr::AdjustRelativeBase(a) => match constant(a) {
+                    Some(v) if v < 0 && v.checked_neg().is_some() => Some(format!("rb -= {}", -v)),
+                    _ => Some(format!("rb += {}", value(a))),
+                },
+                Instr::Halt => Some("halt".to_owned()),
+                Instr::Data(_)
+                | Instr::DataZeroTerminated(_)
+                | Instr::DataLengthPrefixed(_)
+                | Instr::Mutable(..)
+                | Instr::RelativeBase(_) => None,
+            };
+            prev = Some(&stmt.instr);
+            line
+        })
+        .collect()
+}
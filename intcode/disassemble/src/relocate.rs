@@ -0,0 +1,138 @@
+//! Detect loops that copy a block of the program somewhere else and then jump
+//! into the copy.
+//!
+//! Some compilers start every program with such a loop. The copied block is
+//! never executed where it is stored, so without this it is only ever marked
+//! as data. The loop is recognized from the raw values of the program, so it
+//! has to have one of the following shapes.
+//!
+//! ```asm
+//! loop:
+//!     ADD src, #0, dst  ; or `ADD #0, src, dst`, `MUL src, #1, dst`, ...
+//!     ADD #1, loop+1, loop+1
+//!     ADD #1, loop+3, loop+3
+//!     ADD #-1, n, n     ; or `LT loop+1, #end, f` and then `JNZ f, #loop`
+//!     JNZ n, #loop
+//!     JZ #0, #dst       ; or any other unconditional jump into the copy
+//! ```
+
+use crate::program::{Opcode, Program};
+use crate::report::Relocation;
+use crate::statically::try_mark_instr;
+
+/// The most instructions after the copy to look through for the jump back.
+const MAX_LOOP_LEN: usize = 8;
+
+fn addr(value: i64) -> Option<usize> {
+    usize::try_from(value).ok()
+}
+
+/// Returns the raw values of the instruction at the address.
+fn instr(p: &Program, addr: usize) -> Option<Vec<i64>> {
+    let opcode = Opcode::from_value(p.slots.get(addr)?.raw % 100)?;
+    let slots = p.slots.get(addr..addr + 1 + opcode.params())?;
+    Some(slots.iter().map(|slot| slot.raw).collect())
+}
+
+/// Returns the addresses of the source and destination parameters if the
+/// instruction at the address copies one address to another.
+fn copy(raw: &[i64], at: usize) -> Option<(usize, usize)> {
+    match *raw {
+        [1001, _, 0, _] | [1002, _, 1, _] => Some((at + 1, at + 3)),
+        [101, 0, _, _] | [102, 1, _, _] => Some((at + 2, at + 3)),
+        _ => None,
+    }
+}
+
+/// Returns the address if the instruction adds `step` to it.
+fn step(raw: &[i64], step: i64) -> Option<usize> {
+    match *raw {
+        [101, s, a, b] | [1001, a, s, b] if s == step && a == b => addr(a),
+        _ => None,
+    }
+}
+
+/// Returns the target of an unconditional jump.
+fn jump(raw: &[i64]) -> Option<usize> {
+    match *raw {
+        [1105, c, target] if c != 0 => addr(target),
+        [1106, 0, target] => addr(target),
+        _ => None,
+    }
+}
+
+/// Returns the relocation loop starting at the address, if there is one.
+fn detect_at(p: &Program, header: usize) -> Option<Relocation> {
+    let (src_param, dst_param) = copy(&instr(p, header)?, header)?;
+    let src = addr(p.slots[src_param].raw)?;
+    let dst = addr(p.slots[dst_param].raw)?;
+
+    let mut incremented = Vec::new();
+    let mut counter = None;
+    let mut bound = None;
+    let mut at = header + 4;
+    for _ in 0..MAX_LOOP_LEN {
+        let raw = instr(p, at)?;
+        if let [1005, cond, target] = *raw {
+            if target != header as i64 {
+                return None;
+            }
+            if !incremented.contains(&src_param) || !incremented.contains(&dst_param) {
+                return None;
+            }
+            let cond = addr(cond)?;
+            let len = match bound {
+                Some((flag, len)) if flag == cond => len,
+                _ if counter == Some(cond) => p.slots.get(cond)?.raw,
+                _ => return None,
+            };
+            let len = addr(len).filter(|len| *len > 0 && *len <= p.len().saturating_sub(src))?;
+            let end = dst.checked_add(len)?;
+            let entry = jump(&instr(p, at + 3)?).filter(|e| (dst..end).contains(e))?;
+            return Some(Relocation {
+                header,
+                back_edge: at,
+                src,
+                dst,
+                len,
+                entry,
+            });
+        }
+        if let Some(a) = step(&raw, 1) {
+            incremented.push(a);
+        }
+        if let Some(a) = step(&raw, -1) {
+            counter = Some(a);
+        }
+        if let [1007, a, end, flag] = *raw {
+            let start = match addr(a)? {
+                a if a == src_param => src,
+                a if a == dst_param => dst,
+                _ => return None,
+            };
+            bound = Some((addr(flag)?, end.checked_sub(start as i64)?));
+        }
+        at += raw.len();
+    }
+    None
+}
+
+/// Returns each relocation loop in the program.
+pub fn detect(p: &Program) -> Vec<Relocation> {
+    (0..p.len()).filter_map(|addr| detect_at(p, addr)).collect()
+}
+
+/// Marks the code in each copied block as instructions, starting from the
+/// part of it that is jumped to. This stops at the first address that can't
+/// be decoded, the rest of the block is left for the static marker.
+pub fn mark(p: &mut Program, relocations: &[Relocation]) {
+    for r in relocations {
+        let mut addr = r.src + (r.entry - r.dst);
+        while addr < r.src + r.len {
+            match try_mark_instr(p, addr) {
+                Some(len) => addr += len,
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Facts about the program discovered during disassembly.
+
+use std::fmt;
+use std::fmt::Display;
+
+/// The results of analysing the program.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// Addresses that could not be interpreted as code.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Instructions that were marked by the static marker but look unlikely
+    /// to be code.
+    pub doubts: Vec<Doubt>,
+    /// How confident the disassembler is about the mark of each address,
+    /// indexed by address.
+    pub confidence: Vec<Confidence>,
+    /// Whether each address was written to during the dynamic runs, indexed
+    /// by address.
+    pub modified: Vec<bool>,
+    /// The addresses of the instructions that start a routine.
+    pub routines: Vec<usize>,
+    /// The natural loops in the control flow graph.
+    pub loops: Vec<Loop>,
+    /// The loops that copy a block of the program somewhere else and then
+    /// jump into the copy.
+    pub relocations: Vec<Relocation>,
+    /// The distinct immediate constants, in ascending order.
+    pub constants: Vec<Constant>,
+    /// The addresses of the output instructions that have output a value
+    /// depending on the input during the dynamic runs.
+    pub tainted_outputs: Vec<usize>,
+    /// The addresses of the jump instructions whose condition has depended
+    /// on the input during the dynamic runs.
+    pub tainted_jumps: Vec<usize>,
+    /// Whether the analysis budget was used up. The static marking is then
+    /// incomplete and no loops are reported.
+    pub budget_exhausted: bool,
+    /// The indexes of the dynamic runs that were stopped because they used up
+    /// their step or time budget.
+    pub stopped_runs: Vec<usize>,
+}
+
+/// How confident the disassembler is that an address was marked correctly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    /// The address was not recognized and was treated as data.
+    Low,
+    /// The address was decoded as code or string data by the static marker.
+    Medium,
+    /// The address was observed being executed or accessed during the
+    /// dynamic runs.
+    High,
+}
+
+/// An immediate constant and where it is used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constant {
+    pub value: i64,
+    /// The number of parameters that use the constant.
+    pub count: usize,
+    /// The addresses of the instructions that use the constant.
+    pub instrs: Vec<usize>,
+}
+
+/// A natural loop in the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    /// The address of the instruction that dominates the loop.
+    pub header: usize,
+    /// The addresses of the jumps back to the header.
+    pub back_edges: Vec<usize>,
+    /// The addresses of the instructions in the loop, including the header.
+    pub body: Vec<usize>,
+    /// How many loops this loop is nested in, including itself.
+    pub depth: usize,
+}
+
+/// A loop that copies a block of the program somewhere else and then jumps
+/// into the copy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    /// The address of the instruction that copies each address.
+    pub header: usize,
+    /// The address of the jump back to the header.
+    pub back_edge: usize,
+    /// The first address that is copied.
+    pub src: usize,
+    /// The address the first address is copied to.
+    pub dst: usize,
+    /// The number of addresses copied.
+    pub len: usize,
+    /// The address in the copy that is jumped to after the loop.
+    pub entry: usize,
+}
+
+/// An address that could not be interpreted as code, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub addr: usize,
+    pub reason: Reason,
+}
+
+/// An instruction that was marked as code with low confidence, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Doubt {
+    pub addr: usize,
+    pub suspicion: Suspicion,
+}
+
+/// The reason an instruction looks unlikely to be code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Suspicion {
+    /// Every parameter is in positional mode, which is common for small data
+    /// values that happen to decode as an instruction.
+    AllPositional,
+}
+
+/// The reason an address could not be interpreted as an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// The value is not a valid opcode.
+    UnknownOpcode { opcode: i64 },
+    /// The value has more mode digits than the opcode has parameters.
+    ExtraModes { value: i64 },
+    /// A parameter mode digit is not 0, 1, or 2.
+    UnknownMode { param: usize, mode: i64 },
+    /// A parameter that is written to is in immediate mode.
+    ImmediateWrite { param: usize },
+    /// The instruction extends past the end of the program.
+    Truncated,
+    /// A parameter overlaps an address that is already marked.
+    Overlaps { param: usize },
+}
+
+impl Default for Confidence {
+    fn default() -> Self {
+        Self::Low
+    }
+}
+
+impl Report {
+    /// Returns whether the address was written to during the dynamic runs.
+    pub fn is_modified(&self, addr: usize) -> bool {
+        self.modified.get(addr).copied().unwrap_or(false)
+    }
+
+    /// Returns how confident the disassembler is about the address.
+    pub fn confidence_at(&self, addr: usize) -> Confidence {
+        self.confidence.get(addr).copied().unwrap_or_default()
+    }
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOpcode { opcode } => write!(f, "unknown opcode `{}`", opcode),
+            Self::ExtraModes { value } => {
+                write!(f, "too many mode digits in `{}`", value)
+            }
+            Self::UnknownMode { param, mode } => {
+                write!(f, "unknown mode `{}` for parameter {}", mode, param)
+            }
+            Self::ImmediateWrite { param } => {
+                write!(f, "parameter {} is written to but is immediate", param)
+            }
+            Self::Truncated => write!(f, "instruction is truncated"),
+            Self::Overlaps { param } => {
+                write!(f, "parameter {} overlaps other marked code or data", param)
+            }
+        }
+    }
+}
+
+impl Display for Suspicion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AllPositional => write!(f, "all the modes are positional"),
+        }
+    }
+}
+
+impl Display for Doubt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "low confidence: {}", self.suspicion)
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "address {}: {}", self.addr, self.reason)
+    }
+}
@@ -0,0 +1,48 @@
+//! Detect where routines start so the output can be split into sections.
+
+use crate::program::{Mark, Opcode, Program, Purpose};
+
+impl Program {
+    /// Returns whether execution can never fall through the instruction
+    /// starting at the given address.
+    pub fn is_terminal(&self, addr: usize) -> bool {
+        let raw = self.slots[addr].raw;
+        let immediate = raw / 100 % 10 == 1;
+        let value = self.slots.get(addr + 1).map(|slot| slot.raw);
+        match self.slots[addr].mark {
+            Some(Mark::Opcode(Opcode::Halt)) => true,
+            Some(Mark::Opcode(Opcode::JumpZero)) => immediate && value == Some(0),
+            Some(Mark::Opcode(Opcode::JumpNonZero)) => {
+                immediate && matches!(value, Some(v) if v != 0)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the number of instructions that currently jump to the address.
+    fn jumps_to(&self, addr: usize) -> usize {
+        self.slots[addr]
+            .mentions
+            .iter()
+            .filter(|m| m.purpose == Purpose::Jump && self.slots[m.referrer].raw == addr as i64)
+            .count()
+    }
+}
+
+/// Returns the addresses of instructions that start a routine.
+///
+/// A routine starts at an instruction that is jumped to from more than one
+/// place and that the previous instruction can never fall through to.
+pub fn detect(p: &Program) -> Vec<usize> {
+    let mut routines = Vec::new();
+    let mut prev = None;
+    for addr in 0..p.len() {
+        if let Some(Mark::Opcode(_)) = p.slots[addr].mark {
+            if matches!(prev, Some(prev) if p.is_terminal(prev)) && p.jumps_to(addr) > 1 {
+                routines.push(addr);
+            }
+            prev = Some(addr);
+        }
+    }
+    routines
+}
@@ -1,49 +1,69 @@
 use crate::ast::Mode;
+use crate::budget::Meter;
 use crate::program::{Mark, Opcode, Program, Slot};
+use crate::report::{Confidence, Diagnostic, Doubt, Reason, Suspicion};
 
-fn try_mark_instr(p: &mut Program, addr: usize) -> Option<usize> {
-    let slot = &p.slots[addr];
-
-    if slot.mark.is_some() {
-        return None;
-    }
-
-    let instr = slot.raw;
-    let opcode = Opcode::from_value(instr % 100)?;
+/// Decodes the instruction at the given address, returning the opcode and the
+/// address and mode of each parameter.
+fn decode_instr(p: &Program, addr: usize) -> Result<(Opcode, Vec<(usize, Mode)>), Reason> {
+    let instr = p.slots[addr].raw;
+    let opcode = instr % 100;
+    let opcode = Opcode::from_value(opcode).ok_or(Reason::UnknownOpcode { opcode })?;
     let ps = opcode.params();
 
     // Check if the instruction has digits greater than allowed for this
     // parameter count.
     let divs = [100, 1_000, 10_000, 100_000];
     if instr / divs[ps] > 0 {
-        return None;
+        return Err(Reason::ExtraModes { value: instr });
     }
     // Check that there are available parameter slots and they are unmarked.
     let mut modes = Vec::new();
     for (i, div) in divs.iter().enumerate().take(ps) {
-        let addr = addr + i + 1;
-        if p.slots.get(addr)?.mark.is_some() {
-            return None;
+        let param = i + 1;
+        let addr = addr + param;
+        let slot = p.slots.get(addr).ok_or(Reason::Truncated)?;
+        if slot.mark.is_some() {
+            return Err(Reason::Overlaps { param });
+        }
+        let mode = instr / div % 10;
+        let mode = Mode::from_value(mode).ok_or(Reason::UnknownMode { param, mode })?;
+        if mode == Mode::Immediate && opcode.writes() == Some(param) {
+            return Err(Reason::ImmediateWrite { param });
         }
-        let mode = Mode::from_value(instr / div % 10)?;
         modes.push((addr, mode));
     }
+    Ok((opcode, modes))
+}
+
+pub fn try_mark_instr(p: &mut Program, addr: usize) -> Option<usize> {
+    if p.slots[addr].mark.is_some() {
+        return None;
+    }
+
+    let (opcode, modes) = match decode_instr(p, addr) {
+        Ok(decoded) => decoded,
+        Err(reason) => {
+            p.diagnostics.push(Diagnostic { addr, reason });
+            return None;
+        }
+    };
 
     if !modes.is_empty() && modes.iter().all(|(_, m)| matches!(m, Mode::Positional)) {
-        log::warn!(
-            "all the modes are positional for {:?} instruction at address {}",
-            opcode,
-            addr
-        );
+        p.doubts.push(Doubt {
+            addr,
+            suspicion: Suspicion::AllPositional,
+        });
     }
 
     // Everything looks good, mark the instruction and parameters!
+    let len = modes.len() + 1;
     p.mark_opcode(addr, opcode);
     for (addr, mode) in modes {
         p.mark_param(addr, mode);
     }
 
-    Some(ps + 1)
+    Some(len)
 }
 
 fn try_mark_string(p: &mut Program, addr: usize) -> Option<usize> {
@@ -73,8 +93,10 @@ fn try_mark_string(p: &mut Program, addr: usize) -> Option<usize> {
 
 /// Statically mark code in the program if it looks like an instruction and
 /// parameters. This has a lot of false positives, so it best to mark using the
-/// dynamic marker first.
-pub fn mark(p: &mut Program) {
+/// dynamic marker first. Anything that could not be marked is treated as data.
+///
+/// Once the budget is used up the remaining candidates are skipped.
+pub fn mark(p: &mut Program, meter: &mut Meter) {
     // First mark string data, otherwise default to ordinary data
     let indexes: Vec<_> = p
         .slots
@@ -86,6 +108,9 @@ pub fn mark(p: &mut Program) {
         })
         .collect();
     for i in indexes {
+        if !meter.visit() {
+            break;
+        }
         if try_mark_string(p, i).is_none() && p.slots[i].is_unmarked() {
             p.mark(i, Mark::Data)
         }
@@ -102,6 +127,18 @@ pub fn mark(p: &mut Program) {
         })
         .collect();
     for i in indexes {
+        if !meter.visit() {
+            break;
+        }
         try_mark_instr(p, i);
     }
+    p.assign_confidence(Confidence::Medium);
+
+    // Finally, anything left over is ordinary data
+    for i in 0..p.len() {
+        if p.slots[i].is_unmarked() {
+            p.mark(i, Mark::Data);
+        }
+    }
+    p.assign_confidence(Confidence::Low);
 }
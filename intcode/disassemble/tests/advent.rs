@@ -51,7 +51,7 @@ d: DB 50
 fn advent_day5_example_immediate() {
     let asm = "\
 MUL a, #3, a
-a: DB 33
+a: MUT 33
 ";
     let intcode = "1002,4,3,4,33";
     assert(asm, intcode, run_once());
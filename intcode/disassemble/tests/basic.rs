@@ -1,5 +1,12 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use intcode_assemble::debug::DebugInfo;
 use intcode_assemble::Intcode;
-use intcode_disassemble::{Input, Run};
+use intcode_disassemble::{
+    Budget, Confidence, Constant, Diagnostic, Disassembly, Doubt, Input, Instr, LabelScheme, Loop,
+    Mode, Options, Param, Reason, Relocation, Run, Stmt, Suspicion,
+};
 
 use pretty_assertions::assert_eq;
 
@@ -45,3 +52,639 @@ DB "Hello World!\n"
     let intcode = "109,10,204,0,109,1,1205,0,2,99,72,101,108,108,111,32,87,111,114,108,100,33,10";
     assert(asm, intcode, run_once());
 }
+
+#[test]
+fn impossible_encoding() {
+    let intcode = vec![1105, 0, 4, 99, 11101, 1, 2, 3];
+    let opts = Options::new().comments(true);
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    assert_eq!(
+        report.diagnostics,
+        [Diagnostic {
+            addr: 4,
+            reason: Reason::ImmediateWrite { param: 3 }
+        }]
+    );
+    assert_eq!(
+        ast.to_string(),
+        r#"JNZ #0, #a
+HLT
+a: DB 11101, 1, 2, 3 ; address 4: parameter 3 is written to but is immediate
+"#
+    );
+}
+
+#[test]
+fn debug_info_labels() {
+    let asm = r#"
+start:
+    IN  value
+    JNZ value, #start
+    OUT value
+    HLT
+value:
+    DB 0
+"#;
+    let Intcode { output, debug, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let debug: DebugInfo = debug.to_string().parse().unwrap();
+    assert_eq!(debug.label(8), Some("value"));
+    assert_eq!(debug.line(2), Some(4));
+    let opts = Options::new().comments(true).debug_info(debug);
+    let Disassembly { ast, .. } =
+        intcode_disassemble::disassemble(output, run_once(), &opts).unwrap();
+    assert_eq!(
+        ast.to_string(),
+        r#"start: IN value ; line 3
+JNZ value, #start ; line 4
+OUT value ; line 5
+HLT ; line 6
+value: DB 0 ; line 8; runtime-modified
+"#
+    );
+}
+
+#[test]
+fn zero_terminated_string() {
+    let asm = r#"ARB #10
+a: OUT rb
+ARB #1
+JNZ rb, #a
+HLT
+DBZ "Hi"
+"#;
+    let intcode = "109,10,204,0,109,1,1205,0,2,99,72,105,0";
+    assert(asm, intcode, run_once());
+}
+
+#[test]
+fn length_prefixed_string() {
+    let asm = r#"OUT b
+ARB #13
+a: OUT rb
+ARB #1
+JNZ rb, #a
+HLT
+b: DBL "Hi"
+"#;
+    let intcode = "4,12,109,13,204,0,109,1,1205,0,4,99,2,72,105";
+    assert(asm, intcode, run_once());
+}
+
+#[test]
+fn mutable_instruction() {
+    let intcode = "1,7,22,7,1106,0,7,1101,3,3,30,1005,22,21,1101,1,0,22,1106,0,0,99,0";
+    let asm = "\
+a: ADD b, d, b
+JZ #0, #b
+b: MUT 1101, 3, 3, 30
+JNZ d, #c
+ADD #1, #0, d
+JZ #0, #a
+c: HLT
+d: DB 0
+";
+    assert(asm, intcode, run_once());
+}
+
+#[test]
+fn runtime_modified() {
+    let intcode = vec![
+        1, 7, 22, 7, 1106, 0, 7, 1101, 3, 3, 30, 1005, 22, 21, 1101, 1, 0, 22, 1106, 0, 0, 99, 0,
+    ];
+    let opts = Options::new().comments(true);
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    let modified: Vec<_> = (0..report.modified.len())
+        .filter(|&addr| report.is_modified(addr))
+        .collect();
+    assert_eq!(modified, [7, 22]);
+    assert_eq!(
+        ast.to_string(),
+        "\
+a: ADD b, d, b
+JZ #0, #b
+b: MUT 1101, 3, 3, 30 ; runtime-modified
+JNZ d, #c
+ADD #1, #0, d
+JZ #0, #a
+c: HLT
+d: DB 0 ; runtime-modified
+"
+    );
+}
+
+#[test]
+fn semantic_label_scheme() {
+    let intcode = vec![
+        1, 7, 22, 7, 1106, 0, 7, 1101, 3, 3, 30, 1005, 22, 21, 1101, 1, 0, 22, 1106, 0, 0, 99, 0,
+    ];
+    let opts = Options::new().label_scheme(LabelScheme::Semantic);
+    let Disassembly { ast, .. } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    assert_eq!(
+        ast.to_string(),
+        "\
+loop_a: ADD b, data_d, b
+JZ #0, #b
+b: MUT 1101, 3, 3, 30
+JNZ data_d, #c
+ADD #1, #0, data_d
+JZ #0, #loop_a
+c: HLT
+data_d: DB 0
+"
+    );
+}
+
+#[test]
+fn routine_sections() {
+    let asm = "
+    ADD #a, #0, ret
+    JZ #0, #func
+a:  ADD #b, #0, ret
+    JZ #0, #func
+b:  HLT
+func:
+    OUT #1
+    JZ #0, ret
+ret:
+    DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let opts = Options::new().sections(true);
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(output, run_once(), &opts).unwrap();
+    assert_eq!(report.routines, [15]);
+    assert_eq!(
+        ast.to_string(),
+        "\
+ADD #7, #0, b
+JZ #0, #a
+ADD #14, #0, b
+JZ #0, #a
+HLT
+
+; routine `a`
+a: OUT #1
+JZ #0, b
+b: DB 0
+"
+    );
+}
+
+#[test]
+fn xrefs() {
+    let intcode = vec![
+        1, 7, 22, 7, 1106, 0, 7, 1101, 3, 3, 30, 1005, 22, 21, 1101, 1, 0, 22, 1106, 0, 0, 99, 0,
+    ];
+    let opts = Options::new().xrefs(true);
+    let Disassembly { ast, .. } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    assert_eq!(
+        ast.to_string(),
+        "\
+a: ADD b, d, b ; xrefs: b+13
+JZ #0, #b
+b: MUT 1101, 3, 3, 30 ; xrefs: a+1, a+3, a+6
+JNZ d, #c
+ADD #1, #0, d
+JZ #0, #a
+c: HLT ; xrefs: b+6
+d: DB 0 ; xrefs: a+2, b+5, b+10
+"
+    );
+}
+
+#[test]
+fn nested_loops() {
+    let asm = "
+outer:
+    ADD #3, #0, j
+inner:
+    OUT j
+    ADD j, #-1, j
+    JNZ j, #inner
+    ADD i, #-1, i
+    JNZ i, #outer
+    HLT
+i:  DB 2
+j:  DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let opts = Options::new().loops(true);
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(output, run_once(), &opts).unwrap();
+    assert_eq!(
+        report.loops,
+        [
+            Loop {
+                header: 0,
+                back_edges: vec![17],
+                body: vec![0, 4, 6, 10, 13, 17],
+                depth: 1,
+            },
+            Loop {
+                header: 4,
+                back_edges: vec![10],
+                body: vec![4, 6, 10],
+                depth: 2,
+            },
+        ]
+    );
+    assert_eq!(
+        ast.to_string(),
+        "\
+a: ADD #3, #0, d ; loop start
+b: OUT d ; loop start
+ADD d, #-1, d
+JNZ d, #b ; back-edge to b
+ADD c, #-1, c
+JNZ c, #a ; back-edge to a
+HLT
+c: DB 2
+d: DB 0
+"
+    );
+}
+
+#[test]
+fn constant_table() {
+    let asm = "
+    ADD #3, #0, j
+a:  OUT #72
+    ADD j, #-1, j
+    JNZ j, #a
+    MUL #3, #72, j
+    HLT
+j:  DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let opts = Options::new().constants(true);
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(output, run_once(), &opts).unwrap();
+    let c = |value, count, instrs: &[usize]| Constant {
+        value,
+        count,
+        instrs: instrs.to_vec(),
+    };
+    assert_eq!(
+        report.constants,
+        [
+            c(-1, 1, &[6]),
+            c(0, 1, &[0]),
+            c(3, 2, &[0, 13]),
+            c(72, 2, &[4, 13])
+        ]
+    );
+    assert_eq!(
+        ast.to_string(),
+        "\
+ADD #3, #0, b
+a: OUT #72
+ADD b, #-1, b
+JNZ b, #a
+MUL #3, #72, b
+HLT
+b: DB 0
+
+; constants:
+;   -1 used 1 time by a+2
+;   0 used 1 time by 0
+;   3 used 2 times by 0, a+9
+;   72 used 2 times by a, a+9
+"
+    );
+}
+
+#[test]
+fn input_taint() {
+    let asm = "
+    IN x
+    ADD x, #1, z
+    OUT z
+    EQ x, #5, y
+    JNZ y, #yes
+    OUT #0
+    HLT
+yes:
+    HLT
+x:  DB 0
+y:  DB 0
+z:  DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let Disassembly { report, .. } =
+        intcode_disassemble::disassemble(output, run_once(), &Options::new()).unwrap();
+    assert_eq!(report.tainted_outputs, [6]);
+    assert_eq!(report.tainted_jumps, [12]);
+}
+
+#[test]
+fn pseudo_code() {
+    let asm = "
+    IN x
+loop:
+    ADD x, #1, x
+    ADD x, #-1, y
+    LT x, #100, t
+    JNZ t, #loop
+    ARB #3
+    OUT rb+1
+    HLT
+x:  DB 0
+y:  DB 0
+t:  DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let opts = Options::new().pseudo(true);
+    let Disassembly { ast, .. } =
+        intcode_disassemble::disassemble(output, run_once(), &opts).unwrap();
+    assert_eq!(
+        ast.to_string(),
+        "\
+IN b ; b = input()
+a: ADD b, #1, b ; b = b + 1
+ADD b, #-1, c ; c = b - 1
+LT b, #100, d ; d = b < 100
+JNZ d, #a ; if (b < 100) goto a
+ARB #3 ; rb += 3
+OUT rb+1 ; output(rb[1])
+HLT ; halt
+b: DB 0
+c: DB 0
+d: DB 0
+"
+    );
+}
+
+#[test]
+fn pseudo_code_overflow() {
+    // The jumps are never taken so the runs don't overflow, they only mark
+    // each instruction as code.
+    let (max, min) = (i64::MAX, i64::MIN);
+    let intcode = vec![
+        1005, 28, 13, 1005, 28, 17, 1005, 28, 21, 1005, 28, 25, 99, 1101, max, 1, 28, 1102, max, 2,
+        28, 1001, 28, min, 28, 109, min, 99, 0,
+    ];
+    let opts = Options::new().pseudo(true);
+    let Disassembly { ast, .. } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    let lines: Vec<_> = ast
+        .to_string()
+        .lines()
+        .filter_map(|line| line.split_once(" ; ").map(|(_, pseudo)| pseudo.to_owned()))
+        .collect();
+    assert_eq!(
+        lines[5..9],
+        [
+            format!("[28] = {} + 1", max),
+            format!("[28] = {} * 2", max),
+            format!("[28] = [28] + {}", min),
+            format!("rb += {}", min),
+        ]
+    );
+}
+
+#[test]
+fn low_confidence_instructions() {
+    let intcode = vec![1105, 0, 4, 99, 1, 9, 9, 9, 99, 0];
+    let opts = Options::new().confidence(true);
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    assert_eq!(
+        report.doubts,
+        [Doubt {
+            addr: 4,
+            suspicion: Suspicion::AllPositional
+        }]
+    );
+    assert_eq!(
+        ast.to_string(),
+        "\
+JNZ #0, #a
+HLT
+a: ADD 9, 9, 9 ; low confidence: all the modes are positional
+DB 99, 0
+"
+    );
+}
+
+#[test]
+fn confidence_per_address() {
+    let intcode = vec![1105, 0, 4, 99, 1, 9, 9, 9, 99, 0];
+    let opts = Options::new().min_confidence(Confidence::High);
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    use Confidence::*;
+    assert_eq!(
+        report.confidence,
+        [High, High, High, High, Medium, Medium, Medium, Medium, Low, Low]
+    );
+    assert_eq!(
+        ast.to_string(),
+        "JNZ #0, #a\nHLT\na: DB 1, 9, 9, 9, 99, 0\n"
+    );
+}
+
+#[test]
+fn stream_listing() {
+    let asm = "
+    IN x
+    OUT x
+    HLT
+x:  DB 0
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let opts = Options::new().sections(true).constants(true);
+    let Disassembly { ast, .. } =
+        intcode_disassemble::disassemble(output, run_once(), &opts).unwrap();
+    let mut listing = Vec::new();
+    ast.stream_to(&mut listing).unwrap();
+    assert_eq!(String::from_utf8(listing).unwrap(), ast.to_string());
+}
+
+#[test]
+fn sparse_memory() {
+    let asm = "
+    ARB #1000000000000
+    IN rb
+    ADD rb, #1, rb+1000000
+    OUT rb+1000000
+    HLT
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(output, run_once(), &Options::new()).unwrap();
+    assert_eq!(report.tainted_outputs, [8]);
+    assert_eq!(
+        ast.to_string(),
+        "\
+ARB #1000000000000
+IN rb
+ADD rb, #1, rb+1000000
+OUT rb+1000000
+HLT
+"
+    );
+}
+
+#[test]
+fn fold_relative_base() {
+    let intcode = vec![109, 100, 21101, 7, 0, 0, 204, 0, 99];
+    let opts = Options::new().fold_relative_base(true);
+    let Disassembly { ast, .. } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    assert_eq!(ast.to_string(), "RBINIT 100\nADD #7, #0, rb\nOUT rb\nHLT\n");
+}
+
+#[test]
+fn analysis_budget() {
+    let asm = "
+loop:
+    OUT i
+    ADD i, #-1, i
+    JNZ i, #loop
+    HLT
+i:  DB 3
+";
+    let Intcode { output, .. } = intcode_assemble::to_intcode(asm).unwrap();
+    let analyse = |budget| {
+        let opts = Options::new().loops(true).budget(budget);
+        intcode_disassemble::disassemble(output.clone(), run_once(), &opts)
+            .unwrap()
+            .report
+    };
+    let report = analyse(Budget::new());
+    assert!(!report.budget_exhausted);
+    assert_eq!(report.loops.len(), 1);
+
+    let report = analyse(Budget::new().max_iterations(0));
+    assert!(report.budget_exhausted);
+    assert_eq!(report.loops, []);
+
+    let report = analyse(Budget::new().max_visits(0));
+    assert!(report.budget_exhausted);
+    assert_eq!(report.confidence[..10], [Confidence::High; 10]);
+}
+
+#[test]
+fn dynamic_budget_and_progress() {
+    // Loops forever outputting the same value.
+    let intcode = vec![104, 1, 1105, 1, 0];
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let opts = Options::new().progress({
+        let seen = seen.clone();
+        move |progress| seen.lock().unwrap().push(progress)
+    });
+    let runs = [
+        Run::new().input(Input::Forever(0)).max_steps(250_000),
+        Run::new()
+            .input(Input::Forever(0))
+            .timeout(Duration::from_millis(10)),
+    ];
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(intcode, runs, &opts).unwrap();
+    assert_eq!(ast.to_string(), "a: OUT #1\nJNZ #1, #a\n");
+    assert_eq!(report.stopped_runs, [0, 1]);
+
+    let seen = seen.lock().unwrap();
+    let first: Vec<_> = seen
+        .iter()
+        .filter(|p| p.run == 0)
+        .map(|p| (p.steps, p.percent_marked))
+        .collect();
+    assert_eq!(
+        first,
+        [(100_000, 100.0), (200_000, 100.0), (250_000, 100.0)]
+    );
+    assert_eq!(seen.last().unwrap().run, 1);
+}
+
+#[test]
+fn stmt_addresses() {
+    let intcode = vec![109, 100, 104, 72, 99, 7, 8];
+    let opts = Options::new().fold_relative_base(true);
+    let Disassembly { ast, .. } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    let addrs: Vec<_> = ast.iter().map(Stmt::addrs).collect();
+    assert_eq!(addrs, [0..2, 2..4, 4..5, 5..7]);
+    assert_eq!(ast.stmt_at(0).unwrap().instr, Instr::RelativeBase(100));
+    assert_eq!(
+        ast.stmt_at(3).unwrap().instr,
+        Instr::Output(Param::Number(Mode::Immediate, 72))
+    );
+    assert_eq!(ast.stmt_at(6).unwrap().addr, 5);
+    assert_eq!(ast.stmt_at(7), None);
+}
+
+#[test]
+fn relocation_loop() {
+    // Copies `OUT #42` and `HLT` to address 1000 and jumps there.
+    let intcode = vec![
+        1001, 23, 0, 1000, 101, 1, 1, 1, 101, 1, 3, 3, 101, -1, 22, 22, 1005, 22, 0, 1106, 0, 1000,
+        3, 104, 42, 99,
+    ];
+    let opts = Options::new().comments(true);
+    let Disassembly { ast, report } =
+        intcode_disassemble::disassemble(intcode, run_once(), &opts).unwrap();
+    assert_eq!(
+        report.relocations,
+        [Relocation {
+            header: 0,
+            back_edge: 16,
+            src: 23,
+            dst: 1000,
+            len: 3,
+            entry: 1000,
+        }]
+    );
+    assert!(ast.stmt_at(0).unwrap().comments.contains(&String::from(
+        "relocation loop: copies 3 addresses from 23 to 1000"
+    )));
+    let stmt = ast.stmt_at(23).unwrap();
+    assert_eq!(
+        stmt.instr,
+        Instr::Output(Param::Number(Mode::Immediate, 42))
+    );
+    assert_eq!(
+        stmt.comments,
+        ["copied to 1000 by the relocation loop at a", "runs at 1000"]
+    );
+    assert_eq!(ast.stmt_at(25).unwrap().instr, Instr::Halt);
+    assert_eq!(report.confidence[23..], [Confidence::Medium; 3]);
+}
+
+#[test]
+fn relocation_loop_overflow() {
+    // The bound and the destination are both as far from the copied block as
+    // they can be.
+    let intcode = vec![
+        1001,
+        21,
+        0,
+        i64::MAX,
+        101,
+        1,
+        1,
+        1,
+        101,
+        1,
+        3,
+        3,
+        1007,
+        1,
+        i64::MIN,
+        22,
+        1005,
+        22,
+        0,
+        99,
+        0,
+        0,
+        0,
+    ];
+    let Disassembly { report, .. } =
+        intcode_disassemble::disassemble(intcode, None, &Options::new()).unwrap();
+    assert_eq!(report.relocations, []);
+}
@@ -0,0 +1,128 @@
+//! Input made up of several files.
+//!
+//! The files are stored one after another in a single string, separated by a
+//! newline, so that a [`Span`] can still point anywhere in the input.
+//! [`Files::locate()`] turns it back into a [`FileSpan`] in the file it
+//! points into.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::span::Span;
+
+/// Represents a location in one of the files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileSpan {
+    /// The index of the file.
+    pub file: usize,
+    /// The span relative to the start of the file.
+    pub span: Span,
+}
+
+/// A single file in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    /// The name of the file, usually its path.
+    pub name: PathBuf,
+    /// Where the file is in the whole input.
+    pub range: Range<usize>,
+    /// The span of the directive that included this file, if any.
+    pub parent: Option<Span>,
+}
+
+/// The files that make up the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Files {
+    text: String,
+    files: Vec<File>,
+}
+
+impl Files {
+    /// Constructs a new set of files with the main file.
+    pub fn new(name: impl Into<PathBuf>, text: impl Into<String>) -> Self {
+        let text = text.into();
+        let files = vec![File {
+            name: name.into(),
+            range: 0..text.len(),
+            parent: None,
+        }];
+        Self { text, files }
+    }
+
+    /// Adds a file that was included by the directive at the given span,
+    /// returning its index.
+    pub fn push(&mut self, name: impl Into<PathBuf>, text: &str, parent: Span) -> usize {
+        // The separator makes sure the end of one file is never the start of
+        // the next.
+        self.text.push('\n');
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.files.push(File {
+            name: name.into(),
+            range: start..self.text.len(),
+            parent: Some(parent),
+        });
+        self.files.len() - 1
+    }
+
+    /// Returns the whole input.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the number of files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns whether there are no files, which is never the case.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the file with the given index.
+    pub fn get(&self, file: usize) -> &File {
+        &self.files[file]
+    }
+
+    /// Returns the name of the file with the given index.
+    pub fn name(&self, file: usize) -> &Path {
+        &self.files[file].name
+    }
+
+    /// Returns the text of the file with the given index.
+    pub fn source(&self, file: usize) -> &str {
+        &self.text[self.files[file].range.clone()]
+    }
+
+    /// Returns the index of the file containing the given index into the
+    /// whole input.
+    pub fn file_of(&self, index: usize) -> usize {
+        self.files
+            .iter()
+            .rposition(|f| f.range.start <= index)
+            .unwrap_or(0)
+    }
+
+    /// Returns the file and the relative span of the given span.
+    pub fn locate(&self, span: Span) -> FileSpan {
+        let file = self.file_of(span.m);
+        let start = self.files[file].range.start;
+        FileSpan {
+            file,
+            span: Span {
+                m: span.m - start,
+                n: span.n - start,
+            },
+        }
+    }
+
+    /// Returns the span in the main file that the given span was included
+    /// from, which is the span itself if it is in the main file.
+    pub fn origin(&self, mut span: Span) -> Span {
+        while let Some(parent) = self.files[self.file_of(span.m)].parent {
+            span = parent;
+        }
+        span
+    }
+}
@@ -1,4 +1,4 @@
-use std::cmp::max;
+use std::cmp::{max, min};
 use std::fmt;
 use std::fmt::Display;
 use std::marker::PhantomData;
@@ -6,9 +6,14 @@ use std::ops::Range;
 use std::path::Path;
 
 use dairy::Cow;
+use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
-use crate::{Error, Span, Warning};
+use crate::files::{FileSpan, Files};
+use crate::{Error, Note, Span, Warning};
+
+/// The maximum number of other locations listed for a merged diagnostic.
+const ALSO_LIMIT: usize = 3;
 
 pub trait Paint {
     fn fmt<D: Display>(
@@ -17,12 +22,33 @@ pub trait Paint {
         mark: Mark,
         f: &mut fmt::Formatter<'_>,
     ) -> fmt::Result;
+
+    /// Formats the whole diagnostic instead of the source snippet, for
+    /// formats that are read by other programs.
+    fn diagnostic(_: &Diagnostic<'_>) -> Option<String> {
+        None
+    }
+}
+
+/// A diagnostic located in the file it points into.
+#[derive(Debug, Clone, Copy)]
+pub struct Diagnostic<'a> {
+    pub ctx: Context,
+    pub msg: &'a str,
+    pub filename: &'a Path,
+    /// The span relative to the start of the file.
+    pub span: Span,
+    /// The line number, starting at one.
+    pub line: usize,
+    /// The display column, starting at one.
+    pub col: usize,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum Context {
     Error,
     Warning,
+    Note,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -36,6 +62,7 @@ pub enum Mark {
 pub struct Options<'i, P> {
     input: &'i str,
     filename: Cow<'i, Path>,
+    files: Option<&'i Files>,
     mark: PhantomData<P>,
 }
 
@@ -52,18 +79,86 @@ impl Plain {
     pub fn new(input: &str) -> Options<'_, Self> {
         Options::new(input, "<input>")
     }
+
+    pub fn files(files: &Files) -> Options<'_, Self> {
+        Options::with_files(files)
+    }
+}
+
+/// Formats each diagnostic as a JSON object on a single line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Paint for Json {
+    fn fmt<D: Display>(this: D, _: Context, _: Mark, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&this, f)
+    }
+
+    fn diagnostic(d: &Diagnostic<'_>) -> Option<String> {
+        let severity = match d.ctx {
+            Context::Error => "error",
+            Context::Warning => "warning",
+            Context::Note => "note",
+        };
+        Some(format!(
+            r#"{{"severity":"{}","message":"{}","file":"{}","span":{{"start":{},"end":{}}},"line":{},"column":{}}}"#,
+            severity,
+            escape_json(d.msg),
+            escape_json(&d.filename.to_string_lossy()),
+            d.span.m,
+            d.span.n,
+            d.line,
+            d.col,
+        ))
+    }
+}
+
+impl Json {
+    pub fn new(input: &str) -> Options<'_, Self> {
+        Options::new(input, "<input>")
+    }
+
+    pub fn files(files: &Files) -> Options<'_, Self> {
+        Options::with_files(files)
+    }
+}
+
+/// Escapes the string for use inside a JSON string.
+fn escape_json(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Returns the number of columns the string takes up in a terminal.
+///
+/// Each grapheme cluster is at most two columns wide, so that emoji joined
+/// with zero width joiners are not counted as several emoji.
+fn width(s: &str) -> usize {
+    s.graphemes(true).map(|g| min(2, g.width())).sum()
 }
 
+/// Returns the line and display column of the byte offset.
 fn to_line_col(lines: &[&str], offset: usize) -> (usize, usize) {
     let mut n = 0;
     for (i, line) in lines.iter().enumerate() {
-        let len = line.width() + 1;
+        let len = line.len() + 1;
         if n + len > offset {
-            return (i, offset - n);
+            return (i, width(&line[..offset - n]));
         }
         n += len;
     }
-    (lines.len(), lines.last().map(|l| l.width()).unwrap_or(0))
+    (lines.len(), lines.last().copied().map(width).unwrap_or(0))
 }
 
 impl<'i, P: Paint> Options<'i, P> {
@@ -72,10 +167,54 @@ impl<'i, P: Paint> Options<'i, P> {
             mark: PhantomData,
             input,
             filename: filename.into(),
+            files: None,
+        }
+    }
+
+    /// Constructs options for formatting diagnostics with spans that point
+    /// into any of the files.
+    pub fn with_files(files: &'i Files) -> Self {
+        Self {
+            mark: PhantomData,
+            input: files.text(),
+            filename: files.name(0).into(),
+            files: Some(files),
         }
     }
 
-    fn fmt(&self, ctx: Context, msg: &Cow<'_, str>, span: Span) -> String {
+    /// Returns the name and text of the file the span points into, and the
+    /// span relative to it.
+    fn locate(&self, span: Span) -> (&Path, &str, Span) {
+        match self.files {
+            Some(files) => {
+                let FileSpan { file, span } = files.locate(span);
+                (files.name(file), files.source(file), span)
+            }
+            None => (&self.filename, self.input, span),
+        }
+    }
+
+    fn fmt(
+        &self,
+        ctx: Context,
+        msg: &Cow<'_, str>,
+        span: Span,
+        also: &[Span],
+        notes: &[Note],
+    ) -> String {
+        let mut out = self.snippet(ctx, msg, span, also);
+        for note in notes {
+            // Formats for other programs put each diagnostic on its own line.
+            if !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str(&self.snippet(Context::Note, &note.msg, note.span, &[]));
+        }
+        out
+    }
+
+    /// Formats a single diagnostic without its notes.
+    fn snippet(&self, ctx: Context, msg: &Cow<'_, str>, span: Span, also: &[Span]) -> String {
         struct Painted<P, D> {
             paint: PhantomData<P>,
             display: D,
@@ -89,10 +228,22 @@ impl<'i, P: Paint> Options<'i, P> {
             }
         }
 
+        let (filename, input, span) = self.locate(span);
+        let lines: Vec<_> = input.split_terminator('\n').collect();
+        let (line, col) = to_line_col(&lines, span.m);
+        let diagnostic = Diagnostic {
+            ctx,
+            msg: &**msg,
+            filename,
+            span,
+            line: line + 1,
+            col: col + 1,
+        };
+        if let Some(out) = P::diagnostic(&diagnostic) {
+            return out;
+        }
         let span: Range<usize> = span.into();
-        let lines: Vec<_> = self.input.split_terminator('\n').collect();
-        let (line, col) = to_line_col(&lines, span.start);
-        let width = max(1, self.input[span].width());
+        let width = max(1, width(&input[span]));
         let code = lines.get(line).unwrap_or_else(|| lines.last().unwrap());
 
         macro_rules! mark {
@@ -114,7 +265,7 @@ impl<'i, P: Paint> Options<'i, P> {
         let underline = mark!(Underline, "^".repeat(width));
         let msg = mark!(Message, msg);
 
-        format!(
+        let mut out = format!(
             "\n\
             {0:pad$} {arrow} {filename}:{line}:{col}\n \
             {0:pad$} {pipe}\n \
@@ -123,7 +274,7 @@ impl<'i, P: Paint> Options<'i, P> {
             "",
             pad = pad,
             arrow = arrow,
-            filename = self.filename.display(),
+            filename = filename.display(),
             line = line + 1,
             col = col + 1,
             pipe = pipe,
@@ -132,14 +283,65 @@ impl<'i, P: Paint> Options<'i, P> {
             underline = underline,
             width = col + width,
             msg = msg
-        )
+        );
+        if !also.is_empty() {
+            let mut locations = also
+                .iter()
+                .take(ALSO_LIMIT)
+                .map(|span| {
+                    let (name, input, span) = self.locate(*span);
+                    let lines: Vec<_> = input.split_terminator('\n').collect();
+                    let (line, col) = to_line_col(&lines, span.m);
+                    match name == filename {
+                        true => format!("{}:{}", line + 1, col + 1),
+                        false => format!("{}:{}:{}", name.display(), line + 1, col + 1),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            if also.len() > ALSO_LIMIT {
+                locations.push_str(&format!(", and {} more", also.len() - ALSO_LIMIT));
+            }
+            out.push_str(&format!(
+                " {0:pad$} {eq} also at {locations} ({n} occurrences)\n",
+                "",
+                pad = pad,
+                eq = mark!(Margin, "="),
+                locations = locations,
+                n = also.len() + 1
+            ));
+        }
+        out
     }
 
     pub fn error(&self, error: &Error) -> String {
-        self.fmt(Context::Error, &error.msg, error.span)
+        self.fmt(
+            Context::Error,
+            &error.msg,
+            error.span,
+            &error.also,
+            &error.notes,
+        )
     }
 
     pub fn warning(&self, warning: &Warning) -> String {
-        self.fmt(Context::Warning, &warning.msg, warning.span)
+        self.fmt(
+            Context::Warning,
+            &warning.msg,
+            warning.span,
+            &warning.also,
+            &warning.notes,
+        )
+    }
+
+    /// Formats an informational message about the given span.
+    pub fn note(&self, msg: &str, span: impl Into<Span>) -> String {
+        self.snippet(Context::Note, &Cow::from(msg), span.into(), &[])
+    }
+
+    /// Formats the number of errors and warnings left out by
+    /// [`ErrorSet::normalize()`][crate::ErrorSet::normalize].
+    pub fn omitted(&self, n: usize) -> String {
+        format!("\n... and {} more\n", n)
     }
 }
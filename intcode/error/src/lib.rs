@@ -1,8 +1,12 @@
 //! Defines an error type for the compiler.
 
+pub mod files;
+#[cfg(feature = "fmt")]
 pub mod fmt;
 pub mod span;
 
+use std::slice;
+
 use dairy::Cow;
 use thiserror::Error;
 
@@ -21,18 +25,50 @@ pub type ResultSet<T> = std::result::Result<T, ErrorSet>;
 pub struct Error {
     pub msg: Cow<'static, str>,
     pub span: Span,
+    /// The locations of errors with the same message merged into this one by
+    /// [`ErrorSet::normalize()`].
+    pub also: Vec<Span>,
+    /// Other locations involved in the error.
+    pub notes: Vec<Note>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Warning {
     pub msg: Cow<'static, str>,
     pub span: Span,
+    /// The locations of warnings with the same message merged into this one
+    /// by [`ErrorSet::normalize()`].
+    pub also: Vec<Span>,
+    /// Other locations involved in the warning.
+    pub notes: Vec<Note>,
+    /// The name of the lint that raised the warning, so that it can be
+    /// allowed or denied on its own.
+    pub lint: Option<&'static str>,
+}
+
+/// A location that explains an error or warning, like where the thing it is
+/// about was defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    pub msg: Cow<'static, str>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct ErrorSet {
     pub errors: Vec<Error>,
     pub warnings: Vec<Warning>,
+    /// The number of errors and warnings removed by
+    /// [`ErrorSet::normalize()`] because there were too many.
+    pub omitted: usize,
+}
+
+/// Access to the parts of an error or warning that are normalized.
+trait Diagnostic {
+    fn msg(&self) -> &str;
+    fn span(&self) -> Span;
+    fn also(&mut self) -> &mut Vec<Span>;
+    fn notes(&self) -> &[Note];
 }
 
 impl Error {
@@ -40,8 +76,16 @@ impl Error {
         Self {
             span: span.into(),
             msg: msg.into(),
+            also: Vec::new(),
+            notes: Vec::new(),
         }
     }
+
+    /// Adds a note about another location involved in the error.
+    pub fn note(mut self, msg: impl Into<Cow<'static, str>>, span: impl Into<Span>) -> Self {
+        self.notes.push(Note::new(msg, span));
+        self
+    }
 }
 
 impl Warning {
@@ -49,6 +93,162 @@ impl Warning {
         Self {
             span: span.into(),
             msg: msg.into(),
+            also: Vec::new(),
+            notes: Vec::new(),
+            lint: None,
+        }
+    }
+
+    /// Adds a note about another location involved in the warning.
+    pub fn note(mut self, msg: impl Into<Cow<'static, str>>, span: impl Into<Span>) -> Self {
+        self.notes.push(Note::new(msg, span));
+        self
+    }
+
+    /// Tags the warning with the name of the lint that raised it.
+    pub fn lint(mut self, lint: &'static str) -> Self {
+        self.lint = Some(lint);
+        self
+    }
+
+    /// Turns the warning into an error with the same message and spans.
+    pub fn into_error(self) -> Error {
+        let Self {
+            msg,
+            span,
+            also,
+            notes,
+            ..
+        } = self;
+        Error {
+            msg,
+            span,
+            also,
+            notes,
+        }
+    }
+}
+
+impl Note {
+    pub fn new(msg: impl Into<Cow<'static, str>>, span: impl Into<Span>) -> Self {
+        Self {
+            span: span.into(),
+            msg: msg.into(),
+        }
+    }
+}
+
+macro_rules! impl_diagnostic {
+    ($($ty:ty),*) => {
+        $(
+            impl Diagnostic for $ty {
+                fn msg(&self) -> &str {
+                    &self.msg
+                }
+
+                fn span(&self) -> Span {
+                    self.span
+                }
+
+                fn also(&mut self) -> &mut Vec<Span> {
+                    &mut self.also
+                }
+
+                fn notes(&self) -> &[Note] {
+                    &self.notes
+                }
+            }
+        )*
+    };
+}
+
+impl_diagnostic!(Error, Warning);
+
+/// Sorts the diagnostics by span and merges the ones with the same message,
+/// lint, and notes into the first of them.
+///
+/// Comparing the notes keeps diagnostics about different things apart, like
+/// the redefinitions of two different labels.
+fn merge<D: Diagnostic>(diagnostics: &mut Vec<D>, lint: fn(&D) -> Option<&'static str>) {
+    diagnostics.sort_by_key(D::span);
+    let mut merged: Vec<D> = Vec::new();
+    for mut d in diagnostics.drain(..) {
+        match merged
+            .iter_mut()
+            .find(|m| m.msg() == d.msg() && lint(m) == lint(&d) && m.notes() == d.notes())
+        {
+            Some(m) => {
+                let span = d.span();
+                m.also().push(span);
+                m.also().append(d.also());
+            }
+            None => merged.push(d),
+        }
+    }
+    *diagnostics = merged;
+}
+
+impl ErrorSet {
+    /// The maximum number of errors and of warnings kept by
+    /// [`normalize()`][Self::normalize].
+    pub const LIMIT: usize = 20;
+
+    /// Returns whether there are no errors or warnings.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+
+    /// Returns the number of errors and warnings.
+    pub fn len(&self) -> usize {
+        self.errors.len() + self.warnings.len()
+    }
+
+    /// Returns an iterator over the errors.
+    pub fn errors(&self) -> slice::Iter<'_, Error> {
+        self.errors.iter()
+    }
+
+    /// Returns an iterator over the warnings.
+    pub fn warnings(&self) -> slice::Iter<'_, Warning> {
+        self.warnings.iter()
+    }
+
+    /// Moves the errors and warnings from the other set into this one.
+    ///
+    /// Spans are not adjusted, so this is only meaningful for sets from the
+    /// same input unless they are formatted separately afterwards.
+    pub fn merge(&mut self, mut other: ErrorSet) {
+        self.errors.append(&mut other.errors);
+        self.warnings.append(&mut other.warnings);
+        self.omitted += other.omitted;
+    }
+
+    /// Sorts the errors and warnings by where they are.
+    ///
+    /// Diagnostics with the same span keep their order.
+    pub fn sort_by_span(&mut self) {
+        self.errors.sort_by_key(|e| e.span);
+        self.warnings.sort_by_key(|w| w.span);
+    }
+
+    /// Turns every warning into an error.
+    pub fn promote_warnings(&mut self) {
+        self.errors
+            .extend(self.warnings.drain(..).map(Warning::into_error));
+    }
+
+    /// Sorts the errors and warnings by where they are, merges the ones with
+    /// identical messages, lints, and notes, and removes any past the
+    /// [limit][Self::LIMIT].
+    ///
+    /// The number of removed errors and warnings is added to `omitted`.
+    pub fn normalize(&mut self) {
+        merge(&mut self.errors, |_| None);
+        merge(&mut self.warnings, |w| w.lint);
+        for len in [self.errors.len(), self.warnings.len()] {
+            self.omitted += len.saturating_sub(Self::LIMIT);
         }
+        self.errors.truncate(Self::LIMIT);
+        self.warnings.truncate(Self::LIMIT);
     }
 }
@@ -48,6 +48,13 @@ struct CharIndices<'i> {
     len: usize,
 }
 
+/// An iterator over the tokens in a single line, created with [`line()`].
+#[derive(Debug, Clone)]
+pub struct LineTokens<'i> {
+    tokens: Tokens<'i>,
+    done: bool,
+}
+
 /// An iterator over input tokens.
 #[derive(Debug, Clone)]
 pub struct Tokens<'i> {
@@ -127,6 +134,17 @@ impl<'i> Tokens<'i> {
         Self { iter }
     }
 
+    /// Constructs a new iterator over the tokens in part of the input.
+    ///
+    /// Spans are still relative to the start of the whole input.
+    pub fn with_range(input: &'i str, range: ops::Range<usize>) -> Self {
+        let mut iter = CharIndices::new(&input[..range.end]);
+        while iter.peek_index() < range.start {
+            iter.next();
+        }
+        Self { iter }
+    }
+
     /// Lexes the next character if the predicate is satisfied.
     fn lex_if<P>(&mut self, predicate: P) -> bool
     where
@@ -169,6 +187,7 @@ impl<'i> Tokens<'i> {
     }
 
     /// Returns the next token in the lexer.
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<S<Token>> {
         let next = match self.iter.next() {
             None => {
@@ -222,3 +241,34 @@ impl<'i> Tokens<'i> {
         }
     }
 }
+
+/// Returns an iterator over the tokens in a single line of input.
+///
+/// This is meant for editors that highlight each line as it changes. Strings
+/// and comments never continue onto the next line, so each line can be lexed
+/// on its own. Spans are relative to the start of the line and lexing stops
+/// at the first newline. Unlike [`Tokens`], lexing continues after an
+/// invalid token so that the rest of the line can still be highlighted.
+pub fn line(line: &str) -> LineTokens<'_> {
+    LineTokens {
+        tokens: Tokens::new(line),
+        done: false,
+    }
+}
+
+impl Iterator for LineTokens<'_> {
+    type Item = Result<S<Token>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.tokens.next() {
+            Ok(S(Token::Newline | Token::Eof, _)) => {
+                self.done = true;
+                None
+            }
+            result => Some(result),
+        }
+    }
+}
@@ -11,8 +11,9 @@ pub enum Sign {
     Positive,
 }
 
-/// Parse an integer.
-pub fn integer(input: &str, span: Span, sign: Sign) -> Result<i64> {
+/// Parse an integer that fits in a signed integer with the given number of
+/// bits, at most 64.
+pub fn integer(input: &str, span: Span, sign: Sign, bits: u32) -> Result<i64> {
     let digits = span.as_str(input).as_bytes();
     let (i, radix) = match digits {
         [b'0', b'b', ..] => (2, 2),
@@ -20,11 +21,22 @@ pub fn integer(input: &str, span: Span, sign: Sign) -> Result<i64> {
         [b'0', b'x', ..] => (2, 16),
         _ => (0, 10),
     };
-    digits[i..]
+    let err = || {
+        Error::new(
+            format!(
+                "base {} literal out of range for {}-bit integer",
+                radix, bits
+            ),
+            span,
+        )
+    };
+    let max = (1i128 << (bits - 1)) - 1;
+    let min = -(1i128 << (bits - 1));
+    let value = digits[i..]
         .iter()
         .enumerate()
         .filter(|(_, &d)| d != b'_')
-        .try_fold(0i64, |acc, (j, &d)| {
+        .try_fold(0i128, |acc, (j, &d)| {
             let x = (d as char).to_digit(radix).ok_or_else(|| {
                 let m = span.m + i + j;
                 Error::new(
@@ -32,19 +44,15 @@ pub fn integer(input: &str, span: Span, sign: Sign) -> Result<i64> {
                     m..m + 1,
                 )
             })?;
-            let err = || {
-                Error::new(
-                    format!("base {} literal out of range for 64-bit integer", radix),
-                    span,
-                )
-            };
             let value = acc.checked_mul(radix.into()).ok_or_else(err)?;
             match sign {
                 Sign::Positive => value.checked_add(x.into()),
                 Sign::Negative => value.checked_sub(x.into()),
             }
+            .filter(|v| (min..=max).contains(v))
             .ok_or_else(err)
-        })
+        })?;
+    Ok(value as i64)
 }
 
 /// Parse a string.
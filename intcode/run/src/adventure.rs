@@ -0,0 +1,228 @@
+//! Automatically play the text adventure on the cryostasis ship.
+//!
+//! The solver explores every room, picks up every item that is safe to carry,
+//! and then goes to the security checkpoint and tries every combination of
+//! items on the pressure-sensitive floor until the password is printed.
+//!
+//! Items are tested by taking them on a clone of the computer and then moving
+//! through a door, anything that ends the game or stops the droid from moving
+//! is left behind. The only exception is the infinite loop, which can never be
+//! tested because taking it hangs the program.
+
+use std::collections::HashSet;
+
+use crate::{ascii, Computer, Error, Result, State};
+
+/// Items that cannot be tested by taking them.
+const UNTESTABLE: &[&str] = &["infinite loop"];
+
+/// A room described by the game.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Room {
+    pub name: String,
+    pub doors: Vec<String>,
+    pub items: Vec<String>,
+}
+
+/// The text output in response to a command.
+#[derive(Debug)]
+struct Reply {
+    text: String,
+    halted: bool,
+}
+
+#[derive(Debug)]
+struct Solver {
+    computer: Computer,
+    visited: HashSet<String>,
+    inventory: Vec<String>,
+    /// The path from the start to the security checkpoint and the direction
+    /// of the pressure-sensitive floor.
+    checkpoint: Option<(Vec<&'static str>, &'static str)>,
+}
+
+impl Room {
+    /// Parses the last room described in the text, if there is one.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut room: Option<Self> = None;
+        let mut heading = "";
+        for line in text.lines() {
+            if let Some(name) = line.strip_prefix("== ").and_then(|l| l.strip_suffix(" ==")) {
+                room = Some(Self {
+                    name: name.to_owned(),
+                    ..Self::default()
+                });
+                heading = "";
+                continue;
+            }
+            let room = match &mut room {
+                Some(room) => room,
+                None => continue,
+            };
+            match (line.strip_prefix("- "), heading) {
+                (Some(entry), "Doors here lead:") => room.doors.push(entry.to_owned()),
+                (Some(entry), "Items here:") => room.items.push(entry.to_owned()),
+                (Some(_), _) => {}
+                (None, _) => heading = line,
+            }
+        }
+        room
+    }
+}
+
+/// Returns the password printed when the droid is let through.
+fn password(text: &str) -> Option<String> {
+    let (_, rest) = text.split_once("typing ")?;
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    (!digits.is_empty()).then(|| digits)
+}
+
+fn direction(door: &str) -> Option<&'static str> {
+    ["north", "south", "east", "west"]
+        .iter()
+        .copied()
+        .find(|&d| d == door)
+}
+
+fn opposite(dir: &'static str) -> &'static str {
+    match dir {
+        "north" => "south",
+        "south" => "north",
+        "east" => "west",
+        _ => "east",
+    }
+}
+
+/// Runs the computer until the game waits for the next command or ends.
+fn read(c: &mut Computer) -> Result<Reply> {
+    let mut text = String::new();
+    loop {
+        match c.next()?.into_result(c.ptr())? {
+            State::Yielded(value) => text.push(u8::try_from(value).map_or('?', char::from)),
+            State::Waiting => {
+                break Ok(Reply {
+                    text,
+                    halted: false,
+                })
+            }
+            State::Complete => break Ok(Reply { text, halted: true }),
+            _ => {}
+        }
+    }
+}
+
+fn send(c: &mut Computer, cmd: &str) -> Result<Reply> {
+    ascii::send_line(c, cmd);
+    read(c)
+}
+
+impl Solver {
+    /// Returns whether the item can be carried without ending the game.
+    fn is_safe(&self, item: &str, room: &Room) -> Result<bool> {
+        if UNTESTABLE.contains(&item) {
+            return Ok(false);
+        }
+        let mut c = self.computer.clone();
+        if send(&mut c, &format!("take {}", item))?.halted {
+            return Ok(false);
+        }
+        match room.doors.first() {
+            Some(door) => {
+                let reply = send(&mut c, door)?;
+                Ok(Room::parse(&reply.text).is_some() || password(&reply.text).is_some())
+            }
+            None => Ok(true),
+        }
+    }
+
+    /// Explores the room and every room reachable from it, returning to
+    /// the room afterwards.
+    fn visit(&mut self, room: Room, path: &mut Vec<&'static str>) -> Result<Option<String>> {
+        self.visited.insert(room.name.clone());
+        for item in &room.items {
+            if self.is_safe(item, &room)? {
+                send(&mut self.computer, &format!("take {}", item))?;
+                self.inventory.push(item.clone());
+            }
+        }
+        for dir in room.doors.iter().filter_map(|d| direction(d)) {
+            let reply = send(&mut self.computer, dir)?;
+            if let Some(password) = password(&reply.text) {
+                return Ok(Some(password));
+            }
+            let next = match Room::parse(&reply.text) {
+                Some(next) => next,
+                None => continue,
+            };
+            // The pressure-sensitive floor ejects the droid straight back.
+            if next.name == room.name {
+                self.checkpoint = Some((path.clone(), dir));
+                continue;
+            }
+            if !self.visited.contains(&next.name) {
+                path.push(dir);
+                if let Some(password) = self.visit(next, path)? {
+                    return Ok(Some(password));
+                }
+                path.pop();
+            }
+            send(&mut self.computer, opposite(dir))?;
+        }
+        Ok(None)
+    }
+
+    /// Tries every combination of items on the pressure-sensitive floor.
+    fn brute_force(&mut self) -> Result<String> {
+        let (path, floor) = self.checkpoint.take().ok_or(Error::Unsolved {
+            reason: "the security checkpoint was not found",
+        })?;
+        for dir in path {
+            send(&mut self.computer, dir)?;
+        }
+        for item in &self.inventory {
+            send(&mut self.computer, &format!("drop {}", item))?;
+        }
+        // Visit the combinations in gray code order so that only a single
+        // item needs to be taken or dropped each time.
+        let mut held = 0u64;
+        for i in 0u64..1 << self.inventory.len() {
+            let next = i ^ (i >> 1);
+            if let Some(j) = (0..self.inventory.len()).find(|j| (next ^ held) >> j & 1 == 1) {
+                let verb = if next >> j & 1 == 1 { "take" } else { "drop" };
+                send(
+                    &mut self.computer,
+                    &format!("{} {}", verb, self.inventory[j]),
+                )?;
+            }
+            held = next;
+            let reply = send(&mut self.computer, floor)?;
+            if let Some(password) = password(&reply.text) {
+                return Ok(password);
+            }
+            if reply.halted {
+                break;
+            }
+        }
+        Err(Error::Unsolved {
+            reason: "no combination of items was accepted",
+        })
+    }
+}
+
+/// Plays the game and returns the password for the main airlock.
+pub fn solve(mut computer: Computer) -> Result<String> {
+    let reply = read(&mut computer)?;
+    let room = Room::parse(&reply.text).ok_or(Error::Unsolved {
+        reason: "the game did not describe the starting room",
+    })?;
+    let mut solver = Solver {
+        computer,
+        visited: HashSet::new(),
+        inventory: Vec::new(),
+        checkpoint: None,
+    };
+    match solver.visit(room, &mut Vec::new())? {
+        Some(password) => Ok(password),
+        None => solver.brute_force(),
+    }
+}
@@ -0,0 +1,279 @@
+//! Helpers for programs that communicate using ASCII.
+
+use std::mem;
+
+use crate::{Computer, Error, Result};
+
+/// The maximum number of instructions that a springdroid can remember.
+const MAX_INSTRS: usize = 15;
+
+/// A springscript program builder.
+///
+/// Instructions are added using [`and()`][Script::and], [`or()`][Script::or],
+/// and [`not()`][Script::not] and the program is completed using either
+/// [`walk()`][Script::walk] or [`run()`][Script::run] which validate the
+/// program.
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    instrs: Vec<(&'static str, String, String)>,
+}
+
+/// A validated springscript program.
+#[derive(Debug, Clone)]
+pub struct Springscript {
+    lines: Vec<String>,
+}
+
+/// An output decoded by [`DecodeAscii`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decoded {
+    /// A run of printable ASCII, including the newline if it ended in one.
+    Text(String),
+    /// A value that is not printable ASCII.
+    Value(i64),
+}
+
+/// The values that end a line of input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// A line feed, `10`.
+    Lf,
+    /// A carriage return and a line feed, `13,10`.
+    CrLf,
+}
+
+/// How lines of input are translated before they are fed to a program.
+///
+/// Programs written by different people disagree on what ends a line, and
+/// input typed on some platforms ends with a carriage return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineOptions {
+    strip_cr: bool,
+    echo: bool,
+    ending: LineEnding,
+}
+
+/// An iterator adapter that groups printable ASCII outputs into text.
+///
+/// Created with [`decode_ascii()`][DecodeAsciiExt::decode_ascii].
+#[derive(Debug)]
+pub struct DecodeAscii<I> {
+    iter: I,
+    text: String,
+    pending: Option<Result<i64>>,
+}
+
+/// Extends iterators over outputs with [`decode_ascii()`][Self::decode_ascii].
+pub trait DecodeAsciiExt: Iterator<Item = Result<i64>> + Sized {
+    /// Groups runs of printable ASCII outputs that end in a newline into
+    /// text, any other values are passed through as is.
+    fn decode_ascii(self) -> DecodeAscii<Self> {
+        DecodeAscii {
+            iter: self,
+            text: String::new(),
+            pending: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<i64>>> DecodeAsciiExt for I {}
+
+fn is_printable(value: i64) -> bool {
+    matches!(value, 9 | 10 | 32..=126)
+}
+
+impl<I: Iterator<Item = Result<i64>>> Iterator for DecodeAscii<I> {
+    type Item = Result<Decoded>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // A value or error that interrupted some text is returned after the
+        // text.
+        if let Some(pending) = self.pending.take() {
+            return Some(pending.map(Decoded::Value));
+        }
+        loop {
+            match self.iter.next() {
+                Some(Ok(value)) if is_printable(value) => {
+                    self.text.push(value as u8 as char);
+                    if value == 10 {
+                        break Some(Ok(Decoded::Text(mem::take(&mut self.text))));
+                    }
+                }
+                Some(result) if self.text.is_empty() => break Some(result.map(Decoded::Value)),
+                Some(result) => {
+                    self.pending = Some(result);
+                    break Some(Ok(Decoded::Text(mem::take(&mut self.text))));
+                }
+                None if self.text.is_empty() => break None,
+                None => break Some(Ok(Decoded::Text(mem::take(&mut self.text)))),
+            }
+        }
+    }
+}
+
+impl Default for LineEnding {
+    fn default() -> Self {
+        Self::Lf
+    }
+}
+
+impl LineEnding {
+    fn values(self) -> &'static [i64] {
+        match self {
+            Self::Lf => &[10],
+            Self::CrLf => &[13, 10],
+        }
+    }
+}
+
+impl Default for LineOptions {
+    fn default() -> Self {
+        Self {
+            strip_cr: true,
+            echo: false,
+            ending: LineEnding::default(),
+        }
+    }
+}
+
+impl LineOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to remove a carriage return from the end of each line, the
+    /// default is `true`.
+    pub fn strip_cr(mut self, strip_cr: bool) -> Self {
+        self.strip_cr = strip_cr;
+        self
+    }
+
+    /// Whether each line should be echoed to the output by the runner, the
+    /// default is `false`.
+    pub fn echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// What ends each line, the default is [`LineEnding::Lf`].
+    pub fn ending(mut self, ending: LineEnding) -> Self {
+        self.ending = ending;
+        self
+    }
+
+    /// Returns whether each line should be echoed to the output.
+    pub fn is_echo(&self) -> bool {
+        self.echo
+    }
+
+    /// Returns the values to feed for the line.
+    ///
+    /// A trailing newline on the line is replaced with the configured line
+    /// ending, one is added if there isn't one.
+    pub fn encode(&self, line: &str) -> Vec<i64> {
+        let mut line = line.strip_suffix('\n').unwrap_or(line);
+        if self.strip_cr {
+            line = line.strip_suffix('\r').unwrap_or(line);
+        }
+        line.bytes()
+            .map(i64::from)
+            .chain(self.ending.values().iter().copied())
+            .collect()
+    }
+}
+
+/// Feeds the line followed by a newline to the computer.
+pub fn send_line(c: &mut Computer, line: &str) {
+    send_line_with(c, line, &LineOptions::new());
+}
+
+/// Feeds the line followed by the line ending to the computer.
+pub fn send_line_with(c: &mut Computer, line: &str, opts: &LineOptions) {
+    c.feed(opts.encode(line));
+}
+
+/// Feeds each line followed by a newline to the computer.
+pub fn send_program(c: &mut Computer, lines: &[&str]) {
+    for line in lines {
+        send_line(c, line);
+    }
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn instr(mut self, op: &'static str, x: &str, y: &str) -> Self {
+        self.instrs.push((op, x.to_owned(), y.to_owned()));
+        self
+    }
+
+    /// Sets `y` to true if both `x` and `y` are true.
+    pub fn and(self, x: &str, y: &str) -> Self {
+        self.instr("AND", x, y)
+    }
+
+    /// Sets `y` to true if at least one of `x` or `y` is true.
+    pub fn or(self, x: &str, y: &str) -> Self {
+        self.instr("OR", x, y)
+    }
+
+    /// Sets `y` to true if `x` is false.
+    pub fn not(self, x: &str, y: &str) -> Self {
+        self.instr("NOT", x, y)
+    }
+
+    /// Completes the program, only sensors `A` to `D` are available.
+    pub fn walk(self) -> Result<Springscript> {
+        self.finish("WALK", "ABCD")
+    }
+
+    /// Completes the program, sensors `A` to `I` are available.
+    pub fn run(self) -> Result<Springscript> {
+        self.finish("RUN", "ABCDEFGHI")
+    }
+
+    fn finish(self, cmd: &str, sensors: &str) -> Result<Springscript> {
+        if self.instrs.len() > MAX_INSTRS {
+            return Err(Error::ScriptTooLong {
+                len: self.instrs.len(),
+            });
+        }
+        let is_writable = |r: &str| matches!(r, "T" | "J");
+        let is_readable = |r: &str| is_writable(r) || (r.len() == 1 && sensors.contains(r));
+        let mut lines = Vec::with_capacity(self.instrs.len() + 1);
+        for (op, x, y) in self.instrs {
+            let instr = format!("{} {} {}", op, x, y);
+            if !is_readable(&x) {
+                return Err(Error::InvalidScript {
+                    instr,
+                    reason: "first register is not readable",
+                });
+            }
+            if !is_writable(&y) {
+                return Err(Error::InvalidScript {
+                    instr,
+                    reason: "second register is not writable",
+                });
+            }
+            lines.push(instr);
+        }
+        lines.push(cmd.to_owned());
+        Ok(Springscript { lines })
+    }
+}
+
+impl Springscript {
+    /// The lines of the program including the final `WALK` or `RUN`.
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Feeds the program to the computer.
+    pub fn feed(&self, c: &mut Computer) {
+        for line in &self.lines {
+            send_line(c, line);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! The integer types that a computer's memory can be made of.
+
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::ops::{Add, AddAssign, Div, Mul, Rem};
+
+/// An integer type that can be used for each cell in a computer's memory.
+///
+/// This is implemented for `i32`, `i64`, and `i128`. Computers use `i64` by
+/// default which is wide enough for all the Advent of Code programs.
+pub trait Cell:
+    Copy
+    + Debug
+    + Display
+    + Default
+    + Ord
+    + Hash
+    + Send
+    + Sync
+    + 'static
+    + From<i8>
+    + TryInto<i64>
+    + TryInto<usize>
+    + Add<Output = Self>
+    + AddAssign
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+{
+    /// The number of bits in the integer type.
+    const BITS: u32;
+
+    /// Adds two cells, returning `None` if the result overflows.
+    fn checked_add(self, other: Self) -> Option<Self>;
+
+    /// Multiplies two cells, returning `None` if the result overflows.
+    fn checked_mul(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_cell {
+    ($($ty:ty),*) => {
+        $(
+            impl Cell for $ty {
+                const BITS: u32 = <$ty>::BITS;
+
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$ty>::checked_add(self, other)
+                }
+
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    <$ty>::checked_mul(self, other)
+                }
+            }
+        )*
+    };
+}
+
+impl_cell!(i32, i64, i128);
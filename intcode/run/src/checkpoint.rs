@@ -0,0 +1,181 @@
+//! Save the state of a computer so that a long run can be resumed later.
+//!
+//! A checkpoint holds everything that changes while a program runs: the
+//! instruction pointer, the relative base, the number of instructions
+//! executed, the input that has been fed but not consumed yet, and the
+//! modified memory. The program itself is not stored, only a hash of it, so
+//! a checkpoint can only be restored into a computer running the same
+//! program. Memory mapped devices, breakpoints, and recorded events are not
+//! part of a checkpoint.
+//!
+//! The text format has a header line followed by one field on each line.
+//!
+//! ```text
+//! # intcode checkpoint 1
+//! program 9ae16a3b2f90404f
+//! ptr 12
+//! relative_base 0
+//! steps 123
+//! input 1,2,3
+//! outputs 72,105
+//! mem 1024=-7
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Computer, Error, Result};
+
+const HEADER: &str = "# intcode checkpoint 1";
+
+/// The saved state of a computer, created with [`Computer::checkpoint()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// A hash of the original program.
+    pub program: u64,
+    /// The address of the current instruction.
+    pub ptr: usize,
+    /// The relative base.
+    pub relative_base: i64,
+    /// The number of instructions executed.
+    pub steps: u64,
+    /// The input that has been fed to the computer but not consumed yet.
+    pub input: Vec<i64>,
+    /// The outputs so far. The computer doesn't keep these so they are left
+    /// empty for whoever drives it to fill in.
+    pub outputs: Vec<i64>,
+    /// The modified memory, in address order.
+    pub memory: Vec<(usize, i64)>,
+}
+
+/// Returns the FNV-1a hash of the program.
+fn hash(program: &[i64]) -> u64 {
+    program
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+impl Computer {
+    /// Returns the current state of the computer.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let mut memory: Vec<_> = self.mem.modified().collect();
+        memory.sort_unstable();
+        Checkpoint {
+            program: hash(self.mem.program()),
+            ptr: self.ptr,
+            relative_base: self.relative_base,
+            steps: self.instructions,
+            input: self.input.iter().copied().collect(),
+            outputs: Vec::new(),
+            memory,
+        }
+    }
+
+    /// Restores the state of the computer from the checkpoint.
+    ///
+    /// The checkpoint must have been taken from a computer running the same
+    /// program.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> Result<()> {
+        if checkpoint.program != hash(self.mem.program()) {
+            return Err(Error::CheckpointMismatch);
+        }
+        self.mem.clear();
+        for &(addr, value) in &checkpoint.memory {
+            *self.mem.get_mut(addr) = value;
+        }
+        self.ptr = checkpoint.ptr;
+        self.relative_base = checkpoint.relative_base;
+        self.instructions = checkpoint.steps;
+        self.input.clear();
+        self.input.extend(checkpoint.input.iter().copied());
+        self.points.resume = None;
+        if let Some(every) = self.points.every {
+            self.points.next_interval = self.instructions + every;
+        }
+        Ok(())
+    }
+}
+
+fn parse<T>(text: &str, line: usize) -> Result<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    text.trim().parse().map_err(|e| Error::Checkpoint {
+        line,
+        msg: format!("invalid number `{}`: {}", text.trim(), e),
+    })
+}
+
+fn join(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(i64::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "program {:016x}", self.program)?;
+        writeln!(f, "ptr {}", self.ptr)?;
+        writeln!(f, "relative_base {}", self.relative_base)?;
+        writeln!(f, "steps {}", self.steps)?;
+        writeln!(f, "input {}", join(&self.input))?;
+        writeln!(f, "outputs {}", join(&self.outputs))?;
+        for (addr, value) in &self.memory {
+            writeln!(f, "mem {}={}", addr, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim_end()) != Some(HEADER) {
+            return Err(Error::Checkpoint {
+                line: 1,
+                msg: format!("expected `{}`", HEADER),
+            });
+        }
+        let mut checkpoint = Self::default();
+        for (i, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+            let err = |msg: String| Error::Checkpoint { line: i + 1, msg };
+            let list = |text: &str| -> Result<Vec<i64>> {
+                match text.trim() {
+                    "" => Ok(Vec::new()),
+                    text => text.split(',').map(|v| parse(v, i + 1)).collect(),
+                }
+            };
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "program" => {
+                    checkpoint.program = u64::from_str_radix(value.trim(), 16)
+                        .map_err(|e| err(format!("invalid hash `{}`: {}", value.trim(), e)))?;
+                }
+                "ptr" => checkpoint.ptr = parse(value, i + 1)?,
+                "relative_base" => checkpoint.relative_base = parse(value, i + 1)?,
+                "steps" => checkpoint.steps = parse(value, i + 1)?,
+                "input" => checkpoint.input = list(value)?,
+                "outputs" => checkpoint.outputs = list(value)?,
+                "mem" => {
+                    let (addr, value) = value
+                        .split_once('=')
+                        .ok_or_else(|| err(format!("expected `addr=value`, found `{}`", value)))?;
+                    checkpoint
+                        .memory
+                        .push((parse(addr, i + 1)?, parse(value, i + 1)?));
+                }
+                key => return Err(err(format!("unknown field `{}`", key))),
+            }
+        }
+        Ok(checkpoint)
+    }
+}
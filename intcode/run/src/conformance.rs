@@ -0,0 +1,269 @@
+//! Conformance test vectors for intcode interpreters.
+//!
+//! Each vector is a small program exercising a single opcode with a
+//! particular combination of parameter modes, together with its input and
+//! the expected output. The expected output is derived from the intcode
+//! specification directly and not by running the program, so the vectors can
+//! be used to check this interpreter as well as others.
+//!
+//! The vectors can be written to a file with [`write()`], one per line:
+//!
+//! ```text
+//! <name> <program> <input> <output>
+//! ```
+//!
+//! Where each list of values is comma separated, or `-` if it is empty.
+
+use std::io;
+
+/// The relative base that every vector sets before running its instruction.
+const RB: i64 = 5;
+
+/// A conformance test vector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vector {
+    /// A unique name, made up of the opcode name and a letter per parameter
+    /// mode: `p` for positional, `i` for immediate, and `r` for relative.
+    pub name: String,
+    pub program: Vec<i64>,
+    pub input: Vec<i64>,
+    pub output: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Positional,
+    Immediate,
+    Relative,
+}
+
+const READ: [Mode; 3] = [Mode::Positional, Mode::Immediate, Mode::Relative];
+const WRITE: [Mode; 2] = [Mode::Positional, Mode::Relative];
+
+impl Mode {
+    fn value(self) -> i64 {
+        match self {
+            Self::Positional => 0,
+            Self::Immediate => 1,
+            Self::Relative => 2,
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Self::Positional => 'p',
+            Self::Immediate => 'i',
+            Self::Relative => 'r',
+        }
+    }
+
+    /// The parameter that refers to the value stored at `addr`.
+    fn param(self, addr: usize, value: i64) -> i64 {
+        match self {
+            Self::Positional => addr as i64,
+            Self::Immediate => value,
+            Self::Relative => addr as i64 - RB,
+        }
+    }
+}
+
+fn opcode(op: i64, modes: &[Mode]) -> i64 {
+    modes
+        .iter()
+        .enumerate()
+        .map(|(i, m)| m.value() * 10i64.pow(i as u32 + 2))
+        .sum::<i64>()
+        + op
+}
+
+fn name(op: &str, modes: &[Mode]) -> String {
+    let mut name = String::from(op);
+    if !modes.is_empty() {
+        name.push('_');
+        name.extend(modes.iter().map(|m| m.letter()));
+    }
+    name
+}
+
+/// An instruction that outputs the value written with the given mode.
+fn out(mode: Mode, addr: usize) -> [i64; 2] {
+    [opcode(4, &[mode]), mode.param(addr, 0)]
+}
+
+fn arithmetic(vectors: &mut Vec<Vector>) {
+    let ops = [("add", 1), ("mul", 2), ("lt", 7), ("eq", 8)];
+    let eval = |value, a, b| match value {
+        1 => a + b,
+        2 => a * b,
+        7 => (a < b) as i64,
+        _ => (a == b) as i64,
+    };
+    for (op, value) in ops {
+        for (a, b) in [(3, 5), (5, 3), (4, 4), (-2, 7)] {
+            for ma in READ {
+                for mb in READ {
+                    for mc in WRITE {
+                        // 0: ARB #RB
+                        // 2: OP a, b, c
+                        // 6: OUT c
+                        // 8: HLT
+                        // 9: DB a, b, c
+                        let modes = [ma, mb, mc];
+                        let mut program = vec![109, RB, opcode(value, &modes)];
+                        program.extend([ma.param(9, a), mb.param(10, b), mc.param(11, 0)]);
+                        program.extend(out(mc, 11));
+                        program.extend([99, a, b, 0]);
+                        vectors.push(Vector {
+                            name: format!("{}_{}_{}", name(op, &modes), a, b),
+                            program,
+                            input: Vec::new(),
+                            output: vec![eval(value, a, b)],
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn jumps(vectors: &mut Vec<Vector>) {
+    for (op, value) in [("jnz", 5), ("jz", 6)] {
+        for cond in [0, 1, -1] {
+            let jumped = match value {
+                5 => cond != 0,
+                _ => cond == 0,
+            };
+            for ma in READ {
+                for mb in READ {
+                    //  0: ARB #RB
+                    //  2: OP cond, target
+                    //  5: OUT #0
+                    //  7: HLT
+                    //  8: OUT #1
+                    // 10: HLT
+                    // 11: DB cond, target
+                    let modes = [ma, mb];
+                    let program = vec![
+                        109,
+                        RB,
+                        opcode(value, &modes),
+                        ma.param(11, cond),
+                        mb.param(12, 8),
+                        104,
+                        0,
+                        99,
+                        104,
+                        1,
+                        99,
+                        cond,
+                        8,
+                    ];
+                    vectors.push(Vector {
+                        name: format!("{}_{}", name(op, &modes), cond),
+                        program,
+                        input: Vec::new(),
+                        output: vec![jumped as i64],
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn io(vectors: &mut Vec<Vector>) {
+    for m in WRITE {
+        // 0: ARB #RB
+        // 2: IN x
+        // 4: OUT x
+        // 6: HLT
+        // 7: DB x
+        let mut program = vec![109, RB, opcode(3, &[m]), m.param(7, 0)];
+        program.extend(out(m, 7));
+        program.extend([99, 0]);
+        vectors.push(Vector {
+            name: name("in", &[m]),
+            program,
+            input: vec![42],
+            output: vec![42],
+        });
+    }
+    for m in READ {
+        // 0: ARB #RB
+        // 2: OUT x
+        // 4: HLT
+        // 5: DB x
+        let program = vec![109, RB, opcode(4, &[m]), m.param(5, 17), 99, 17];
+        vectors.push(Vector {
+            name: name("out", &[m]),
+            program,
+            input: Vec::new(),
+            output: vec![17],
+        });
+    }
+}
+
+fn adjust_relative_base(vectors: &mut Vec<Vector>) {
+    for m in READ {
+        // 0: ARB #RB
+        // 2: ARB x
+        // 4: OUT rb
+        // 6: HLT
+        // 7: DB x, 0, 0, ...
+        // The relative base ends up at RB + 3 = 8 which is the second data
+        // cell.
+        let program = vec![109, RB, opcode(9, &[m]), m.param(7, 3), 204, 0, 99, 3, 23];
+        vectors.push(Vector {
+            name: name("arb", &[m]),
+            program,
+            input: Vec::new(),
+            output: vec![23],
+        });
+    }
+}
+
+/// Returns all the conformance test vectors.
+pub fn vectors() -> Vec<Vector> {
+    let mut vectors = vec![Vector {
+        name: name("hlt", &[]),
+        program: vec![99, 104, 1],
+        input: Vec::new(),
+        output: Vec::new(),
+    }];
+    arithmetic(&mut vectors);
+    jumps(&mut vectors);
+    io(&mut vectors);
+    adjust_relative_base(&mut vectors);
+    vectors
+}
+
+fn join(values: &[i64]) -> String {
+    if values.is_empty() {
+        return String::from("-");
+    }
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Writes all the conformance test vectors, one per line.
+pub fn write(mut w: impl io::Write) -> io::Result<()> {
+    for Vector {
+        name,
+        program,
+        input,
+        output,
+    } in vectors()
+    {
+        writeln!(
+            w,
+            "{} {} {} {}",
+            name,
+            join(&program),
+            join(&input),
+            join(&output)
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! Memory mapped devices.
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+type ReadFn<C> = dyn FnMut(usize) -> C + Send;
+type WriteFn<C> = dyn FnMut(usize, C) + Send;
+
+/// A range of memory where reads and writes are handled by the host.
+///
+/// Clones share the same closures, so a device behaves like a single piece of
+/// hardware attached to every clone of a computer.
+pub struct Device<C> {
+    range: Range<usize>,
+    read: Arc<Mutex<ReadFn<C>>>,
+    write: Arc<Mutex<WriteFn<C>>>,
+}
+
+// Derived `Clone` would require `C: Clone` even though only the `Arc`s are
+// cloned.
+impl<C> Clone for Device<C> {
+    fn clone(&self) -> Self {
+        Self {
+            range: self.range.clone(),
+            read: self.read.clone(),
+            write: self.write.clone(),
+        }
+    }
+}
+
+impl<C> fmt::Debug for Device<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Device")
+            .field("range", &self.range)
+            .finish()
+    }
+}
+
+impl<C> Device<C> {
+    pub fn new<R, W>(range: Range<usize>, read: R, write: W) -> Self
+    where
+        R: FnMut(usize) -> C + Send + 'static,
+        W: FnMut(usize, C) + Send + 'static,
+    {
+        Self {
+            range,
+            read: Arc::new(Mutex::new(read)),
+            write: Arc::new(Mutex::new(write)),
+        }
+    }
+
+    /// Returns whether the address is handled by this device.
+    pub fn contains(&self, addr: usize) -> bool {
+        self.range.contains(&addr)
+    }
+
+    /// Reads the value at the given address from the device.
+    pub fn read(&self, addr: usize) -> C {
+        (self.read.lock().unwrap())(addr)
+    }
+
+    /// Writes the value at the given address to the device.
+    pub fn write(&self, addr: usize, value: C) {
+        (self.write.lock().unwrap())(addr, value)
+    }
+}
@@ -0,0 +1,172 @@
+//! Drive a computer with a finite source of input.
+
+use std::iter;
+use std::iter::Fuse;
+use std::sync::Arc;
+
+use crate::cell::Cell;
+use crate::{Computer, Error, Result, State};
+
+/// An iterator over the outputs of a computer, created with
+/// [`Computer::drive()`].
+#[derive(Debug)]
+pub struct Drive<'a, I, C = i64> {
+    computer: &'a mut Computer<C>,
+    input: Fuse<I>,
+    done: bool,
+}
+
+/// The result of running a program to completion, created with
+/// [`Computer::run_to_end()`].
+///
+/// The extractors cover the usual shapes of a puzzle answer and return an
+/// error if the run doesn't have that shape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOutcome<C = i64> {
+    outputs: Vec<C>,
+    memory0: C,
+}
+
+impl<C: Cell> Computer<C> {
+    /// Returns an iterator over the outputs of the program, feeding values from
+    /// the input whenever the program waits.
+    ///
+    /// If the program waits after the input is exhausted then the iterator
+    /// yields [`Error::Starved`] instead of waiting forever. Breakpoints and
+    /// watchpoints are ignored.
+    pub fn drive<I>(&mut self, input: I) -> Drive<'_, I::IntoIter, C>
+    where
+        I: IntoIterator<Item = C>,
+    {
+        Drive {
+            computer: self,
+            input: input.into_iter().fuse(),
+            done: false,
+        }
+    }
+
+    /// Returns an iterator over the outputs of the program, using only the
+    /// input that has already been fed to the computer.
+    pub fn outputs(&mut self) -> Drive<'_, iter::Empty<C>, C> {
+        self.drive(iter::empty())
+    }
+
+    /// Runs the program to completion with the given input and returns all
+    /// the outputs.
+    pub fn run_with(&mut self, input: impl IntoIterator<Item = C>) -> Result<Vec<C>> {
+        self.drive(input).collect()
+    }
+
+    /// Runs the program to completion with the values in the slice as input
+    /// and returns all the outputs.
+    pub fn run_with_input(&mut self, input: &[C]) -> Result<Vec<C>> {
+        self.run_with(input.iter().copied())
+    }
+
+    /// Runs the program to completion with the given input and returns the
+    /// outputs along with the final state of memory.
+    pub fn run_to_end(&mut self, input: impl IntoIterator<Item = C>) -> Result<RunOutcome<C>> {
+        let outputs = self.run_with(input)?;
+        Ok(RunOutcome {
+            outputs,
+            memory0: self.peek(0),
+        })
+    }
+}
+
+/// Runs the program to completion with the given input and returns all the
+/// outputs.
+///
+/// This is a shorthand for constructing a [`Computer`] and calling
+/// [`Computer::run_with_input()`] on it.
+pub fn execute<C: Cell>(program: impl Into<Arc<[C]>>, input: &[C]) -> Result<Vec<C>> {
+    Computer::new(program).run_with_input(input)
+}
+
+fn to_i64<C: Cell>(value: C, what: &'static str) -> Result<i64> {
+    value.try_into().map_err(|_| Error::Overflow {
+        what,
+        value: value.to_string(),
+    })
+}
+
+impl<C: Cell> RunOutcome<C> {
+    /// Returns all the outputs.
+    pub fn outputs(&self) -> &[C] {
+        &self.outputs
+    }
+
+    /// Returns the first output.
+    pub fn first_output(&self) -> Result<i64> {
+        let value = *self.outputs.first().ok_or(Error::NoOutput)?;
+        to_i64(value, "output")
+    }
+
+    /// Returns the last output.
+    pub fn last_output(&self) -> Result<i64> {
+        let value = *self.outputs.last().ok_or(Error::NoOutput)?;
+        to_i64(value, "output")
+    }
+
+    /// Returns the outputs decoded as ASCII text.
+    pub fn outputs_as_string(&self) -> Result<String> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| {
+                value
+                    .try_into()
+                    .ok()
+                    .and_then(|v: i64| u8::try_from(v).ok())
+                    .filter(u8::is_ascii)
+                    .map(char::from)
+                    .ok_or_else(|| Error::NotAscii {
+                        index,
+                        value: value.to_string(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the value at address 0 after the program completed.
+    pub fn memory0(&self) -> Result<i64> {
+        to_i64(self.memory0, "value at address 0")
+    }
+}
+
+impl<I, C> Iterator for Drive<'_, I, C>
+where
+    I: Iterator<Item = C>,
+    C: Cell,
+{
+    type Item = Result<C>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            let state = self.computer.next();
+            let result = match state.and_then(|s| s.into_result(self.computer.ptr())) {
+                Ok(State::Yielded(value)) => Ok(value),
+                Ok(State::Waiting) => match self.input.next() {
+                    Some(value) => {
+                        self.computer.feed([value]);
+                        continue;
+                    }
+                    None => Err(Error::Starved {
+                        ptr: self.computer.ptr(),
+                    }),
+                },
+                Ok(State::Complete) => {
+                    self.done = true;
+                    return None;
+                }
+                Ok(_) => continue,
+                Err(err) => Err(err),
+            };
+            self.done = result.is_err();
+            return Some(result);
+        }
+    }
+}
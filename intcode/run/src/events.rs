@@ -0,0 +1,111 @@
+//! A log of the events that happen while a program runs.
+//!
+//! Only the kinds of events selected by the [`EventMask`] are recorded so
+//! that logging can be kept cheap. With the `serde` feature enabled the
+//! events can be serialized with any serde format, otherwise
+//! [`EventLog::write_csv()`] can be used.
+
+use std::fmt::Display;
+use std::io;
+use std::ops;
+
+/// An event that happened while running a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Event<C = i64> {
+    /// A value was consumed by an input instruction.
+    Input { ptr: usize, value: C },
+    /// A value was produced by an output instruction.
+    Output { ptr: usize, value: C },
+    /// A jump instruction moved the instruction pointer.
+    Jump { ptr: usize, target: usize },
+    /// The relative base was adjusted.
+    AdjustRelativeBase { ptr: usize, relative_base: C },
+}
+
+/// A set of event kinds to record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventMask(u8);
+
+/// The recorded events.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EventLog<C = i64> {
+    #[cfg_attr(feature = "serde", serde(skip))]
+    mask: EventMask,
+    events: Vec<Event<C>>,
+}
+
+impl<C> Event<C> {
+    fn kind(&self) -> EventMask {
+        match self {
+            Self::Input { .. } => EventMask::INPUT,
+            Self::Output { .. } => EventMask::OUTPUT,
+            Self::Jump { .. } => EventMask::JUMP,
+            Self::AdjustRelativeBase { .. } => EventMask::ADJUST_RELATIVE_BASE,
+        }
+    }
+}
+
+impl EventMask {
+    pub const NONE: Self = Self(0);
+    pub const INPUT: Self = Self(1);
+    pub const OUTPUT: Self = Self(1 << 1);
+    pub const JUMP: Self = Self(1 << 2);
+    pub const ADJUST_RELATIVE_BASE: Self = Self(1 << 3);
+    pub const ALL: Self = Self(0b1111);
+
+    /// Returns whether all the kinds in `other` are in this mask.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for EventMask {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl<C: Display> EventLog<C> {
+    pub fn new(mask: EventMask) -> Self {
+        Self {
+            mask,
+            events: Vec::new(),
+        }
+    }
+
+    /// Records the event if its kind is selected by the mask.
+    pub(crate) fn record(&mut self, event: Event<C>) {
+        if self.mask.contains(event.kind()) {
+            self.events.push(event);
+        }
+    }
+
+    /// Returns the recorded events.
+    pub fn events(&self) -> &[Event<C>] {
+        &self.events
+    }
+
+    /// Writes the events as CSV with the columns `kind,ptr,value`.
+    ///
+    /// The value is the input or output value, the jump target, or the new
+    /// relative base.
+    pub fn write_csv(&self, mut w: impl io::Write) -> io::Result<()> {
+        writeln!(w, "kind,ptr,value")?;
+        for event in &self.events {
+            match event {
+                Event::Input { ptr, value } => writeln!(w, "input,{},{}", ptr, value)?,
+                Event::Output { ptr, value } => writeln!(w, "output,{},{}", ptr, value)?,
+                Event::Jump { ptr, target } => writeln!(w, "jump,{},{}", ptr, target)?,
+                Event::AdjustRelativeBase { ptr, relative_base } => {
+                    writeln!(w, "adjust_relative_base,{},{}", ptr, relative_base)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+//! Explore an unknown grid using a droid controlled by an intcode program.
+//!
+//! The droid is expected to follow the common movement protocol: it reads a
+//! single movement command and responds with a single status code. Each
+//! explored position keeps its own clone of the computer so that the search
+//! never needs to backtrack the droid.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{Computer, Error, Result, State};
+
+/// A position on the grid as `(x, y)`, the droid starts at `(0, 0)`.
+pub type Pos = (i64, i64);
+
+/// A movement direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    North,
+    South,
+    West,
+    East,
+}
+
+/// The discovered map.
+#[derive(Debug, Clone)]
+pub struct Map<T> {
+    /// The tiles the droid was able to move onto.
+    pub tiles: HashMap<Pos, T>,
+    /// The positions the droid was not able to move onto.
+    pub walls: HashSet<Pos>,
+    /// The fewest number of moves required to reach each tile.
+    pub distances: HashMap<Pos, usize>,
+}
+
+impl Direction {
+    /// All the directions in the order they are tried.
+    pub const ALL: [Self; 4] = [Self::North, Self::South, Self::West, Self::East];
+
+    /// The input value that moves the droid in this direction.
+    pub fn command(self) -> i64 {
+        match self {
+            Self::North => 1,
+            Self::South => 2,
+            Self::West => 3,
+            Self::East => 4,
+        }
+    }
+
+    /// Returns the direction after turning left.
+    pub fn left(self) -> Self {
+        match self {
+            Self::North => Self::West,
+            Self::West => Self::South,
+            Self::South => Self::East,
+            Self::East => Self::North,
+        }
+    }
+
+    /// Returns the direction after turning right.
+    pub fn right(self) -> Self {
+        match self {
+            Self::North => Self::East,
+            Self::East => Self::South,
+            Self::South => Self::West,
+            Self::West => Self::North,
+        }
+    }
+
+    /// Returns the position one step in this direction.
+    pub fn step(self, (x, y): Pos) -> Pos {
+        match self {
+            Self::North => (x, y - 1),
+            Self::South => (x, y + 1),
+            Self::West => (x - 1, y),
+            Self::East => (x + 1, y),
+        }
+    }
+}
+
+impl<T> Map<T> {
+    /// Returns the position of the first tile matching the predicate.
+    pub fn find<P>(&self, mut predicate: P) -> Option<Pos>
+    where
+        P: FnMut(&T) -> bool,
+    {
+        self.tiles
+            .iter()
+            .find(|(_, t)| predicate(t))
+            .map(|(pos, _)| *pos)
+    }
+
+    /// Returns the fewest number of moves from the start to the position.
+    pub fn distance(&self, pos: Pos) -> Option<usize> {
+        self.distances.get(&pos).copied()
+    }
+
+    /// Returns the fewest number of moves from the given position to every
+    /// reachable tile.
+    pub fn distances_from(&self, start: Pos) -> HashMap<Pos, usize> {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        if self.tiles.contains_key(&start) {
+            distances.insert(start, 0);
+            queue.push_back(start);
+        }
+        while let Some(pos) = queue.pop_front() {
+            let d = distances[&pos];
+            for dir in Direction::ALL {
+                let next = dir.step(pos);
+                if self.tiles.contains_key(&next) && !distances.contains_key(&next) {
+                    distances.insert(next, d + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+}
+
+/// Sends a single movement command and returns the status output.
+fn send(c: &mut Computer, dir: Direction) -> Result<i64> {
+    c.feed([dir.command()]);
+    loop {
+        match c.next()?.into_result(c.ptr())? {
+            State::Yielded(status) => break Ok(status),
+            State::Waiting => break Err(Error::UnexpectedWait),
+            State::Complete => break Err(Error::UnexpectedHalt),
+            _ => {}
+        }
+    }
+}
+
+/// Explore the entire reachable grid using a breadth first search.
+///
+/// The `status` function interprets each status output, returning `None` if
+/// the droid hit a wall and did not move or the tile that the droid moved
+/// onto otherwise. The starting position is given the `start` tile.
+pub fn explore<T, F>(computer: Computer, start: T, mut status: F) -> Result<Map<T>>
+where
+    F: FnMut(i64) -> Option<T>,
+{
+    let mut map = Map {
+        tiles: HashMap::new(),
+        walls: HashSet::new(),
+        distances: HashMap::new(),
+    };
+    map.tiles.insert((0, 0), start);
+    map.distances.insert((0, 0), 0);
+
+    let mut queue = VecDeque::new();
+    queue.push_back(((0, 0), computer));
+
+    while let Some((pos, computer)) = queue.pop_front() {
+        let d = map.distances[&pos];
+        for dir in Direction::ALL {
+            let next = dir.step(pos);
+            if map.tiles.contains_key(&next) || map.walls.contains(&next) {
+                continue;
+            }
+            let mut c = computer.clone();
+            match status(send(&mut c, dir)?) {
+                Some(tile) => {
+                    map.tiles.insert(next, tile);
+                    map.distances.insert(next, d + 1);
+                    queue.push_back((next, c));
+                }
+                None => {
+                    map.walls.insert(next);
+                }
+            }
+        }
+    }
+
+    Ok(map)
+}
@@ -0,0 +1,248 @@
+//! Run a computer asynchronously.
+//!
+//! An [`AsyncComputer`] awaits a [`Source`] whenever the program needs input
+//! and a [`Sink`] whenever it outputs a value. Both are polled just like
+//! futures so they work with any executor. A [`channel()`] connects the
+//! output of one computer to the input of another.
+//!
+//! Long stretches of computation without any input or output would block the
+//! executor, so the computer also yields every so often, see
+//! [`AsyncComputer::budget()`].
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::{Computer, Error, Pause, Result, State};
+
+/// An asynchronous source of input.
+pub trait Source {
+    /// Polls for the next value, `None` means that no more will come.
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<i64>>;
+}
+
+/// An asynchronous destination for output.
+pub trait Sink {
+    /// Polls to send the value.
+    ///
+    /// The value is only sent once this is ready, until then it is polled
+    /// again with the same value.
+    fn poll_send(&mut self, cx: &mut Context<'_>, value: i64) -> Poll<()>;
+}
+
+/// A computer that awaits its input and output.
+#[derive(Debug, Clone)]
+pub struct AsyncComputer {
+    computer: Computer,
+    budget: u64,
+}
+
+/// The sending half of a channel, created with [`channel()`].
+#[derive(Debug)]
+pub struct Sender {
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// The receiving half of a channel, created with [`channel()`].
+#[derive(Debug)]
+pub struct Receiver {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    queue: VecDeque<i64>,
+    senders: usize,
+    waker: Option<Waker>,
+}
+
+struct Next<'a, S: ?Sized>(&'a mut S);
+
+struct Deliver<'a, K: ?Sized>(&'a mut K, i64);
+
+struct YieldNow(bool);
+
+/// Constructs an unbounded channel.
+///
+/// The receiver returns `None` once every sender has been dropped and the
+/// values sent have been received.
+pub fn channel() -> (Sender, Receiver) {
+    let shared = Arc::new(Mutex::new(Shared {
+        senders: 1,
+        ..Shared::default()
+    }));
+    let sender = Sender {
+        shared: shared.clone(),
+    };
+    (sender, Receiver { shared })
+}
+
+impl Sender {
+    /// Sends a value, this never waits.
+    pub fn send(&self, value: i64) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.queue.push_back(value);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Sink for Sender {
+    fn poll_send(&mut self, _: &mut Context<'_>, value: i64) -> Poll<()> {
+        self.send(value);
+        Poll::Ready(())
+    }
+}
+
+impl Source for Receiver {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<i64>> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.queue.pop_front() {
+            Some(value) => Poll::Ready(Some(value)),
+            None if shared.senders == 0 => Poll::Ready(None),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Source for VecDeque<i64> {
+    fn poll_next(&mut self, _: &mut Context<'_>) -> Poll<Option<i64>> {
+        Poll::Ready(self.pop_front())
+    }
+}
+
+impl<S: Source + ?Sized> Source for &mut S {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<i64>> {
+        (**self).poll_next(cx)
+    }
+}
+
+impl Sink for Vec<i64> {
+    fn poll_send(&mut self, _: &mut Context<'_>, value: i64) -> Poll<()> {
+        self.push(value);
+        Poll::Ready(())
+    }
+}
+
+impl<K: Sink + ?Sized> Sink for &mut K {
+    fn poll_send(&mut self, cx: &mut Context<'_>, value: i64) -> Poll<()> {
+        (**self).poll_send(cx, value)
+    }
+}
+
+impl<S: Source + ?Sized> Future for Next<'_, S> {
+    type Output = Option<i64>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_next(cx)
+    }
+}
+
+impl<K: Sink + ?Sized> Future for Deliver<'_, K> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let value = self.1;
+        self.0.poll_send(cx, value)
+    }
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+impl From<Computer> for AsyncComputer {
+    fn from(computer: Computer) -> Self {
+        Self {
+            computer,
+            budget: 10_000,
+        }
+    }
+}
+
+impl AsyncComputer {
+    /// Constructs a new computer running the program.
+    pub fn new(program: impl Into<Arc<[i64]>>) -> Self {
+        Self::from(Computer::new(program))
+    }
+
+    /// Sets the number of instructions to execute before yielding to the
+    /// executor, the default is 10,000.
+    pub fn budget(mut self, steps: u64) -> Self {
+        self.budget = steps;
+        self
+    }
+
+    /// Returns the underlying computer.
+    pub fn into_inner(self) -> Computer {
+        self.computer
+    }
+
+    /// Runs the program to completion, awaiting the source whenever it needs
+    /// input and the sink whenever it outputs a value.
+    ///
+    /// If the program waits after the source has ended then
+    /// [`Error::Starved`] is returned. This uses
+    /// [`Computer::pause_every()`] to yield, breakpoints and other pauses are
+    /// ignored.
+    pub async fn run<S, K>(&mut self, mut input: S, mut output: K) -> Result<()>
+    where
+        S: Source,
+        K: Sink,
+    {
+        self.computer.pause_every(Some(self.budget));
+        loop {
+            match self.computer.next()?.into_result(self.computer.ptr())? {
+                State::Yielded(value) => Deliver(&mut output, value).await,
+                State::Waiting => match Next(&mut input).await {
+                    Some(value) => self.computer.feed([value]),
+                    None => {
+                        let ptr = self.computer.ptr();
+                        return Err(Error::Starved { ptr });
+                    }
+                },
+                State::Complete => return Ok(()),
+                State::Paused {
+                    reason: Pause::Interval { .. },
+                } => YieldNow(false).await,
+                _ => {}
+            }
+        }
+    }
+}
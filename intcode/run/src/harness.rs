@@ -0,0 +1,204 @@
+//! A harness for solving puzzles using an intcode program.
+//!
+//! Implement [`Solution`] for each part of the puzzle and the harness takes
+//! care of loading the program, setting up a fresh computer for each part,
+//! timing the solution, and printing the answer.
+//!
+//! ```no_run
+//! use intcode_run::harness::{Harness, LastOutput};
+//!
+//! Harness::load("input.txt")?
+//!     .part("part 1", LastOutput(vec![1]))?
+//!     .part("part 2", LastOutput(vec![5]))?;
+//! # Ok::<(), intcode_run::Error>(())
+//! ```
+
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::drive::RunOutcome;
+use crate::load::{self, Lenient};
+use crate::{ascii, Computer, Error, Result, State};
+
+/// The answer to a part of the puzzle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    Number(i64),
+    Text(String),
+}
+
+/// The input and output of the computer a solution is driving.
+#[derive(Debug)]
+pub struct MachineIo {
+    computer: Computer,
+}
+
+/// A solution to a part of the puzzle.
+pub trait Solution {
+    /// Drive the computer and return the answer.
+    fn drive(&mut self, io: &mut MachineIo) -> Result<Answer>;
+}
+
+/// Feeds the input and runs the program to completion, the answer is the
+/// last output.
+#[derive(Debug, Clone)]
+pub struct LastOutput(pub Vec<i64>);
+
+/// Feeds the input and runs the program to completion, the answer is all the
+/// outputs separated by commas.
+#[derive(Debug, Clone)]
+pub struct Outputs(pub Vec<i64>);
+
+/// Loads a program and runs solutions against it.
+#[derive(Debug, Clone)]
+pub struct Harness {
+    program: Arc<[i64]>,
+    answers: Vec<Answer>,
+}
+
+impl Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => Display::fmt(n, f),
+            Self::Text(s) => Display::fmt(s, f),
+        }
+    }
+}
+
+impl From<i64> for Answer {
+    fn from(n: i64) -> Self {
+        Self::Number(n)
+    }
+}
+
+impl From<String> for Answer {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl MachineIo {
+    pub fn new(computer: Computer) -> Self {
+        Self { computer }
+    }
+
+    /// Returns the underlying computer.
+    pub fn computer(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    /// Feeds the values to the computer.
+    pub fn send(&mut self, values: impl IntoIterator<Item = i64>) {
+        self.computer.feed(values)
+    }
+
+    /// Feeds the line followed by a newline to the computer.
+    pub fn send_line(&mut self, line: &str) {
+        ascii::send_line(&mut self.computer, line)
+    }
+
+    /// Runs the computer until the next output, returns `None` if the program
+    /// completes instead.
+    ///
+    /// Since input is only ever sent by the solution, it is an error for the
+    /// program to wait for more input than was sent.
+    pub fn recv(&mut self) -> Result<Option<i64>> {
+        loop {
+            match self.computer.next()?.into_result(self.computer.ptr())? {
+                State::Yielded(value) => break Ok(Some(value)),
+                State::Waiting => {
+                    break Err(Error::Starved {
+                        ptr: self.computer.ptr(),
+                    })
+                }
+                State::Complete => break Ok(None),
+                _ => {}
+            }
+        }
+    }
+
+    /// Runs the computer to completion and returns all the outputs.
+    pub fn recv_all(&mut self) -> Result<Vec<i64>> {
+        let mut outputs = Vec::new();
+        while let Some(value) = self.recv()? {
+            outputs.push(value);
+        }
+        Ok(outputs)
+    }
+
+    /// Runs the computer to completion and returns the outcome.
+    pub fn finish(&mut self) -> Result<RunOutcome> {
+        self.computer.run_to_end([])
+    }
+}
+
+impl<F> Solution for F
+where
+    F: FnMut(&mut MachineIo) -> Result<Answer>,
+{
+    fn drive(&mut self, io: &mut MachineIo) -> Result<Answer> {
+        self(io)
+    }
+}
+
+impl Solution for LastOutput {
+    fn drive(&mut self, io: &mut MachineIo) -> Result<Answer> {
+        io.send(self.0.iter().copied());
+        io.finish()?.last_output().map(Answer::Number)
+    }
+}
+
+impl Solution for Outputs {
+    fn drive(&mut self, io: &mut MachineIo) -> Result<Answer> {
+        io.send(self.0.iter().copied());
+        let outputs: Vec<_> = io.recv_all()?.iter().map(|n| n.to_string()).collect();
+        Ok(Answer::Text(outputs.join(",")))
+    }
+}
+
+impl Harness {
+    pub fn new(program: impl Into<Arc<[i64]>>) -> Self {
+        Self {
+            program: program.into(),
+            answers: Vec::new(),
+        }
+    }
+
+    /// Loads a comma separated intcode program from a file.
+    ///
+    /// The first line that is an intcode program is used, see
+    /// [`load::lenient`], and each other line is reported and ignored.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let error = |msg: String| Error::Load {
+            path: path.display().to_string(),
+            msg,
+        };
+        let contents = fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+        let Lenient { program, ignored } = load::lenient(&contents)
+            .ok_or_else(|| error("does not contain an intcode program".into()))?;
+        for (line, text) in ignored {
+            eprintln!("ignored line {} of {}: {}", line, path.display(), text);
+        }
+        Ok(Self::new(program))
+    }
+
+    /// Runs the solution on a fresh computer and prints the answer.
+    pub fn part<S: Solution>(mut self, name: &str, mut solution: S) -> Result<Self> {
+        let mut io = MachineIo::new(Computer::new(self.program.clone()));
+        let start = Instant::now();
+        let answer = solution.drive(&mut io)?;
+        println!("{}: {} ({:.2?})", name, answer, start.elapsed());
+        self.answers.push(answer);
+        Ok(self)
+    }
+
+    /// Returns the answers to all the parts run so far.
+    pub fn answers(&self) -> &[Answer] {
+        &self.answers
+    }
+}
@@ -0,0 +1,128 @@
+//! Count how often each address is accessed while a program runs.
+//!
+//! Recording is turned on with [`Computer::record_heatmap()`]. Each address
+//! of the program has a counter for the reads, writes, and executions of it.
+//! Addresses past the end of the program are counted separately so that a
+//! program writing far away doesn't make the heatmap huge.
+//!
+//! [`Computer::record_heatmap()`]: crate::Computer::record_heatmap
+
+use std::collections::BTreeMap;
+use std::io;
+
+/// The number of accesses to a single address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    /// Reads by a positional or relative parameter.
+    pub reads: u64,
+    /// Writes by a parameter.
+    pub writes: u64,
+    /// Executions as the opcode or a parameter of an instruction.
+    pub executes: u64,
+}
+
+/// The access counts of every address that was accessed.
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    /// The counts of each address in the program.
+    program: Vec<Counts>,
+    /// The counts of the addresses past the end of the program.
+    heap: BTreeMap<usize, Counts>,
+}
+
+impl Heatmap {
+    /// Constructs an empty heatmap for a program of the given length.
+    pub fn new(len: usize) -> Self {
+        Self {
+            program: vec![Counts::default(); len],
+            heap: BTreeMap::new(),
+        }
+    }
+
+    fn counts_mut(&mut self, addr: usize) -> &mut Counts {
+        match self.program.get_mut(addr) {
+            Some(counts) => counts,
+            None => self.heap.entry(addr).or_default(),
+        }
+    }
+
+    pub(crate) fn read(&mut self, addr: usize) {
+        self.counts_mut(addr).reads += 1;
+    }
+
+    pub(crate) fn write(&mut self, addr: usize) {
+        self.counts_mut(addr).writes += 1;
+    }
+
+    pub(crate) fn execute(&mut self, addr: usize) {
+        self.counts_mut(addr).executes += 1;
+    }
+
+    /// Undoes an execution, for an input instruction that has to wait.
+    pub(crate) fn unexecute(&mut self, addr: usize) {
+        self.counts_mut(addr).executes -= 1;
+    }
+
+    /// Returns the counts for the address.
+    pub fn get(&self, addr: usize) -> Counts {
+        match self.program.get(addr) {
+            Some(counts) => *counts,
+            None => self.heap.get(&addr).copied().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the length of the program.
+    pub fn program_len(&self) -> usize {
+        self.program.len()
+    }
+
+    /// Returns the counts of every address in the program followed by the
+    /// addresses past the end of it that were accessed, in address order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Counts)> + '_ {
+        self.program
+            .iter()
+            .copied()
+            .enumerate()
+            .chain(self.heap.iter().map(|(addr, counts)| (*addr, *counts)))
+    }
+
+    /// Writes the counts as CSV with the columns
+    /// `address,reads,writes,executes`.
+    pub fn write_csv(&self, mut w: impl io::Write) -> io::Result<()> {
+        writeln!(w, "address,reads,writes,executes")?;
+        for (addr, c) in self.iter() {
+            writeln!(w, "{},{},{},{}", addr, c.reads, c.writes, c.executes)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the program addresses as a binary PPM image with `width`
+    /// addresses in each row, starting at the top left.
+    ///
+    /// Writes are red, reads are green, and executions are blue. The
+    /// brightness of each is logarithmic in the count relative to the
+    /// highest count of that kind, and addresses that were never accessed in
+    /// that way have none of that color.
+    pub fn write_ppm(&self, mut w: impl io::Write, width: usize) -> io::Result<()> {
+        let width = width.max(1);
+        let height = (self.program.len() + width - 1) / width;
+        write!(w, "P6\n{} {}\n255\n", width, height.max(1))?;
+        let max = |f: fn(&Counts) -> u64| self.program.iter().map(f).max().unwrap_or(0);
+        let maxes = [max(|c| c.writes), max(|c| c.reads), max(|c| c.executes)];
+        let shade = |count: u64, max: u64| match count {
+            0 => 0,
+            count => {
+                let scale = (count as f64).ln_1p() / (max as f64).ln_1p();
+                64 + (191.0 * scale) as u8
+            }
+        };
+        let mut pixels = Vec::with_capacity(width * height.max(1) * 3);
+        for c in &self.program {
+            pixels.push(shade(c.writes, maxes[0]));
+            pixels.push(shade(c.reads, maxes[1]));
+            pixels.push(shade(c.executes, maxes[2]));
+        }
+        pixels.resize(width * height.max(1) * 3, 0);
+        w.write_all(&pixels)
+    }
+}
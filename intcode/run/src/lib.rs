@@ -1,10 +1,47 @@
-use std::cmp::max;
-use std::collections::VecDeque;
+pub mod adventure;
+pub mod ascii;
+pub mod cancel;
+pub mod cell;
+pub mod checkpoint;
+pub mod conformance;
+mod device;
+pub mod drive;
+pub mod events;
+pub mod explore;
+#[cfg(feature = "async")]
+pub mod future;
+pub mod harness;
+pub mod heatmap;
+pub mod load;
+pub mod lockstep;
+mod mem;
+pub mod mem_init;
+pub mod network;
+pub mod pipeline;
+pub mod pool;
+pub mod render;
+pub mod scaffold;
+pub mod symbolic;
+
+use std::collections::{BTreeSet, VecDeque};
 use std::fmt::Debug;
+use std::ops::Range;
 use std::result;
+use std::sync::Arc;
 
 use thiserror::Error;
 
+use crate::cancel::CancelToken;
+use crate::cell::Cell;
+use crate::device::Device;
+use crate::events::{Event, EventLog, EventMask};
+use crate::heatmap::Heatmap;
+use crate::mem::Memory;
+
+pub use crate::drive::execute;
+#[cfg(feature = "async")]
+pub use crate::future::AsyncComputer;
+
 pub type Result<T> = result::Result<T, Error>;
 
 #[derive(Debug, Error)]
@@ -13,126 +50,806 @@ pub enum Error {
     UnknownMode { mode: i64 },
     #[error("unknown opcode `{}`", .opcode)]
     UnknownOpcode { opcode: i64 },
+    #[error("program halted unexpectedly")]
+    UnexpectedHalt,
+    #[error("program unexpectedly requested input")]
+    UnexpectedWait,
+    #[error("entry address `{}` is not in the program of length {}", .ptr, .len)]
+    InvalidEntry { ptr: usize, len: usize },
+    #[error("program is waiting for input at address `{}` but no more input will come", .ptr)]
+    Starved { ptr: usize },
+    #[error("invalid springscript instruction `{}`: {}", .instr, .reason)]
+    InvalidScript { instr: String, reason: &'static str },
+    #[error("springscript has {} instructions but at most 15 are allowed", .len)]
+    ScriptTooLong { len: usize },
+    #[error("{} are not allowed in strict mode", .feature)]
+    Strict { feature: &'static str },
+    #[error("failed to solve the adventure: {}", .reason)]
+    Unsolved { reason: &'static str },
+    #[error("failed to load program from `{}`: {}", .path, .msg)]
+    Load { path: String, msg: String },
+    #[error("invalid memory override on line {}: {}", .line, .msg)]
+    MemInit { line: usize, msg: String },
+    #[error("failed to write output: {}", .msg)]
+    Write { msg: String },
+    #[error("instruction at address `{}` used invalid address `{}`", .ptr, .addr)]
+    InvalidAddress { ptr: usize, addr: String },
+    #[error("instruction at address `{}` overflowed the {}-bit cell", .ptr, .bits)]
+    ArithmeticOverflow { ptr: usize, bits: u32 },
+    #[error("program completed without any output")]
+    NoOutput,
+    #[error("output `{}` at index {} is not an ASCII character", .value, .index)]
+    NotAscii { index: usize, value: String },
+    #[error("{} `{}` does not fit in 64 bits", .what, .value)]
+    Overflow { what: &'static str, value: String },
+    #[error("invalid checkpoint on line {}: {}", .line, .msg)]
+    Checkpoint { line: usize, msg: String },
+    #[error("checkpoint was taken from a different program")]
+    CheckpointMismatch,
+    #[error("program ran out of fuel at address `{}`", .ptr)]
+    OutOfFuel { ptr: usize },
+    #[error("program was cancelled at address `{}`", .ptr)]
+    Cancelled { ptr: usize },
 }
 
 /// The state of the computer.
-#[derive(Debug)]
-pub enum State {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State<C = i64> {
     /// An output.
-    Yielded(i64),
+    Yielded(C),
     /// Waiting for input.
     Waiting,
     /// Program execution has finished.
     Complete,
+    /// Stopped before executing an instruction because of a breakpoint or a
+    /// watchpoint.
+    ///
+    /// Running the computer again executes the instruction without pausing.
+    Paused { reason: Pause },
+    /// Stopped before executing an instruction because the fuel set with
+    /// [`Computer::set_fuel()`] ran out.
+    ///
+    /// The computer stays in this state until it is given more fuel.
+    OutOfFuel,
+}
+
+/// Why the computer paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pause {
+    /// The instruction at the address has a breakpoint.
+    Breakpoint { ptr: usize },
+    /// The instruction at `ptr` is about to access the watched address.
+    Watchpoint {
+        ptr: usize,
+        addr: usize,
+        access: Access,
+    },
+    /// Another interval of instructions has been executed, see
+    /// [`Computer::pause_every()`].
+    Interval { steps: u64 },
+    /// The cancel token was cancelled before the instruction at the address,
+    /// see [`Computer::set_cancel_token()`].
+    Cancelled { ptr: usize },
 }
 
-#[derive(Debug)]
-pub struct Computer {
-    mem: Vec<i64>,
+/// How an instruction accesses memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A read of an address that was never initialized, see
+/// [`Computer::set_poison()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitRead {
+    /// The address of the instruction that read it.
+    pub ptr: usize,
+    /// The address that was read.
+    pub addr: usize,
+}
+
+/// An intcode computer.
+///
+/// Cloning a computer is cheap, the original program is shared between the
+/// clones and only the modified memory is copied.
+///
+/// Each cell of memory is an `i64` by default, but any [`Cell`] type can be
+/// used instead.
+#[derive(Debug, Clone)]
+pub struct Computer<C = i64> {
+    mem: Memory<C>,
     ptr: usize,
-    relative_base: i64,
-    input: VecDeque<i64>,
+    relative_base: C,
+    input: VecDeque<C>,
+    devices: Vec<Device<C>>,
+    events: Option<EventLog<C>>,
+    heatmap: Option<Heatmap>,
+    cancel: Option<CancelToken>,
+    strict: bool,
+    /// The first setting that departs from canonical behaviour, which a
+    /// strict computer refuses to run with.
+    nonstandard: Option<&'static str>,
+    instructions: u64,
+    fuel: Option<u64>,
+    trace: Option<Trace<C>>,
+    points: Points,
+    poison: Option<Poison<C>>,
+}
+
+/// The value returned for uninitialized memory and the first read of it.
+#[derive(Debug, Clone)]
+struct Poison<C> {
+    value: C,
+    first: Option<UninitRead>,
+}
+
+/// The breakpoints and watchpoints of a computer.
+#[derive(Debug, Clone, Default)]
+struct Points {
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    /// The address of the instruction that paused, it doesn't pause again
+    /// when execution continues.
+    resume: Option<usize>,
+    /// The number of instructions between each interval pause.
+    every: Option<u64>,
+    /// The number of instructions executed when the next interval pause
+    /// happens.
+    next_interval: u64,
+}
+
+/// The values read and written by the instruction being stepped.
+#[derive(Debug, Clone, Default)]
+struct Trace<C> {
+    reads: Vec<C>,
+    write: Option<(usize, C)>,
 }
 
-fn cast(num: i64) -> usize {
-    usize::try_from(num).unwrap()
+/// A description of a single executed instruction, returned by
+/// [`Computer::step()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepInfo<C = i64> {
+    /// The address of the instruction.
+    pub ptr: usize,
+    /// The opcode without the parameter modes.
+    pub opcode: i64,
+    /// The mode of each parameter.
+    pub modes: Vec<i64>,
+    /// The address each parameter refers to, for immediate parameters this is
+    /// the address of the parameter itself.
+    pub addrs: Vec<usize>,
+    /// The values read from the parameters in the order they were read.
+    pub reads: Vec<C>,
+    /// The address and value written, if any.
+    pub write: Option<(usize, C)>,
+    /// The address of the next instruction.
+    pub next_ptr: usize,
+    /// The relative base before the instruction.
+    pub relative_base: C,
+    /// The relative base after the instruction.
+    pub next_relative_base: C,
+    /// Set if the instruction output a value, waited for input, or halted.
+    pub state: Option<State<C>>,
 }
 
-impl Computer {
-    pub fn new(program: Vec<i64>) -> Self {
+/// Counters describing the work done by a computer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of instructions executed.
+    pub instructions: u64,
+    /// The number of memory cells in use, this is the program plus any cells
+    /// written past the end of it.
+    ///
+    /// Memory is never freed so this is also the peak.
+    pub cells: usize,
+}
+
+/// Converts an address used by the instruction at `ptr` to a `usize`.
+fn cast<C: Cell>(num: C, ptr: usize) -> Result<usize> {
+    num.try_into().map_err(|_| Error::InvalidAddress {
+        ptr,
+        addr: num.to_string(),
+    })
+}
+
+/// Converts a single digit or an opcode, which always fit, to an `i64`.
+fn small<C: Cell>(num: C) -> i64 {
+    num.try_into().ok().unwrap()
+}
+
+/// Returns the mode of parameter `i` of the instruction.
+fn mode<C: Cell>(instr: C, i: usize) -> i64 {
+    let place = (0..=i).fold(C::from(1), |acc, _| acc * C::from(10));
+    small(instr / place % C::from(10))
+}
+
+impl<C> State<C> {
+    /// Turns the states that the drivers in this crate can't continue from
+    /// into errors, `ptr` is the address the computer stopped at.
+    ///
+    /// A cancelled computer becomes [`Error::Cancelled`] and a computer that
+    /// ran out of fuel becomes [`Error::OutOfFuel`], any other state is
+    /// returned as is.
+    pub fn into_result(self, ptr: usize) -> Result<Self> {
+        match self {
+            Self::Paused {
+                reason: Pause::Cancelled { .. },
+            } => Err(Error::Cancelled { ptr }),
+            Self::OutOfFuel => Err(Error::OutOfFuel { ptr }),
+            state => Ok(state),
+        }
+    }
+}
+
+impl<C: Cell> Computer<C> {
+    /// Constructs a new computer from the given program.
+    ///
+    /// The program can be shared between multiple computers by passing an
+    /// `Arc<[C]>`.
+    pub fn new(program: impl Into<Arc<[C]>>) -> Self {
         Self {
-            mem: program,
+            mem: Memory::new(program.into()),
             ptr: 0,
-            relative_base: 0,
+            relative_base: C::default(),
             input: VecDeque::new(),
+            devices: Vec::new(),
+            events: None,
+            heatmap: None,
+            cancel: None,
+            strict: false,
+            nonstandard: None,
+            instructions: 0,
+            fuel: None,
+            trace: None,
+            points: Points::default(),
+            poison: None,
         }
     }
 
-    pub fn feed(&mut self, iter: impl IntoIterator<Item = i64>) {
+    /// Starts execution at the given address instead of the first one.
+    ///
+    /// This allows a single routine in a program to be run on its own. The
+    /// address must be inside the program.
+    pub fn with_entry(mut self, ptr: usize) -> Result<Self> {
+        let len = self.mem.program_len();
+        if ptr >= len {
+            return Err(Error::InvalidEntry { ptr, len });
+        }
+        if ptr != 0 {
+            self.nonstandard.get_or_insert("entry addresses");
+        }
+        self.ptr = ptr;
+        Ok(self)
+    }
+
+    /// Resets this computer to be the same as the snapshot.
+    ///
+    /// This is the same as cloning the snapshot but it reuses the existing
+    /// memory and input buffers.
+    pub fn reset_to(&mut self, snapshot: &Self) {
+        self.mem.reset_to(&snapshot.mem);
+        self.ptr = snapshot.ptr;
+        self.relative_base = snapshot.relative_base;
+        self.input.clear();
+        self.input.extend(snapshot.input.iter().copied());
+        self.devices.clone_from(&snapshot.devices);
+        self.events.clone_from(&snapshot.events);
+        self.heatmap.clone_from(&snapshot.heatmap);
+        self.cancel.clone_from(&snapshot.cancel);
+        self.strict = snapshot.strict;
+        self.nonstandard = snapshot.nonstandard;
+        self.instructions = snapshot.instructions;
+        self.fuel = snapshot.fuel;
+        self.trace = None;
+        self.points.clone_from(&snapshot.points);
+        self.poison.clone_from(&snapshot.poison);
+    }
+
+    pub fn feed(&mut self, iter: impl IntoIterator<Item = C>) {
         self.input.extend(iter)
     }
 
-    fn mem_get(&self, addr: usize) -> i64 {
-        self.mem.get(addr).copied().unwrap_or(0)
+    /// Returns the number of instructions executed and memory cells used so
+    /// far.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            instructions: self.instructions,
+            cells: self.mem.cells(),
+        }
+    }
+
+    /// Returns the relative base.
+    pub fn relative_base(&self) -> C {
+        self.relative_base
+    }
+
+    /// Sets the relative base, for programs that expect to start with a
+    /// particular one.
+    pub fn set_relative_base(&mut self, relative_base: C) {
+        self.nonstandard.get_or_insert("relative base overrides");
+        self.relative_base = relative_base;
+    }
+
+    /// Returns the value in memory at the address.
+    ///
+    /// Memory mapped devices are not read, the underlying memory is returned
+    /// instead.
+    pub fn peek(&self, addr: usize) -> C {
+        self.mem.get(addr)
+    }
+
+    /// Overwrites memory at each address with the value.
+    ///
+    /// Memory mapped devices are not written, the underlying memory is
+    /// changed instead.
+    pub fn init_memory(&mut self, values: impl IntoIterator<Item = (usize, C)>) {
+        self.nonstandard.get_or_insert("memory overrides");
+        for (addr, value) in values {
+            *self.mem.get_mut(addr) = value;
+        }
+    }
+
+    /// Returns the address of the current instruction.
+    pub fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    /// Maps a range of memory to a device.
+    ///
+    /// Reads from an address in the range call `read` with the address and
+    /// writes call `write` with the address and the value, the underlying
+    /// memory is never touched. If ranges overlap then the most recently
+    /// mapped device handles the address.
+    pub fn map_device<R, W>(&mut self, range: Range<usize>, read: R, write: W)
+    where
+        R: FnMut(usize) -> C + Send + 'static,
+        W: FnMut(usize, C) + Send + 'static,
+    {
+        self.devices.push(Device::new(range, read, write));
+    }
+
+    /// Whether to only allow canonical Advent of Code behaviour.
+    ///
+    /// A strict computer refuses to run with memory mapped devices, poison
+    /// values, a different entry address, or overridden memory or relative
+    /// base.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Audit reads of uninitialized memory.
+    ///
+    /// Memory past the end of the program that has never been written to
+    /// reads as `value` instead of zero, and the first such read is recorded.
+    /// Programs that rely on implicit zeros then behave differently, which
+    /// makes the reliance easy to spot. Passing `None` turns this off again.
+    ///
+    /// Poison is not canonical behaviour so this fails on a strict computer.
+    pub fn set_poison(&mut self, value: Option<C>) -> Result<()> {
+        if self.strict && value.is_some() {
+            return Err(Error::Strict {
+                feature: "poison values",
+            });
+        }
+        self.poison = value.map(|value| Poison { value, first: None });
+        Ok(())
+    }
+
+    /// Returns the first read of uninitialized memory, if poisoning is
+    /// enabled and there was one.
+    pub fn uninit_read(&self) -> Option<UninitRead> {
+        self.poison.as_ref().and_then(|p| p.first)
+    }
+
+    /// Pauses execution before the instruction at the address.
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.points.breakpoints.insert(addr);
+    }
+
+    /// Removes a breakpoint, returning whether there was one.
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.points.breakpoints.remove(&addr)
+    }
+
+    /// Pauses execution before any instruction that reads or writes the
+    /// address through one of its parameters.
+    pub fn add_watchpoint(&mut self, addr: usize) {
+        self.points.watchpoints.insert(addr);
+    }
+
+    /// Removes a watchpoint, returning whether there was one.
+    pub fn remove_watchpoint(&mut self, addr: usize) -> bool {
+        self.points.watchpoints.remove(&addr)
+    }
+
+    /// Limits the number of instructions that can still be executed, once
+    /// they have been used up the computer stops with [`State::OutOfFuel`].
+    ///
+    /// This keeps a buggy program from looping forever. Waiting for input
+    /// doesn't use any fuel.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.fuel = Some(fuel);
+    }
+
+    /// Returns the number of instructions that can still be executed, if
+    /// limited.
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Pauses execution before the next instruction whenever the token is
+    /// cancelled. The token stays cancelled, so it can be shared between
+    /// computers, and the computer keeps pausing until the token is reset.
+    /// Passing `None` stops checking a token.
+    ///
+    /// The drivers in this crate, like [`Computer::run_with()`], stop with
+    /// [`Error::Cancelled`] instead of pausing.
+    pub fn set_cancel_token(&mut self, token: Option<CancelToken>) {
+        self.cancel = token;
+    }
+
+    /// Pauses execution every time another `steps` instructions have been
+    /// executed, for example to save a checkpoint during a long run. Passing
+    /// `None` turns this off again.
+    pub fn pause_every(&mut self, steps: Option<u64>) {
+        self.points.every = steps.map(|steps| steps.max(1));
+        self.points.next_interval = self.instructions + steps.unwrap_or(0).max(1);
+    }
+
+    /// Returns why the current instruction should pause, if it should.
+    fn pause(&mut self) -> Result<Option<Pause>> {
+        let ptr = self.ptr;
+        if self.points.resume.take() == Some(ptr) {
+            return Ok(None);
+        }
+        if self.points.breakpoints.contains(&ptr) {
+            return Ok(Some(Pause::Breakpoint { ptr }));
+        }
+        if self.points.watchpoints.is_empty() {
+            return Ok(None);
+        }
+        let (arity, write) = match small(self.mem_get(ptr) % C::from(100)) {
+            1 | 2 | 7 | 8 => (3, Some(3)),
+            5 | 6 => (2, None),
+            3 => (1, Some(1)),
+            4 | 9 => (1, None),
+            _ => return Ok(None),
+        };
+        for i in 1..=arity {
+            let addr = self.param_ptr(i)?;
+            if self.points.watchpoints.contains(&addr) {
+                let access = match write == Some(i) {
+                    true => Access::Write,
+                    false => Access::Read,
+                };
+                return Ok(Some(Pause::Watchpoint { ptr, addr, access }));
+            }
+        }
+        Ok(None)
     }
 
-    fn mem_get_mut(&mut self, addr: usize) -> &mut i64 {
-        self.mem.resize(max(self.mem.len(), addr + 1), 0);
-        &mut self.mem[addr]
+    fn device(&self, addr: usize) -> Option<&Device<C>> {
+        self.devices.iter().rev().find(|d| d.contains(addr))
+    }
+
+    /// Start recording events of the given kinds, discarding any previously
+    /// recorded events.
+    pub fn record_events(&mut self, mask: EventMask) {
+        self.events = Some(EventLog::new(mask));
+    }
+
+    /// Returns the recorded events.
+    pub fn events(&self) -> Option<&EventLog<C>> {
+        self.events.as_ref()
+    }
+
+    /// Stops recording events and returns the recorded events.
+    pub fn take_events(&mut self) -> Option<EventLog<C>> {
+        self.events.take()
+    }
+
+    /// Start counting the accesses to each address, discarding any previous
+    /// counts.
+    pub fn record_heatmap(&mut self) {
+        self.heatmap = Some(Heatmap::new(self.mem.program_len()));
+    }
+
+    /// Returns the access counts.
+    pub fn heatmap(&self) -> Option<&Heatmap> {
+        self.heatmap.as_ref()
+    }
+
+    /// Stops counting accesses and returns the counts.
+    pub fn take_heatmap(&mut self) -> Option<Heatmap> {
+        self.heatmap.take()
+    }
+
+    fn record(&mut self, event: Event<C>) {
+        if let Some(log) = &mut self.events {
+            log.record(event);
+        }
+    }
+
+    fn mem_get(&self, addr: usize) -> C {
+        match (self.device(addr), &self.poison) {
+            (Some(device), _) => device.read(addr),
+            (None, Some(p)) if !self.mem.is_init(addr) => p.value,
+            (None, _) => self.mem.get(addr),
+        }
+    }
+
+    /// Records the read if it is the first of uninitialized memory.
+    fn audit(&mut self, addr: usize) {
+        if let Some(Poison { first: None, .. }) = self.poison {
+            if self.device(addr).is_none() && !self.mem.is_init(addr) {
+                let read = UninitRead {
+                    ptr: self.ptr,
+                    addr,
+                };
+                if let Some(p) = &mut self.poison {
+                    p.first = Some(read);
+                }
+            }
+        }
+    }
+
+    fn mem_set(&mut self, addr: usize, value: C) {
+        match self.device(addr) {
+            Some(device) => device.write(addr, value),
+            None => *self.mem.get_mut(addr) = value,
+        }
     }
 
     fn param_ptr(&self, i: usize) -> Result<usize> {
-        let opcode = self.mem_get(self.ptr);
         let ptr = self.ptr + i;
-        match opcode / (10i64.pow((1 + i) as u32)) % 10 {
-            0 => Ok(cast(self.mem_get(ptr))),
+        match mode(self.mem_get(self.ptr), i) {
+            0 => cast(self.mem_get(ptr), self.ptr),
             1 => Ok(ptr),
-            2 => Ok(cast(self.relative_base + self.mem_get(ptr))),
+            2 => {
+                let addr = self.relative_base.checked_add(self.mem_get(ptr));
+                cast(addr.ok_or_else(|| self.overflow())?, self.ptr)
+            }
             mode => Err(Error::UnknownMode { mode }),
         }
     }
 
-    fn param(&self, i: usize) -> Result<i64> {
-        self.param_ptr(i).map(move |ptr| self.mem_get(ptr))
+    fn overflow(&self) -> Error {
+        Error::ArithmeticOverflow {
+            ptr: self.ptr,
+            bits: C::BITS,
+        }
     }
 
-    fn param_mut(&mut self, i: usize) -> Result<&mut i64> {
-        self.param_ptr(i).map(move |ptr| self.mem_get_mut(ptr))
+    fn param(&mut self, i: usize) -> Result<C> {
+        self.audit(self.ptr + i);
+        let addr = self.param_ptr(i)?;
+        self.audit(addr);
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.execute(self.ptr + i);
+            if mode(self.mem.get(self.ptr), i) != 1 {
+                heatmap.read(addr);
+            }
+        }
+        let value = self.mem_get(addr);
+        if let Some(trace) = &mut self.trace {
+            trace.reads.push(value);
+        }
+        Ok(value)
     }
 
-    pub fn next(&mut self) -> Result<State> {
-        loop {
-            match self.mem_get(self.ptr) % 100 {
-                1 => {
-                    *self.param_mut(3)? = self.param(1)? + self.param(2)?;
-                    self.ptr += 4;
-                }
-                2 => {
-                    *self.param_mut(3)? = self.param(1)? * self.param(2)?;
-                    self.ptr += 4;
-                }
-                3 => {
-                    if let Some(input) = self.input.pop_front() {
-                        *self.param_mut(1)? = input;
-                        self.ptr += 2;
-                    } else {
-                        break Ok(State::Waiting);
-                    }
-                }
-                4 => {
-                    let output = self.param(1)?;
+    fn set_param(&mut self, i: usize, value: C) -> Result<()> {
+        self.audit(self.ptr + i);
+        let ptr = self.param_ptr(i)?;
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.execute(self.ptr + i);
+            heatmap.write(ptr);
+        }
+        self.mem_set(ptr, value);
+        if let Some(trace) = &mut self.trace {
+            trace.write = Some((ptr, value));
+        }
+        Ok(())
+    }
+
+    fn jump(&mut self, target: usize) {
+        self.record(Event::Jump {
+            ptr: self.ptr,
+            target,
+        });
+        self.ptr = target;
+    }
+
+    fn check_strict(&self) -> Result<()> {
+        if !self.strict {
+            return Ok(());
+        }
+        if !self.devices.is_empty() {
+            return Err(Error::Strict {
+                feature: "memory mapped devices",
+            });
+        }
+        if self.poison.is_some() {
+            return Err(Error::Strict {
+                feature: "poison values",
+            });
+        }
+        match self.nonstandard {
+            Some(feature) => Err(Error::Strict { feature }),
+            None => Ok(()),
+        }
+    }
+
+    /// Executes a single instruction, returning the state if the computer
+    /// should stop running.
+    fn exec(&mut self) -> Result<Option<State<C>>> {
+        let zero = C::default();
+        if let Some(token) = &self.cancel {
+            if token.is_cancelled() {
+                return Ok(Some(State::Paused {
+                    reason: Pause::Cancelled { ptr: self.ptr },
+                }));
+            }
+        }
+        let points = &mut self.points;
+        if let Some(every) = points.every {
+            if self.instructions >= points.next_interval {
+                points.next_interval = self.instructions + every;
+                let steps = self.instructions;
+                return Ok(Some(State::Paused {
+                    reason: Pause::Interval { steps },
+                }));
+            }
+        }
+        if !points.breakpoints.is_empty() || !points.watchpoints.is_empty() {
+            if let Some(reason) = self.pause()? {
+                self.points.resume = Some(self.ptr);
+                return Ok(Some(State::Paused { reason }));
+            }
+        }
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 {
+                return Ok(Some(State::OutOfFuel));
+            }
+            *fuel -= 1;
+        }
+        self.instructions += 1;
+        self.audit(self.ptr);
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.execute(self.ptr);
+        }
+        match small(self.mem_get(self.ptr) % C::from(100)) {
+            1 => {
+                let value = self.param(1)?.checked_add(self.param(2)?);
+                let value = value.ok_or_else(|| self.overflow())?;
+                self.set_param(3, value)?;
+                self.ptr += 4;
+            }
+            2 => {
+                let value = self.param(1)?.checked_mul(self.param(2)?);
+                let value = value.ok_or_else(|| self.overflow())?;
+                self.set_param(3, value)?;
+                self.ptr += 4;
+            }
+            3 => {
+                if let Some(input) = self.input.pop_front() {
+                    self.set_param(1, input)?;
+                    self.record(Event::Input {
+                        ptr: self.ptr,
+                        value: input,
+                    });
                     self.ptr += 2;
-                    break Ok(State::Yielded(output));
-                }
-                5 => {
-                    if self.param(1)? != 0 {
-                        self.ptr = cast(self.param(2)?);
-                    } else {
-                        self.ptr += 3;
+                } else {
+                    // The instruction runs again once there is input, without
+                    // pausing again.
+                    self.instructions -= 1;
+                    if let Some(fuel) = &mut self.fuel {
+                        *fuel += 1;
                     }
-                }
-                6 => {
-                    if self.param(1)? == 0 {
-                        self.ptr = cast(self.param(2)?);
-                    } else {
-                        self.ptr += 3;
+                    if let Some(heatmap) = &mut self.heatmap {
+                        heatmap.unexecute(self.ptr);
                     }
+                    self.points.resume = Some(self.ptr);
+                    return Ok(Some(State::Waiting));
                 }
-                7 => {
-                    *self.param_mut(3)? = (self.param(1)? < self.param(2)?) as i64;
-                    self.ptr += 4;
-                }
-                8 => {
-                    *self.param_mut(3)? = (self.param(1)? == self.param(2)?) as i64;
-                    self.ptr += 4;
+            }
+            4 => {
+                let output = self.param(1)?;
+                self.record(Event::Output {
+                    ptr: self.ptr,
+                    value: output,
+                });
+                self.ptr += 2;
+                return Ok(Some(State::Yielded(output)));
+            }
+            5 => {
+                if self.param(1)? != zero {
+                    let target = cast(self.param(2)?, self.ptr)?;
+                    self.jump(target);
+                } else {
+                    self.ptr += 3;
                 }
-                9 => {
-                    self.relative_base += self.param(1)?;
-                    self.ptr += 2;
+            }
+            6 => {
+                if self.param(1)? == zero {
+                    let target = cast(self.param(2)?, self.ptr)?;
+                    self.jump(target);
+                } else {
+                    self.ptr += 3;
                 }
-                99 => break Ok(State::Complete),
-                opcode => break Err(Error::UnknownOpcode { opcode }),
             }
+            7 => {
+                let value = self.param(1)? < self.param(2)?;
+                self.set_param(3, C::from(value as i8))?;
+                self.ptr += 4;
+            }
+            8 => {
+                let value = self.param(1)? == self.param(2)?;
+                self.set_param(3, C::from(value as i8))?;
+                self.ptr += 4;
+            }
+            9 => {
+                let adjustment = self.param(1)?;
+                let relative_base = self.relative_base.checked_add(adjustment);
+                self.relative_base = relative_base.ok_or_else(|| self.overflow())?;
+                self.record(Event::AdjustRelativeBase {
+                    ptr: self.ptr,
+                    relative_base: self.relative_base,
+                });
+                self.ptr += 2;
+            }
+            99 => return Ok(Some(State::Complete)),
+            opcode => return Err(Error::UnknownOpcode { opcode }),
         }
+        Ok(None)
+    }
+
+    /// Runs the program until it outputs a value, waits for input, or halts.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<State<C>> {
+        self.check_strict()?;
+        loop {
+            if let Some(state) = self.exec()? {
+                break Ok(state);
+            }
+        }
+    }
+
+    /// Executes exactly one instruction and describes what it did.
+    ///
+    /// If the instruction is an input and there is no input then nothing is
+    /// executed and the state is [`State::Waiting`].
+    pub fn step(&mut self) -> Result<StepInfo<C>> {
+        self.check_strict()?;
+        let ptr = self.ptr;
+        let relative_base = self.relative_base;
+        let instr = self.mem_get(ptr);
+        let opcode = small(instr % C::from(100));
+        let arity = match opcode {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            99 => 0,
+            opcode => return Err(Error::UnknownOpcode { opcode }),
+        };
+        let modes = (1..=arity).map(|i| mode(instr, i)).collect();
+        let addrs = (1..=arity)
+            .map(|i| self.param_ptr(i))
+            .collect::<Result<_>>()?;
+        self.trace = Some(Trace::default());
+        let result = self.exec();
+        let trace = self.trace.take().unwrap_or_default();
+        Ok(StepInfo {
+            ptr,
+            opcode,
+            modes,
+            addrs,
+            reads: trace.reads,
+            write: trace.write,
+            next_ptr: self.ptr,
+            relative_base,
+            next_relative_base: self.relative_base,
+            state: result?,
+        })
     }
 }
@@ -0,0 +1,36 @@
+//! Find an intcode program in a puzzle input.
+//!
+//! Puzzle inputs are sometimes saved with notes or expected outputs after
+//! the program. The first line that is an intcode program is used and every
+//! other line that is not blank is returned so that it can be reported.
+
+use std::result;
+
+/// A program and the lines that were ignored around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lenient {
+    pub program: Vec<i64>,
+    /// The line number and text of each ignored line that was not blank.
+    pub ignored: Vec<(usize, String)>,
+}
+
+/// Parses the first line that is an intcode program.
+pub fn lenient(input: &str) -> Option<Lenient> {
+    let mut program = None;
+    let mut ignored = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values = line
+            .trim()
+            .split(',')
+            .map(str::parse)
+            .collect::<result::Result<Vec<i64>, _>>();
+        match (&program, values) {
+            (None, Ok(values)) => program = Some(values),
+            _ => ignored.push((i + 1, line.to_owned())),
+        }
+    }
+    program.map(|program| Lenient { program, ignored })
+}
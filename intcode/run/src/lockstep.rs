@@ -0,0 +1,183 @@
+//! Run two programs side by side and find where their behavior differs.
+//!
+//! Both computers are given the same input and are compared after every
+//! step. Comparing instructions only makes sense for programs where each
+//! instruction has a counterpart, like a program and a patched copy of it. A
+//! rewrite that is meant to behave the same way can instead be compared by
+//! its outputs alone.
+
+use std::fmt;
+
+use crate::{Computer, Result, State};
+
+/// What to compare after each step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Each step runs until the next output and only the outputs are
+    /// compared.
+    Outputs,
+    /// Each step is a single instruction, the values written, jumps taken,
+    /// and relative base adjustments are compared as well as the outputs.
+    Instructions,
+}
+
+/// The observable effect of a step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Nothing observable happened, for example a jump was not taken.
+    None,
+    /// A value was output.
+    Output(i64),
+    /// A value was written to the address.
+    Write { addr: usize, value: i64 },
+    /// A jump was taken to the target.
+    Jump { target: usize },
+    /// The relative base was adjusted to the value.
+    RelativeBase(i64),
+    /// The program halted.
+    Halt,
+    /// The program needs more input than it was given.
+    Starved,
+}
+
+/// What one of the programs did at the step where they diverged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Side {
+    /// The address of the instruction that had the effect.
+    pub ptr: usize,
+    pub effect: Effect,
+}
+
+/// The first step where the programs behaved differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// The number of steps that matched before this one.
+    pub step: u64,
+    pub left: Side,
+    pub right: Side,
+}
+
+/// The result of comparing two programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Both programs finished after the same behavior.
+    Same { steps: u64 },
+    /// The programs behaved differently.
+    Diverged(Divergence),
+    /// The step or instruction limit was reached before either finished or
+    /// they diverged.
+    Undecided { steps: u64 },
+}
+
+impl Effect {
+    fn is_final(self) -> bool {
+        matches!(self, Self::Halt | Self::Starved)
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "did nothing"),
+            Self::Output(value) => write!(f, "output {}", value),
+            Self::Write { addr, value } => write!(f, "wrote {} to address {}", value, addr),
+            Self::Jump { target } => write!(f, "jumped to address {}", target),
+            Self::RelativeBase(value) => write!(f, "set the relative base to {}", value),
+            Self::Halt => write!(f, "halted"),
+            Self::Starved => write!(f, "ran out of input"),
+        }
+    }
+}
+
+fn effect(state: State) -> Option<Effect> {
+    match state {
+        State::Yielded(value) => Some(Effect::Output(value)),
+        State::Waiting => Some(Effect::Starved),
+        State::Complete => Some(Effect::Halt),
+        State::Paused { .. } | State::OutOfFuel => None,
+    }
+}
+
+/// Executes the next step and returns what it did, or `None` if the computer
+/// ran out of fuel first.
+fn step(c: &mut Computer, level: Level) -> Result<Option<Side>> {
+    match level {
+        Level::Outputs => loop {
+            let state = c.next()?;
+            if state == State::OutOfFuel {
+                break Ok(None);
+            }
+            if let Some(effect) = effect(state.into_result(c.ptr())?) {
+                // The pointer has already moved past an output instruction.
+                let ptr = match effect {
+                    Effect::Output(_) => c.ptr() - 2,
+                    _ => c.ptr(),
+                };
+                break Ok(Some(Side { ptr, effect }));
+            }
+        },
+        Level::Instructions => {
+            let ptr = c.ptr();
+            let info = c.step()?;
+            match info.state {
+                Some(State::OutOfFuel) => return Ok(None),
+                Some(state) => {
+                    state.into_result(ptr)?;
+                }
+                None => {}
+            }
+            let effect = match (info.state.and_then(effect), info.write) {
+                (Some(effect), _) => effect,
+                (None, Some((addr, value))) => Effect::Write { addr, value },
+                (None, None) if info.opcode == 9 => Effect::RelativeBase(info.next_relative_base),
+                (None, None) if info.next_ptr != ptr + info.addrs.len() + 1 => Effect::Jump {
+                    target: info.next_ptr,
+                },
+                (None, None) => Effect::None,
+            };
+            Ok(Some(Side { ptr, effect }))
+        }
+    }
+}
+
+/// Runs both computers with the same input until they diverge, both finish,
+/// or `limit` steps have been taken.
+///
+/// A step at the [`Level::Outputs`] level can execute any number of
+/// instructions, so each computer is also limited to executing `limit`
+/// instructions. Running out of fuel counts as reaching the limit.
+pub fn compare(
+    mut left: Computer,
+    mut right: Computer,
+    input: &[i64],
+    level: Level,
+    limit: u64,
+) -> Result<Outcome> {
+    left.feed(input.iter().copied());
+    right.feed(input.iter().copied());
+    if level == Level::Outputs {
+        for c in [&mut left, &mut right] {
+            c.set_fuel(c.fuel().map_or(limit, |fuel| fuel.min(limit)));
+        }
+    }
+    for step in 0..limit {
+        let (l, r) = match (
+            self::step(&mut left, level)?,
+            self::step(&mut right, level)?,
+        ) {
+            (Some(l), Some(r)) => (l, r),
+            _ => return Ok(Outcome::Undecided { steps: step }),
+        };
+        if l.effect != r.effect {
+            return Ok(Outcome::Diverged(Divergence {
+                step,
+                left: l,
+                right: r,
+            }));
+        }
+        if l.effect.is_final() {
+            return Ok(Outcome::Same { steps: step + 1 });
+        }
+    }
+    Ok(Outcome::Undecided { steps: limit })
+}
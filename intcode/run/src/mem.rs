@@ -0,0 +1,87 @@
+//! Copy-on-write memory for the computer.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::cell::Cell;
+
+/// The memory of a computer.
+///
+/// The original program is shared between all computers created from it and
+/// only the cells that have been written to are stored separately. This makes
+/// cloning a computer proportional to the number of modified cells instead of
+/// the size of the program.
+#[derive(Debug, Clone)]
+pub struct Memory<C> {
+    /// The original program.
+    image: Arc<[C]>,
+    /// The cells that have been modified since the program was loaded.
+    overlay: HashMap<usize, C>,
+}
+
+impl<C: Cell> Memory<C> {
+    pub fn new(image: Arc<[C]>) -> Self {
+        Self {
+            image,
+            overlay: HashMap::new(),
+        }
+    }
+
+    /// Returns the value at the given address.
+    pub fn get(&self, addr: usize) -> C {
+        match self.overlay.get(&addr) {
+            Some(value) => *value,
+            None => self.image.get(addr).copied().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the length of the original program.
+    pub fn program_len(&self) -> usize {
+        self.image.len()
+    }
+
+    /// Returns whether the address is part of the program or has been
+    /// written to.
+    pub fn is_init(&self, addr: usize) -> bool {
+        addr < self.image.len() || self.overlay.contains_key(&addr)
+    }
+
+    /// Returns a mutable reference to the value at the given address.
+    pub fn get_mut(&mut self, addr: usize) -> &mut C {
+        let image = &self.image;
+        self.overlay
+            .entry(addr)
+            .or_insert_with(|| image.get(addr).copied().unwrap_or_default())
+    }
+
+    /// Returns the original program.
+    pub fn program(&self) -> &Arc<[C]> {
+        &self.image
+    }
+
+    /// Returns the cells that have been modified, in no particular order.
+    pub fn modified(&self) -> impl Iterator<Item = (usize, C)> + '_ {
+        self.overlay.iter().map(|(&addr, &value)| (addr, value))
+    }
+
+    /// Forgets all modifications, as if the program was just loaded.
+    pub fn clear(&mut self) {
+        self.overlay.clear();
+    }
+
+    /// Returns the number of cells in use, the program plus any cells that
+    /// were written past the end of it.
+    pub fn cells(&self) -> usize {
+        let len = self.image.len();
+        len + self.overlay.keys().filter(|&&addr| addr >= len).count()
+    }
+
+    /// Resets this memory to be the same as the other memory, reusing the
+    /// existing allocation.
+    pub fn reset_to(&mut self, other: &Self) {
+        self.image = other.image.clone();
+        self.overlay.clear();
+        self.overlay
+            .extend(other.overlay.iter().map(|(&addr, &value)| (addr, value)));
+    }
+}
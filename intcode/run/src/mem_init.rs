@@ -0,0 +1,31 @@
+//! Parse memory overrides to apply before a program runs.
+//!
+//! Each line is an address and the value to store there, as in `1024=-7`.
+//! Blank lines are ignored. The overrides are applied using
+//! [`Computer::init_memory()`][crate::Computer::init_memory].
+
+use crate::{Error, Result};
+
+/// Parses the `addr=value` lines into address and value pairs.
+pub fn parse(input: &str) -> Result<Vec<(usize, i64)>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let err = |msg: String| Error::MemInit { line: i + 1, msg };
+            let (addr, value) = line
+                .split_once('=')
+                .ok_or_else(|| err(format!("expected `addr=value`, found `{}`", line)))?;
+            let addr = addr
+                .trim()
+                .parse()
+                .map_err(|e| err(format!("invalid address `{}`: {}", addr.trim(), e)))?;
+            let value = value
+                .trim()
+                .parse()
+                .map_err(|e| err(format!("invalid value `{}`: {}", value.trim(), e)))?;
+            Ok((addr, value))
+        })
+        .collect()
+}
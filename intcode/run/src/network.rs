@@ -0,0 +1,397 @@
+//! Run a network of computers that send packets to each other.
+//!
+//! Each computer is given its address as its first input and then sends
+//! packets as three outputs: the destination address followed by `x` and `y`.
+//! Whenever a computer waits for input and no packet is queued it receives
+//! `-1`. Packets sent to an address outside the network are passed to the
+//! handler given to [`Network::run()`].
+//!
+//! A network can also have a NAT, see [`Network::nat()`]. The NAT keeps the
+//! last packet sent to its address and whenever the network is idle, that is
+//! every computer is waiting for a packet and none are in flight, it sends
+//! that packet to address `0`.
+//!
+//! By default the network is run with a deterministic [`Schedule`] so that
+//! the same program always produces the same packets in the same order. A
+//! threaded schedule is also available which is faster but makes no
+//! guarantees about the order in which packets are delivered.
+
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::cancel::CancelToken;
+use crate::{Computer, Error, Result, State};
+
+/// A packet sent by a computer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// How the computers in a network take turns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Run each computer in turn on the current thread until it waits for a
+    /// packet that has not arrived yet.
+    ///
+    /// Every round the order of the computers is shuffled using the seed, a
+    /// seed of zero always visits the computers in address order. Runs with
+    /// the same seed are always reproducible.
+    RoundRobin { seed: u64 },
+    /// Run each computer on its own thread, sending packets over channels.
+    ///
+    /// This is not deterministic, packets from different computers may arrive
+    /// in any order.
+    Threaded,
+}
+
+/// A network of computers running the same program.
+#[derive(Debug, Clone)]
+pub struct Network {
+    program: Arc<[i64]>,
+    size: usize,
+    schedule: Schedule,
+    nat: Option<i64>,
+    cancel: Option<CancelToken>,
+}
+
+/// The last packet sent to the NAT.
+#[derive(Debug)]
+struct Nat {
+    addr: Option<i64>,
+    packet: Option<Packet>,
+}
+
+/// A single computer in the network.
+#[derive(Debug)]
+struct Node {
+    computer: Computer,
+    output: Vec<i64>,
+}
+
+/// A xorshift generator used to break ties in the round robin schedule.
+#[derive(Debug)]
+struct Rng(u64);
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::RoundRobin { seed: 0 }
+    }
+}
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        if self.0 == 0 {
+            return;
+        }
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+impl Node {
+    fn new(program: &Arc<[i64]>, addr: usize, cancel: Option<CancelToken>) -> Self {
+        let mut computer = Computer::new(program.clone());
+        computer.set_cancel_token(cancel);
+        computer.feed([addr as i64]);
+        Self {
+            computer,
+            output: Vec::new(),
+        }
+    }
+
+    fn receive(&mut self, packet: Packet) {
+        self.computer.feed([packet.x, packet.y]);
+    }
+
+    /// Runs the computer until it waits for a packet that has not arrived or
+    /// halts, returning whether it halted.
+    fn turn(&mut self, packets: &mut Vec<Packet>) -> Result<bool> {
+        let mut polled = false;
+        loop {
+            match self.computer.next()?.into_result(self.computer.ptr())? {
+                State::Yielded(value) => {
+                    self.output.push(value);
+                    if let [dest, x, y] = self.output[..] {
+                        packets.push(Packet { dest, x, y });
+                        self.output.clear();
+                    }
+                }
+                State::Waiting if !polled => {
+                    self.computer.feed([-1]);
+                    polled = true;
+                }
+                State::Waiting => return Ok(false),
+                State::Complete => return Ok(true),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Nat {
+    /// Keeps the packet if it was sent to the NAT.
+    fn keep(&mut self, packet: Packet) {
+        if self.addr == Some(packet.dest) {
+            self.packet = Some(packet);
+        }
+    }
+
+    /// Returns the packet to send to address `0` when the network is idle.
+    fn wake(&self) -> Option<Packet> {
+        self.packet
+            .map(|Packet { x, y, .. }| Packet { dest: 0, x, y })
+    }
+}
+
+impl Network {
+    /// Constructs a new network of `size` computers running the program.
+    pub fn new(program: impl Into<Arc<[i64]>>, size: usize) -> Self {
+        Self {
+            program: program.into(),
+            size,
+            schedule: Schedule::default(),
+            nat: None,
+            cancel: None,
+        }
+    }
+
+    /// Adds a NAT at the given address, usually `255`.
+    ///
+    /// Packets sent to the NAT are still passed to the handler. When the
+    /// network is idle the NAT sends the last of them to address `0`, and
+    /// that packet is passed to the handler too, with a destination of `0`.
+    ///
+    /// # Panics
+    ///
+    /// If the address is inside the network.
+    pub fn nat(mut self, addr: i64) -> Self {
+        assert!(
+            usize::try_from(addr).map_or(true, |a| a >= self.size),
+            "NAT address {} is inside the network",
+            addr
+        );
+        self.nat = Some(addr);
+        self
+    }
+
+    /// Sets how the computers take turns.
+    pub fn schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Sets a token that stops every computer with [`Error::Cancelled`] once
+    /// it is cancelled.
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    fn address(&self, packet: &Packet) -> Option<usize> {
+        usize::try_from(packet.dest).ok().filter(|&a| a < self.size)
+    }
+
+    /// Runs the network, passing each packet sent outside the network to the
+    /// handler until it breaks.
+    ///
+    /// If every computer halts before then [`Error::UnexpectedHalt`] is
+    /// returned.
+    pub fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnMut(Packet) -> ControlFlow<T>,
+    {
+        match self.schedule {
+            Schedule::RoundRobin { seed } => self.run_round_robin(seed, f),
+            Schedule::Threaded => self.run_threaded(f),
+        }
+    }
+
+    fn run_round_robin<T, F>(&self, seed: u64, mut f: F) -> Result<T>
+    where
+        F: FnMut(Packet) -> ControlFlow<T>,
+    {
+        let mut nodes: Vec<_> = (0..self.size)
+            .map(|addr| Some(Node::new(&self.program, addr, self.cancel.clone())))
+            .collect();
+        let mut rng = Rng(seed);
+        let mut order: Vec<_> = (0..self.size).collect();
+        let mut packets = Vec::new();
+        let mut nat = Nat {
+            addr: self.nat,
+            packet: None,
+        };
+        while nodes.iter().any(Option::is_some) {
+            rng.shuffle(&mut order);
+            // Every computer consumes all of its input on its turn, so the
+            // network is idle after a round in which nothing was sent.
+            let mut idle = true;
+            for &addr in &order {
+                let halted = match &mut nodes[addr] {
+                    Some(node) => node.turn(&mut packets)?,
+                    None => continue,
+                };
+                if halted {
+                    nodes[addr] = None;
+                }
+                idle &= packets.is_empty();
+                for packet in packets.drain(..) {
+                    match self.address(&packet) {
+                        Some(dest) => {
+                            if let Some(node) = &mut nodes[dest] {
+                                node.receive(packet);
+                            }
+                        }
+                        None => {
+                            nat.keep(packet);
+                            if let ControlFlow::Break(value) = f(packet) {
+                                return Ok(value);
+                            }
+                        }
+                    }
+                }
+            }
+            if let (true, Some(packet)) = (idle, nat.wake()) {
+                if let Some(node) = &mut nodes[0] {
+                    node.receive(packet);
+                }
+                if let ControlFlow::Break(value) = f(packet) {
+                    return Ok(value);
+                }
+            }
+        }
+        Err(Error::UnexpectedHalt)
+    }
+
+    fn run_threaded<T, F>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut(Packet) -> ControlFlow<T>,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        // The packets sent between computers that have not been handled yet,
+        // and whether each computer did nothing on its last turn.
+        let pending = Arc::new(AtomicUsize::new(0));
+        let idle: Arc<[AtomicBool]> = (0..self.size).map(|_| AtomicBool::new(false)).collect();
+        let (external, received) = mpsc::channel::<Result<Packet>>();
+        let (senders, receivers): (Vec<Sender<Packet>>, Vec<_>) =
+            (0..self.size).map(|_| mpsc::channel()).unzip();
+
+        let handles: Vec<_> = receivers
+            .into_iter()
+            .enumerate()
+            .map(|(addr, receiver)| {
+                let mut node = Node::new(&self.program, addr, self.cancel.clone());
+                let network = self.clone();
+                let senders = senders.clone();
+                let external = external.clone();
+                let stop = stop.clone();
+                let pending = pending.clone();
+                let idle = idle.clone();
+                thread::spawn(move || {
+                    let mut packets = Vec::new();
+                    while !stop.load(Ordering::Relaxed) {
+                        let mut received = 0;
+                        for packet in receiver.try_iter() {
+                            node.receive(packet);
+                            received += 1;
+                        }
+                        let halted = match node.turn(&mut packets) {
+                            Ok(halted) => halted,
+                            Err(err) => {
+                                let _ = external.send(Err(err));
+                                break;
+                            }
+                        };
+                        idle[addr].store(received == 0 && packets.is_empty(), Ordering::SeqCst);
+                        // Sends only fail once the network is stopping.
+                        for packet in packets.drain(..) {
+                            match network.address(&packet) {
+                                Some(dest) => {
+                                    pending.fetch_add(1, Ordering::SeqCst);
+                                    let _ = senders[dest].send(packet);
+                                }
+                                None => {
+                                    let _ = external.send(Ok(packet));
+                                }
+                            }
+                        }
+                        // Only count the received packets as handled once
+                        // anything they caused has been sent.
+                        pending.fetch_sub(received, Ordering::SeqCst);
+                        if halted {
+                            break;
+                        }
+                        thread::yield_now();
+                    }
+                })
+            })
+            .collect();
+        // Only the computers hold senders now, so receiving fails once they
+        // have all halted.
+        drop(external);
+
+        let mut nat = Nat {
+            addr: self.nat,
+            packet: None,
+        };
+        // The network is only considered idle if it looks idle twice in a
+        // row, in case a computer was between turns the first time.
+        let mut quiet = false;
+        let result = loop {
+            let packet = match self.nat {
+                Some(_) => received.recv_timeout(Duration::from_millis(1)),
+                None => received.recv().map_err(RecvTimeoutError::from),
+            };
+            match packet {
+                Ok(Ok(packet)) => {
+                    quiet = false;
+                    nat.keep(packet);
+                    if let ControlFlow::Break(value) = f(packet) {
+                        break Ok(value);
+                    }
+                }
+                Ok(Err(err)) => break Err(err),
+                Err(RecvTimeoutError::Timeout) => {
+                    let now = pending.load(Ordering::SeqCst) == 0
+                        && idle.iter().all(|i| i.load(Ordering::SeqCst));
+                    match (quiet && now, nat.wake()) {
+                        (true, Some(packet)) => {
+                            quiet = false;
+                            for i in idle.iter() {
+                                i.store(false, Ordering::SeqCst);
+                            }
+                            pending.fetch_add(1, Ordering::SeqCst);
+                            let _ = senders[0].send(packet);
+                            if let ControlFlow::Break(value) = f(packet) {
+                                break Ok(value);
+                            }
+                        }
+                        _ => quiet = now,
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break Err(Error::UnexpectedHalt),
+            }
+        };
+        stop.store(true, Ordering::Relaxed);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        result
+    }
+}
@@ -0,0 +1,113 @@
+//! Chain computers so that the outputs of each one feed the next.
+//!
+//! Every stage runs the same program and is given its phase setting as its
+//! first input. The first stage is then given the initial input, which is a
+//! single `0` by default. With feedback the outputs of the last stage are fed
+//! back into the first, and the pipeline keeps going until the last stage
+//! halts. Either way the result is the last output of the last stage.
+
+use std::sync::Arc;
+
+use crate::cancel::CancelToken;
+use crate::{Computer, Error, Result, State};
+
+/// A chain of computers running the same program.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    program: Arc<[i64]>,
+    phases: Vec<i64>,
+    input: Vec<i64>,
+    feedback: bool,
+    cancel: Option<CancelToken>,
+}
+
+impl Pipeline {
+    /// Constructs a new pipeline with a stage for each phase setting.
+    pub fn new(program: impl Into<Arc<[i64]>>, phases: &[i64]) -> Self {
+        Self {
+            program: program.into(),
+            phases: phases.to_vec(),
+            input: vec![0],
+            feedback: false,
+            cancel: None,
+        }
+    }
+
+    /// Sets the input given to the first stage after its phase setting.
+    pub fn input(mut self, input: impl IntoIterator<Item = i64>) -> Self {
+        self.input = input.into_iter().collect();
+        self
+    }
+
+    /// Sets whether the outputs of the last stage are fed back into the
+    /// first.
+    pub fn feedback(mut self, feedback: bool) -> Self {
+        self.feedback = feedback;
+        self
+    }
+
+    /// Sets a token that stops every stage with [`Error::Cancelled`] once it
+    /// is cancelled.
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Runs the stages in turn until the last one halts and returns its last
+    /// output.
+    ///
+    /// If every stage that is still running waits for input that will never
+    /// come then [`Error::Starved`] is returned. If the last stage halts
+    /// without any output then [`Error::NoOutput`] is returned.
+    pub fn run(&self) -> Result<i64> {
+        let mut stages: Vec<_> = self
+            .phases
+            .iter()
+            .map(|&phase| {
+                let mut computer = Computer::new(self.program.clone());
+                computer.set_cancel_token(self.cancel.clone());
+                computer.feed([phase]);
+                computer
+            })
+            .collect();
+        let last = match stages.len().checked_sub(1) {
+            Some(last) => last,
+            None => return Err(Error::NoOutput),
+        };
+        stages[0].feed(self.input.iter().copied());
+        let mut halted = vec![false; stages.len()];
+        let mut signal = None;
+        loop {
+            // Every stage consumes all of its input on its turn, so if no
+            // stage produced an output then none of them can continue.
+            let mut progress = false;
+            for i in 0..stages.len() {
+                while !halted[i] {
+                    match stages[i].next()?.into_result(stages[i].ptr())? {
+                        State::Yielded(value) => {
+                            progress = true;
+                            if i == last {
+                                signal = Some(value);
+                            }
+                            if i < last {
+                                stages[i + 1].feed([value]);
+                            } else if self.feedback {
+                                stages[0].feed([value]);
+                            }
+                        }
+                        State::Waiting => break,
+                        State::Complete => halted[i] = true,
+                        _ => {}
+                    }
+                }
+            }
+            if halted[last] {
+                return signal.ok_or(Error::NoOutput);
+            }
+            if !progress {
+                let ptr = stages[last].ptr();
+                return Err(Error::Starved { ptr });
+            }
+        }
+    }
+}
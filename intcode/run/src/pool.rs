@@ -0,0 +1,130 @@
+//! A pool of computers that can be reused for many evaluations.
+//!
+//! Searching over inputs usually means running the same program thousands of
+//! times. A [`MachinePool`] keeps a fixed number of computers around and
+//! hands them out to worker threads. When a computer is returned to the pool
+//! it is reset to the snapshot it started from, which is much cheaper than
+//! constructing a new computer for every evaluation.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use std::thread;
+//!
+//! use intcode_run::pool::MachinePool;
+//!
+//! // Outputs the input multiplied by two.
+//! let pool = Arc::new(MachinePool::new(vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0], 2));
+//! let handles: Vec<_> = (0..4)
+//!     .map(|i| {
+//!         let pool = pool.clone();
+//!         thread::spawn(move || pool.get().run_with([i]).unwrap())
+//!     })
+//!     .collect();
+//! for (i, handle) in handles.into_iter().enumerate() {
+//!     assert_eq!(handle.join().unwrap(), [i as i64 * 2]);
+//! }
+//! ```
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::Computer;
+
+/// A fixed size pool of computers.
+#[derive(Debug)]
+pub struct MachinePool {
+    snapshot: Computer,
+    idle: Mutex<Vec<Computer>>,
+    available: Condvar,
+}
+
+/// A computer borrowed from a [`MachinePool`].
+///
+/// The computer is reset and returned to the pool when this is dropped.
+#[derive(Debug)]
+pub struct PooledComputer<'a> {
+    pool: &'a MachinePool,
+    computer: Option<Computer>,
+}
+
+impl MachinePool {
+    /// Constructs a new pool of `size` computers running the program.
+    pub fn new(program: impl Into<Arc<[i64]>>, size: usize) -> Self {
+        Self::from_snapshot(Computer::new(program), size)
+    }
+
+    /// Constructs a new pool of `size` copies of the computer.
+    ///
+    /// Every computer is reset to this snapshot when it is returned, so it
+    /// can be used to skip running a common prefix of the program or to
+    /// share input that every evaluation starts with.
+    pub fn from_snapshot(snapshot: Computer, size: usize) -> Self {
+        Self {
+            idle: Mutex::new(vec![snapshot.clone(); size]),
+            snapshot,
+            available: Condvar::new(),
+        }
+    }
+
+    /// Takes a computer from the pool, waiting until one is available.
+    ///
+    /// A pool with a size of zero will wait forever.
+    pub fn get(&self) -> PooledComputer<'_> {
+        let mut idle = self.idle.lock().unwrap();
+        let computer = loop {
+            match idle.pop() {
+                Some(computer) => break computer,
+                None => idle = self.available.wait(idle).unwrap(),
+            }
+        };
+        PooledComputer {
+            pool: self,
+            computer: Some(computer),
+        }
+    }
+
+    /// Takes a computer from the pool if one is available.
+    pub fn try_get(&self) -> Option<PooledComputer<'_>> {
+        let computer = self.idle.lock().unwrap().pop()?;
+        Some(PooledComputer {
+            pool: self,
+            computer: Some(computer),
+        })
+    }
+
+    fn put(&self, mut computer: Computer) {
+        computer.reset_to(&self.snapshot);
+        self.idle.lock().unwrap().push(computer);
+        self.available.notify_one();
+    }
+}
+
+impl PooledComputer<'_> {
+    /// Resets the computer to the pool's snapshot without returning it.
+    pub fn reset(&mut self) {
+        let snapshot = &self.pool.snapshot;
+        self.computer.as_mut().unwrap().reset_to(snapshot);
+    }
+}
+
+impl Deref for PooledComputer<'_> {
+    type Target = Computer;
+
+    fn deref(&self) -> &Self::Target {
+        self.computer.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledComputer<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.computer.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledComputer<'_> {
+    fn drop(&mut self) {
+        if let Some(computer) = self.computer.take() {
+            self.pool.put(computer);
+        }
+    }
+}
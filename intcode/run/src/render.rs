@@ -0,0 +1,99 @@
+//! Customizable formatting of a program's outputs.
+//!
+//! A [`Render`] decides how each output value is written. The common formats
+//! are provided and anything else can be plugged in by implementing the trait
+//! or by wrapping a closure with [`from_fn()`].
+
+use std::io;
+
+use crate::{Computer, Error, Result};
+
+/// Writes output values.
+pub trait Render {
+    /// Writes a single output value.
+    fn render(&mut self, w: &mut dyn io::Write, value: i64) -> io::Result<()>;
+}
+
+/// Each value in decimal on its own line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Decimal;
+
+/// Each value in hexadecimal with a `0x` prefix on its own line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hex;
+
+/// Printable ASCII characters and newlines as text, any other value in
+/// decimal on its own line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ascii;
+
+/// A renderer that calls a closure, created with [`from_fn()`].
+#[derive(Debug, Clone, Copy)]
+pub struct FromFn<F>(F);
+
+/// Returns a renderer that writes the text returned by the closure for each
+/// value.
+pub fn from_fn<F>(f: F) -> FromFn<F>
+where
+    F: FnMut(i64) -> String,
+{
+    FromFn(f)
+}
+
+impl Render for Decimal {
+    fn render(&mut self, w: &mut dyn io::Write, value: i64) -> io::Result<()> {
+        writeln!(w, "{}", value)
+    }
+}
+
+impl Render for Hex {
+    fn render(&mut self, w: &mut dyn io::Write, value: i64) -> io::Result<()> {
+        match value < 0 {
+            true => writeln!(w, "-{:#x}", value.unsigned_abs()),
+            false => writeln!(w, "{:#x}", value),
+        }
+    }
+}
+
+impl Render for Ascii {
+    fn render(&mut self, w: &mut dyn io::Write, value: i64) -> io::Result<()> {
+        match value {
+            10 | 0x20..=0x7e => w.write_all(&[value as u8]),
+            _ => writeln!(w, "{}", value),
+        }
+    }
+}
+
+impl<F> Render for FromFn<F>
+where
+    F: FnMut(i64) -> String,
+{
+    fn render(&mut self, w: &mut dyn io::Write, value: i64) -> io::Result<()> {
+        w.write_all((self.0)(value).as_bytes())
+    }
+}
+
+impl<R: Render + ?Sized> Render for &mut R {
+    fn render(&mut self, w: &mut dyn io::Write, value: i64) -> io::Result<()> {
+        (**self).render(w, value)
+    }
+}
+
+impl Computer {
+    /// Runs the program to completion with the given input, writing each
+    /// output using the renderer.
+    pub fn render_with(
+        &mut self,
+        input: impl IntoIterator<Item = i64>,
+        mut r: impl Render,
+        w: &mut dyn io::Write,
+    ) -> Result<()> {
+        let write = |err: io::Error| Error::Write {
+            msg: err.to_string(),
+        };
+        for value in self.drive(input) {
+            r.render(w, value?).map_err(write)?;
+        }
+        w.flush().map_err(write)
+    }
+}
@@ -0,0 +1,222 @@
+//! Helpers for the vacuum robot walking along scaffolding.
+//!
+//! The camera output is parsed into a grid, the path along the scaffold is
+//! calculated, and then compressed into a main routine and three movement
+//! functions that fit into the robot's memory.
+
+use std::fmt;
+use std::fmt::Display;
+
+use crate::ascii;
+use crate::explore::{Direction, Pos};
+use crate::Computer;
+
+/// The maximum number of characters in a single routine, excluding the
+/// newline.
+const MAX_LEN: usize = 20;
+
+/// The names of the movement functions.
+const NAMES: [char; 3] = ['A', 'B', 'C'];
+
+/// A single movement instruction for the robot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Left,
+    Right,
+    Forward(usize),
+}
+
+/// The image from the robot's camera.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    rows: Vec<Vec<u8>>,
+}
+
+/// A compressed movement program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Routines {
+    /// The order in which to call the movement functions, as indexes into
+    /// `functions`.
+    pub main: Vec<usize>,
+    /// The movement functions, at most three.
+    pub functions: Vec<Vec<Move>>,
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Left => f.write_str("L"),
+            Self::Right => f.write_str("R"),
+            Self::Forward(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+fn join<D: Display>(items: impl IntoIterator<Item = D>) -> String {
+    items
+        .into_iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Camera {
+    /// Parse the ASCII camera output.
+    pub fn parse(output: &str) -> Self {
+        let rows = output
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.bytes().collect())
+            .collect();
+        Self { rows }
+    }
+
+    fn get(&self, (x, y): Pos) -> Option<u8> {
+        let x = usize::try_from(x).ok()?;
+        let y = usize::try_from(y).ok()?;
+        self.rows.get(y)?.get(x).copied()
+    }
+
+    /// Returns whether there is scaffolding at the given position.
+    pub fn is_scaffold(&self, pos: Pos) -> bool {
+        matches!(self.get(pos), Some(b'#' | b'^' | b'v' | b'<' | b'>'))
+    }
+
+    /// Returns the position and direction of the robot.
+    pub fn robot(&self) -> Option<(Pos, Direction)> {
+        self.rows.iter().enumerate().find_map(|(y, row)| {
+            row.iter().enumerate().find_map(|(x, c)| {
+                let dir = match c {
+                    b'^' => Direction::North,
+                    b'v' => Direction::South,
+                    b'<' => Direction::West,
+                    b'>' => Direction::East,
+                    _ => return None,
+                };
+                Some(((x as i64, y as i64), dir))
+            })
+        })
+    }
+
+    /// Returns all the positions where scaffolding intersects.
+    pub fn intersections(&self) -> Vec<Pos> {
+        let mut intersections = Vec::new();
+        for (y, row) in self.rows.iter().enumerate() {
+            for x in 0..row.len() {
+                let pos = (x as i64, y as i64);
+                if self.is_scaffold(pos)
+                    && Direction::ALL
+                        .iter()
+                        .all(|dir| self.is_scaffold(dir.step(pos)))
+                {
+                    intersections.push(pos);
+                }
+            }
+        }
+        intersections
+    }
+
+    /// Returns the path that visits all the scaffolding, always continuing
+    /// straight over intersections.
+    pub fn path(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let (mut pos, mut dir) = match self.robot() {
+            Some(robot) => robot,
+            None => return moves,
+        };
+        loop {
+            let mut n = 0;
+            while self.is_scaffold(dir.step(pos)) {
+                pos = dir.step(pos);
+                n += 1;
+            }
+            if n > 0 {
+                moves.push(Move::Forward(n));
+            }
+            if self.is_scaffold(dir.left().step(pos)) {
+                dir = dir.left();
+                moves.push(Move::Left);
+            } else if self.is_scaffold(dir.right().step(pos)) {
+                dir = dir.right();
+                moves.push(Move::Right);
+            } else {
+                break moves;
+            }
+        }
+    }
+}
+
+fn fits(moves: &[Move]) -> bool {
+    join(moves).len() <= MAX_LEN
+}
+
+fn search<'a>(path: &'a [Move], functions: &mut Vec<&'a [Move]>, main: &mut Vec<usize>) -> bool {
+    if path.is_empty() {
+        return true;
+    }
+    // Each call takes two characters including the comma.
+    if 2 * main.len() + 1 > MAX_LEN {
+        return false;
+    }
+    for i in 0..functions.len() {
+        let function = functions[i];
+        if path.starts_with(function) {
+            main.push(i);
+            if search(&path[function.len()..], functions, main) {
+                return true;
+            }
+            main.pop();
+        }
+    }
+    if functions.len() < NAMES.len() {
+        // Try the longest functions first since they lead to a shorter main
+        // routine.
+        for len in (1..=path.len()).rev() {
+            let function = &path[..len];
+            if !fits(function) {
+                continue;
+            }
+            main.push(functions.len());
+            functions.push(function);
+            if search(&path[len..], functions, main) {
+                return true;
+            }
+            functions.pop();
+            main.pop();
+        }
+    }
+    false
+}
+
+/// Compress the path into a main routine and up to three movement functions
+/// that each fit within the twenty character limit.
+pub fn compress(path: &[Move]) -> Option<Routines> {
+    let mut functions = Vec::new();
+    let mut main = Vec::new();
+    search(path, &mut functions, &mut main).then(|| Routines {
+        main,
+        functions: functions.into_iter().map(<[Move]>::to_vec).collect(),
+    })
+}
+
+impl Routines {
+    /// The lines to input to the robot: the main routine followed by each of
+    /// the three movement functions.
+    pub fn lines(&self) -> Vec<String> {
+        let main = join(self.main.iter().map(|&i| NAMES[i]));
+        let functions = (0..NAMES.len()).map(|i| match self.functions.get(i) {
+            Some(function) => join(function),
+            None => String::new(),
+        });
+        std::iter::once(main).chain(functions).collect()
+    }
+
+    /// Feeds the movement program to the robot, followed by the answer to
+    /// whether a continuous video feed is wanted.
+    pub fn feed(&self, c: &mut Computer, video: bool) {
+        for line in self.lines() {
+            ascii::send_line(c, &line);
+        }
+        ascii::send_line(c, if video { "y" } else { "n" });
+    }
+}
@@ -0,0 +1,432 @@
+//! An experimental concolic search for inputs that reach a target.
+//!
+//! The program is run concretely with a candidate input vector, while
+//! alongside each value we track a linear expression of the inputs it was
+//! computed from. Anything that is not linear in the inputs (like multiplying
+//! two inputs together) falls back to its concrete value. Every jump whose
+//! condition depends on the input records a constraint, and new candidate
+//! inputs are found by solving for a single input that flips one of these
+//! constraints. The search is bounded both in the number of steps per run and
+//! the number of runs. Expressions that would overflow are dropped, again
+//! falling back to the concrete value.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+/// What the search is trying to make the program do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// Execute the instruction at this address.
+    Address(usize),
+    /// Output this value.
+    Output(i64),
+}
+
+/// A bounded search for an input vector that reaches a target.
+#[derive(Debug, Clone)]
+pub struct Search {
+    program: Arc<[i64]>,
+    target: Target,
+    max_steps: usize,
+    max_runs: usize,
+}
+
+/// A linear expression of the inputs: `constant + Σ coeffs[i] * input[i]`.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Expr {
+    constant: i64,
+    coeffs: Vec<i64>,
+}
+
+/// A relation on an expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rel {
+    /// The expression is equal to zero.
+    Eq,
+    /// The expression is less than zero.
+    Lt,
+}
+
+/// A condition on the inputs.
+#[derive(Debug, Clone, PartialEq)]
+struct Constraint {
+    expr: Expr,
+    rel: Rel,
+}
+
+/// The symbolic value tracked alongside a concrete value.
+#[derive(Debug, Clone, PartialEq)]
+enum Sym {
+    Linear(Expr),
+    /// A value of 1 or 0 depending on whether the constraint holds.
+    Cond(Constraint),
+}
+
+/// An arithmetic operation.
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Mul,
+}
+
+/// A constraint seen at a jump and whether it held.
+#[derive(Debug, Clone)]
+struct Branch {
+    constraint: Constraint,
+    held: bool,
+}
+
+/// The result of a single concolic run.
+#[derive(Debug)]
+struct Trace {
+    /// The inputs that were actually consumed.
+    input: Vec<i64>,
+    branches: Vec<Branch>,
+    reached: bool,
+}
+
+impl Expr {
+    fn input(i: usize) -> Self {
+        let mut coeffs = vec![0; i + 1];
+        coeffs[i] = 1;
+        Self {
+            constant: 0,
+            coeffs,
+        }
+    }
+
+    fn combine(&self, other: &Self, f: impl Fn(i64, i64) -> Option<i64>) -> Option<Self> {
+        let len = self.coeffs.len().max(other.coeffs.len());
+        let get = |e: &Self, i| e.coeffs.get(i).copied().unwrap_or(0);
+        Some(Self {
+            constant: f(self.constant, other.constant)?,
+            coeffs: (0..len)
+                .map(|i| f(get(self, i), get(other, i)))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    fn scale(&self, k: i64) -> Option<Self> {
+        Some(Self {
+            constant: self.constant.checked_mul(k)?,
+            coeffs: self
+                .coeffs
+                .iter()
+                .map(|c| c.checked_mul(k))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    fn eval(&self, input: &[i64]) -> Option<i64> {
+        self.coeffs
+            .iter()
+            .zip(input)
+            .try_fold(self.constant, |acc, (c, x)| {
+                acc.checked_add(c.checked_mul(*x)?)
+            })
+    }
+}
+
+impl Constraint {
+    /// Returns whether the constraint holds, or `None` if evaluating it
+    /// overflows.
+    fn holds(&self, input: &[i64]) -> Option<bool> {
+        let value = self.expr.eval(input)?;
+        Some(match self.rel {
+            Rel::Eq => value == 0,
+            Rel::Lt => value < 0,
+        })
+    }
+
+    /// Tries to find inputs that make the constraint hold or not by changing
+    /// a single input.
+    fn solve(&self, input: &[i64], want: bool) -> Option<Vec<i64>> {
+        for (i, &c) in self.expr.coeffs.iter().enumerate() {
+            if c == 0 || i >= input.len() {
+                continue;
+            }
+            // The value of the expression without this input.
+            let rest = self
+                .expr
+                .eval(input)
+                .and_then(|v| v.checked_sub(c.checked_mul(input[i])?));
+            let root = rest.and_then(|r| r.checked_neg()?.checked_div(c));
+            let candidates = [
+                root,
+                root.and_then(|r| r.checked_sub(1)),
+                root.and_then(|r| r.checked_add(1)),
+                input[i].checked_add(1),
+                input[i].checked_sub(1),
+            ];
+            for x in candidates.into_iter().flatten() {
+                let mut candidate = input.to_vec();
+                candidate[i] = x;
+                if self.holds(&candidate) == Some(want) {
+                    return Some(candidate);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Sym {
+    fn as_linear(&self) -> Option<&Expr> {
+        match self {
+            Self::Linear(e) => Some(e),
+            Self::Cond(_) => None,
+        }
+    }
+}
+
+/// Returns a linear expression for the value, if it has one.
+fn linear(value: i64, sym: &Option<Sym>) -> Option<Expr> {
+    match sym {
+        None => Some(Expr {
+            constant: value,
+            coeffs: Vec::new(),
+        }),
+        Some(s) => s.as_linear().cloned(),
+    }
+}
+
+struct Machine<'a> {
+    mem: Vec<i64>,
+    shadow: Vec<Option<Sym>>,
+    ptr: usize,
+    relative_base: i64,
+    input: &'a mut Vec<i64>,
+    consumed: usize,
+    branches: Vec<Branch>,
+}
+
+impl Machine<'_> {
+    fn get(&self, addr: usize) -> (i64, Option<Sym>) {
+        (
+            self.mem.get(addr).copied().unwrap_or(0),
+            self.shadow.get(addr).cloned().flatten(),
+        )
+    }
+
+    fn set(&mut self, addr: usize, value: i64, sym: Option<Sym>) {
+        if addr >= self.mem.len() {
+            self.mem.resize(addr + 1, 0);
+            self.shadow.resize(addr + 1, None);
+        }
+        self.mem[addr] = value;
+        self.shadow[addr] = sym;
+    }
+
+    /// Returns the address of the parameter, addresses are always concrete.
+    fn addr(&self, i: usize) -> Option<usize> {
+        let mode = self.mem[self.ptr] / [100, 1_000, 10_000][i - 1] % 10;
+        let raw = self.get(self.ptr + i).0;
+        let addr = match mode {
+            0 => raw,
+            1 => (self.ptr + i) as i64,
+            2 => self.relative_base.checked_add(raw)?,
+            _ => return None,
+        };
+        usize::try_from(addr).ok()
+    }
+
+    fn param(&self, i: usize) -> Option<(i64, Option<Sym>)> {
+        self.addr(i).map(|addr| self.get(addr))
+    }
+
+    fn arith(&mut self, op: Op) -> Option<()> {
+        let (a, sa) = self.param(1)?;
+        let (b, sb) = self.param(2)?;
+        let sym = match (&sa, &sb) {
+            (None, None) => None,
+            _ => match (op, linear(a, &sa), linear(b, &sb)) {
+                (Op::Add, Some(ea), Some(eb)) => ea.combine(&eb, i64::checked_add).map(Sym::Linear),
+                // Multiplying by a constant is still linear.
+                (Op::Mul, Some(ea), _) if sb.is_none() => ea.scale(b).map(Sym::Linear),
+                (Op::Mul, _, Some(eb)) if sa.is_none() => eb.scale(a).map(Sym::Linear),
+                // Otherwise fall back to the concrete value.
+                _ => None,
+            },
+        };
+        // The computer stops when the value overflows so the run ends here.
+        let value = match op {
+            Op::Add => a.checked_add(b)?,
+            Op::Mul => a.checked_mul(b)?,
+        };
+        let addr = self.addr(3)?;
+        self.set(addr, value, sym);
+        self.ptr += 4;
+        Some(())
+    }
+
+    fn compare(&mut self, rel: Rel) -> Option<()> {
+        let (a, sa) = self.param(1)?;
+        let (b, sb) = self.param(2)?;
+        let value = match rel {
+            Rel::Eq => a == b,
+            Rel::Lt => a < b,
+        };
+        let sym = match (&sa, &sb) {
+            (None, None) => None,
+            _ => match (linear(a, &sa), linear(b, &sb)) {
+                (Some(ea), Some(eb)) => ea
+                    .combine(&eb, i64::checked_sub)
+                    .map(|expr| Sym::Cond(Constraint { expr, rel })),
+                _ => None,
+            },
+        };
+        let addr = self.addr(3)?;
+        self.set(addr, value as i64, sym);
+        self.ptr += 4;
+        Some(())
+    }
+
+    fn jump(&mut self, if_zero: bool) -> Option<()> {
+        let (cond, sym) = self.param(1)?;
+        let (target, _) = self.param(2)?;
+        let zero = cond == 0;
+        // Record the condition as "the value is zero".
+        match sym {
+            Some(Sym::Linear(expr)) => self.branches.push(Branch {
+                constraint: Constraint { expr, rel: Rel::Eq },
+                held: zero,
+            }),
+            // The value is zero when the constraint does not hold, so we
+            // record the constraint itself and whether it held.
+            Some(Sym::Cond(constraint)) => self.branches.push(Branch {
+                constraint,
+                held: !zero,
+            }),
+            None => {}
+        }
+        if zero == if_zero {
+            self.ptr = usize::try_from(target).ok()?;
+        } else {
+            self.ptr += 3;
+        }
+        Some(())
+    }
+
+    /// Executes a single instruction, returning the output if there was one.
+    fn step(&mut self) -> Option<Option<i64>> {
+        match self.mem.get(self.ptr)? % 100 {
+            1 => self.arith(Op::Add)?,
+            2 => self.arith(Op::Mul)?,
+            3 => {
+                if self.consumed == self.input.len() {
+                    self.input.push(0);
+                }
+                let value = self.input[self.consumed];
+                let sym = Sym::Linear(Expr::input(self.consumed));
+                self.consumed += 1;
+                let addr = self.addr(1)?;
+                self.set(addr, value, Some(sym));
+                self.ptr += 2;
+            }
+            4 => {
+                let (value, _) = self.param(1)?;
+                self.ptr += 2;
+                return Some(Some(value));
+            }
+            5 => self.jump(false)?,
+            6 => self.jump(true)?,
+            7 => self.compare(Rel::Lt)?,
+            8 => self.compare(Rel::Eq)?,
+            9 => {
+                let (value, _) = self.param(1)?;
+                self.relative_base = self.relative_base.checked_add(value)?;
+                self.ptr += 2;
+            }
+            _ => return None,
+        }
+        Some(None)
+    }
+}
+
+impl Search {
+    /// Creates a new search for inputs that make the program reach the
+    /// target.
+    pub fn new(program: impl Into<Arc<[i64]>>, target: Target) -> Self {
+        Self {
+            program: program.into(),
+            target,
+            max_steps: 100_000,
+            max_runs: 1_000,
+        }
+    }
+
+    /// The maximum number of instructions to execute in each run.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// The maximum number of runs to try.
+    pub fn max_runs(mut self, max_runs: usize) -> Self {
+        self.max_runs = max_runs;
+        self
+    }
+
+    fn trace(&self, mut input: Vec<i64>) -> Trace {
+        let mut m = Machine {
+            mem: self.program.to_vec(),
+            shadow: vec![None; self.program.len()],
+            ptr: 0,
+            relative_base: 0,
+            input: &mut input,
+            consumed: 0,
+            branches: Vec::new(),
+        };
+        let mut reached = false;
+        for _ in 0..self.max_steps {
+            if self.target == Target::Address(m.ptr) {
+                reached = true;
+                break;
+            }
+            match m.step() {
+                Some(Some(value)) if self.target == Target::Output(value) => {
+                    reached = true;
+                    break;
+                }
+                Some(_) => {}
+                // The program halted or hit an error.
+                None => break,
+            }
+        }
+        let consumed = m.consumed;
+        let branches = m.branches;
+        input.truncate(consumed);
+        Trace {
+            input,
+            branches,
+            reached,
+        }
+    }
+
+    /// Runs the search, returning the inputs that reach the target if any
+    /// were found.
+    pub fn run(&self) -> Option<Vec<i64>> {
+        let mut queue = VecDeque::from([Vec::new()]);
+        let mut seen = HashSet::new();
+        let mut runs = 0;
+        while let Some(input) = queue.pop_front() {
+            if runs == self.max_runs {
+                break;
+            }
+            if !seen.insert(input.clone()) {
+                continue;
+            }
+            runs += 1;
+            let trace = self.trace(input);
+            if trace.reached {
+                return Some(trace.input);
+            }
+            for branch in &trace.branches {
+                if let Some(candidate) = branch.constraint.solve(&trace.input, !branch.held) {
+                    queue.push_back(candidate);
+                }
+            }
+        }
+        None
+    }
+}
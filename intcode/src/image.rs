@@ -0,0 +1,282 @@
+//! A program together with everything known about it.
+//!
+//! An [`Image`] is the values of a program plus the optional information that
+//! goes with them: where execution starts, the relative base it expects, and
+//! with the `assemble` feature the label names, source lines, and source map.
+//! Passing an image around instead of a `Vec<i64>` means none of this is lost
+//! between assembling, running, and disassembling.
+//!
+//! An image can be read and written in the following forms:
+//!
+//! - Text: comma separated integers, the usual intcode format.
+//! - Binary: each value as eight little endian bytes.
+//! - Compact: a magic number and then each value as a zigzag encoded LEB128
+//!   varint, so small values take one byte. Unlike the other forms it can be
+//!   told apart from text with [`Image::is_compact()`].
+//! - Bundle: the text form, a blank line, and then the debug info, requires
+//!   the `assemble` feature.
+
+use std::error;
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[cfg(feature = "assemble")]
+use crate::assemble::debug::DebugInfo;
+#[cfg(feature = "assemble")]
+use crate::assemble::meta::Metadata;
+#[cfg(feature = "assemble")]
+use crate::assemble::source_map::SourceMap;
+#[cfg(feature = "assemble")]
+use crate::assemble::Intcode;
+use crate::run::Computer;
+
+/// The bytes at the start of the compact form.
+const MAGIC: &[u8] = b"\0ic1";
+
+/// A program and its metadata.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Image {
+    /// The values of the program.
+    pub program: Vec<i64>,
+    /// The address execution starts at, if not the first one.
+    pub entry: Option<usize>,
+    /// The relative base the program expects to start with.
+    pub relative_base: Option<i64>,
+    /// Label names, source lines, and metadata for the program.
+    #[cfg(feature = "assemble")]
+    pub debug: Option<DebugInfo>,
+    /// The source of each range of addresses.
+    #[cfg(feature = "assemble")]
+    pub source_map: Option<SourceMap>,
+}
+
+/// An error reading an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    msg: String,
+}
+
+impl ParseError {
+    fn new(msg: impl Into<String>) -> Self {
+        Self { msg: msg.into() }
+    }
+}
+
+impl Image {
+    /// Constructs an image with only the values of a program.
+    pub fn new(program: Vec<i64>) -> Self {
+        Self {
+            program,
+            ..Self::default()
+        }
+    }
+
+    /// Parses the text form of a program.
+    pub fn from_text(text: &str) -> Result<Self, ParseError> {
+        text.trim()
+            .split(',')
+            .map(|value| value.trim().parse())
+            .collect::<Result<_, _>>()
+            .map(Self::new)
+            .map_err(|err| ParseError::new(format!("invalid value: {}", err)))
+    }
+
+    /// Parses the binary form of a program.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        if bytes.len() % 8 != 0 {
+            return Err(ParseError::new(format!(
+                "expected a multiple of 8 bytes, found {}",
+                bytes.len()
+            )));
+        }
+        let program = bytes
+            .chunks_exact(8)
+            .map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Ok(Self::new(program))
+    }
+
+    /// Returns whether the bytes are the compact form of a program.
+    pub fn is_compact(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    /// Parses the compact form of a program.
+    pub fn from_compact(bytes: &[u8]) -> Result<Self, ParseError> {
+        let bytes = bytes
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| ParseError::new("missing the compact form header"))?;
+        let mut program = Vec::new();
+        let mut value = 0u64;
+        let mut shift = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            if shift == 63 && byte > 1 {
+                return Err(ParseError::new(format!(
+                    "value ending at byte {} overflows 64 bits",
+                    MAGIC.len() + i
+                )));
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                program.push((value >> 1) as i64 ^ -((value & 1) as i64));
+                value = 0;
+                shift = 0;
+            } else {
+                shift += 7;
+            }
+        }
+        if shift != 0 {
+            return Err(ParseError::new("unexpected end of the last value"));
+        }
+        Ok(Self::new(program))
+    }
+
+    /// Parses the bundle form of a program.
+    #[cfg(feature = "assemble")]
+    pub fn from_bundle(bundle: &str) -> Result<Self, ParseError> {
+        let (text, debug) = bundle.split_once("\n\n").unwrap_or((bundle, ""));
+        let debug: DebugInfo = debug
+            .parse()
+            .map_err(|err| ParseError::new(format!("invalid debug info: {}", err)))?;
+        let mut image = Self::from_text(text)?;
+        image.entry = debug.meta.entry;
+        image.relative_base = debug.meta.relative_base;
+        image.debug = Some(debug);
+        Ok(image)
+    }
+
+    /// Returns the text form of the program.
+    pub fn to_text(&self) -> String {
+        self.to_string()
+    }
+
+    /// Returns the binary form of the program.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.program.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// Returns the compact form of the program.
+    pub fn to_compact(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for &v in &self.program {
+            let mut value = ((v << 1) ^ (v >> 63)) as u64;
+            while value >= 0x80 {
+                bytes.push(value as u8 | 0x80);
+                value >>= 7;
+            }
+            bytes.push(value as u8);
+        }
+        bytes
+    }
+
+    /// Returns the bundle form of the program.
+    #[cfg(feature = "assemble")]
+    pub fn to_bundle(&self) -> String {
+        let mut debug = self.debug.clone().unwrap_or_default();
+        debug.meta.entry = self.entry;
+        debug.meta.relative_base = self.relative_base;
+        format!("{}\n\n{}", self, debug)
+    }
+
+    /// Writes the text form of the program to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, format!("{}\n", self))
+    }
+
+    /// Overwrites the value at the address, growing the program with zeros
+    /// if the address is past the end of it.
+    pub fn patch(&mut self, addr: usize, value: i64) {
+        if addr >= self.program.len() {
+            self.program.resize(addr + 1, 0);
+        }
+        self.program[addr] = value;
+    }
+
+    /// Returns a computer that is ready to run the program from its entry
+    /// point with its relative base.
+    pub fn computer(&self) -> crate::run::Result<Computer> {
+        let mut c = Computer::new(self.program.clone());
+        if let Some(entry) = self.entry {
+            c = c.with_entry(entry)?;
+        }
+        if let Some(relative_base) = self.relative_base {
+            c.set_relative_base(relative_base);
+        }
+        Ok(c)
+    }
+
+    /// Runs the program with the given input until it halts, returning the
+    /// outputs.
+    pub fn run_with(&self, input: impl IntoIterator<Item = i64>) -> crate::run::Result<Vec<i64>> {
+        self.computer()?.run_with(input)
+    }
+
+    /// Runs the program with the given input until it halts, returning the
+    /// outputs along with the final state of memory.
+    pub fn run_to_end(
+        &self,
+        input: impl IntoIterator<Item = i64>,
+    ) -> crate::run::Result<crate::run::drive::RunOutcome> {
+        self.computer()?.run_to_end(input)
+    }
+
+    /// Disassembles the program, the label names from the debug info are
+    /// used if there is any.
+    #[cfg(feature = "disassemble")]
+    pub fn disassemble(
+        &self,
+        runs: impl IntoIterator<Item = crate::disassemble::Run>,
+        opts: crate::disassemble::Options,
+    ) -> crate::disassemble::Result<crate::disassemble::Disassembly> {
+        let opts = match &self.debug {
+            Some(debug) => opts.debug_info(debug.clone()),
+            None => opts,
+        };
+        crate::disassemble::disassemble(self.program.clone(), runs, &opts)
+    }
+}
+
+#[cfg(feature = "assemble")]
+impl From<Intcode> for Image {
+    fn from(intcode: Intcode) -> Self {
+        let Intcode {
+            output,
+            debug,
+            source_map,
+            ..
+        } = intcode;
+        // An embedded header jumps to the entry point and sets the relative
+        // base itself.
+        let embedded = Metadata::from_header(&output).is_some();
+        Self {
+            entry: debug.meta.entry.filter(|_| !embedded),
+            relative_base: debug.meta.relative_base.filter(|_| !embedded),
+            program: output,
+            debug: Some(debug),
+            source_map,
+        }
+    }
+}
+
+impl Display for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, value) in self.program.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl error::Error for ParseError {}
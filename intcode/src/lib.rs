@@ -1,5 +1,45 @@
+//! The single public entry point for assembling, disassembling, and running
+//! intcode programs.
+//!
+//! Depend on this crate rather than the individual `intcode-*` crates, they
+//! are implementation details and are re-exported here under shorter names.
+//!
+//! # Features
+//!
+//! - `assemble` (default) enables the [`assemble`], [`error`], and [`lex`]
+//!   modules.
+//! - `fmt` (default) enables the [`fmt`] module for displaying errors with
+//!   the source they point at.
+//! - `disassemble` enables the [`disassemble`] module.
+//! - `fingerprint` lets the assembler add a hash of its version, options,
+//!   and source files to the debug info.
+//! - `serde` derives `Serialize` for the machine events.
+//! - `async` enables [`run::AsyncComputer`] for running a computer with any
+//!   async executor.
+//!
+//! The [`run`] module is always available and only depends on `thiserror`.
+//! To embed just the computer disable the default features.
+//!
+//! ```toml
+//! [dependencies]
+//! intcode = { version = "*", default-features = false }
+//! ```
+//!
+//! An [`Image`] carries a program between the modules along with its entry
+//! point, relative base, and debug info.
+
+pub mod image;
+
+#[cfg(feature = "assemble")]
 pub use intcode_assemble as assemble;
 #[cfg(feature = "disassemble")]
 pub use intcode_disassemble as disassemble;
+#[cfg(feature = "intcode-error")]
 pub use intcode_error as error;
+#[cfg(feature = "fmt")]
+pub use intcode_error::fmt;
+#[cfg(feature = "intcode-lex")]
+pub use intcode_lex as lex;
 pub use intcode_run as run;
+
+pub use crate::image::Image;
@@ -0,0 +1,117 @@
+//! Each test only runs when the features it needs are enabled, so that the
+//! feature matrix checks every module is available when it should be.
+//!
+//! ```sh
+//! cargo test -p intcode --no-default-features
+//! cargo test -p intcode --no-default-features --features assemble
+//! cargo test -p intcode --all-features
+//! ```
+
+#[test]
+fn run() {
+    let mut c = intcode::run::Computer::new(vec![104, 7, 99]);
+    assert_eq!(c.run_with([]).unwrap(), [7]);
+}
+
+#[cfg(feature = "assemble")]
+#[test]
+fn assemble() {
+    let intcode = intcode::assemble::to_intcode("OUT #7\nHLT\n").unwrap();
+    assert_eq!(intcode.output, [104, 7, 99]);
+}
+
+#[cfg(feature = "assemble")]
+#[test]
+fn lex_line() {
+    use intcode::error::span::S;
+    use intcode::lex::Token;
+
+    let tokens: Vec<_> = intcode::lex::line("OUT @, \"hi ; x\n")
+        .map(|t| {
+            t.map(|S(tk, span)| (tk, span.m..span.n))
+                .map_err(|e| e.span)
+        })
+        .collect();
+    assert_eq!(
+        tokens,
+        [
+            Ok((Token::Ident, 0..3)),
+            Ok((Token::Whitespace, 3..4)),
+            Err((4..5).into()),
+            Ok((Token::Comma, 5..6)),
+            Ok((Token::Whitespace, 6..7)),
+            Err((7..14).into()),
+        ]
+    );
+}
+
+#[cfg(all(feature = "assemble", feature = "fmt"))]
+#[test]
+fn fmt() {
+    let asm = "OUT #7\nFOO\n";
+    let set = intcode::assemble::to_intcode(asm).unwrap_err();
+    let msg = intcode::fmt::Plain::new(asm).error(&set.errors[0]);
+    assert!(msg.contains(&*set.errors[0].msg), "{}", msg);
+}
+
+#[cfg(feature = "disassemble")]
+#[test]
+fn disassemble() {
+    use intcode::disassemble::{disassemble, Options, Run};
+
+    let disassembly = disassemble(vec![104, 7, 99], vec![Run::new()], &Options::new()).unwrap();
+    assert_eq!(disassembly.ast.to_string(), "OUT #7\nHLT\n");
+}
+
+#[test]
+fn image() {
+    use intcode::Image;
+
+    let mut image = Image::from_text("104,7,99\n").unwrap();
+    assert_eq!(image.run_with([]).unwrap(), [7]);
+    assert_eq!(Image::from_bytes(&image.to_bytes()).unwrap(), image);
+    assert!(Image::from_bytes(&[1, 2, 3]).is_err());
+    assert_eq!(Image::from_compact(&image.to_compact()).unwrap(), image);
+    assert!(Image::is_compact(&image.to_compact()));
+    assert!(!Image::is_compact(b"104,7,99"));
+    assert!(Image::from_text("1,x").is_err());
+
+    image.patch(1, 8);
+    image.patch(4, 5);
+    assert_eq!(image.to_text(), "104,8,99,0,5");
+    image.entry = Some(2);
+    assert!(image.run_with([]).unwrap().is_empty());
+    image.entry = Some(5);
+    assert!(image.computer().is_err());
+}
+
+#[test]
+fn image_compact() {
+    use intcode::Image;
+
+    let image = Image::new(vec![0, -1, 1, 63, -64, 64, i64::MAX, i64::MIN]);
+    let bytes = image.to_compact();
+    assert_eq!(bytes[4..10], [0, 1, 2, 126, 127, 128]);
+    assert_eq!(bytes.len(), 4 + 5 + 2 + 10 + 10);
+    assert_eq!(Image::from_compact(&bytes).unwrap(), image);
+    assert!(Image::from_compact(&bytes[..bytes.len() - 1]).is_err());
+    let overflow = [&b"\0ic1"[..], &[0xff; 9], &[2]].concat();
+    assert!(Image::from_compact(&overflow).is_err());
+    assert!(Image::from_compact(&[1, 2, 3]).is_err());
+}
+
+#[cfg(feature = "assemble")]
+#[test]
+fn image_assembled() {
+    use intcode::Image;
+
+    let asm = "ENTRY start\nRBINIT 10\nOUT #1\nstart: ADD #7, #0, rb\nOUT rb\nHLT\n";
+    let image = Image::from(intcode::assemble::to_intcode(asm).unwrap());
+    assert_eq!(image.entry, Some(2));
+    assert_eq!(image.relative_base, Some(10));
+    assert_eq!(image.run_with([]).unwrap(), [7]);
+    assert_eq!(
+        Image::from_bundle(&image.to_bundle()).unwrap().debug,
+        image.debug
+    );
+}